@@ -1,6 +1,9 @@
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use order_book_hybrid::engine::orderbook::OrderBook;
-use order_book_hybrid::engine::order::OrderSide;
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use order_book_hybrid::engine::orderbook::{OrderBook, PriceLevel};
+use order_book_hybrid::engine::order::{Order, OrderSide};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 fn benchmark_add_order(c: &mut Criterion) {
@@ -13,7 +16,7 @@ fn benchmark_add_order(c: &mut Criterion) {
                 .unwrap()
                 .as_millis() as u64;
             
-            orderbook.add_order(
+            let _ = orderbook.add_order(
                 black_box(OrderSide::Bid),
                 black_box(100.0),
                 black_box(1.0),
@@ -34,7 +37,7 @@ fn benchmark_match_orders(c: &mut Criterion) {
                 .as_millis() as u64;
             
             
-            orderbook.add_order(
+            let _ = orderbook.add_order(
                 OrderSide::Ask,
                 100.0,
                 1.0,
@@ -43,7 +46,7 @@ fn benchmark_match_orders(c: &mut Criterion) {
             );
             
             
-            orderbook.add_order(
+            let _ = orderbook.add_order(
                 OrderSide::Bid,
                 100.0,
                 1.0,
@@ -63,14 +66,14 @@ fn benchmark_get_depth(c: &mut Criterion) {
     
     
     for i in 0..100 {
-        orderbook.add_order(
+        let _ = orderbook.add_order(
             OrderSide::Bid,
             100.0 - i as f64,
             1.0,
             timestamp,
             format!("user{}", i),
         );
-        orderbook.add_order(
+        let _ = orderbook.add_order(
             OrderSide::Ask,
             101.0 + i as f64,
             1.0,
@@ -99,7 +102,7 @@ fn benchmark_high_frequency(c: &mut Criterion) {
                 let side = if i % 2 == 0 { OrderSide::Bid } else { OrderSide::Ask };
                 let price = if side == OrderSide::Bid { 99.0 } else { 101.0 };
                 
-                orderbook.add_order(
+                let _ = orderbook.add_order(
                     side,
                     price,
                     1.0,
@@ -111,11 +114,87 @@ fn benchmark_high_frequency(c: &mut Criterion) {
     });
 }
 
+const DEEP_LEVEL_ORDERS: u64 = 10_000;
+
+// Front-of-queue lookups on one price level holding 10k orders, which
+// matching does once per fill.
+fn benchmark_deep_level_first_order(c: &mut Criterion) {
+    let level = PriceLevel::new(100.0);
+    for id in 0..DEEP_LEVEL_ORDERS {
+        level.add_order(Order::new(id, OrderSide::Ask, 100.0, 1.0, id, format!("user{}", id)));
+    }
+    
+    c.bench_function("deep_level_first_order_10k", |b| {
+        b.iter(|| {
+            black_box(level.get_first_order());
+        });
+    });
+}
+
+// One taker sweeping 1k orders off the front of a 10k-order level.
+fn benchmark_deep_level_sweep(c: &mut Criterion) {
+    let mut group = c.benchmark_group("deep_level");
+    group.sample_size(10);
+    group.bench_function("sweep_1k_of_10k", |b| {
+        b.iter_batched(
+            || {
+                let orderbook = OrderBook::new();
+                for i in 0..DEEP_LEVEL_ORDERS {
+                    let _ = orderbook.add_order(OrderSide::Ask, 101.0, 1.0, i, format!("user{}", i));
+                }
+                orderbook
+            },
+            |orderbook| {
+                let _ = orderbook.add_order(OrderSide::Bid, 101.0, 1_000.0, DEEP_LEVEL_ORDERS, "taker".to_string());
+                orderbook
+            },
+            BatchSize::LargeInput,
+        );
+    });
+    group.finish();
+}
+
+// Top-of-book reads while another thread matches continuously, as the depth
+// broadcast does against live feeds.
+fn benchmark_best_price_during_matching(c: &mut Criterion) {
+    let orderbook = Arc::new(OrderBook::new());
+    for i in 0..100 {
+        let _ = orderbook.add_order(OrderSide::Bid, 99.0 - i as f64, 1.0, 0, format!("user{}", i));
+        let _ = orderbook.add_order(OrderSide::Ask, 102.0 + i as f64, 1.0, 0, format!("user{}", i + 100));
+    }
+    
+    let stop = Arc::new(AtomicBool::new(false));
+    let matcher = {
+        let orderbook = orderbook.clone();
+        let stop = stop.clone();
+        thread::spawn(move || {
+            let mut timestamp = 1;
+            while !stop.load(Ordering::Relaxed) {
+                let _ = orderbook.add_order(OrderSide::Ask, 101.0, 1.0, timestamp, "seller".to_string());
+                let _ = orderbook.add_order(OrderSide::Bid, 101.0, 1.0, timestamp + 1, "buyer".to_string());
+                timestamp += 2;
+            }
+        })
+    };
+    
+    c.bench_function("best_price_during_matching", |b| {
+        b.iter(|| {
+            black_box((orderbook.get_best_bid(), orderbook.get_best_ask()));
+        });
+    });
+    
+    stop.store(true, Ordering::Relaxed);
+    matcher.join().unwrap();
+}
+
 criterion_group!(
     benches,
     benchmark_add_order,
     benchmark_match_orders,
     benchmark_get_depth,
-    benchmark_high_frequency
+    benchmark_high_frequency,
+    benchmark_best_price_during_matching,
+    benchmark_deep_level_first_order,
+    benchmark_deep_level_sweep
 );
 criterion_main!(benches);
\ No newline at end of file
@@ -0,0 +1,83 @@
+use std::collections::{HashMap, HashSet};
+use actix_web::HttpRequest;
+
+pub const API_KEY_HEADER: &str = "X-API-Key";
+
+// Maps API keys to the user they act as. Loaded from `API_KEYS` as a comma
+// separated list of `key:user_id` pairs; users listed in `API_ADMINS` may
+// also call the admin routes.
+#[derive(Debug, Clone, Default)]
+pub struct ApiKeys {
+    keys: HashMap<String, String>,
+    admins: HashSet<String>,
+}
+
+impl ApiKeys {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_env() -> Self {
+        let mut api_keys: Self = std::env::var("API_KEYS")
+            .map(|spec| Self::parse(&spec))
+            .unwrap_or_default();
+        if let Ok(admins) = std::env::var("API_ADMINS") {
+            for user_id in admins.split(',').map(str::trim).filter(|u| !u.is_empty()) {
+                api_keys.add_admin(user_id);
+            }
+        }
+        api_keys
+    }
+
+    pub fn parse(spec: &str) -> Self {
+        let keys = spec
+            .split(',')
+            .filter_map(|pair| pair.trim().split_once(':'))
+            .filter(|(key, user_id)| !key.is_empty() && !user_id.is_empty())
+            .map(|(key, user_id)| (key.to_string(), user_id.to_string()))
+            .collect();
+        Self { keys, admins: HashSet::new() }
+    }
+
+    pub fn insert(&mut self, key: impl Into<String>, user_id: impl Into<String>) {
+        self.keys.insert(key.into(), user_id.into());
+    }
+
+    pub fn add_admin(&mut self, user_id: impl Into<String>) {
+        self.admins.insert(user_id.into());
+    }
+
+    pub fn is_admin(&self, user_id: &str) -> bool {
+        self.admins.contains(user_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    pub fn user_for_key(&self, key: &str) -> Option<&str> {
+        self.keys.get(key).map(String::as_str)
+    }
+
+    // Resolves the caller from the `X-API-Key` header, falling back to an
+    // `api_key` query parameter for browser WebSockets that can't set headers.
+    pub fn authenticate(&self, req: &HttpRequest) -> Option<String> {
+        let header_key = req
+            .headers()
+            .get(API_KEY_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+
+        let key = header_key.or_else(|| {
+            url::form_urlencoded::parse(req.query_string().as_bytes())
+                .find(|(name, _)| name == "api_key")
+                .map(|(_, value)| value.into_owned())
+        })?;
+
+        self.user_for_key(&key).map(str::to_string)
+    }
+}
@@ -0,0 +1,96 @@
+// Every book the API serves, keyed by symbol. Requests pick a book with a
+// `symbol` path segment or query parameter (order entry also reads it from
+// the body, and order ids carry their own); requests that name none get the
+// default book, so single-symbol clients keep working unchanged.
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::ops::Deref;
+use actix_web::dev::Payload;
+use actix_web::error::{ErrorInternalServerError, InternalError};
+use actix_web::web::{Data, Query};
+use actix_web::{Error, FromRequest, HttpRequest, HttpResponse};
+use futures_util::future::{ready, Ready};
+use serde::Deserialize;
+
+use crate::engine::orderbook::OrderBook;
+
+pub struct BookRegistry {
+    books: HashMap<String, Arc<OrderBook>>,
+    default_symbol: String,
+}
+
+impl BookRegistry {
+    // `default_book` also answers requests that don't name a symbol.
+    pub fn new(default_book: Arc<OrderBook>) -> Self {
+        let default_symbol = default_book.config().symbol.clone();
+        let mut books = HashMap::new();
+        books.insert(default_symbol.clone(), default_book);
+        Self { books, default_symbol }
+    }
+
+    pub fn with_book(mut self, book: Arc<OrderBook>) -> Self {
+        self.books.insert(book.config().symbol.clone(), book);
+        self
+    }
+
+    // Symbols match case-insensitively; `None` picks the default book.
+    pub fn get(&self, symbol: Option<&str>) -> Option<&Arc<OrderBook>> {
+        match symbol {
+            Some(symbol) => self.books.get(&symbol.to_ascii_uppercase()),
+            None => Some(self.default_book()),
+        }
+    }
+
+    pub fn default_book(&self) -> &Arc<OrderBook> {
+        &self.books[&self.default_symbol]
+    }
+
+    pub fn symbols(&self) -> Vec<String> {
+        let mut symbols: Vec<String> = self.books.keys().cloned().collect();
+        symbols.sort();
+        symbols
+    }
+}
+
+#[derive(Deserialize)]
+struct SymbolQuery {
+    symbol: Option<String>,
+}
+
+// The book a request is addressed to, from its `{symbol}` path segment or
+// `?symbol=` query parameter. Unknown symbols are refused with 404.
+pub struct SymbolBook(Arc<OrderBook>);
+
+impl Deref for SymbolBook {
+    type Target = Arc<OrderBook>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl FromRequest for SymbolBook {
+    type Error = Error;
+    type Future = Ready<Result<Self, Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let Some(books) = req.app_data::<Data<BookRegistry>>() else {
+            return ready(Err(ErrorInternalServerError("Book registry not configured")));
+        };
+        let symbol = match req.match_info().get("symbol") {
+            Some(symbol) => Some(symbol.to_string()),
+            None => Query::<SymbolQuery>::from_query(req.query_string())
+                .ok()
+                .and_then(|query| query.into_inner().symbol),
+        };
+        ready(match books.get(symbol.as_deref()) {
+            Some(book) => Ok(SymbolBook(book.clone())),
+            None => Err(not_found(symbol.as_deref().unwrap_or_default())),
+        })
+    }
+}
+
+fn not_found(symbol: &str) -> Error {
+    let message = format!("Unknown symbol {}", symbol);
+    InternalError::from_response(message.clone(), HttpResponse::NotFound().json(message)).into()
+}
@@ -0,0 +1,138 @@
+// Registry of live WebSocket sessions so operators can see who is connected
+// and drop a runaway client. Sessions add themselves when their actor starts
+// and remove themselves when it stops. The registry also hands out the
+// connection slots that cap how many sessions may exist at once.
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+use actix::{Message, Recipient};
+use dashmap::DashMap;
+
+// Asks a session to close its socket; it unregisters itself once stopped.
+#[derive(Debug, Clone, Copy, Message)]
+#[rtype(result = "()")]
+pub struct Disconnect;
+
+#[derive(Debug, Clone)]
+pub struct ConnectionInfo {
+    pub id: u64,
+    pub symbol: String,
+    pub user_id: Option<String>,
+    pub channels: Vec<String>,
+    pub connected_at_ms: u64,
+}
+
+struct Connection {
+    info: ConnectionInfo,
+    disconnect: Recipient<Disconnect>,
+}
+
+#[derive(Default)]
+pub struct ConnectionRegistry {
+    next_id: AtomicU64,
+    connections: DashMap<u64, Connection>,
+    max_connections: Option<usize>,
+    // Sessions send a `Heartbeat` frame this often, even on an idle book.
+    keepalive_interval: Option<Duration>,
+    // Slots held by sessions that are live or still upgrading. Claimed
+    // before the upgrade, so a burst of handshakes can't overshoot the cap.
+    slots: AtomicUsize,
+}
+
+impl ConnectionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_connections(mut self, max_connections: usize) -> Self {
+        self.max_connections = Some(max_connections);
+        self
+    }
+
+    pub fn max_connections(&self) -> Option<usize> {
+        self.max_connections
+    }
+
+    pub fn with_keepalive_interval(mut self, interval: Duration) -> Self {
+        self.keepalive_interval = Some(interval);
+        self
+    }
+
+    pub fn keepalive_interval(&self) -> Option<Duration> {
+        self.keepalive_interval
+    }
+
+    // Claims a slot for a new session; false once the cap is reached.
+    pub fn try_acquire_slot(&self) -> bool {
+        let max_connections = self.max_connections.unwrap_or(usize::MAX);
+        self.slots
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |slots| {
+                (slots < max_connections).then_some(slots + 1)
+            })
+            .is_ok()
+    }
+
+    pub fn release_slot(&self) {
+        self.slots.fetch_sub(1, Ordering::AcqRel);
+    }
+
+    pub fn register(
+        &self,
+        symbol: impl Into<String>,
+        user_id: Option<String>,
+        channels: Vec<String>,
+        connected_at_ms: u64,
+        disconnect: Recipient<Disconnect>,
+    ) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let info = ConnectionInfo {
+            id,
+            symbol: symbol.into(),
+            user_id,
+            channels,
+            connected_at_ms,
+        };
+        self.connections.insert(id, Connection { info, disconnect });
+        id
+    }
+
+    pub fn set_channels(&self, id: u64, channels: Vec<String>) {
+        if let Some(mut connection) = self.connections.get_mut(&id) {
+            connection.info.channels = channels;
+        }
+    }
+
+    pub fn unregister(&self, id: u64) -> bool {
+        self.connections.remove(&id).is_some()
+    }
+
+    // Oldest connection first.
+    pub fn list(&self) -> Vec<ConnectionInfo> {
+        let mut connections: Vec<ConnectionInfo> = self
+            .connections
+            .iter()
+            .map(|connection| connection.info.clone())
+            .collect();
+        connections.sort_by_key(|info| info.id);
+        connections
+    }
+
+    // Returns false for an unknown id. The entry stays listed until the
+    // session has actually stopped.
+    pub fn disconnect(&self, id: u64) -> bool {
+        match self.connections.get(&id) {
+            Some(connection) => {
+                connection.disconnect.do_send(Disconnect);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.connections.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.connections.is_empty()
+    }
+}
@@ -1,3 +1,6 @@
+pub mod auth;
+pub mod books;
+pub mod connections;
 pub mod routes;
 pub mod types;
-pub mod websocket;
\ No newline at end of file
+pub mod websocket;
@@ -1,53 +1,438 @@
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
-use actix_web::{delete, get, post, web::{Data, Json}, HttpResponse, Responder};
-use crate::engine::orderbook::OrderBook;
-use crate::engine::order::OrderSide;
+use actix_web::{delete, get, http::header, patch, post, routes, web::{Bytes, Data, Json, Path, Query}, HttpRequest, HttpResponse, HttpResponseBuilder, Responder};
+use futures_util::future::ready;
+use futures_util::stream::{self, StreamExt};
+use serde::Serialize;
+use crate::engine::accounting::LeaderboardMetric;
+use crate::engine::candles::{CandleInterval, CANDLE_CAPACITY};
+use crate::engine::conditional::SpreadCondition;
+use crate::engine::config::{OrderBookConfig, OrderKind};
+use crate::engine::consolidated::{ConsolidatedBook, VenueQuote};
+use crate::engine::gateway::OrderGateways;
+use crate::engine::killswitch;
+use crate::engine::orderbook::{bucket_depth, DepthBucket, OrderBook, OrderBookStats};
+use crate::engine::order::{validate_user_id, Order, OrderOptions, OrderSide, TimeInForce};
+use crate::engine::price::Price;
+use crate::engine::snapshot::OrderBookSnapshot;
+use crate::engine::trade::TradeColumns;
+use crate::events::OrderEventStatus;
+use crate::exchange::supervisor::{FeedStatus, FeedSupervisor};
+use crate::api::auth::ApiKeys;
+use crate::api::books::{BookRegistry, SymbolBook};
+use crate::api::connections::ConnectionRegistry;
 use crate::api::types::*;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+// Media type a client sends in `Accept` to receive enveloped responses.
+pub const ENVELOPE_MEDIA_TYPE: &str = "application/vnd.orderbook.envelope+json";
+
+fn wants_envelope(req: &HttpRequest, orderbook: &OrderBook) -> bool {
+    orderbook.config().envelope_responses
+        || req
+            .headers()
+            .get(header::ACCEPT)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|accept| accept.contains(ENVELOPE_MEDIA_TYPE))
+}
+
+// Serializes `payload` as-is, or inside a `ResponseEnvelope` when requested.
+fn respond<T: Serialize>(
+    req: &HttpRequest,
+    orderbook: &OrderBook,
+    mut builder: HttpResponseBuilder,
+    payload: T,
+) -> HttpResponse {
+    if !wants_envelope(req, orderbook) {
+        return builder.json(payload);
+    }
+
+    let server_time_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    builder.json(ResponseEnvelope {
+        data: payload,
+        server_time_ms,
+        symbol: orderbook.config().symbol.clone(),
+    })
+}
+
+// Parses windows like "30s", "5m", "1h" or "1d".
+fn parse_window(window: &str) -> Option<Duration> {
+    let window = window.trim();
+    let split = window.len().checked_sub(1)?;
+    let (amount, unit) = window.split_at(split);
+    let amount: u64 = amount.parse().ok()?;
+    let seconds = match unit {
+        "s" => amount,
+        "m" => amount * 60,
+        "h" => amount * 3600,
+        "d" => amount * 86_400,
+        _ => return None,
+    };
+    Some(Duration::from_secs(seconds))
+}
+
+fn unknown_symbol(req: &HttpRequest, books: &BookRegistry, symbol: &str) -> HttpResponse {
+    respond(req, books.default_book(), HttpResponse::NotFound(), format!("Unknown symbol {}", symbol))
+}
+
+// Maps a public `SYMBOL-000123` id to the book it names and the engine id
+// within that book.
+fn resolve_order_id(
+    req: &HttpRequest,
+    books: &BookRegistry,
+    order_id: &str,
+) -> Result<(Arc<OrderBook>, u64), Box<HttpResponse>> {
+    let (symbol, id) = match parse_order_id(order_id) {
+        Some(parsed) => parsed,
+        None => {
+            let response = respond(req, books.default_book(), HttpResponse::BadRequest(), "Invalid order_id");
+            return Err(Box::new(response));
+        }
+    };
+    match books.get(Some(&symbol)) {
+        Some(book) => Ok((book.clone(), id)),
+        None => Err(Box::new(unknown_symbol(req, books, &symbol))),
+    }
+}
+
+// Also served as `/depth/{symbol}`. `?normalize=pct` also gives each level as a percentage offset from mid,
+// so books at very different prices can be drawn on one axis.
+#[routes]
 #[get("/depth")]
-pub async fn get_depth(orderbook: Data<Arc<OrderBook>>) -> impl Responder {
+#[get("/depth/{symbol}")]
+pub async fn get_depth(
+    req: HttpRequest,
+    orderbook: SymbolBook,
+    query: Query<DepthQuery>,
+) -> impl Responder {
+    if let Some(width) = query.bucket {
+        if query.normalize.is_some() {
+            return respond(&req, &orderbook, HttpResponse::BadRequest(), "bucket and normalize can't be combined");
+        }
+        if !width.is_finite() || width <= 0.0 {
+            return respond(&req, &orderbook, HttpResponse::BadRequest(), "bucket must be a positive price width");
+        }
+        return get_bucketed_depth(&req, &orderbook, width);
+    }
+    
     let (bids, asks) = orderbook.get_market_depth(20);
+    let config = orderbook.config();
+    
+    match query.normalize.as_deref() {
+        None => {}
+        Some("pct") => {
+            // Mid of this same snapshot, so the offsets agree with the levels.
+            let mid = match (bids.first(), asks.first()) {
+                (Some(&(bid, _)), Some(&(ask, _))) => Some((bid + ask) / 2.0),
+                _ => None,
+            };
+            let level = |(price, quantity): (f64, f64)| NormalizedDepthLevel {
+                price,
+                pct_from_mid: mid.map(|mid| (price - mid) / mid * 100.0),
+                quantity: config.display_quantity(quantity),
+            };
+            let response = NormalizedDepthResponse {
+                mid,
+                bids: bids.into_iter().map(level).collect(),
+                asks: asks.into_iter().map(level).collect(),
+            };
+            return respond(&req, &orderbook, HttpResponse::Ok(), response);
+        }
+        Some(_) => return respond(&req, &orderbook, HttpResponse::BadRequest(), "normalize must be pct"),
+    }
     
     let response = DepthResponse {
         bids: bids.into_iter()
-            .map(|(price, quantity)| DepthLevel { price, quantity })
+            .map(|(price, quantity)| DepthLevel { price, quantity: config.display_quantity(quantity) })
             .collect(),
         asks: asks.into_iter()
-            .map(|(price, quantity)| DepthLevel { price, quantity })
+            .map(|(price, quantity)| DepthLevel { price, quantity: config.display_quantity(quantity) })
             .collect(),
     };
     
-    HttpResponse::Ok().json(response)
+    respond(&req, &orderbook, HttpResponse::Ok(), response)
+}
+
+// The top 20 buckets per side. Buckets are built from the whole book, so a
+// wide bucket holds every level inside it, not just the top 20.
+fn get_bucketed_depth(req: &HttpRequest, orderbook: &OrderBook, width: f64) -> HttpResponse {
+    let (bids, asks) = orderbook.get_market_depth(usize::MAX);
+    let config = orderbook.config();
+    let level = |bucket: DepthBucket| DepthBucketLevel {
+        price: bucket.price,
+        quantity: config.display_quantity(bucket.quantity),
+        average_price: bucket.average_price,
+    };
+    
+    let response = BucketedDepthResponse {
+        bucket: width,
+        bids: bucket_depth(&bids, OrderSide::Bid, width, 20).into_iter().map(level).collect(),
+        asks: bucket_depth(&asks, OrderSide::Ask, width, 20).into_iter().map(level).collect(),
+    };
+    respond(req, orderbook, HttpResponse::Ok(), response)
+}
+
+// Depth summed across the per-venue sub-books, when the server keeps them.
+#[get("/depth/consolidated")]
+pub async fn get_consolidated_depth(
+    req: HttpRequest,
+    orderbook: SymbolBook,
+    consolidated: Option<Data<HashMap<String, Arc<ConsolidatedBook>>>>,
+    query: Query<ConsolidatedDepthQuery>,
+) -> impl Responder {
+    let consolidated = match consolidated.as_ref().and_then(|venues| venues.get(&orderbook.config().symbol)) {
+        Some(consolidated) => consolidated,
+        None => return respond(&req, &orderbook, HttpResponse::NotFound(), "Venue books are not enabled"),
+    };
+    let (bids, asks) = consolidated.get_market_depth(query.levels.unwrap_or(20));
+    let config = consolidated.config();
+    
+    let response = ConsolidatedDepthResponse {
+        venues: consolidated.venues(),
+        bids: bids.into_iter()
+            .map(|(price, quantity)| DepthLevel { price, quantity: config.display_quantity(quantity) })
+            .collect(),
+        asks: asks.into_iter()
+            .map(|(price, quantity)| DepthLevel { price, quantity: config.display_quantity(quantity) })
+            .collect(),
+    };
+    
+    respond(&req, &orderbook, HttpResponse::Ok(), response)
+}
+
+// Best bid and ask across the venue sub-books, each with the venue quoting it.
+#[get("/nbbo/{symbol}")]
+pub async fn get_nbbo(
+    req: HttpRequest,
+    orderbook: SymbolBook,
+    consolidated: Option<Data<HashMap<String, Arc<ConsolidatedBook>>>>,
+) -> impl Responder {
+    let consolidated = match consolidated.as_ref().and_then(|venues| venues.get(&orderbook.config().symbol)) {
+        Some(consolidated) => consolidated,
+        None => return respond(&req, &orderbook, HttpResponse::NotFound(), "Venue books are not enabled"),
+    };
+    let config = consolidated.config();
+    let quote = |quote: VenueQuote| VenueQuoteResponse {
+        venue: quote.venue,
+        price: quote.price,
+        quantity: config.display_quantity(quote.quantity),
+    };
+    let (bid, ask) = (consolidated.best_bid(), consolidated.best_ask());
+    
+    let response = NbboResponse {
+        symbol: config.symbol.clone(),
+        spread: bid.as_ref().zip(ask.as_ref()).map(|(bid, ask)| ask.price - bid.price),
+        bid: bid.map(quote),
+        ask: ask.map(quote),
+    };
+    
+    respond(&req, &orderbook, HttpResponse::Ok(), response)
+}
+
+// Full book plus the sequence it was taken at, for clients recovering from a
+// gap in the diff stream. `?max_notional=` stops each side once its levels
+// cover that much quote currency.
+#[get("/depth/resync")]
+pub async fn resync_depth(
+    req: HttpRequest,
+    orderbook: SymbolBook,
+    query: Query<DepthResyncQuery>,
+) -> impl Responder {
+    let (sequence, mut bids, mut asks) = orderbook.depth_with_sequence(usize::MAX);
+    let config = orderbook.config();
+    
+    let mut truncated = false;
+    if let Some(max_notional) = query.max_notional {
+        if !max_notional.is_finite() || max_notional <= 0.0 {
+            return respond(&req, &orderbook, HttpResponse::BadRequest(), "max_notional must be positive");
+        }
+        truncated |= truncate_to_notional(&mut bids, max_notional);
+        truncated |= truncate_to_notional(&mut asks, max_notional);
+    }
+    
+    let response = DepthResyncResponse {
+        sequence,
+        truncated,
+        bids: bids.into_iter()
+            .map(|(price, quantity)| DepthLevel { price, quantity: config.display_quantity(quantity) })
+            .collect(),
+        asks: asks.into_iter()
+            .map(|(price, quantity)| DepthLevel { price, quantity: config.display_quantity(quantity) })
+            .collect(),
+    };
+    
+    respond(&req, &orderbook, HttpResponse::Ok(), response)
+}
+
+// Keeps levels, best first, until their cumulative notional reaches
+// `max_notional`; the level that reaches it is kept. True if any were cut.
+fn truncate_to_notional(levels: &mut Vec<(f64, f64)>, max_notional: f64) -> bool {
+    let mut covered = 0.0;
+    let keep = levels
+        .iter()
+        .position(|(price, quantity)| {
+            covered += price * quantity;
+            covered >= max_notional
+        })
+        .map_or(levels.len(), |last| last + 1);
+    let truncated = keep < levels.len();
+    levels.truncate(keep);
+    truncated
+}
+
+// Runs an order-entry command on the book, through its sequencing gateway
+// when gateways are configured. `None` means the gateway's engine thread is
+// gone.
+async fn execute<T, F>(orderbook: &Arc<OrderBook>, gateways: Option<&Data<OrderGateways>>, command: F) -> Option<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&OrderBook) -> T + Send + 'static,
+{
+    match gateways.and_then(|gateways| gateways.get(&orderbook.config().symbol)) {
+        Some(gateway) => gateway.submit(command).await.map(|(_, result)| result),
+        None => Some(command(orderbook)),
+    }
+}
+
+fn gateway_unavailable(req: &HttpRequest, orderbook: &OrderBook) -> HttpResponse {
+    respond(req, orderbook, HttpResponse::ServiceUnavailable(), "Order gateway unavailable")
 }
 
 #[post("/order")]
 pub async fn create_order(
-    orderbook: Data<Arc<OrderBook>>,
+    req: HttpRequest,
+    books: Data<BookRegistry>,
+    gateway: Option<Data<OrderGateways>>,
+    api_keys: Data<ApiKeys>,
     order: Json<CreateOrderRequest>,
 ) -> impl Responder {
+    let orderbook = match books.get(order.symbol.as_deref()) {
+        Some(book) => book.clone(),
+        None => return unknown_symbol(&req, &books, order.symbol.as_deref().unwrap_or_default()),
+    };
+    if let Err(e) = validate_user_id(&order.user_id) {
+        return respond(&req, &orderbook, HttpResponse::BadRequest(), e.to_string());
+    }
+    let gateway = gateway.as_ref();
+    if order.fee_exempt {
+        if let Some(response) = reject_non_admin(&req, &orderbook, &api_keys) {
+            return response;
+        }
+    }
+    if order.protection_price.is_some() && order.order_type != OrderType::Market {
+        return respond(&req, &orderbook, HttpResponse::BadRequest(), "protection_price only applies to market orders");
+    }
+    if !order.time_in_force.rests() && order.order_type != OrderType::Limit {
+        return respond(&req, &orderbook, HttpResponse::BadRequest(), "time_in_force only applies to limit orders");
+    }
+    if order.display_quantity.is_some() && (order.order_type != OrderType::Limit || !order.time_in_force.rests()) {
+        return respond(&req, &orderbook, HttpResponse::BadRequest(), "display_quantity only applies to GTC limit orders");
+    }
+    if order.refresh_peg.is_some() && order.display_quantity.is_none() {
+        return respond(&req, &orderbook, HttpResponse::BadRequest(), "refresh_peg only applies to iceberg orders");
+    }
+    
     let timestamp = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap()
         .as_millis() as u64;
     
-    let side = match order.side {
-        Side::Buy => OrderSide::Bid,
-        Side::Sell => OrderSide::Ask,
+    let side = OrderSide::from(order.side);
+    let options = OrderOptions {
+        client_metadata: order.client_metadata.clone(),
+        expires_at: order.expires_at,
+        fee_exempt: order.fee_exempt,
+        time_in_force: order.time_in_force,
+        display_quantity: order.display_quantity,
+        refresh_peg: order.refresh_peg,
+        take_profit: order.take_profit,
+        ..Default::default()
     };
     
+    if let OrderType::ConditionalSpread { threshold, direction } = order.order_type {
+        let (price, quantity, user_id) = (order.price, order.quantity, order.user_id.clone());
+        let order_id = execute(&orderbook, gateway, move |book| {
+            book.add_conditional_order(
+                side,
+                price,
+                quantity,
+                timestamp,
+                user_id,
+                SpreadCondition::new(threshold, direction),
+                options,
+            )
+        });
+        let order_id = match order_id.await {
+            Some(Ok(order_id)) => order_id,
+            Some(Err(e)) => return respond(&req, &orderbook, HttpResponse::BadRequest(), e.to_string()),
+            None => return gateway_unavailable(&req, &orderbook),
+        };
+        
+        let response = CreateOrderResponse {
+            order_id: format_order_id(&orderbook.config().symbol, order_id),
+            filled_quantity: 0.0,
+            remaining_quantity: order.quantity,
+            cancelled_quantity: 0.0,
+            average_price: 0.0,
+            best_price: None,
+            worst_price: None,
+            levels_touched: 0,
+            fills: Vec::new(),
+            status: OrderStatus::Pending,
+            client_metadata: order.client_metadata.clone(),
+            rest_reason: Some(RestReason::AwaitingCondition),
+            take_profit_order_ids: Vec::new(),
+        };
+        return respond(&req, &orderbook, HttpResponse::Ok(), response);
+    }
     
-    let (order_id, trades) = orderbook.add_order(
-        side,
-        order.price,
-        order.quantity,
-        timestamp,
-        order.user_id.clone(),
-    );
+    let (price, quantity, user_id) = (order.price, order.quantity, order.user_id.clone());
+    let (order_id, trades, cancelled_quantity) = if order.order_type == OrderType::Market {
+        // Market orders ignore `price`; they sweep up to `protection_price`, if any.
+        let protection_price = order.protection_price;
+        let result = execute(&orderbook, gateway, move |book| {
+            book.add_market_order(side, quantity, protection_price, timestamp, user_id, options)
+        });
+        match result.await {
+            Some(Ok(result)) => (result.order_id, result.trades, result.cancelled_quantity),
+            Some(Err(e)) => return respond(&req, &orderbook, HttpResponse::BadRequest(), e.to_string()),
+            None => return gateway_unavailable(&req, &orderbook),
+        }
+    } else {
+        let result = execute(&orderbook, gateway, move |book| {
+            book.add_order_with_options(side, price, quantity, timestamp, user_id, options)
+        });
+        match result.await {
+            // Cancelled on arrival (IOC or FOK remainder, self-trade
+            // prevention), so whatever didn't fill was dropped.
+            Some(Ok((order_id, trades))) if matches!(
+                orderbook.terminal_status(order_id),
+                Some((_, OrderEventStatus::Cancelled))
+            ) => {
+                let filled_quantity: f64 = trades.iter().map(|t| t.quantity).sum();
+                (order_id, trades, (order.quantity - filled_quantity).max(0.0))
+            }
+            Some(Ok((order_id, trades))) => (order_id, trades, 0.0),
+            Some(Err(e)) => return respond(&req, &orderbook, HttpResponse::BadRequest(), e.to_string()),
+            None => return gateway_unavailable(&req, &orderbook),
+        }
+    };
     
     
     let filled_quantity: f64 = trades.iter().map(|t| t.quantity).sum();
-    let remaining_quantity = order.quantity - filled_quantity;
+    let cancelled_quantity = orderbook.config().display_quantity(cancelled_quantity);
+    // What actually rests, which self-trade prevention may have reduced
+    // below the unfilled quantity.
+    let remaining_quantity = if cancelled_quantity > 0.0 {
+        0.0
+    } else {
+        orderbook
+            .get_order(order_id)
+            .map_or(order.quantity - filled_quantity, |o| orderbook.config().display_quantity(o.remaining_quantity()))
+    };
     
     
     let total_value: f64 = trades.iter().map(|t| t.price * t.quantity).sum();
@@ -58,69 +443,1059 @@ pub async fn create_order(
     };
     
     
-    let status = if filled_quantity == 0.0 {
-        OrderStatus::New
+    // A market, IOC or FOK order keeps what it filled; the dropped remainder
+    // shows up in `cancelled_quantity`.
+    let (status, rest_reason) = if cancelled_quantity > 0.0 && filled_quantity > 0.0 {
+        (OrderStatus::PartiallyFilled, None)
+    } else if cancelled_quantity > 0.0 {
+        (OrderStatus::Cancelled, None)
+    } else if filled_quantity == 0.0 {
+        (OrderStatus::New, Some(RestReason::NoMarketableLiquidity))
     } else if remaining_quantity > 0.0 {
-        OrderStatus::PartiallyFilled
+        (OrderStatus::PartiallyFilled, Some(RestReason::LimitPriceReached))
     } else {
-        OrderStatus::Filled
+        (OrderStatus::Filled, None)
     };
     
     
-    let fills: Vec<Fill> = trades.iter().map(|t| t.into()).collect();
+    let fills: Vec<Fill> = trades.iter()
+        .map(|t| {
+            let mut fill = Fill::new(t, &orderbook.config().symbol);
+            fill.quantity = orderbook.config().display_quantity(fill.quantity);
+            fill
+        })
+        .collect();
+    
+    // Best/worst from the taker's point of view: a buy prefers lower prices.
+    let fill_prices = fills.iter().map(|f| f.price);
+    let lowest = fill_prices.clone().reduce(f64::min);
+    let highest = fill_prices.reduce(f64::max);
+    let (best_price, worst_price) = match side {
+        OrderSide::Bid => (lowest, highest),
+        OrderSide::Ask => (highest, lowest),
+    };
+    
+    let mut touched: Vec<Price> = fills.iter().map(|f| Price::new(f.price)).collect();
+    touched.sort();
+    touched.dedup();
+    let levels_touched = touched.len();
+    
+    let take_profit_order_ids = orderbook
+        .take_profit_orders(order_id)
+        .into_iter()
+        .map(|child_id| format_order_id(&orderbook.config().symbol, child_id))
+        .collect();
     
     let response = CreateOrderResponse {
-        order_id: order_id.to_string(),
+        order_id: format_order_id(&orderbook.config().symbol, order_id),
         filled_quantity,
         remaining_quantity,
+        cancelled_quantity,
         average_price,
+        best_price,
+        worst_price,
+        levels_touched,
         fills,
         status,
+        client_metadata: order.client_metadata.clone(),
+        rest_reason,
+        take_profit_order_ids,
     };
     
-    HttpResponse::Ok().json(response)
+    respond(&req, &orderbook, HttpResponse::Ok(), response)
+}
+
+#[get("/order/{order_id}")]
+pub async fn get_order(
+    req: HttpRequest,
+    books: Data<BookRegistry>,
+    path: Path<String>,
+) -> impl Responder {
+    let (orderbook, order_id) = match resolve_order_id(&req, &books, &path) {
+        Ok(resolved) => resolved,
+        Err(response) => return *response,
+    };
+    
+    let order = match orderbook.get_order(order_id) {
+        Some(order) => order,
+        None => return respond(&req, &orderbook, HttpResponse::NotFound(), "Order not found"),
+    };
+    
+    respond(&req, &orderbook, HttpResponse::Ok(), order_response(&orderbook, order))
+}
+
+#[get("/order/{order_id}/position")]
+pub async fn get_queue_position(
+    req: HttpRequest,
+    books: Data<BookRegistry>,
+    path: Path<String>,
+) -> impl Responder {
+    let (orderbook, order_id) = match resolve_order_id(&req, &books, &path) {
+        Ok(resolved) => resolved,
+        Err(response) => return *response,
+    };
+    
+    let (position, orders_at_level) = match orderbook.queue_position(order_id) {
+        Some(position) => position,
+        None => return respond(&req, &orderbook, HttpResponse::NotFound(), "Order not resting"),
+    };
+    
+    let response = QueuePositionResponse {
+        order_id: format_order_id(&orderbook.config().symbol, order_id),
+        position,
+        orders_at_level,
+    };
+    respond(&req, &orderbook, HttpResponse::Ok(), response)
+}
+
+// Every order `user_id` has resting on the book, oldest first.
+#[get("/orders")]
+pub async fn get_user_orders(
+    req: HttpRequest,
+    orderbook: SymbolBook,
+    query: Query<UserOrdersQuery>,
+) -> impl Responder {
+    if let Err(e) = validate_user_id(&query.user_id) {
+        return respond(&req, &orderbook, HttpResponse::BadRequest(), e.to_string());
+    }
+    let orders = orderbook
+        .get_user_orders(&query.user_id)
+        .into_iter()
+        .map(|order| order_response(&orderbook, order))
+        .collect();
+    let response = UserOrdersResponse {
+        user_id: query.into_inner().user_id,
+        orders,
+    };
+    respond(&req, &orderbook, HttpResponse::Ok(), response)
+}
+
+fn order_response(orderbook: &OrderBook, order: Order) -> OrderResponse {
+    let config = orderbook.config();
+    OrderResponse {
+        order_id: format_order_id(&config.symbol, order.id),
+        remaining_quantity: config.display_quantity(order.remaining_quantity()),
+        display_quantity: order.display_quantity,
+        user_id: order.user_id,
+        side: order.side.into(),
+        price: order.price.as_f64(),
+        timestamp: order.timestamp,
+        client_metadata: order.client_metadata,
+    }
 }
 
 #[delete("/order")]
 pub async fn delete_order(
-    orderbook: Data<Arc<OrderBook>>,
+    req: HttpRequest,
+    books: Data<BookRegistry>,
+    gateway: Option<Data<OrderGateways>>,
     request: Json<DeleteOrderRequest>,
 ) -> impl Responder {
-    let order_id: u64 = match request.order_id.parse() {
-        Ok(id) => id,
-        Err(_) => return HttpResponse::BadRequest().json("Invalid order_id"),
+    let (orderbook, order_id) = match resolve_order_id(&req, &books, &request.order_id) {
+        Ok(resolved) => resolved,
+        Err(response) => return *response,
     };
+    if let Err(e) = validate_user_id(&request.user_id) {
+        return respond(&req, &orderbook, HttpResponse::BadRequest(), e.to_string());
+    }
     
-    match orderbook.remove_order(order_id, &request.user_id) {
+    let user_id = request.user_id.clone();
+    let removed = execute(&orderbook, gateway.as_ref(), move |book| book.remove_order(order_id, &user_id));
+    let removed = match removed.await {
+        Some(removed) => removed,
+        None => return gateway_unavailable(&req, &orderbook),
+    };
+    match removed {
         Some(order) => {
             let response = DeleteOrderResponse {
                 success: true,
-                remaining_quantity: order.quantity,
+                remaining_quantity: order.remaining_quantity(),
                 filled_quantity: 0.0, 
+                client_metadata: order.client_metadata,
+                reason: None,
             };
-            HttpResponse::Ok().json(response)
+            respond(&req, &orderbook, HttpResponse::Ok(), response)
         }
         None => {
             let response = DeleteOrderResponse {
                 success: false,
                 remaining_quantity: 0.0,
                 filled_quantity: 0.0,
+                client_metadata: None,
+                reason: Some(cancel_reject_reason(&orderbook, order_id, &request.user_id)),
+            };
+            respond(&req, &orderbook, HttpResponse::Ok(), response)
+        }
+    }
+}
+
+// Cancels every resting (and pending conditional) order the user has.
+#[delete("/orders")]
+pub async fn cancel_all_orders(
+    req: HttpRequest,
+    books: Data<BookRegistry>,
+    gateway: Option<Data<OrderGateways>>,
+    request: Json<CancelAllOrdersRequest>,
+) -> impl Responder {
+    let CancelAllOrdersRequest { user_id, symbol } = request.into_inner();
+    let orderbook = match books.get(symbol.as_deref()) {
+        Some(book) => book.clone(),
+        None => return unknown_symbol(&req, &books, symbol.as_deref().unwrap_or_default()),
+    };
+    if let Err(e) = validate_user_id(&user_id) {
+        return respond(&req, &orderbook, HttpResponse::BadRequest(), e.to_string());
+    }
+    let cancelled = execute(&orderbook, gateway.as_ref(), move |book| book.cancel_all_for_user(&user_id));
+    let cancelled = match cancelled.await {
+        Some(cancelled) => cancelled,
+        None => return gateway_unavailable(&req, &orderbook),
+    };
+    let symbol = &orderbook.config().symbol;
+    let response = CancelAllOrdersResponse {
+        cancelled_count: cancelled.len(),
+        order_ids: cancelled.iter().map(|order| format_order_id(symbol, order.id)).collect(),
+    };
+    respond(&req, &orderbook, HttpResponse::Ok(), response)
+}
+
+// Panic button: cancels everything the user has open on every book. Only
+// the user's own API key, or an admin's, may call it.
+#[delete("/orders/all")]
+pub async fn cancel_orders_everywhere(
+    req: HttpRequest,
+    books: Data<BookRegistry>,
+    gateway: Option<Data<OrderGateways>>,
+    api_keys: Data<ApiKeys>,
+    query: Query<CancelEverywhereQuery>,
+) -> impl Responder {
+    let user_id = query.into_inner().user_id;
+    let default_book = books.default_book();
+    if let Err(e) = validate_user_id(&user_id) {
+        return respond(&req, default_book, HttpResponse::BadRequest(), e.to_string());
+    }
+    if let Some(response) = reject_other_user(&req, default_book, &api_keys, &user_id) {
+        return response;
+    }
+    
+    let mut symbols = BTreeMap::new();
+    for symbol in books.symbols() {
+        let Some(orderbook) = books.get(Some(&symbol)) else { continue };
+        let owner = user_id.clone();
+        let cancelled = match execute(orderbook, gateway.as_ref(), move |book| book.cancel_all_for_user(&owner)).await {
+            Some(cancelled) => cancelled,
+            None => return gateway_unavailable(&req, orderbook),
+        };
+        let summary = CancelAllOrdersResponse {
+            cancelled_count: cancelled.len(),
+            order_ids: cancelled.iter().map(|order| format_order_id(&symbol, order.id)).collect(),
+        };
+        symbols.insert(symbol, summary);
+    }
+    
+    let response = CancelEverywhereResponse {
+        user_id,
+        cancelled_count: symbols.values().map(|summary| summary.cancelled_count).sum(),
+        symbols,
+    };
+    respond(&req, default_book, HttpResponse::Ok(), response)
+}
+
+// Explains a cancel that removed nothing: the order is someone else's, is
+// too young to cancel, has already reached a final state, or isn't known.
+fn cancel_reject_reason(orderbook: &OrderBook, order_id: u64, user_id: &str) -> CancelRejectReason {
+    if let Some(order) = orderbook.get_order(order_id) {
+        return if order.user_id == user_id {
+            CancelRejectReason::MinRestingTime
+        } else {
+            CancelRejectReason::NotOwner
+        };
+    }
+    match orderbook.terminal_status(order_id) {
+        Some((owner, _)) if owner != user_id => CancelRejectReason::NotOwner,
+        Some((_, OrderEventStatus::Filled)) => CancelRejectReason::AlreadyFilled,
+        Some((_, OrderEventStatus::Expired)) => CancelRejectReason::AlreadyExpired,
+        Some(_) => CancelRejectReason::AlreadyCancelled,
+        None => CancelRejectReason::NotFound,
+    }
+}
+
+// Changes an order's price and/or quantity without cancelling it. Only a
+// pure size reduction keeps its queue position.
+#[patch("/order")]
+pub async fn amend_order(
+    req: HttpRequest,
+    books: Data<BookRegistry>,
+    gateway: Option<Data<OrderGateways>>,
+    request: Json<AmendOrderRequest>,
+) -> impl Responder {
+    let (orderbook, order_id) = match resolve_order_id(&req, &books, &request.order_id) {
+        Ok(resolved) => resolved,
+        Err(response) => return *response,
+    };
+    if let Err(e) = validate_user_id(&request.user_id) {
+        return respond(&req, &orderbook, HttpResponse::BadRequest(), e.to_string());
+    }
+    if request.price.is_none() && request.quantity.is_none() {
+        return respond(&req, &orderbook, HttpResponse::BadRequest(), "price or quantity is required");
+    }
+    
+    let user_id = request.user_id.clone();
+    let (price, quantity) = (request.price, request.quantity);
+    let result = execute(&orderbook, gateway.as_ref(), move |book| {
+        book.modify_order(order_id, &user_id, price, quantity)
+    });
+    let result = match result.await {
+        Some(Ok(Some(result))) => result,
+        Some(Ok(None)) => {
+            return match orderbook.get_order(order_id) {
+                Some(order) if order.user_id != request.user_id => {
+                    respond(&req, &orderbook, HttpResponse::Forbidden(), "Order belongs to another user")
+                }
+                _ => respond(&req, &orderbook, HttpResponse::NotFound(), "Order not found"),
             };
-            HttpResponse::Ok().json(response)
         }
+        Some(Err(e)) => return respond(&req, &orderbook, HttpResponse::BadRequest(), e.to_string()),
+        None => return gateway_unavailable(&req, &orderbook),
+    };
+    
+    let config = orderbook.config();
+    let response = AmendOrderResponse {
+        order_id: format_order_id(&config.symbol, result.order_id),
+        remaining_quantity: config.display_quantity(result.remaining_quantity),
+        priority_kept: result.priority_kept,
+        filled_quantity: result.trades.iter().fold(0.0, |filled, t| filled + t.quantity),
+        fills: result.trades.iter()
+            .map(|t| {
+                let mut fill = Fill::new(t, &config.symbol);
+                fill.quantity = config.display_quantity(fill.quantity);
+                fill
+            })
+            .collect(),
+    };
+    respond(&req, &orderbook, HttpResponse::Ok(), response)
+}
+
+#[post("/order/cancel-replace")]
+pub async fn cancel_replace_order(
+    req: HttpRequest,
+    books: Data<BookRegistry>,
+    gateway: Option<Data<OrderGateways>>,
+    request: Json<CancelReplaceRequest>,
+) -> impl Responder {
+    let (orderbook, old_order_id) = match resolve_order_id(&req, &books, &request.order_id) {
+        Ok(resolved) => resolved,
+        Err(response) => return *response,
+    };
+    if let Err(e) = validate_user_id(&request.user_id) {
+        return respond(&req, &orderbook, HttpResponse::BadRequest(), e.to_string());
+    }
+    
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    
+    let side = OrderSide::from(request.side);
+    
+    let user_id = request.user_id.clone();
+    let (price, quantity, place_if_cancel_fails) = (request.price, request.quantity, request.place_if_cancel_fails);
+    let result = execute(&orderbook, gateway.as_ref(), move |book| {
+        book.cancel_replace(old_order_id, &user_id, side, price, quantity, timestamp, place_if_cancel_fails)
+    });
+    let result = match result.await {
+        Some(Ok(result)) => result,
+        Some(Err(e)) => return respond(&req, &orderbook, HttpResponse::BadRequest(), e.to_string()),
+        None => return gateway_unavailable(&req, &orderbook),
+    };
+    
+    let filled_quantity: f64 = result.trades.iter().map(|t| t.quantity).sum();
+    
+    let response = CancelReplaceResponse {
+        cancelled: result.cancelled.is_some(),
+        order_id: result.order_id.map(|id| format_order_id(&orderbook.config().symbol, id)),
+        filled_quantity,
+        remaining_quantity: orderbook.config().display_quantity(result.remaining_quantity),
+        fills: result.trades.iter()
+            .map(|t| {
+                let mut fill = Fill::new(t, &orderbook.config().symbol);
+                fill.quantity = orderbook.config().display_quantity(fill.quantity);
+                fill
+            })
+            .collect(),
+    };
+    
+    respond(&req, &orderbook, HttpResponse::Ok(), response)
+}
+
+// Arming, like the heartbeat, needs the user's own API key or an admin's,
+// so nobody can arm a switch that cancels someone else's orders.
+#[post("/deadman")]
+pub async fn arm_dead_man(
+    req: HttpRequest,
+    orderbook: SymbolBook,
+    api_keys: Data<ApiKeys>,
+    request: Json<DeadManRequest>,
+) -> impl Responder {
+    if let Err(e) = validate_user_id(&request.user_id) {
+        return respond(&req, &orderbook, HttpResponse::BadRequest(), e.to_string());
+    }
+    if let Some(response) = reject_other_user(&req, &orderbook, &api_keys, &request.user_id) {
+        return response;
+    }
+    
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    
+    let dead_man = orderbook.dead_man();
+    if request.timeout_ms == 0 {
+        dead_man.disarm(&request.user_id);
+    } else {
+        dead_man.arm(&request.user_id, request.timeout_ms, timestamp);
     }
+    
+    let expires_at = dead_man.expires_at(&request.user_id);
+    respond(&req, &orderbook, HttpResponse::Ok(), DeadManResponse {
+        user_id: request.user_id.clone(),
+        armed: expires_at.is_some(),
+        expires_at,
+    })
+}
+
+#[post("/deadman/heartbeat")]
+pub async fn dead_man_heartbeat(
+    req: HttpRequest,
+    orderbook: SymbolBook,
+    api_keys: Data<ApiKeys>,
+    request: Json<DeadManHeartbeatRequest>,
+) -> impl Responder {
+    if let Err(e) = validate_user_id(&request.user_id) {
+        return respond(&req, &orderbook, HttpResponse::BadRequest(), e.to_string());
+    }
+    if let Some(response) = reject_other_user(&req, &orderbook, &api_keys, &request.user_id) {
+        return response;
+    }
+    
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    
+    let dead_man = orderbook.dead_man();
+    if !dead_man.heartbeat(&request.user_id, timestamp) {
+        return respond(&req, &orderbook, HttpResponse::NotFound(), "No dead-man's switch armed for user");
+    }
+    
+    respond(&req, &orderbook, HttpResponse::Ok(), DeadManResponse {
+        user_id: request.user_id.clone(),
+        armed: true,
+        expires_at: dead_man.expires_at(&request.user_id),
+    })
 }
 
 #[get("/stats")]
-pub async fn get_stats(orderbook: Data<Arc<OrderBook>>) -> impl Responder {
+pub async fn get_stats(req: HttpRequest, orderbook: SymbolBook) -> impl Responder {
     let stats = orderbook.get_stats();
-    HttpResponse::Ok().json(stats)
+    respond(&req, &orderbook, HttpResponse::Ok(), stats)
+}
+
+#[get("/stats/volatility")]
+pub async fn get_volatility(
+    req: HttpRequest,
+    orderbook: SymbolBook,
+    query: Query<WindowQuery>,
+) -> impl Responder {
+    let window = query.window.clone().unwrap_or_else(|| "5m".to_string());
+    let duration = match parse_window(&window) {
+        Some(duration) => duration,
+        None => return respond(&req, &orderbook, HttpResponse::BadRequest(), "Invalid window"),
+    };
+    
+    respond(&req, &orderbook, HttpResponse::Ok(), VolatilityResponse {
+        volatility: orderbook.realized_volatility(duration),
+        window,
+    })
+}
+
+#[get("/stats/twap")]
+pub async fn get_twap(
+    req: HttpRequest,
+    orderbook: SymbolBook,
+    query: Query<WindowQuery>,
+) -> impl Responder {
+    let window = query.window.clone().unwrap_or_else(|| "5m".to_string());
+    let duration = match parse_window(&window) {
+        Some(duration) => duration,
+        None => return respond(&req, &orderbook, HttpResponse::BadRequest(), "Invalid window"),
+    };
+    
+    respond(&req, &orderbook, HttpResponse::Ok(), TwapResponse {
+        twap: orderbook.twap(duration),
+        window,
+    })
+}
+
+#[get("/stats/depth-slope")]
+pub async fn get_depth_slope(
+    req: HttpRequest,
+    orderbook: SymbolBook,
+    query: Query<DepthSlopeQuery>,
+) -> impl Responder {
+    let levels = query.levels.unwrap_or(10);
+    if levels < 2 {
+        return respond(&req, &orderbook, HttpResponse::BadRequest(), "levels must be at least 2");
+    }
+    
+    let (bid_slope, ask_slope) = orderbook.depth_slope(levels);
+    respond(&req, &orderbook, HttpResponse::Ok(), DepthSlopeResponse { levels, bid_slope, ask_slope })
+}
+
+// Upper bound on `buckets` so one request can't ask for a huge histogram.
+const MAX_VOLUME_PROFILE_BUCKETS: usize = 1000;
+
+#[get("/volume-profile")]
+pub async fn get_volume_profile(
+    req: HttpRequest,
+    orderbook: SymbolBook,
+    query: Query<VolumeProfileQuery>,
+) -> impl Responder {
+    let window = query.window.clone().unwrap_or_else(|| "1h".to_string());
+    let duration = match parse_window(&window) {
+        Some(duration) => duration,
+        None => return respond(&req, &orderbook, HttpResponse::BadRequest(), "Invalid window"),
+    };
+    let buckets = query.buckets.unwrap_or(50);
+    if buckets == 0 || buckets > MAX_VOLUME_PROFILE_BUCKETS {
+        return respond(
+            &req,
+            &orderbook,
+            HttpResponse::BadRequest(),
+            format!("buckets must be between 1 and {}", MAX_VOLUME_PROFILE_BUCKETS),
+        );
+    }
+    
+    let buckets = orderbook
+        .volume_profile(duration, buckets)
+        .into_iter()
+        .map(|(price, volume)| VolumeBucket { price, volume: orderbook.config().display_quantity(volume) })
+        .collect();
+    respond(&req, &orderbook, HttpResponse::Ok(), VolumeProfileResponse { window, buckets })
+}
+
+// Candles returned when `limit` is omitted.
+const DEFAULT_CANDLE_LIMIT: usize = 100;
+
+// OHLCV candles from the trade tape, oldest first, ending with the one in
+// progress.
+#[get("/candles/{symbol}")]
+pub async fn get_candles(
+    req: HttpRequest,
+    orderbook: SymbolBook,
+    query: Query<CandlesQuery>,
+) -> impl Responder {
+    let interval = match query.interval.as_deref().map(CandleInterval::parse) {
+        None => CandleInterval::OneMinute,
+        Some(Some(interval)) => interval,
+        Some(None) => return respond(&req, &orderbook, HttpResponse::BadRequest(), "interval must be 1m, 5m or 1h"),
+    };
+    let limit = query.limit.unwrap_or(DEFAULT_CANDLE_LIMIT);
+    if limit == 0 || limit > CANDLE_CAPACITY {
+        return respond(
+            &req,
+            &orderbook,
+            HttpResponse::BadRequest(),
+            format!("limit must be between 1 and {}", CANDLE_CAPACITY),
+        );
+    }
+    
+    let config = orderbook.config();
+    let candles = orderbook
+        .get_candles(interval, limit)
+        .into_iter()
+        .map(|candle| CandleResponse {
+            start_ms: candle.start_ms,
+            open: candle.open,
+            high: candle.high,
+            low: candle.low,
+            close: candle.close,
+            volume: config.display_quantity(candle.volume),
+        })
+        .collect();
+    let response = CandlesResponse {
+        symbol: config.symbol.clone(),
+        interval,
+        candles,
+    };
+    respond(&req, &orderbook, HttpResponse::Ok(), response)
+}
+
+#[get("/config")]
+pub async fn get_config(req: HttpRequest, orderbook: SymbolBook) -> impl Responder {
+    let config = orderbook.config();
+    // A type counts as offered if any of its time-in-force forms is allowed.
+    let mut order_types: Vec<String> = [
+        ("Limit", OrderKind::Limit(TimeInForce::Gtc)),
+        ("Limit", OrderKind::Limit(TimeInForce::Ioc)),
+        ("Limit", OrderKind::Limit(TimeInForce::Fok)),
+        ("Market", OrderKind::Market),
+        ("ConditionalSpread", OrderKind::ConditionalSpread),
+    ]
+    .into_iter()
+    .filter(|(_, kind)| config.check_order_kind(*kind).is_ok())
+    .map(|(name, _)| name.to_string())
+    .collect();
+    order_types.dedup();
+    let response = ConfigResponse {
+        symbol: config.symbol.clone(),
+        lot_size: 1.0 / config.quantity_scale as f64,
+        tick_size: config.tick_size,
+        precision_policy: config.precision_policy,
+        display_quantity_decimals: config.display_quantity_decimals,
+        order_types,
+        allowed_order_kinds: config.allowed_order_kinds.clone(),
+        fees: config.fee_schedule,
+        tie_break: config.tie_break.clone(),
+        designated_maker: config.designated_maker.clone(),
+        prevent_same_source_match: config.prevent_same_source_match,
+        self_trade_policy: config.self_trade_policy,
+        locked_market_policy: config.locked_market_policy,
+        min_resting_ms: config.min_resting_ms,
+        max_staleness_ms: config.max_staleness_ms,
+        max_source_silence_ms: config.max_source_silence_ms,
+        l3_enabled: config.order_level_events,
+        match_events: config.match_events,
+        envelope_responses: config.envelope_responses,
+    };
+    respond(&req, &orderbook, HttpResponse::Ok(), response)
+}
+
+const MAX_TRADES_LIMIT: usize = 1000;
+
+// Prints from the tape since a timestamp, oldest first, so a poller can
+// append what it hasn't seen: `/trades?since_ms=1704988800000&limit=100`.
+#[get("/trades")]
+pub async fn get_trades(
+    req: HttpRequest,
+    orderbook: SymbolBook,
+    query: Query<TradesQuery>,
+) -> impl Responder {
+    let limit = query.limit.unwrap_or(100);
+    if limit == 0 || limit > MAX_TRADES_LIMIT {
+        return respond(
+            &req,
+            &orderbook,
+            HttpResponse::BadRequest(),
+            format!("limit must be between 1 and {}", MAX_TRADES_LIMIT),
+        );
+    }
+    
+    // Without `since_ms` the latest prints are wanted, and nothing follows them.
+    let (trades, has_more) = match query.since_ms {
+        Some(since_ms) => {
+            let mut trades = orderbook.trades_since(since_ms, limit + 1);
+            let has_more = trades.len() > limit;
+            trades.truncate(limit);
+            (trades, has_more)
+        }
+        None => (orderbook.get_recent_trades(limit), false),
+    };
+    
+    let config = orderbook.config();
+    let trades = trades
+        .into_iter()
+        .map(|t| TapeTrade {
+            bid_order_id: format_order_id(&config.symbol, t.bid_order_id),
+            ask_order_id: format_order_id(&config.symbol, t.ask_order_id),
+            price: t.price,
+            quantity: config.display_quantity(t.quantity),
+            timestamp: t.timestamp,
+            executed_at: t.executed_at,
+        })
+        .collect();
+    respond(&req, &orderbook, HttpResponse::Ok(), TradesResponse { trades, has_more })
+}
+
+// The whole tape buffer for bulk ingestion, oldest first, as raw engine
+// trades. `format=jsonl` (the default) streams one trade per line;
+// `format=columnar` returns a single `TradeColumns` object.
+#[get("/trades/export")]
+pub async fn export_trades(
+    req: HttpRequest,
+    orderbook: SymbolBook,
+    query: Query<TradeExportQuery>,
+) -> impl Responder {
+    let columnar = match query.format.as_deref() {
+        None | Some("jsonl") => false,
+        Some("columnar") => true,
+        Some(_) => return respond(&req, &orderbook, HttpResponse::BadRequest(), "format must be jsonl or columnar"),
+    };
+    let trades = orderbook.trades_since(query.since_ms.unwrap_or(0), usize::MAX);
+    if columnar {
+        return respond(&req, &orderbook, HttpResponse::Ok(), TradeColumns::from_trades(&trades));
+    }
+    
+    let mut body = Vec::new();
+    for trade in &trades {
+        if let Err(e) = serde_json::to_writer(&mut body, trade) {
+            return respond(&req, &orderbook, HttpResponse::InternalServerError(), e.to_string());
+        }
+        body.push(b'\n');
+    }
+    HttpResponse::Ok().content_type("application/x-ndjson").body(body)
+}
+
+const MAX_LEADERBOARD_LIMIT: usize = 100;
+
+// Top users by `volume` (traded notional), `pnl` or `orders`, e.g.
+// `/leaderboard?metric=volume&limit=10`.
+#[get("/leaderboard")]
+pub async fn get_leaderboard(
+    req: HttpRequest,
+    orderbook: SymbolBook,
+    query: Query<LeaderboardQuery>,
+) -> impl Responder {
+    let metric_name = query.metric.clone().unwrap_or_else(|| "volume".to_string());
+    let metric = match LeaderboardMetric::parse(&metric_name) {
+        Some(metric) => metric,
+        None => return respond(&req, &orderbook, HttpResponse::BadRequest(), "metric must be volume, pnl or orders"),
+    };
+    let limit = query.limit.unwrap_or(10);
+    if limit == 0 || limit > MAX_LEADERBOARD_LIMIT {
+        return respond(
+            &req,
+            &orderbook,
+            HttpResponse::BadRequest(),
+            format!("limit must be between 1 and {}", MAX_LEADERBOARD_LIMIT),
+        );
+    }
+    
+    let entries = orderbook
+        .leaderboard(metric, limit)
+        .into_iter()
+        .enumerate()
+        .map(|(i, (user_id, account, pnl))| LeaderboardEntry {
+            rank: i + 1,
+            user_id,
+            volume: account.volume,
+            pnl,
+            orders: account.orders,
+        })
+        .collect();
+    respond(&req, &orderbook, HttpResponse::Ok(), LeaderboardResponse { metric: metric_name, entries })
+}
+
+// One user's totals on a book, including the spread captured by their
+// resting orders, e.g. `/account?user_id=mm1`.
+#[get("/account")]
+pub async fn get_account(
+    req: HttpRequest,
+    orderbook: SymbolBook,
+    query: Query<AccountQuery>,
+) -> impl Responder {
+    let Some((account, pnl)) = orderbook.account(&query.user_id) else {
+        return respond(&req, &orderbook, HttpResponse::NotFound(), "No account for user");
+    };
+    let config = orderbook.config();
+    let response = AccountResponse {
+        user_id: query.into_inner().user_id,
+        orders: account.orders,
+        volume: account.volume,
+        position: config.display_quantity(account.position),
+        cash: account.cash,
+        pnl,
+        maker: MakerFills {
+            bought_quantity: config.display_quantity(account.maker_bought),
+            average_buy_price: account.maker_buy_price(),
+            sold_quantity: config.display_quantity(account.maker_sold),
+            average_sell_price: account.maker_sell_price(),
+            captured_spread: account.captured_spread(),
+            fees: account.maker_fees,
+            net_edge: account.net_maker_edge(),
+        },
+    };
+    respond(&req, &orderbook, HttpResponse::Ok(), response)
+}
+
+// How much base a market order spending a fixed quote amount would fill,
+// e.g. `/quote?side=buy&notional=10000`.
+#[get("/quote")]
+pub async fn get_quote(
+    req: HttpRequest,
+    orderbook: SymbolBook,
+    query: Query<QuoteQuery>,
+) -> impl Responder {
+    let side = match query.side.to_ascii_lowercase().as_str() {
+        "buy" => Side::Buy,
+        "sell" => Side::Sell,
+        _ => return respond(&req, &orderbook, HttpResponse::BadRequest(), "side must be buy or sell"),
+    };
+    if !query.notional.is_finite() || query.notional <= 0.0 {
+        return respond(&req, &orderbook, HttpResponse::BadRequest(), "notional must be positive");
+    }
+    
+    let fill = orderbook.fill_for_notional(side.into(), query.notional);
+    respond(&req, &orderbook, HttpResponse::Ok(), QuoteResponse {
+        side,
+        notional: query.notional,
+        notional_filled: fill.notional,
+        quantity: orderbook.config().display_quantity(fill.quantity),
+        average_price: fill.average_price,
+        complete: fill.complete,
+    })
+}
+
+// What sweeping a fixed quantity would do to the book, without trading,
+// e.g. `/resilience?side=buy&quantity=5`. The levels left beyond the swept
+// region hint at how quickly the price could recover.
+#[get("/resilience")]
+pub async fn get_resilience(
+    req: HttpRequest,
+    orderbook: SymbolBook,
+    query: Query<ResilienceQuery>,
+) -> impl Responder {
+    let side = match query.side.to_ascii_lowercase().as_str() {
+        "buy" => Side::Buy,
+        "sell" => Side::Sell,
+        _ => return respond(&req, &orderbook, HttpResponse::BadRequest(), "side must be buy or sell"),
+    };
+    if !query.quantity.is_finite() || query.quantity <= 0.0 {
+        return respond(&req, &orderbook, HttpResponse::BadRequest(), "quantity must be positive");
+    }
+
+    let sweep = orderbook.sweep_resilience(side.into(), query.quantity);
+    let config = orderbook.config();
+    respond(&req, &orderbook, HttpResponse::Ok(), ResilienceResponse {
+        side,
+        quantity: query.quantity,
+        filled_quantity: config.display_quantity(sweep.filled_quantity),
+        average_price: sweep.average_price,
+        start_price: sweep.start_price,
+        end_price: sweep.end_price,
+        price_move: sweep.price_move,
+        price_move_pct: sweep.price_move_pct,
+        levels_swept: sweep.levels_swept,
+        levels_remaining: sweep.levels_remaining,
+        quantity_remaining: config.display_quantity(sweep.quantity_remaining),
+        resulting_best: sweep.resulting_best,
+        complete: sweep.complete,
+    })
+}
+
+#[get("/auction/indicative")]
+pub async fn get_indicative_auction(req: HttpRequest, orderbook: SymbolBook) -> impl Responder {
+    let auction = orderbook.indicative_auction();
+    let config = orderbook.config();
+    respond(&req, &orderbook, HttpResponse::Ok(), AuctionIndicativeResponse {
+        indicative_price: auction.indicative_price,
+        matched_volume: config.display_quantity(auction.matched_volume),
+        imbalance: config.display_quantity(auction.imbalance),
+    })
+}
+
+// Admin routes require an API key whose user is listed in `API_ADMINS`;
+// returns the error response for any other caller.
+fn reject_non_admin(req: &HttpRequest, orderbook: &OrderBook, api_keys: &ApiKeys) -> Option<HttpResponse> {
+    match api_keys.authenticate(req) {
+        Some(user_id) if api_keys.is_admin(&user_id) => None,
+        Some(_) => Some(respond(req, orderbook, HttpResponse::Forbidden(), "Admin access required")),
+        None => Some(respond(req, orderbook, HttpResponse::Unauthorized(), "Missing or invalid API key")),
+    }
+}
+
+// For routes that act on everything a user has open: only that user's own
+// API key, or an admin's, may call them.
+fn reject_other_user(req: &HttpRequest, orderbook: &OrderBook, api_keys: &ApiKeys, user_id: &str) -> Option<HttpResponse> {
+    match api_keys.authenticate(req) {
+        Some(caller) if caller == user_id || api_keys.is_admin(&caller) => None,
+        Some(_) => Some(respond(req, orderbook, HttpResponse::Forbidden(), "Can only act on your own orders")),
+        None => Some(respond(req, orderbook, HttpResponse::Unauthorized(), "Missing or invalid API key")),
+    }
+}
+
+#[get("/admin/connections")]
+pub async fn list_connections(
+    req: HttpRequest,
+    orderbook: SymbolBook,
+    api_keys: Data<ApiKeys>,
+    registry: Data<ConnectionRegistry>,
+) -> impl Responder {
+    if let Some(response) = reject_non_admin(&req, &orderbook, &api_keys) {
+        return response;
+    }
+    
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    let connections: Vec<ConnectionResponse> = registry
+        .list()
+        .into_iter()
+        .map(|info| ConnectionResponse {
+            id: info.id,
+            symbol: info.symbol,
+            user_id: info.user_id,
+            channels: info.channels,
+            age_ms: now.saturating_sub(info.connected_at_ms),
+        })
+        .collect();
+    respond(&req, &orderbook, HttpResponse::Ok(), connections)
+}
+
+#[delete("/admin/connections/{id}")]
+pub async fn disconnect_connection(
+    req: HttpRequest,
+    orderbook: SymbolBook,
+    api_keys: Data<ApiKeys>,
+    registry: Data<ConnectionRegistry>,
+    path: Path<u64>,
+) -> impl Responder {
+    if let Some(response) = reject_non_admin(&req, &orderbook, &api_keys) {
+        return response;
+    }
+    
+    let id = path.into_inner();
+    if !registry.disconnect(id) {
+        return respond(&req, &orderbook, HttpResponse::NotFound(), "Connection not found");
+    }
+    respond(&req, &orderbook, HttpResponse::Ok(), DisconnectResponse { id, disconnecting: true })
+}
+
+// Emergency stop for the whole venue: no book accepts orders or matches
+// until `/admin/revive`. With `?cancel=true` every resting order is
+// cancelled as well, venue sub-books included.
+#[post("/admin/kill")]
+pub async fn kill_trading(
+    req: HttpRequest,
+    books: Data<BookRegistry>,
+    gateway: Option<Data<OrderGateways>>,
+    api_keys: Data<ApiKeys>,
+    consolidated: Option<Data<HashMap<String, Arc<ConsolidatedBook>>>>,
+    query: Query<KillQuery>,
+) -> impl Responder {
+    let default_book = books.default_book();
+    if let Some(response) = reject_non_admin(&req, default_book, &api_keys) {
+        return response;
+    }
+    let admin = api_keys.authenticate(&req).unwrap_or_default();
+    if killswitch::engage() {
+        log::warn!("🛑 Kill switch engaged by {}: trading halted on every symbol", admin);
+    }
+    
+    let mut cancelled_orders = BTreeMap::new();
+    if query.cancel {
+        for symbol in books.symbols() {
+            let Some(orderbook) = books.get(Some(&symbol)) else { continue };
+            let cancelled = match execute(orderbook, gateway.as_ref(), |book| book.cancel_all_resting()).await {
+                Some(cancelled) => cancelled.len(),
+                None => return gateway_unavailable(&req, orderbook),
+            };
+            let venue_books = consolidated.as_ref().and_then(|venues| venues.get(&symbol));
+            let venue_cancelled: usize = venue_books
+                .map(|venues| venues.books().iter().map(|book| book.cancel_all_resting().len()).sum())
+                .unwrap_or(0);
+            cancelled_orders.insert(symbol, cancelled + venue_cancelled);
+        }
+        log::warn!(
+            "🛑 Kill switch cancelled {} resting orders",
+            cancelled_orders.values().sum::<usize>()
+        );
+    }
+    respond(&req, default_book, HttpResponse::Ok(), KillSwitchResponse { killed: true, cancelled_orders })
+}
+
+#[post("/admin/revive")]
+pub async fn revive_trading(
+    req: HttpRequest,
+    books: Data<BookRegistry>,
+    api_keys: Data<ApiKeys>,
+) -> impl Responder {
+    let default_book = books.default_book();
+    if let Some(response) = reject_non_admin(&req, default_book, &api_keys) {
+        return response;
+    }
+    if killswitch::release() {
+        log::warn!("✅ Kill switch released by {}: trading resumed", api_keys.authenticate(&req).unwrap_or_default());
+    }
+    let response = KillSwitchResponse { killed: false, cancelled_orders: BTreeMap::new() };
+    respond(&req, default_book, HttpResponse::Ok(), response)
+}
+
+// Trades serialized per chunk of an archive download.
+const ARCHIVE_TRADES_PER_CHUNK: usize = 1000;
+
+// Every `SessionArchive` field but the tape, which is streamed after it.
+#[derive(Serialize)]
+struct ArchiveHead<'a> {
+    symbol: &'a str,
+    archived_at_ms: u64,
+    config: &'a OrderBookConfig,
+    stats: &'a OrderBookStats,
+    snapshot: &'a OrderBookSnapshot,
+}
+
+// The book's `SessionArchive` as one JSON download. The tape is serialized a
+// chunk at a time as the body is written, so a long tape is never held as a
+// single JSON string.
+#[get("/admin/archive")]
+pub async fn get_archive(
+    req: HttpRequest,
+    orderbook: SymbolBook,
+    api_keys: Data<ApiKeys>,
+) -> impl Responder {
+    if let Some(response) = reject_non_admin(&req, &orderbook, &api_keys) {
+        return response;
+    }
+    
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    let archive = orderbook.archive(now);
+    let head = ArchiveHead {
+        symbol: &archive.symbol,
+        archived_at_ms: archive.archived_at_ms,
+        config: &archive.config,
+        stats: &archive.stats,
+        snapshot: &archive.snapshot,
+    };
+    let mut head = match serde_json::to_vec(&head) {
+        Ok(head) => head,
+        Err(e) => return respond(&req, &orderbook, HttpResponse::InternalServerError(), e.to_string()),
+    };
+    // Reopen the object to append the tape.
+    head.pop();
+    head.extend_from_slice(b",\"trades\":[");
+    
+    let filename = format!("{}-archive-{}.json", archive.symbol, now);
+    let chunks = stream::unfold((archive.trades.into_iter(), true), |(mut trades, first)| async move {
+        let mut chunk = Vec::new();
+        for (i, trade) in trades.by_ref().take(ARCHIVE_TRADES_PER_CHUNK).enumerate() {
+            if i > 0 || !first {
+                chunk.push(b',');
+            }
+            if let Err(e) = serde_json::to_writer(&mut chunk, &trade) {
+                return Some((Err(actix_web::error::ErrorInternalServerError(e)), (trades, false)));
+            }
+        }
+        (!chunk.is_empty()).then(|| (Ok(Bytes::from(chunk)), (trades, false)))
+    });
+    let body = stream::once(ready(Ok(Bytes::from(head))))
+        .chain(chunks)
+        .chain(stream::once(ready(Ok::<_, actix_web::Error>(Bytes::from_static(b"]}")))));
+    
+    HttpResponse::Ok()
+        .content_type("application/json")
+        .insert_header((header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)))
+        .streaming(body)
 }
 
 #[get("/health")]
-pub async fn health_check() -> impl Responder {
+pub async fn health_check(feeds: Option<Data<FeedSupervisor>>) -> impl Responder {
+    let feeds: Vec<FeedStatus> = feeds.map(|feeds| feeds.statuses()).unwrap_or_default();
     HttpResponse::Ok().json(serde_json::json!({
         "status": "healthy",
-        "service": "order-book-hybrid"
+        "service": "order-book-hybrid",
+        "feeds": feeds
     }))
 }
\ No newline at end of file
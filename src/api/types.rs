@@ -1,6 +1,20 @@
+use std::collections::BTreeMap;
 use serde::{Deserialize, Serialize};
+use crate::engine::candles::CandleInterval;
+use crate::engine::conditional::SpreadDirection;
+use crate::engine::config::{FeeSchedule, LockedMarketPolicy, OrderKind, PrecisionPolicy, SelfTradePolicy, TieBreak};
+use crate::engine::order::{OrderSide, RefreshPeg, TakeProfit, TimeInForce};
 use crate::engine::trade::Trade;
 
+// Opt-in wrapper around any REST payload; `server_time_ms` lets clients
+// estimate clock skew and round-trip latency.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResponseEnvelope<T> {
+    pub data: T,
+    pub server_time_ms: u64,
+    pub symbol: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreateOrderRequest {
     pub price: f64,
@@ -9,22 +23,74 @@ pub struct CreateOrderRequest {
     pub side: Side,
     #[serde(default = "default_order_type")]
     pub order_type: OrderType,
+    // Opaque tag echoed back on queries, fills and cancels (max 256 bytes).
+    #[serde(default)]
+    pub client_metadata: Option<String>,
+    // Good-till-date expiry in ms since epoch.
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+    // Waives fees on this order's fills; requires an admin API key.
+    #[serde(default)]
+    pub fee_exempt: bool,
+    // Market orders only: worst price the sweep may reach.
+    #[serde(default)]
+    pub protection_price: Option<f64>,
+    // Limit orders only; `Gtc` rests the unfilled part as before.
+    #[serde(default)]
+    pub time_in_force: TimeInForce,
+    // Makes a limit order an iceberg: only this much shows in depth at a
+    // time, refreshed from the rest as each slice fills.
+    #[serde(default)]
+    pub display_quantity: Option<f64>,
+    // Icebergs only: reprices each refreshed slice against the book.
+    #[serde(default)]
+    pub refresh_peg: Option<RefreshPeg>,
+    // Places a limit order on the other side for whatever fills, now or
+    // later.
+    #[serde(default)]
+    pub take_profit: Option<TakeProfit>,
+    // Book to trade on; the server's default symbol when omitted.
+    #[serde(default)]
+    pub symbol: Option<String>,
 }
 
 fn default_order_type() -> OrderType {
     OrderType::Limit
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Side {
     Buy,
     Sell,
 }
 
+impl From<Side> for OrderSide {
+    fn from(side: Side) -> Self {
+        match side {
+            Side::Buy => OrderSide::Bid,
+            Side::Sell => OrderSide::Ask,
+        }
+    }
+}
+
+impl From<OrderSide> for Side {
+    fn from(side: OrderSide) -> Self {
+        match side {
+            OrderSide::Bid => Side::Buy,
+            OrderSide::Ask => Side::Sell,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum OrderType {
     Limit,
     Market,
+    // Held off-book until the spread crosses `threshold`, then placed as a limit order.
+    ConditionalSpread {
+        threshold: f64,
+        direction: SpreadDirection,
+    },
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -32,9 +98,29 @@ pub struct CreateOrderResponse {
     pub order_id: String,
     pub filled_quantity: f64,
     pub remaining_quantity: f64,
+    // Unfilled market, IOC or FOK quantity that was cancelled instead of resting.
+    pub cancelled_quantity: f64,
     pub average_price: f64,
+    pub best_price: Option<f64>,
+    pub worst_price: Option<f64>,
+    pub levels_touched: usize,
     pub fills: Vec<Fill>,
     pub status: OrderStatus,
+    pub client_metadata: Option<String>,
+    // Why some of the order is resting on the book; `None` once fully filled.
+    pub rest_reason: Option<RestReason>,
+    // Take-profit orders placed for what filled on arrival.
+    pub take_profit_order_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum RestReason {
+    // Nothing on the opposite side was at or through the limit price.
+    NoMarketableLiquidity,
+    // Some liquidity crossed, but the remainder hit the limit price.
+    LimitPriceReached,
+    // A conditional order waiting for its spread trigger.
+    AwaitingCondition,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -42,43 +128,183 @@ pub struct Fill {
     pub trade_id: String,
     pub quantity: f64,
     pub price: f64,
-    pub maker_order_id: String,
-    pub taker_order_id: String,
+    // `None` only for an opening auction print, which has no taker.
+    pub maker_order_id: Option<String>,
+    pub taker_order_id: Option<String>,
     pub timestamp: u64,
+    // Fee charged to the order the response is about (the taker).
+    pub fee: f64,
 }
 
-impl From<&Trade> for Fill {
-    fn from(trade: &Trade) -> Self {
+impl Fill {
+    pub fn new(trade: &Trade, symbol: &str) -> Self {
         Self {
             trade_id: format!("{}_{}", trade.bid_order_id, trade.ask_order_id),
             quantity: trade.quantity,
             price: trade.price,
-            maker_order_id: trade.bid_order_id.to_string(),
-            taker_order_id: trade.ask_order_id.to_string(),
+            maker_order_id: trade.maker_order_id.map(|id| format_order_id(symbol, id)),
+            taker_order_id: trade.taker_order_id.map(|id| format_order_id(symbol, id)),
             timestamp: trade.timestamp,
+            fee: trade.taker_fee,
         }
     }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum OrderStatus {
+    Pending,
     New,
     PartiallyFilled,
     Filled,
     Cancelled,
 }
 
+// Public order ids look like `BTC-000123` (or `BTC-USDT-000123` for a book
+// with a fixed quote): the book's symbol plus the engine's numeric id,
+// zero-padded so they sort and read naturally.
+pub fn format_order_id(symbol: &str, order_id: u64) -> String {
+    format!("{}-{:06}", symbol, order_id)
+}
+
+pub fn parse_order_id(order_id: &str) -> Option<(String, u64)> {
+    let (symbol, id) = order_id.rsplit_once('-')?;
+    if symbol.split('-').any(|part| part.is_empty() || !part.chars().all(|c| c.is_ascii_alphanumeric())) {
+        return None;
+    }
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some((symbol.to_string(), id.parse().ok()?))
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DeleteOrderRequest {
     pub order_id: String,
     pub user_id: String,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CancelAllOrdersRequest {
+    pub user_id: String,
+    #[serde(default)]
+    pub symbol: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CancelAllOrdersResponse {
+    pub cancelled_count: usize,
+    pub order_ids: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CancelEverywhereQuery {
+    pub user_id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CancelEverywhereResponse {
+    pub user_id: String,
+    pub cancelled_count: usize,
+    // Every symbol, including those where nothing was open.
+    pub symbols: BTreeMap<String, CancelAllOrdersResponse>,
+}
+
+// At least one of `price` and `quantity` must be set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AmendOrderRequest {
+    pub order_id: String,
+    pub user_id: String,
+    #[serde(default)]
+    pub price: Option<f64>,
+    #[serde(default)]
+    pub quantity: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AmendOrderResponse {
+    pub order_id: String,
+    pub remaining_quantity: f64,
+    // False when the amendment sent the order to the back of the queue.
+    pub priority_kept: bool,
+    pub filled_quantity: f64,
+    pub fills: Vec<Fill>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CancelReplaceRequest {
+    pub order_id: String,
+    pub user_id: String,
+    pub side: Side,
+    pub price: f64,
+    pub quantity: f64,
+    // Place the new order even if the old one was already filled or cancelled.
+    #[serde(default)]
+    pub place_if_cancel_fails: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CancelReplaceResponse {
+    pub cancelled: bool,
+    pub order_id: Option<String>,
+    pub filled_quantity: f64,
+    pub remaining_quantity: f64,
+    pub fills: Vec<Fill>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DeleteOrderResponse {
     pub success: bool,
     pub remaining_quantity: f64,
     pub filled_quantity: f64,
+    pub client_metadata: Option<String>,
+    // Why nothing was cancelled; `None` on success.
+    pub reason: Option<CancelRejectReason>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum CancelRejectReason {
+    // Unknown id, or one that left the book too long ago to remember.
+    NotFound,
+    // The order exists but belongs to another user.
+    NotOwner,
+    AlreadyFilled,
+    AlreadyCancelled,
+    AlreadyExpired,
+    // The order hasn't rested for the book's minimum resting time yet.
+    MinRestingTime,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OrderResponse {
+    pub order_id: String,
+    pub user_id: String,
+    pub side: Side,
+    pub price: f64,
+    // Includes an iceberg's hidden reserve.
+    pub remaining_quantity: f64,
+    // Iceberg slice size; `null` for a fully displayed order.
+    pub display_quantity: Option<f64>,
+    pub timestamp: u64,
+    pub client_metadata: Option<String>,
+}
+
+// `position` is 0-based: 0 means the order is next to fill at its price.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QueuePositionResponse {
+    pub order_id: String,
+    pub position: usize,
+    pub orders_at_level: usize,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UserOrdersQuery {
+    pub user_id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserOrdersResponse {
+    pub user_id: String,
+    pub orders: Vec<OrderResponse>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -87,8 +313,394 @@ pub struct DepthResponse {
     pub asks: Vec<DepthLevel>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct DepthQuery {
+    // `pct` adds each level's offset from mid.
+    pub normalize: Option<String>,
+    // Groups levels into price buckets this wide.
+    pub bucket: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DepthBucketLevel {
+    // The bucket's inner edge: its low for bids, its high for asks.
+    pub price: f64,
+    pub quantity: f64,
+    // Quantity-weighted average price of the levels in the bucket.
+    pub average_price: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BucketedDepthResponse {
+    pub bucket: f64,
+    pub bids: Vec<DepthBucketLevel>,
+    pub asks: Vec<DepthBucketLevel>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NormalizedDepthLevel {
+    pub price: f64,
+    // Signed percentage from mid: negative for bids, positive for asks.
+    pub pct_from_mid: Option<f64>,
+    pub quantity: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NormalizedDepthResponse {
+    // `None` while either side is empty, and then so is every offset.
+    pub mid: Option<f64>,
+    pub bids: Vec<NormalizedDepthLevel>,
+    pub asks: Vec<NormalizedDepthLevel>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConsolidatedDepthQuery {
+    pub levels: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConsolidatedDepthResponse {
+    pub venues: Vec<String>,
+    pub bids: Vec<DepthLevel>,
+    pub asks: Vec<DepthLevel>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VenueQuoteResponse {
+    pub venue: String,
+    pub price: f64,
+    pub quantity: f64,
+}
+
+// `spread` is ask minus bid, negative when one venue's bid crosses
+// another's ask.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NbboResponse {
+    pub symbol: String,
+    pub bid: Option<VenueQuoteResponse>,
+    pub ask: Option<VenueQuoteResponse>,
+    pub spread: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DepthResyncQuery {
+    pub max_notional: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DepthResyncResponse {
+    pub sequence: u64,
+    // True when `max_notional` left levels out on either side.
+    pub truncated: bool,
+    pub bids: Vec<DepthLevel>,
+    pub asks: Vec<DepthLevel>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DepthLevel {
     pub price: f64,
     pub quantity: f64,
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeadManRequest {
+    pub user_id: String,
+    // 0 disarms the switch.
+    pub timeout_ms: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeadManHeartbeatRequest {
+    pub user_id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeadManResponse {
+    pub user_id: String,
+    pub armed: bool,
+    pub expires_at: Option<u64>,
+}
+
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConnectionResponse {
+    pub id: u64,
+    pub symbol: String,
+    pub user_id: Option<String>,
+    pub channels: Vec<String>,
+    pub age_ms: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DisconnectResponse {
+    pub id: u64,
+    pub disconnecting: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct KillQuery {
+    // Also cancel every resting order once matching has stopped.
+    #[serde(default)]
+    pub cancel: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KillSwitchResponse {
+    pub killed: bool,
+    // Resting orders cancelled per symbol; empty unless `cancel` was set.
+    pub cancelled_orders: BTreeMap<String, usize>,
+}
+
+
+#[derive(Debug, Deserialize)]
+pub struct WindowQuery {
+    pub window: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VolumeProfileQuery {
+    pub window: Option<String>,
+    pub buckets: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct QuoteQuery {
+    pub side: String,
+    pub notional: f64,
+}
+
+// `notional_filled` falls short of `notional` when `complete` is false.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QuoteResponse {
+    pub side: Side,
+    pub notional: f64,
+    pub notional_filled: f64,
+    pub quantity: f64,
+    pub average_price: Option<f64>,
+    pub complete: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResilienceQuery {
+    pub side: String,
+    pub quantity: f64,
+}
+
+// Prices are `null` when the opposite side is empty.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResilienceResponse {
+    pub side: Side,
+    pub quantity: f64,
+    pub filled_quantity: f64,
+    pub average_price: Option<f64>,
+    pub start_price: Option<f64>,
+    pub end_price: Option<f64>,
+    pub price_move: f64,
+    pub price_move_pct: Option<f64>,
+    pub levels_swept: usize,
+    pub levels_remaining: usize,
+    pub quantity_remaining: f64,
+    pub resulting_best: Option<f64>,
+    pub complete: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuctionIndicativeResponse {
+    pub indicative_price: Option<f64>,
+    pub matched_volume: f64,
+    pub imbalance: f64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DepthSlopeQuery {
+    pub levels: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DepthSlopeResponse {
+    pub levels: usize,
+    pub bid_slope: Option<f64>,
+    pub ask_slope: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VolumeBucket {
+    pub price: f64,
+    pub volume: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VolumeProfileResponse {
+    pub window: String,
+    pub buckets: Vec<VolumeBucket>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CandlesQuery {
+    pub interval: Option<String>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CandleResponse {
+    pub start_ms: u64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CandlesResponse {
+    pub symbol: String,
+    pub interval: CandleInterval,
+    pub candles: Vec<CandleResponse>,
+}
+
+// The book's active matching rules, so clients don't have to guess them.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConfigResponse {
+    pub symbol: String,
+    // Smallest quantity step the engine tracks.
+    pub lot_size: f64,
+    pub tick_size: f64,
+    pub precision_policy: PrecisionPolicy,
+    pub display_quantity_decimals: Option<u32>,
+    pub order_types: Vec<String>,
+    // The exact types and time-in-force combinations allowed; `None` when
+    // the book allows everything.
+    pub allowed_order_kinds: Option<Vec<OrderKind>>,
+    pub fees: FeeSchedule,
+    pub tie_break: TieBreak,
+    pub designated_maker: Option<String>,
+    pub prevent_same_source_match: bool,
+    pub self_trade_policy: Option<SelfTradePolicy>,
+    pub locked_market_policy: LockedMarketPolicy,
+    pub min_resting_ms: Option<u64>,
+    pub max_staleness_ms: Option<u64>,
+    pub max_source_silence_ms: Option<u64>,
+    pub l3_enabled: bool,
+    pub match_events: bool,
+    pub envelope_responses: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TradesQuery {
+    #[serde(alias = "since")]
+    pub since_ms: Option<u64>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TapeTrade {
+    pub bid_order_id: String,
+    pub ask_order_id: String,
+    pub price: f64,
+    pub quantity: f64,
+    pub timestamp: u64,
+    pub executed_at: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TradeExportQuery {
+    pub format: Option<String>,
+    pub since_ms: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TradesResponse {
+    pub trades: Vec<TapeTrade>,
+    // More trades matched than `limit` allowed; poll again from the last timestamp.
+    pub has_more: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LeaderboardQuery {
+    pub metric: Option<String>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LeaderboardEntry {
+    pub rank: usize,
+    pub user_id: String,
+    pub volume: f64,
+    // Cash flow plus the open position marked at the last trade price.
+    pub pnl: f64,
+    pub orders: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LeaderboardResponse {
+    pub metric: String,
+    pub entries: Vec<LeaderboardEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccountQuery {
+    pub user_id: String,
+}
+
+// Fills where the user's order was resting. The averages are `None` until
+// there is a fill on that side, and the spread and edge until both sides
+// have one.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MakerFills {
+    pub bought_quantity: f64,
+    pub average_buy_price: Option<f64>,
+    pub sold_quantity: f64,
+    pub average_sell_price: Option<f64>,
+    // Average sell price minus average buy price.
+    pub captured_spread: Option<f64>,
+    pub fees: f64,
+    // Captured spread times the quantity made on both sides, less `fees`.
+    pub net_edge: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccountResponse {
+    pub user_id: String,
+    pub orders: u64,
+    pub volume: f64,
+    pub position: f64,
+    pub cash: f64,
+    // Cash flow plus the open position marked at the last trade price.
+    pub pnl: f64,
+    pub maker: MakerFills,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VolatilityResponse {
+    pub window: String,
+    pub volatility: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TwapResponse {
+    pub window: String,
+    pub twap: Option<f64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sides_convert_both_ways() {
+        for (side, order_side) in [(Side::Buy, OrderSide::Bid), (Side::Sell, OrderSide::Ask)] {
+            assert_eq!(OrderSide::from(side), order_side);
+            assert_eq!(Side::from(order_side), side);
+            assert_eq!(Side::from(OrderSide::from(side)), side);
+        }
+    }
+
+    #[test]
+    fn order_ids_round_trip_for_quoted_symbols() {
+        let order_id = format_order_id("BTC-USDT", 42);
+        assert_eq!(order_id, "BTC-USDT-000042");
+        assert_eq!(parse_order_id(&order_id), Some(("BTC-USDT".to_string(), 42)));
+        assert_eq!(parse_order_id("BTC-000042"), Some(("BTC".to_string(), 42)));
+        assert_eq!(parse_order_id("BTC--000042"), None);
+    }
+}
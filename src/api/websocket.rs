@@ -1,14 +1,27 @@
-use actix::{Actor, ActorContext, AsyncContext, Handler, Message, StreamHandler};
+use actix::{Actor, ActorContext, AsyncContext, Handler, StreamHandler};
 use actix_web::{web, Error, HttpRequest, HttpResponse};
 use actix_web_actors::ws;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use crate::engine::orderbook::OrderBook;
+use crate::api::auth::ApiKeys;
+use crate::api::books::SymbolBook;
+use crate::api::connections::{ConnectionRegistry, Disconnect};
+use crate::api::types::format_order_id;
+use crate::engine::orderbook::{DepthSide, OrderBook};
+use crate::engine::price::Price;
+use crate::events::OrderBookEvent;
+use tokio::sync::broadcast::error::RecvError;
 
 const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
 const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+// Least time between two rounds of depth frames; changes in between are
+// sent together.
+const DEPTH_MIN_INTERVAL: Duration = Duration::from_millis(100);
+// Levels per side a session's depth covers.
+const DEPTH_LEVELS: usize = 20;
 
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,9 +29,20 @@ const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
 pub enum WsMessage {
    
     DepthUpdate {
+        sequence: u64,
         bids: Vec<(f64, f64)>,
         asks: Vec<(f64, f64)>,
     },
+
+    // One level of the session's depth changed since the last frame:
+    // `new_quantity` rests at `price` now, zero once the level is gone or
+    // has dropped out of the top `DEPTH_LEVELS`.
+    DepthDelta {
+        sequence: u64,
+        side: String,
+        price: f64,
+        new_quantity: f64,
+    },
   
     TradeExecuted {
         price: f64,
@@ -31,6 +55,31 @@ pub enum WsMessage {
         order_id: String,
         status: String,
         filled_quantity: f64,
+        remaining_quantity: f64,
+        client_metadata: Option<String>,
+    },
+
+    // One fill as seen by a subscribed user, who may be either side of it.
+    // `role` is `Maker` or `Taker`; `fee` is what that side paid.
+    Match {
+        role: String,
+        order_id: String,
+        maker_order_id: String,
+        taker_order_id: String,
+        aggressor_side: String,
+        price: f64,
+        quantity: f64,
+        fee: f64,
+        timestamp: u64,
+    },
+
+    // Market-by-order frame on the `l3` channel. Never names the owner.
+    BookOrder {
+        action: String,
+        order_id: String,
+        side: String,
+        price: f64,
+        quantity: f64,
     },
     
     StatsUpdate {
@@ -40,15 +89,77 @@ pub enum WsMessage {
         volume_24h: f64,
     },
 
+    Subscribed {
+        channel: String,
+    },
+
+    // Sent to every session when a stale book is auto-cleared.
+    BookCleared {
+        symbol: String,
+        last_updated_ms: u64,
+        cleared_orders: usize,
+    },
+
+    Error {
+        message: String,
+    },
+
+    // Liveness frame sent on a fixed schedule when keepalives are enabled,
+    // whether or not anything changed; `sequence` is the book's current one.
+    Heartbeat {
+        server_time_ms: u64,
+        sequence: u64,
+    },
+
     Pong,
 }
 
+// JSON commands a client can send, e.g.
+// `{"action":"subscribe","channel":"orders","user_id":"alice"}`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum ClientCommand {
+    Subscribe {
+        channel: String,
+        user_id: Option<String>,
+    },
+    Unsubscribe {
+        channel: String,
+    },
+}
+
 
 pub struct OrderBookWebSocket {
     
     hb: Instant,
     
     orderbook: Arc<OrderBook>,
+    
+    // User resolved from the API key presented at connect time, if any.
+    authenticated_user: Option<String>,
+    
+    // User whose order updates this session receives.
+    order_subscription: Option<String>,
+    
+    // Receives per-order book events; only possible when the book publishes them.
+    l3_subscription: bool,
+    
+    registry: Option<Arc<ConnectionRegistry>>,
+    
+    // Assigned by `registry` once the session has started.
+    connection_id: Option<u64>,
+    
+    // Depth as of the last frame, which deltas are taken against, with its
+    // sequence and send time.
+    depth_bids: DepthSide,
+    depth_asks: DepthSide,
+    depth_sequence: u64,
+    depth_sent_at: Option<Instant>,
+    // Deltas are scheduled for the end of `DEPTH_MIN_INTERVAL`.
+    depth_pending: bool,
+    
+    // Last stats sent, so an unchanged book sends none.
+    last_stats: Option<(Option<f64>, Option<f64>, f64)>,
 }
 
 impl OrderBookWebSocket {
@@ -56,6 +167,94 @@ impl OrderBookWebSocket {
         Self {
             hb: Instant::now(),
             orderbook,
+            authenticated_user: None,
+            order_subscription: None,
+            l3_subscription: false,
+            registry: None,
+            connection_id: None,
+            depth_bids: Vec::new(),
+            depth_asks: Vec::new(),
+            depth_sequence: 0,
+            depth_sent_at: None,
+            depth_pending: false,
+            last_stats: None,
+        }
+    }
+
+    pub fn with_authenticated_user(mut self, user_id: Option<String>) -> Self {
+        self.authenticated_user = user_id;
+        self
+    }
+
+    // The session must hold one of `registry`'s slots; it gives it back
+    // when it stops.
+    pub fn with_registry(mut self, registry: Arc<ConnectionRegistry>) -> Self {
+        self.registry = Some(registry);
+        self
+    }
+
+    // Depth and stats are pushed to every session; orders and l3 only on request.
+    fn channels(&self) -> Vec<String> {
+        let mut channels = vec!["depth".to_string(), "stats".to_string()];
+        if self.order_subscription.is_some() {
+            channels.push("orders".to_string());
+        }
+        if self.l3_subscription {
+            channels.push("l3".to_string());
+        }
+        channels
+    }
+
+    fn update_registry(&self) {
+        if let (Some(registry), Some(id)) = (&self.registry, self.connection_id) {
+            registry.set_channels(id, self.channels());
+        }
+    }
+
+    fn send_message(&self, ctx: &mut ws::WebsocketContext<Self>, msg: &WsMessage) {
+        if let Ok(json) = serde_json::to_string(msg) {
+            ctx.text(json);
+        }
+    }
+
+    fn send_error(&self, ctx: &mut ws::WebsocketContext<Self>, message: impl Into<String>) {
+        self.send_message(ctx, &WsMessage::Error { message: message.into() });
+    }
+
+    fn handle_command(&mut self, command: ClientCommand, ctx: &mut ws::WebsocketContext<Self>) {
+        match command {
+            ClientCommand::Subscribe { channel, user_id } if channel == "orders" => {
+                let user_id = match user_id.or_else(|| self.authenticated_user.clone()) {
+                    Some(user_id) => user_id,
+                    None => return self.send_error(ctx, "orders channel requires a user_id"),
+                };
+                // Users may only watch their own orders.
+                if self.authenticated_user.as_deref() != Some(user_id.as_str()) {
+                    return self.send_error(ctx, "not authorized for this user's orders");
+                }
+                self.order_subscription = Some(user_id);
+                self.update_registry();
+                self.send_message(ctx, &WsMessage::Subscribed { channel });
+            }
+            ClientCommand::Unsubscribe { channel } if channel == "orders" => {
+                self.order_subscription = None;
+                self.update_registry();
+            }
+            ClientCommand::Subscribe { channel, .. } if channel == "l3" => {
+                if !self.orderbook.config().order_level_events {
+                    return self.send_error(ctx, "l3 channel is not enabled");
+                }
+                self.l3_subscription = true;
+                self.update_registry();
+                self.send_message(ctx, &WsMessage::Subscribed { channel });
+            }
+            ClientCommand::Unsubscribe { channel } if channel == "l3" => {
+                self.l3_subscription = false;
+                self.update_registry();
+            }
+            ClientCommand::Subscribe { channel, .. } | ClientCommand::Unsubscribe { channel } => {
+                self.send_error(ctx, format!("unknown channel {}", channel));
+            }
         }
     }
 
@@ -74,31 +273,99 @@ impl OrderBookWebSocket {
     }
 
     
-    fn send_depth(&self, ctx: &mut ws::WebsocketContext<Self>) {
-        let (bids, asks) = self.orderbook.get_market_depth(20);
+    // Depth as this session reports it, with the sequence it was read at.
+    fn read_depth(&self) -> (u64, DepthSide, DepthSide) {
+        let (sequence, bids, asks) = self.orderbook.depth_with_sequence(DEPTH_LEVELS);
+        let config = self.orderbook.config();
+        let bids = bids.into_iter().map(|(p, q)| (p, config.display_quantity(q))).collect();
+        let asks = asks.into_iter().map(|(p, q)| (p, config.display_quantity(q))).collect();
+        (sequence, bids, asks)
+    }
+
+    // Full snapshot, which later deltas build on.
+    fn send_depth(&mut self, ctx: &mut ws::WebsocketContext<Self>) {
+        let (sequence, bids, asks) = self.read_depth();
+        self.depth_bids = bids.clone();
+        self.depth_asks = asks.clone();
+        self.depth_sequence = sequence;
+        self.depth_sent_at = Some(Instant::now());
         
-        let msg = WsMessage::DepthUpdate { bids, asks };
+        let msg = WsMessage::DepthUpdate { sequence, bids, asks };
         
         if let Ok(json) = serde_json::to_string(&msg) {
             ctx.text(json);
         }
     }
 
+    // A `DepthDelta` for every level that differs from the last frames.
+    fn send_depth_deltas(&mut self, ctx: &mut ws::WebsocketContext<Self>) {
+        let (sequence, bids, asks) = self.read_depth();
+        self.depth_sequence = sequence;
+        self.depth_sent_at = Some(Instant::now());
+        
+        for (side, previous, current) in [("Bid", &self.depth_bids, &bids), ("Ask", &self.depth_asks, &asks)] {
+            for (price, new_quantity) in depth_deltas(previous, current) {
+                let msg = WsMessage::DepthDelta { sequence, side: side.to_string(), price, new_quantity };
+                self.send_message(ctx, &msg);
+            }
+        }
+        self.depth_bids = bids;
+        self.depth_asks = asks;
+    }
+
+    // Sends deltas for a change the last frames didn't include: straight
+    // away, or once `DEPTH_MIN_INTERVAL` has passed since them. The depth is
+    // read when they are sent, so they cover every change up to then.
+    fn depth_changed(&mut self, sequence: u64, ctx: &mut ws::WebsocketContext<Self>) {
+        if sequence <= self.depth_sequence || self.depth_pending {
+            return;
+        }
+        let wait = self
+            .depth_sent_at
+            .map_or(Duration::ZERO, |sent_at| DEPTH_MIN_INTERVAL.saturating_sub(sent_at.elapsed()));
+        if wait.is_zero() {
+            self.send_depth_deltas(ctx);
+            return;
+        }
+        self.depth_pending = true;
+        ctx.run_later(wait, |act, ctx| {
+            act.depth_pending = false;
+            act.send_depth_deltas(ctx);
+        });
+    }
+
     
-    fn send_stats(&self, ctx: &mut ws::WebsocketContext<Self>) {
+    fn send_stats(&mut self, ctx: &mut ws::WebsocketContext<Self>) {
         let stats = self.orderbook.get_stats();
+        self.last_stats = Some((stats.best_bid, stats.best_ask, stats.volume_24h));
         
         let msg = WsMessage::StatsUpdate {
             best_bid: stats.best_bid,
             best_ask: stats.best_ask,
             spread: stats.spread,
-            volume_24h: stats.total_volume_traded,
+            volume_24h: stats.volume_24h,
         };
         
         if let Ok(json) = serde_json::to_string(&msg) {
             ctx.text(json);
         }
     }
+
+    fn send_stats_if_changed(&mut self, ctx: &mut ws::WebsocketContext<Self>) {
+        let stats = self.orderbook.get_stats();
+        if self.last_stats != Some((stats.best_bid, stats.best_ask, stats.volume_24h)) {
+            self.send_stats(ctx);
+        }
+    }
+
+    fn send_heartbeat(&self, ctx: &mut ws::WebsocketContext<Self>) {
+        let server_time_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        let msg = WsMessage::Heartbeat { server_time_ms, sequence: self.orderbook.sequence() };
+        self.send_message(ctx, &msg);
+    }
 }
 
 impl Actor for OrderBookWebSocket {
@@ -108,23 +375,69 @@ impl Actor for OrderBookWebSocket {
         println!("WebSocket connection established");
         self.hb(ctx);
         
+        if let Some(registry) = &self.registry {
+            let connected_at_ms = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as u64;
+            self.connection_id = Some(registry.register(
+                self.orderbook.config().symbol.clone(),
+                self.authenticated_user.clone(),
+                self.channels(),
+                connected_at_ms,
+                ctx.address().recipient(),
+            ));
+        }
         
-        self.send_depth(ctx);
-        self.send_stats(ctx);
-        
-        
-        ctx.run_interval(Duration::from_millis(100), |act, ctx| {
-            act.send_depth(ctx);
+        // A lag is passed on so the session can drop itself; the book never
+        // waits for a slow reader.
+        let events = futures_util::stream::unfold(self.orderbook.subscribe(), |mut rx| async move {
+            match rx.recv().await {
+                Err(RecvError::Closed) => None,
+                received => Some((received, rx)),
+            }
         });
+        ctx.add_stream(events);
         
+        // Taken after subscribing, so no change can fall between the
+        // snapshot and the first delta.
+        self.send_depth(ctx);
+        self.send_stats(ctx);
         
+        // Stats include the rolling 24h volume, which moves with the clock,
+        // so they are checked on a timer and sent when they differ.
         ctx.run_interval(Duration::from_secs(1), |act, ctx| {
-            act.send_stats(ctx);
+            act.send_stats_if_changed(ctx);
         });
+        
+        if let Some(interval) = self.registry.as_ref().and_then(|registry| registry.keepalive_interval()) {
+            ctx.run_interval(interval, |act, ctx| {
+                act.send_heartbeat(ctx);
+            });
+        }
     }
 
     fn stopped(&mut self, _: &mut Self::Context) {
         println!("WebSocket connection closed");
+        if let Some(registry) = &self.registry {
+            if let Some(id) = self.connection_id {
+                registry.unregister(id);
+            }
+            registry.release_slot();
+        }
+    }
+}
+
+impl Handler<Disconnect> for OrderBookWebSocket {
+    type Result = ();
+
+    fn handle(&mut self, _: Disconnect, ctx: &mut Self::Context) {
+        log::info!("Disconnecting WebSocket connection {:?} on request", self.connection_id);
+        ctx.close(Some(ws::CloseReason {
+            code: ws::CloseCode::Policy,
+            description: Some("disconnected by operator".to_string()),
+        }));
+        ctx.stop();
     }
 }
 
@@ -139,6 +452,12 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for OrderBookWebSocke
             Ok(ws::Message::Pong(_)) => {
                 self.hb = Instant::now();
             }
+            Ok(ws::Message::Text(text)) if text.trim_start().starts_with('{') => {
+                match serde_json::from_str::<ClientCommand>(&text) {
+                    Ok(command) => self.handle_command(command, ctx),
+                    Err(e) => self.send_error(ctx, format!("invalid command: {}", e)),
+                }
+            }
             Ok(ws::Message::Text(text)) => {
                 // Handle client commands
                 match text.trim() {
@@ -162,12 +481,150 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for OrderBookWebSocke
 }
 
 
+impl StreamHandler<Result<OrderBookEvent, RecvError>> for OrderBookWebSocket {
+    fn handle(&mut self, event: Result<OrderBookEvent, RecvError>, ctx: &mut Self::Context) {
+        let event = match event {
+            Ok(event) => event,
+            // It has already missed events, so anything sent from here on
+            // would be inconsistent; make it reconnect and resync instead.
+            Err(skipped) => {
+                log::warn!("Dropping lagging WebSocket connection {:?}: {}", self.connection_id, skipped);
+                ctx.close(Some(ws::CloseReason {
+                    code: ws::CloseCode::Again,
+                    description: Some("client too slow, events were skipped".to_string()),
+                }));
+                ctx.stop();
+                return;
+            }
+        };
+        match event {
+            OrderBookEvent::OrderUpdate(update) => {
+                if self.order_subscription.as_deref() != Some(update.user_id.as_str()) {
+                    return;
+                }
+                let msg = WsMessage::OrderUpdate {
+                    order_id: format_order_id(&self.orderbook.config().symbol, update.order_id),
+                    status: format!("{:?}", update.status),
+                    filled_quantity: update.filled_quantity,
+                    remaining_quantity: update.remaining_quantity,
+                    client_metadata: update.client_metadata,
+                };
+                self.send_message(ctx, &msg);
+            }
+            OrderBookEvent::BookCleared(cleared) => {
+                let msg = WsMessage::BookCleared {
+                    symbol: self.orderbook.config().symbol.clone(),
+                    last_updated_ms: cleared.last_updated_ms,
+                    cleared_orders: cleared.cleared_orders,
+                };
+                self.send_message(ctx, &msg);
+            }
+            OrderBookEvent::BookOrder(book_order) => {
+                if !self.l3_subscription {
+                    return;
+                }
+                let msg = WsMessage::BookOrder {
+                    action: format!("{:?}", book_order.action),
+                    order_id: format_order_id(&self.orderbook.config().symbol, book_order.order_id),
+                    side: format!("{:?}", book_order.side),
+                    price: book_order.price,
+                    quantity: self.orderbook.config().display_quantity(book_order.quantity),
+                };
+                self.send_message(ctx, &msg);
+            }
+            OrderBookEvent::Match(fill) => {
+                let subscription = match self.order_subscription.as_deref() {
+                    Some(user_id) => user_id,
+                    None => return,
+                };
+                let symbol = &self.orderbook.config().symbol;
+                // A user trading against themselves gets both frames.
+                let perspectives = [
+                    ("Maker", fill.maker_user_id == subscription, fill.maker_order_id, fill.maker_fee),
+                    ("Taker", fill.taker_user_id == subscription, fill.taker_order_id, fill.taker_fee),
+                ];
+                for (role, mine, order_id, fee) in perspectives {
+                    if !mine {
+                        continue;
+                    }
+                    let msg = WsMessage::Match {
+                        role: role.to_string(),
+                        order_id: format_order_id(symbol, order_id),
+                        maker_order_id: format_order_id(symbol, fill.maker_order_id),
+                        taker_order_id: format_order_id(symbol, fill.taker_order_id),
+                        aggressor_side: format!("{:?}", fill.aggressor),
+                        price: fill.price,
+                        quantity: self.orderbook.config().display_quantity(fill.quantity),
+                        fee,
+                        timestamp: fill.timestamp,
+                    };
+                    self.send_message(ctx, &msg);
+                }
+            }
+            OrderBookEvent::Trade(trade) => {
+                // Opening-auction prints have no taker.
+                let side = trade.taker_side.map_or("Auction".to_string(), |side| format!("{:?}", side));
+                let msg = WsMessage::TradeExecuted {
+                    price: trade.price,
+                    quantity: self.orderbook.config().display_quantity(trade.quantity),
+                    side,
+                    timestamp: trade.timestamp,
+                };
+                self.send_message(ctx, &msg);
+            }
+            OrderBookEvent::DepthChanged(changed) => self.depth_changed(changed.sequence, ctx),
+            // Compliance signal for operators, not something clients act on.
+            OrderBookEvent::TradeThrough(_) => {}
+        }
+    }
+
+    // The event stream ending must not close the client connection.
+    fn finished(&mut self, _ctx: &mut Self::Context) {}
+}
+
+
+// Levels whose quantity differs between two depth reads of one side, as
+// (price, quantity now); zero for a level no longer in `current`.
+fn depth_deltas(previous: &[(f64, f64)], current: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    let before: HashMap<Price, f64> = previous.iter().map(|&(p, q)| (Price::new(p), q)).collect();
+    let now: HashMap<Price, f64> = current.iter().map(|&(p, q)| (Price::new(p), q)).collect();
+    let removed = previous
+        .iter()
+        .filter(|(p, _)| !now.contains_key(&Price::new(*p)))
+        .map(|&(p, _)| (p, 0.0));
+    let changed = current
+        .iter()
+        .filter(|(p, q)| before.get(&Price::new(*p)) != Some(q))
+        .copied();
+    removed.chain(changed).collect()
+}
+
+// Streams the book named by `?symbol=`, or the default book.
 pub async fn ws_index(
     req: HttpRequest,
     stream: web::Payload,
-    orderbook: web::Data<Arc<OrderBook>>,
+    orderbook: SymbolBook,
+    api_keys: web::Data<ApiKeys>,
+    registry: web::Data<ConnectionRegistry>,
 ) -> Result<HttpResponse, Error> {
-    let ws = OrderBookWebSocket::new(orderbook.get_ref().clone());
-    let resp = ws::start(ws, &req, stream)?;
-    Ok(resp)
+    if !registry.try_acquire_slot() {
+        log::warn!(
+            "Refusing WebSocket connection: limit of {} reached",
+            registry.max_connections().unwrap_or(usize::MAX)
+        );
+        return Ok(HttpResponse::ServiceUnavailable().json("Too many WebSocket connections"));
+    }
+    
+    let registry = registry.into_inner();
+    let ws = OrderBookWebSocket::new(Arc::clone(&orderbook))
+        .with_authenticated_user(api_keys.authenticate(&req))
+        .with_registry(registry.clone());
+    match ws::start(ws, &req, stream) {
+        Ok(resp) => Ok(resp),
+        Err(e) => {
+            // The session never started, so it won't release its slot itself.
+            registry.release_slot();
+            Err(e)
+        }
+    }
 }
\ No newline at end of file
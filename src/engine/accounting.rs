@@ -0,0 +1,201 @@
+use std::collections::BTreeMap;
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::engine::order::OrderSide;
+
+// One user's running totals since the book started (or was last cleared).
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct UserAccount {
+    pub orders: u64,
+    // Notional traded, on either side of the fill.
+    pub volume: f64,
+    // Net base quantity bought minus sold.
+    pub position: f64,
+    // Quote received minus quote paid, fees included.
+    pub cash: f64,
+    // Fills of the user's resting orders, by side, for the captured spread.
+    #[serde(default)]
+    pub maker_bought: f64,
+    #[serde(default)]
+    pub maker_buy_notional: f64,
+    #[serde(default)]
+    pub maker_sold: f64,
+    #[serde(default)]
+    pub maker_sell_notional: f64,
+    // Fees paid on those fills; negative for a net rebate.
+    #[serde(default)]
+    pub maker_fees: f64,
+}
+
+impl UserAccount {
+    // Realised plus unrealised PnL, with the open position marked at `mark_price`.
+    pub fn net_pnl(&self, mark_price: f64) -> f64 {
+        self.cash + self.position * mark_price
+    }
+
+    pub fn maker_buy_price(&self) -> Option<f64> {
+        (self.maker_bought > 0.0).then(|| self.maker_buy_notional / self.maker_bought)
+    }
+
+    pub fn maker_sell_price(&self) -> Option<f64> {
+        (self.maker_sold > 0.0).then(|| self.maker_sell_notional / self.maker_sold)
+    }
+
+    // Average maker sell price minus average maker buy price: what each unit
+    // bought and sold back by resting orders earned. Needs maker fills on
+    // both sides.
+    pub fn captured_spread(&self) -> Option<f64> {
+        Some(self.maker_sell_price()? - self.maker_buy_price()?)
+    }
+
+    // The captured spread over the quantity made on both sides, less every
+    // maker fee paid.
+    pub fn net_maker_edge(&self) -> Option<f64> {
+        Some(self.captured_spread()? * self.maker_bought.min(self.maker_sold) - self.maker_fees)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeaderboardMetric {
+    Volume,
+    Pnl,
+    Orders,
+}
+
+impl LeaderboardMetric {
+    pub fn parse(metric: &str) -> Option<Self> {
+        match metric {
+            "volume" => Some(Self::Volume),
+            "pnl" => Some(Self::Pnl),
+            "orders" => Some(Self::Orders),
+            _ => None,
+        }
+    }
+}
+
+// Per-user order and fill accounting, updated by the book as orders arrive
+// and trade.
+#[derive(Debug, Default)]
+pub struct UserAccounts {
+    accounts: DashMap<String, UserAccount>,
+}
+
+impl UserAccounts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Each `record_*` returns the user's account as it now stands.
+    pub fn record_order(&self, user_id: &str) -> UserAccount {
+        let mut account = self.accounts.entry(user_id.to_string()).or_default();
+        account.orders += 1;
+        *account
+    }
+
+    // `maker` is true when the user's order was the one resting.
+    pub fn record_fill(&self, user_id: &str, side: OrderSide, price: f64, quantity: f64, fee: f64, maker: bool) -> UserAccount {
+        let mut account = self.accounts.entry(user_id.to_string()).or_default();
+        let notional = price * quantity;
+        account.volume += notional;
+        if maker {
+            match side {
+                OrderSide::Bid => {
+                    account.maker_bought += quantity;
+                    account.maker_buy_notional += notional;
+                }
+                OrderSide::Ask => {
+                    account.maker_sold += quantity;
+                    account.maker_sell_notional += notional;
+                }
+            }
+            account.maker_fees += fee;
+        }
+        match side {
+            OrderSide::Bid => {
+                account.position += quantity;
+                account.cash -= notional;
+            }
+            OrderSide::Ask => {
+                account.position -= quantity;
+                account.cash += notional;
+            }
+        }
+        account.cash -= fee;
+        *account
+    }
+
+    pub fn get(&self, user_id: &str) -> Option<UserAccount> {
+        self.accounts.get(user_id).map(|account| *account)
+    }
+
+    // The top `limit` users by `metric`, best first, each with their net PnL
+    // marked at `mark_price`. Ties keep user id order.
+    pub fn leaderboard(&self, metric: LeaderboardMetric, mark_price: f64, limit: usize) -> Vec<(String, UserAccount, f64)> {
+        let mut ranked: Vec<(String, UserAccount, f64)> = self
+            .accounts
+            .iter()
+            .map(|entry| (entry.key().clone(), *entry.value(), entry.net_pnl(mark_price)))
+            .collect();
+        let score = |(_, account, pnl): &(String, UserAccount, f64)| match metric {
+            LeaderboardMetric::Volume => account.volume,
+            LeaderboardMetric::Pnl => *pnl,
+            LeaderboardMetric::Orders => account.orders as f64,
+        };
+        ranked.sort_by(|a, b| score(b).total_cmp(&score(a)).then_with(|| a.0.cmp(&b.0)));
+        ranked.truncate(limit);
+        ranked
+    }
+
+    // Overwrites one user's account, as an event log replays it.
+    pub fn set(&self, user_id: &str, account: UserAccount) {
+        self.accounts.insert(user_id.to_string(), account);
+    }
+
+    pub fn snapshot(&self) -> BTreeMap<String, UserAccount> {
+        self.accounts.iter().map(|entry| (entry.key().clone(), *entry.value())).collect()
+    }
+
+    pub fn restore(&self, accounts: BTreeMap<String, UserAccount>) {
+        self.accounts.clear();
+        for (user_id, account) in accounts {
+            self.accounts.insert(user_id, account);
+        }
+    }
+
+    pub fn clear(&self) {
+        self.accounts.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(ranked: &[(String, UserAccount, f64)]) -> Vec<&str> {
+        ranked.iter().map(|(user_id, _, _)| user_id.as_str()).collect()
+    }
+
+    #[test]
+    fn leaderboard_ranks_by_the_chosen_metric() {
+        let accounts = UserAccounts::new();
+        for _ in 0..3 {
+            accounts.record_order("carol");
+        }
+        accounts.record_order("alice");
+        accounts.record_order("bob");
+        // Alice buys 2 at 100 from Bob; Carol buys 1 at 90 from Bob.
+        accounts.record_fill("alice", OrderSide::Bid, 100.0, 2.0, 0.0, false);
+        accounts.record_fill("bob", OrderSide::Ask, 100.0, 2.0, 0.0, true);
+        accounts.record_fill("carol", OrderSide::Bid, 90.0, 1.0, 0.0, false);
+        accounts.record_fill("bob", OrderSide::Ask, 90.0, 1.0, 0.0, true);
+
+        assert_eq!(names(&accounts.leaderboard(LeaderboardMetric::Volume, 100.0, 10)), vec!["bob", "alice", "carol"]);
+        assert_eq!(names(&accounts.leaderboard(LeaderboardMetric::Orders, 100.0, 10)), vec!["carol", "alice", "bob"]);
+        // Marked at 100, Carol is up 10, Alice flat and Bob short at a loss.
+        let by_pnl = accounts.leaderboard(LeaderboardMetric::Pnl, 100.0, 2);
+        assert_eq!(names(&by_pnl), vec!["carol", "alice"]);
+        assert_eq!(by_pnl[0].2, 10.0);
+    }
+}
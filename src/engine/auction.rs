@@ -0,0 +1,71 @@
+use serde::{Deserialize, Serialize};
+
+// Uncrossing result for a call auction: the single price at which the most
+// volume would trade if the book were matched now.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AuctionInfo {
+    // `None` when no bid reaches any ask.
+    pub indicative_price: Option<f64>,
+    pub matched_volume: f64,
+    // Buy volume minus sell volume willing to trade at the indicative price;
+    // positive means buyers would be left over.
+    pub imbalance: f64,
+}
+
+impl AuctionInfo {
+    pub fn empty() -> Self {
+        Self {
+            indicative_price: None,
+            matched_volume: 0.0,
+            imbalance: 0.0,
+        }
+    }
+}
+
+// Picks the clearing price from `(price, quantity)` levels on each side, in
+// any order. The price maximising matched volume wins; ties go to the
+// smallest absolute imbalance, then to the lower price.
+pub fn clearing_price(bids: &[(f64, f64)], asks: &[(f64, f64)]) -> AuctionInfo {
+    let mut bids = bids.to_vec();
+    let mut asks = asks.to_vec();
+    bids.sort_by(|a, b| a.0.total_cmp(&b.0));
+    asks.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut candidates: Vec<f64> = bids.iter().chain(asks.iter()).map(|(price, _)| *price).collect();
+    candidates.sort_by(f64::total_cmp);
+    candidates.dedup();
+
+    // Walking prices upwards, bids below the price drop out of the buy
+    // volume and asks at or below it join the sell volume.
+    let mut buy_volume: f64 = bids.iter().map(|(_, quantity)| quantity).sum();
+    let mut sell_volume = 0.0;
+    let (mut next_bid, mut next_ask) = (0, 0);
+    let mut best = AuctionInfo::empty();
+
+    for price in candidates {
+        while next_bid < bids.len() && bids[next_bid].0 < price {
+            buy_volume -= bids[next_bid].1;
+            next_bid += 1;
+        }
+        while next_ask < asks.len() && asks[next_ask].0 <= price {
+            sell_volume += asks[next_ask].1;
+            next_ask += 1;
+        }
+
+        let matched_volume = buy_volume.min(sell_volume);
+        let imbalance = buy_volume - sell_volume;
+        let better = matched_volume > best.matched_volume
+            || (matched_volume == best.matched_volume
+                && best.indicative_price.is_some()
+                && imbalance.abs() < best.imbalance.abs());
+        if matched_volume > 0.0 && better {
+            best = AuctionInfo {
+                indicative_price: Some(price),
+                matched_volume,
+                imbalance,
+            };
+        }
+    }
+
+    best
+}
@@ -0,0 +1,161 @@
+// OHLCV candles built from the trade tape, one series per interval.
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use crate::engine::trade::Trade;
+
+// Candles kept per interval; also the most `candles` returns.
+pub const CANDLE_CAPACITY: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CandleInterval {
+    #[serde(rename = "1m")]
+    OneMinute,
+    #[serde(rename = "5m")]
+    FiveMinutes,
+    #[serde(rename = "1h")]
+    OneHour,
+}
+
+impl CandleInterval {
+    pub const ALL: [CandleInterval; 3] = [CandleInterval::OneMinute, CandleInterval::FiveMinutes, CandleInterval::OneHour];
+
+    pub fn parse(interval: &str) -> Option<Self> {
+        match interval.trim() {
+            "1m" => Some(CandleInterval::OneMinute),
+            "5m" => Some(CandleInterval::FiveMinutes),
+            "1h" => Some(CandleInterval::OneHour),
+            _ => None,
+        }
+    }
+
+    pub fn as_ms(&self) -> u64 {
+        match self {
+            CandleInterval::OneMinute => 60 * 1000,
+            CandleInterval::FiveMinutes => 5 * 60 * 1000,
+            CandleInterval::OneHour => 60 * 60 * 1000,
+        }
+    }
+
+    fn bucket_start(&self, timestamp: u64) -> u64 {
+        timestamp - timestamp % self.as_ms()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Candle {
+    pub start_ms: u64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    // Base quantity traded.
+    pub volume: f64,
+}
+
+impl Candle {
+    fn opened_by(start_ms: u64, trade: &Trade) -> Self {
+        Self {
+            start_ms,
+            open: trade.price,
+            high: trade.price,
+            low: trade.price,
+            close: trade.price,
+            volume: trade.quantity,
+        }
+    }
+
+    // A bucket nothing traded in: flat at the previous close.
+    fn flat(start_ms: u64, close: f64) -> Self {
+        Self {
+            start_ms,
+            open: close,
+            high: close,
+            low: close,
+            close,
+            volume: 0.0,
+        }
+    }
+
+    fn add(&mut self, trade: &Trade) {
+        self.high = self.high.max(trade.price);
+        self.low = self.low.min(trade.price);
+        self.close = trade.price;
+        self.volume += trade.quantity;
+    }
+}
+
+// Only buckets that saw a trade are stored, oldest first; the empty ones in
+// between are filled in when the series is read.
+#[derive(Debug)]
+pub struct CandleSeries {
+    interval: CandleInterval,
+    candles: VecDeque<Candle>,
+}
+
+impl CandleSeries {
+    pub fn new(interval: CandleInterval) -> Self {
+        Self {
+            interval,
+            candles: VecDeque::new(),
+        }
+    }
+
+    // `timestamp` is when the trade executed; `trade.timestamp` can be the
+    // older maker's. One executed before the newest candle joins its own
+    // bucket if that is still kept, otherwise the newest.
+    pub fn record(&mut self, trade: &Trade, timestamp: u64) {
+        let start = self.interval.bucket_start(timestamp);
+        if self.candles.back().is_none_or(|candle| candle.start_ms < start) {
+            if self.candles.len() == CANDLE_CAPACITY {
+                self.candles.pop_front();
+            }
+            self.candles.push_back(Candle::opened_by(start, trade));
+        } else if let Some(candle) = self.candles.iter_mut().rev().find(|candle| candle.start_ms == start) {
+            candle.add(trade);
+        } else if let Some(candle) = self.candles.back_mut() {
+            candle.add(trade);
+        }
+    }
+
+    // The last `limit` buckets up to the one holding `now`, oldest first,
+    // starting no earlier than the first kept trade.
+    pub fn candles(&self, limit: usize, now: u64) -> Vec<Candle> {
+        let (Some(first), Some(last)) = (self.candles.front(), self.candles.back()) else {
+            return Vec::new();
+        };
+        let limit = limit.min(CANDLE_CAPACITY);
+        if limit == 0 {
+            return Vec::new();
+        }
+        let step = self.interval.as_ms();
+        let last_start = self.interval.bucket_start(now).max(last.start_ms);
+        let start = last_start.saturating_sub((limit as u64 - 1) * step).max(first.start_ms);
+
+        // The window opens on a stored candle or after one, whose close
+        // carries into any empty buckets before the next.
+        let skip = self.candles.partition_point(|candle| candle.start_ms < start);
+        let mut close = self.candles[skip.saturating_sub(1)].close;
+        let mut stored = self.candles.range(skip..).peekable();
+
+        let mut candles = Vec::with_capacity(((last_start - start) / step + 1) as usize);
+        for bucket in (start..=last_start).step_by(step as usize) {
+            let candle = match stored.next_if(|candle| candle.start_ms == bucket) {
+                Some(candle) => candle.clone(),
+                None => Candle::flat(bucket, close),
+            };
+            close = candle.close;
+            candles.push(candle);
+        }
+        candles
+    }
+
+    pub fn interval(&self) -> CandleInterval {
+        self.interval
+    }
+
+    pub fn clear(&mut self) {
+        self.candles.clear();
+    }
+}
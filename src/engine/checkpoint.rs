@@ -0,0 +1,76 @@
+// Periodic crash-recovery snapshots. A `Checkpointer` rewrites one book's
+// resting state to `<dir>/<symbol>.snapshot.json` every
+// `snapshot_every_trades` trades or `snapshot_every_ms`, whichever comes
+// first, so a restart only has to restore the latest file.
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::engine::orderbook::OrderBook;
+use crate::engine::snapshot::OrderBookSnapshot;
+
+pub struct Checkpointer {
+    book: Arc<OrderBook>,
+    path: PathBuf,
+}
+
+impl Checkpointer {
+    pub fn new(book: Arc<OrderBook>, dir: impl AsRef<Path>) -> Self {
+        let path = dir.as_ref().join(format!("{}.snapshot.json", book.config().symbol));
+        Self { book, path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn write(&self) -> io::Result<()> {
+        self.book.checkpoint().save_to_path(&self.path)
+    }
+
+    // Restores the book from the last snapshot written, if there is one.
+    // Returns whether it did.
+    pub fn restore(&self) -> io::Result<bool> {
+        let Some(snapshot) = OrderBookSnapshot::load_from_path(&self.path)? else {
+            return Ok(false);
+        };
+        self.book
+            .restore(snapshot)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(true)
+    }
+
+    // Waits for the book to ask for a checkpoint, or for `snapshot_every_ms`
+    // to pass, and writes one on the blocking pool. Skipped when nothing has
+    // changed since the last.
+    pub fn spawn(self) {
+        let every = self.book.config().snapshot_every_ms.map(Duration::from_millis);
+        let checkpointer = Arc::new(self);
+        tokio::spawn(async move {
+            let mut written_sequence = None;
+            loop {
+                match every {
+                    Some(every) => {
+                        let _ = tokio::time::timeout(every, checkpointer.book.checkpoint_due()).await;
+                    }
+                    None => checkpointer.book.checkpoint_due().await,
+                }
+
+                let sequence = checkpointer.book.sequence();
+                if written_sequence == Some(sequence) {
+                    continue;
+                }
+                let writer = checkpointer.clone();
+                match tokio::task::spawn_blocking(move || writer.write()).await {
+                    Ok(Ok(())) => {
+                        written_sequence = Some(sequence);
+                        log::debug!("Checkpointed {}", checkpointer.path.display());
+                    }
+                    Ok(Err(e)) => log::error!(" Could not write {}: {}", checkpointer.path.display(), e),
+                    Err(e) => log::error!(" Checkpoint task for {} failed: {}", checkpointer.path.display(), e),
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+
+use crate::engine::order::Order;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SpreadDirection {
+    Narrows,  // Fires once spread <= threshold
+    Widens,   // Fires once spread >= threshold
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SpreadCondition {
+    pub threshold: f64,
+    pub direction: SpreadDirection,
+}
+
+impl SpreadCondition {
+    pub fn new(threshold: f64, direction: SpreadDirection) -> Self {
+        Self { threshold, direction }
+    }
+
+    // A one-sided book has no spread, so nothing can trigger on it.
+    pub fn is_triggered(&self, spread: Option<f64>) -> bool {
+        match (spread, self.direction) {
+            (Some(spread), SpreadDirection::Narrows) => spread <= self.threshold,
+            (Some(spread), SpreadDirection::Widens) => spread >= self.threshold,
+            (None, _) => false,
+        }
+    }
+}
+
+// A limit order parked off-book until its spread condition is met.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConditionalOrder {
+    pub order: Order,
+    pub condition: SpreadCondition,
+}
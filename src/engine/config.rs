@@ -0,0 +1,466 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::engine::error::{ConfigError, OrderError};
+use crate::engine::order::TimeInForce;
+use crate::engine::price::{Price, DEFAULT_TICK_SIZE, PRICE_SCALE};
+
+pub const DEFAULT_QUANTITY_SCALE: u64 = 1_000_000;
+pub const DEFAULT_TRADE_HISTORY_CAPACITY: usize = 10_000;
+pub const DEFAULT_MID_EMA_ALPHA: f64 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PrecisionPolicy {
+    Reject,  // Refuse quantities finer than the scale
+    Round,   // Round them to the nearest representable quantity
+}
+
+// How orders resting at the same price are ordered beyond plain arrival time.
+// Synthetic venue orders otherwise queue in whatever order the feeds happen to
+// deliver them, which makes multi-venue runs hard to reproduce.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TieBreak {
+    #[default]
+    Fifo,
+    // Orders sourced from this venue go first, FIFO among themselves.
+    PrimaryVenue(String),
+    // The largest order goes first; equal sizes fall back to FIFO.
+    LargerSize,
+}
+
+// What to do when a fill executes at a worse price than the best resting
+// opposite price at that moment (a trade-through). Matching walks the book
+// best-first, so a detection always means an engine bug.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TradeThroughCheck {
+    #[default]
+    Off,
+    Report,  // Log a warning and publish an event
+    Strict,  // Panic in debug builds; error log and event in release
+}
+
+// What happens when an order would trade against a resting order from the
+// same `user_id`. Checked before the trade is generated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SelfTradePolicy {
+    // Cancel the resting order and keep matching past it.
+    CancelResting,
+    // Cancel what is left of the incoming order.
+    CancelIncoming,
+    // Reduce both by the smaller quantity without printing a trade.
+    DecrementBoth,
+}
+
+// What happens to an order priced exactly at the opposite best while the
+// book isn't matching (the opening auction), where resting it would leave
+// the book locked with best bid equal to best ask.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LockedMarketPolicy {
+    // Trade it against the opposite best level at that price right away.
+    #[default]
+    Match,
+    // Refuse the order.
+    Reject,
+    // Rest it and leave the book locked until the uncross.
+    Allow,
+}
+
+// The order types a book can be restricted to; limit orders count as a
+// different type for each time in force.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OrderKind {
+    Limit(TimeInForce),
+    Market,
+    ConditionalSpread,
+}
+
+impl OrderKind {
+    // `limit` (GTC), `ioc`, `fok`, `market` or `conditional`.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "limit" | "gtc" => Some(OrderKind::Limit(TimeInForce::Gtc)),
+            "ioc" => Some(OrderKind::Limit(TimeInForce::Ioc)),
+            "fok" => Some(OrderKind::Limit(TimeInForce::Fok)),
+            "market" => Some(OrderKind::Market),
+            "conditional" => Some(OrderKind::ConditionalSpread),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for OrderKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OrderKind::Limit(TimeInForce::Gtc) => write!(f, "GTC limit"),
+            OrderKind::Limit(TimeInForce::Ioc) => write!(f, "IOC limit"),
+            OrderKind::Limit(TimeInForce::Fok) => write!(f, "FOK limit"),
+            OrderKind::Market => write!(f, "market"),
+            OrderKind::ConditionalSpread => write!(f, "conditional"),
+        }
+    }
+}
+
+// Fees as a fraction of traded notional, e.g. 0.001 for 10 bps.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct FeeSchedule {
+    pub maker_rate: f64,
+    pub taker_rate: f64,
+}
+
+impl FeeSchedule {
+    pub fn new(maker_rate: f64, taker_rate: f64) -> Self {
+        Self { maker_rate, taker_rate }
+    }
+
+    // Fee-exempt orders pay nothing whatever the rate.
+    pub fn maker_fee(&self, notional: f64, exempt: bool) -> f64 {
+        if exempt {
+            0.0
+        } else {
+            notional * self.maker_rate
+        }
+    }
+
+    pub fn taker_fee(&self, notional: f64, exempt: bool) -> f64 {
+        if exempt {
+            0.0
+        } else {
+            notional * self.taker_rate
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderBookConfig {
+    // Symbol the book trades; used to namespace order ids in API responses.
+    pub symbol: String,
+    // Designated market maker: its orders are matched ahead of other orders
+    // resting at the same price level (price priority still applies).
+    pub designated_maker: Option<String>,
+    // Decimals that reported depth and fill quantities are rounded to. This is
+    // presentation only: matching always uses the exact quantity, so the sum
+    // of rounded levels or fills can differ slightly from what was matched.
+    pub display_quantity_decimals: Option<u32>,
+    // Quantities are tracked as integer multiples of 1/quantity_scale, e.g.
+    // 100_000_000 for 8-decimal assets like BTC.
+    pub quantity_scale: u64,
+    pub precision_policy: PrecisionPolicy,
+    // Limit prices are rounded to the nearest multiple of this. Defaults to
+    // the finest price `Price` can hold, which is effectively no tick size.
+    pub tick_size: f64,
+    // Wrap every REST response in a `{ data, server_time_ms, symbol }`
+    // envelope. Clients can also opt in per request via the Accept header.
+    pub envelope_responses: bool,
+    // Applied after designated-maker priority.
+    pub tie_break: TieBreak,
+    // Never let an order with a `source` venue tag trade against a resting
+    // order from the same venue; the incoming order is dropped instead.
+    pub prevent_same_source_match: bool,
+    // Never let a user trade with themselves; `None` allows it.
+    pub self_trade_policy: Option<SelfTradePolicy>,
+    // Let a quote set's own bid and ask cross, so the pair trades with each
+    // other when applied. Off by default: such a set is refused whole.
+    pub allow_crossed_quote_sets: bool,
+    pub locked_market_policy: LockedMarketPolicy,
+    // Clear every resting order once nothing has been placed for this long,
+    // so a book whose feeds have all died can't be traded against.
+    pub max_staleness_ms: Option<u64>,
+    // Cancel the resting orders of any `source` venue that has sent nothing
+    // for this long, so a dead feed's quotes don't linger among live ones.
+    pub max_source_silence_ms: Option<u64>,
+    // Invariant check run on every fill; off by default since it never fires
+    // in a correct engine.
+    pub trade_through_check: TradeThroughCheck,
+    pub fee_schedule: FeeSchedule,
+    // Publish a `BookOrder` event for every resting order added, reduced or
+    // removed (a market-by-order feed). Off by default: it costs one event
+    // per order change on top of the order updates.
+    pub order_level_events: bool,
+    // Prints kept on the tape for `/trades`; the oldest are dropped first.
+    pub trade_history_capacity: usize,
+    // Publish a `MatchEvent` for every continuous-matching fill, naming both
+    // maker and taker. Off by default, like `order_level_events`.
+    pub match_events: bool,
+    // Refuse to cancel a resting order until it has rested this long, so
+    // quotes can't be flashed and pulled (a crude anti-spoofing rule).
+    pub min_resting_ms: Option<u64>,
+    // Start in an opening auction: orders rest without matching until
+    // `OrderBook::open` uncrosses them at one price.
+    pub opening_auction: bool,
+    // Order types users may place on this book; `None` allows every type.
+    // Orders mirrored from a venue feed are never restricted.
+    pub allowed_order_kinds: Option<Vec<OrderKind>>,
+    // Share of the gap to the current mid that `mid_ema` closes per second,
+    // in (0, 1]; higher tracks the mid more closely.
+    pub mid_ema_alpha: f64,
+    // Ask for a checkpoint snapshot once this many trades have printed since
+    // the last one; `Checkpointer` writes it. `None` never asks by count.
+    pub snapshot_every_trades: Option<u64>,
+    // Longest a `Checkpointer` waits between snapshots, whatever the count.
+    pub snapshot_every_ms: Option<u64>,
+}
+
+impl Default for OrderBookConfig {
+    fn default() -> Self {
+        Self {
+            symbol: "BTC".to_string(),
+            designated_maker: None,
+            display_quantity_decimals: None,
+            quantity_scale: DEFAULT_QUANTITY_SCALE,
+            tick_size: DEFAULT_TICK_SIZE,
+            precision_policy: PrecisionPolicy::Round,
+            envelope_responses: false,
+            tie_break: TieBreak::Fifo,
+            prevent_same_source_match: false,
+            self_trade_policy: None,
+            allow_crossed_quote_sets: false,
+            locked_market_policy: LockedMarketPolicy::Match,
+            max_staleness_ms: None,
+            max_source_silence_ms: None,
+            trade_through_check: TradeThroughCheck::Off,
+            fee_schedule: FeeSchedule::default(),
+            order_level_events: false,
+            trade_history_capacity: DEFAULT_TRADE_HISTORY_CAPACITY,
+            match_events: false,
+            min_resting_ms: None,
+            opening_auction: false,
+            allowed_order_kinds: None,
+            mid_ema_alpha: DEFAULT_MID_EMA_ALPHA,
+            snapshot_every_trades: None,
+            snapshot_every_ms: None,
+        }
+    }
+}
+
+impl OrderBookConfig {
+    pub fn with_symbol(mut self, symbol: impl Into<String>) -> Self {
+        self.symbol = symbol.into();
+        self
+    }
+
+    pub fn with_designated_maker(mut self, user_id: impl Into<String>) -> Self {
+        self.designated_maker = Some(user_id.into());
+        self
+    }
+
+    pub fn with_display_quantity_decimals(mut self, decimals: u32) -> Self {
+        self.display_quantity_decimals = Some(decimals);
+        self
+    }
+
+    pub fn with_quantity_scale(mut self, scale: u64, policy: PrecisionPolicy) -> Self {
+        self.quantity_scale = scale;
+        self.precision_policy = policy;
+        self
+    }
+
+    pub fn with_tick_size(mut self, tick_size: f64) -> Self {
+        self.tick_size = tick_size;
+        self
+    }
+
+    pub fn with_envelope_responses(mut self, enabled: bool) -> Self {
+        self.envelope_responses = enabled;
+        self
+    }
+
+    pub fn with_tie_break(mut self, tie_break: TieBreak) -> Self {
+        self.tie_break = tie_break;
+        self
+    }
+
+    pub fn with_prevent_same_source_match(mut self, enabled: bool) -> Self {
+        self.prevent_same_source_match = enabled;
+        self
+    }
+
+    pub fn with_self_trade_policy(mut self, policy: SelfTradePolicy) -> Self {
+        self.self_trade_policy = Some(policy);
+        self
+    }
+
+    pub fn with_allow_crossed_quote_sets(mut self, allowed: bool) -> Self {
+        self.allow_crossed_quote_sets = allowed;
+        self
+    }
+
+    pub fn with_locked_market_policy(mut self, policy: LockedMarketPolicy) -> Self {
+        self.locked_market_policy = policy;
+        self
+    }
+
+    pub fn with_max_staleness_ms(mut self, max_staleness_ms: u64) -> Self {
+        self.max_staleness_ms = Some(max_staleness_ms);
+        self
+    }
+
+    pub fn with_max_source_silence_ms(mut self, max_source_silence_ms: u64) -> Self {
+        self.max_source_silence_ms = Some(max_source_silence_ms);
+        self
+    }
+
+    pub fn with_trade_through_check(mut self, check: TradeThroughCheck) -> Self {
+        self.trade_through_check = check;
+        self
+    }
+
+    pub fn with_fee_schedule(mut self, fee_schedule: FeeSchedule) -> Self {
+        self.fee_schedule = fee_schedule;
+        self
+    }
+
+    pub fn with_order_level_events(mut self, enabled: bool) -> Self {
+        self.order_level_events = enabled;
+        self
+    }
+
+    pub fn with_trade_history_capacity(mut self, capacity: usize) -> Self {
+        self.trade_history_capacity = capacity.max(1);
+        self
+    }
+
+    pub fn with_match_events(mut self, enabled: bool) -> Self {
+        self.match_events = enabled;
+        self
+    }
+
+    pub fn with_min_resting_ms(mut self, min_resting_ms: u64) -> Self {
+        self.min_resting_ms = Some(min_resting_ms);
+        self
+    }
+
+    pub fn with_opening_auction(mut self, enabled: bool) -> Self {
+        self.opening_auction = enabled;
+        self
+    }
+
+    pub fn with_allowed_order_kinds(mut self, kinds: impl IntoIterator<Item = OrderKind>) -> Self {
+        self.allowed_order_kinds = Some(kinds.into_iter().collect());
+        self
+    }
+
+    pub fn with_mid_ema_alpha(mut self, alpha: f64) -> Self {
+        self.mid_ema_alpha = alpha;
+        self
+    }
+
+    pub fn with_snapshot_every_trades(mut self, trades: u64) -> Self {
+        self.snapshot_every_trades = Some(trades);
+        self
+    }
+
+    pub fn with_snapshot_every_ms(mut self, every_ms: u64) -> Self {
+        self.snapshot_every_ms = Some(every_ms);
+        self
+    }
+
+    pub fn check_order_kind(&self, kind: OrderKind) -> Result<(), OrderError> {
+        match &self.allowed_order_kinds {
+            Some(allowed) if !allowed.contains(&kind) => Err(OrderError::OrderKindNotAllowed {
+                kind,
+                symbol: self.symbol.clone(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    // Validates an incoming quantity against the book's scale, applying the
+    // precision policy to anything finer than it.
+    pub fn normalize_quantity(&self, quantity: f64) -> Result<f64, OrderError> {
+        if !quantity.is_finite() || quantity <= 0.0 {
+            return Err(OrderError::InvalidQuantity { quantity });
+        }
+
+        let scale = self.quantity_scale as f64;
+        let units = quantity * scale;
+        // Allow for float noise in the multiplication itself.
+        if (units - units.round()).abs() <= 1e-3 {
+            return Ok(quantity);
+        }
+
+        match self.precision_policy {
+            PrecisionPolicy::Reject => Err(OrderError::PrecisionExceeded {
+                quantity,
+                scale: self.quantity_scale,
+            }),
+            PrecisionPolicy::Round => {
+                let rounded = units.round() / scale;
+                if rounded <= 0.0 {
+                    Err(OrderError::InvalidQuantity { quantity })
+                } else {
+                    Ok(rounded)
+                }
+            }
+        }
+    }
+
+    // Settings that would leave the book unable to price orders sensibly.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let tick_units = self.tick_size * PRICE_SCALE as f64;
+        // Allow for float noise in the multiplication itself.
+        if !tick_units.is_finite() || tick_units.round() < 1.0 || (tick_units - tick_units.round()).abs() > 1e-3 {
+            return Err(ConfigError::InvalidTickSize {
+                tick_size: self.tick_size,
+                price_precision: DEFAULT_TICK_SIZE,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn round_price(&self, price: f64) -> Price {
+        Price::from_f64(price, self.tick_size)
+    }
+
+    // A limit price rounded to the tick size, refused if it isn't finite or
+    // rounds to zero or below.
+    pub fn limit_price(&self, price: f64) -> Result<Price, OrderError> {
+        let rounded = self.round_price(price);
+        if !price.is_finite() || rounded.units() <= 0 {
+            return Err(OrderError::InvalidPrice { price });
+        }
+        Ok(rounded)
+    }
+
+    pub fn display_quantity(&self, quantity: f64) -> f64 {
+        match self.display_quantity_decimals {
+            Some(decimals) => {
+                let factor = 10f64.powi(decimals as i32);
+                (quantity * factor).round() / factor
+            }
+            None => quantity,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quantity_precision_follows_the_scale_and_policy() {
+        let fine = OrderBookConfig::default().with_quantity_scale(100_000_000, PrecisionPolicy::Reject);
+        assert_eq!(fine.normalize_quantity(0.12345678), Ok(0.12345678));
+
+        let coarse = OrderBookConfig::default().with_quantity_scale(10_000, PrecisionPolicy::Reject);
+        assert_eq!(
+            coarse.normalize_quantity(0.12345678),
+            Err(OrderError::PrecisionExceeded { quantity: 0.12345678, scale: 10_000 })
+        );
+
+        let rounding = OrderBookConfig::default().with_quantity_scale(10_000, PrecisionPolicy::Round);
+        assert_eq!(rounding.normalize_quantity(0.12345678), Ok(0.1235));
+        // Rounding to nothing is no quantity at all.
+        assert_eq!(rounding.normalize_quantity(0.00001), Err(OrderError::InvalidQuantity { quantity: 0.00001 }));
+    }
+
+    #[test]
+    fn tick_size_must_be_whole_units_of_price_precision() {
+        for tick_size in [0.01, 0.5, 25.0, DEFAULT_TICK_SIZE] {
+            assert_eq!(OrderBookConfig::default().with_tick_size(tick_size).validate(), Ok(()));
+        }
+        for tick_size in [0.0, -0.01, f64::NAN, f64::INFINITY, 1e-9, 0.015e-6] {
+            assert!(OrderBookConfig::default().with_tick_size(tick_size).validate().is_err(), "{}", tick_size);
+        }
+    }
+}
@@ -0,0 +1,170 @@
+// One sub-book per venue for a symbol, aggregated on demand. Keeping venues
+// apart means a venue's feed reset clears only its own liquidity, and the
+// venues never match against each other.
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use parking_lot::RwLock;
+
+use crate::engine::config::OrderBookConfig;
+use crate::engine::orderbook::{DepthSide, OrderBook};
+use crate::engine::price::Price;
+
+// One venue's top of book on a side.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VenueQuote {
+    pub venue: String,
+    pub price: f64,
+    pub quantity: f64,
+}
+
+pub struct ConsolidatedBook {
+    config: OrderBookConfig,
+    venues: RwLock<BTreeMap<String, Arc<OrderBook>>>,
+}
+
+impl ConsolidatedBook {
+    // Sub-books are created from `config` as venues first appear.
+    pub fn new(config: OrderBookConfig) -> Self {
+        Self {
+            config,
+            venues: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> &OrderBookConfig {
+        &self.config
+    }
+
+    // The venue's sub-book, created empty on first use.
+    pub fn venue(&self, venue: &str) -> Arc<OrderBook> {
+        if let Some(book) = self.venues.read().get(venue) {
+            return book.clone();
+        }
+        self.venues
+            .write()
+            .entry(venue.to_string())
+            .or_insert_with(|| Arc::new(OrderBook::with_config(self.config.clone())))
+            .clone()
+    }
+
+    pub fn venues(&self) -> Vec<String> {
+        self.venues.read().keys().cloned().collect()
+    }
+
+    pub fn books(&self) -> Vec<Arc<OrderBook>> {
+        self.venues.read().values().cloned().collect()
+    }
+
+    // Clears one venue's sub-book; returns false for an unknown venue.
+    pub fn clear_venue(&self, venue: &str) -> bool {
+        match self.venues.read().get(venue) {
+            Some(book) => {
+                book.clear();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn get_best_bid(&self) -> Option<f64> {
+        self.books().iter().filter_map(|book| book.get_best_bid()).reduce(f64::max)
+    }
+
+    pub fn get_best_ask(&self) -> Option<f64> {
+        self.books().iter().filter_map(|book| book.get_best_ask()).reduce(f64::min)
+    }
+
+    // Highest bid across the venues and the venue quoting it; on a tie, the
+    // venue first by name.
+    pub fn best_bid(&self) -> Option<VenueQuote> {
+        self.venue_tops(|(bids, _)| bids)
+            .reduce(|best, quote| if quote.price > best.price { quote } else { best })
+    }
+
+    // Lowest ask across the venues, ties as for `best_bid`.
+    pub fn best_ask(&self) -> Option<VenueQuote> {
+        self.venue_tops(|(_, asks)| asks)
+            .reduce(|best, quote| if quote.price < best.price { quote } else { best })
+    }
+
+    fn venue_tops(
+        &self,
+        side: impl Fn((DepthSide, DepthSide)) -> DepthSide,
+    ) -> impl Iterator<Item = VenueQuote> {
+        let venues: Vec<(String, Arc<OrderBook>)> =
+            self.venues.read().iter().map(|(venue, book)| (venue.clone(), book.clone())).collect();
+        venues.into_iter().filter_map(move |(venue, book)| {
+            let &(price, quantity) = side(book.get_market_depth(1)).first()?;
+            Some(VenueQuote { venue, price, quantity })
+        })
+    }
+
+    // Top `levels` of the summed venue depth. Each venue contributes its own
+    // top `levels`, which is all the merged top `levels` can draw from. The
+    // venues are read one after another, so the view isn't an atomic
+    // snapshot across venues.
+    pub fn get_market_depth(&self, levels: usize) -> (DepthSide, DepthSide) {
+        let mut bids: BTreeMap<Price, f64> = BTreeMap::new();
+        let mut asks: BTreeMap<Price, f64> = BTreeMap::new();
+        for book in self.books() {
+            let (venue_bids, venue_asks) = book.get_market_depth(levels);
+            for (price, quantity) in venue_bids {
+                *bids.entry(Price::new(price)).or_default() += quantity;
+            }
+            for (price, quantity) in venue_asks {
+                *asks.entry(Price::new(price)).or_default() += quantity;
+            }
+        }
+
+        let bids = bids.into_iter().rev().take(levels).map(|(price, quantity)| (price.as_f64(), quantity)).collect();
+        let asks = asks.into_iter().take(levels).map(|(price, quantity)| (price.as_f64(), quantity)).collect();
+        (bids, asks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::order::OrderSide;
+
+    #[test]
+    fn clearing_one_venue_keeps_the_others_in_consolidated_depth() {
+        let book = ConsolidatedBook::new(OrderBookConfig::default());
+        for (venue, quantity) in [("binance", 1.0), ("coinbase", 2.0), ("kraken", 4.0)] {
+            let venue_book = book.venue(venue);
+            venue_book.add_order(OrderSide::Bid, 99.0, quantity, 1, venue.to_string()).unwrap();
+            venue_book.add_order(OrderSide::Ask, 101.0, quantity, 1, venue.to_string()).unwrap();
+        }
+        assert_eq!(book.get_market_depth(5), (vec![(99.0, 7.0)], vec![(101.0, 7.0)]));
+
+        assert!(book.clear_venue("coinbase"));
+        assert!(!book.clear_venue("bitstamp"));
+
+        assert_eq!(book.get_market_depth(5), (vec![(99.0, 5.0)], vec![(101.0, 5.0)]));
+        assert!(book.venue("coinbase").get_best_bid().is_none());
+        assert_eq!(book.venues(), vec!["binance", "coinbase", "kraken"]);
+    }
+
+    #[test]
+    fn best_bid_and_ask_come_from_whichever_venue_quotes_them() {
+        let book = ConsolidatedBook::new(OrderBookConfig::default());
+        assert!(book.best_bid().is_none());
+        for (venue, bid, ask) in [("binance", 99.0, 101.5), ("coinbase", 99.5, 101.0), ("kraken", 99.5, 102.0)] {
+            let venue_book = book.venue(venue);
+            venue_book.add_order(OrderSide::Bid, bid, 1.0, 1, venue.to_string()).unwrap();
+            venue_book.add_order(OrderSide::Ask, ask, 2.0, 1, venue.to_string()).unwrap();
+        }
+
+        // Coinbase and Kraken tie on the bid; the first by name wins.
+        let bid = book.best_bid().unwrap();
+        assert_eq!((bid.venue.as_str(), bid.price, bid.quantity), ("coinbase", 99.5, 1.0));
+        let ask = book.best_ask().unwrap();
+        assert_eq!((ask.venue.as_str(), ask.price, ask.quantity), ("coinbase", 101.0, 2.0));
+
+        // A crossed pair across venues is an arbitrage gap, and shows as one.
+        book.venue("kraken").add_order(OrderSide::Bid, 101.2, 1.0, 2, "kraken".to_string()).unwrap();
+        let bid = book.best_bid().unwrap();
+        assert_eq!((bid.venue.as_str(), bid.price), ("kraken", 101.2));
+        assert!(bid.price > book.best_ask().unwrap().price);
+    }
+}
@@ -0,0 +1,78 @@
+use dashmap::DashMap;
+
+#[derive(Debug, Clone, Copy)]
+struct Deadline {
+    timeout_ms: u64,
+    expires_at: u64,
+}
+
+// Per-user dead-man's switch: once armed, a user must heartbeat within
+// `timeout_ms` or the sweeper cancels all of their resting orders.
+#[derive(Debug, Default)]
+pub struct DeadMansSwitch {
+    deadlines: DashMap<String, Deadline>,
+}
+
+impl DeadMansSwitch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn arm(&self, user_id: &str, timeout_ms: u64, now: u64) {
+        self.deadlines.insert(
+            user_id.to_string(),
+            Deadline { timeout_ms, expires_at: now.saturating_add(timeout_ms) },
+        );
+    }
+
+    pub fn disarm(&self, user_id: &str) -> bool {
+        self.deadlines.remove(user_id).is_some()
+    }
+
+    // Pushes the deadline out by another full timeout. Returns false if the
+    // user has no switch armed.
+    pub fn heartbeat(&self, user_id: &str, now: u64) -> bool {
+        match self.deadlines.get_mut(user_id) {
+            Some(mut deadline) => {
+                deadline.expires_at = now.saturating_add(deadline.timeout_ms);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn expires_at(&self, user_id: &str) -> Option<u64> {
+        self.deadlines.get(user_id).map(|d| d.expires_at)
+    }
+
+    // Disarms and returns every user whose deadline has passed.
+    pub fn take_expired(&self, now: u64) -> Vec<String> {
+        let expired: Vec<String> = self
+            .deadlines
+            .iter()
+            .filter(|entry| entry.expires_at <= now)
+            .map(|entry| entry.key().clone())
+            .collect();
+
+        for user_id in &expired {
+            self.deadlines.remove(user_id);
+        }
+
+        expired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deadlines_saturate_instead_of_overflowing() {
+        let dead_man = DeadMansSwitch::new();
+        dead_man.arm("mm", u64::MAX, 10);
+        assert_eq!(dead_man.expires_at("mm"), Some(u64::MAX));
+        assert!(dead_man.heartbeat("mm", 20));
+        assert_eq!(dead_man.expires_at("mm"), Some(u64::MAX));
+        assert!(dead_man.take_expired(u64::MAX - 1).is_empty());
+    }
+}
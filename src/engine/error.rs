@@ -0,0 +1,109 @@
+use std::fmt;
+
+use crate::engine::config::OrderKind;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrderError {
+    InvalidQuantity { quantity: f64 },
+    // Not finite, or at or below zero once rounded to the tick size.
+    InvalidPrice { price: f64 },
+    // Quantity has finer precision than the book's quantity scale can represent.
+    PrecisionExceeded { quantity: f64, scale: u64 },
+    ClientMetadataTooLong { len: usize, max: usize },
+    InvalidUserId { max: usize },
+    InvalidProtectionPrice { price: f64 },
+    InvalidPegOffset { offset: f64 },
+    InvalidTakeProfit { value: f64 },
+    // Market orders have nothing to sweep until the opening auction ends.
+    AuctionInProgress,
+    // The venue-wide kill switch is engaged.
+    TradingHalted,
+    // A quote set's highest bid is at or above its lowest ask.
+    CrossedQuoteSet { bid: f64, ask: f64 },
+    // Would rest at the opposite best under `LockedMarketPolicy::Reject`.
+    WouldLockMarket { price: f64 },
+    // The book's `allowed_order_kinds` leaves this type out.
+    OrderKindNotAllowed { kind: OrderKind, symbol: String },
+    // Taking the order off the book to amend it would cancel it before
+    // `OrderBookConfig::min_resting_ms` has passed.
+    MinRestingTime { min_resting_ms: u64 },
+}
+
+impl fmt::Display for OrderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OrderError::InvalidQuantity { quantity } => {
+                write!(f, "quantity {} must be a positive number", quantity)
+            }
+            OrderError::InvalidPrice { price } => {
+                write!(f, "price {} must be a positive number of ticks", price)
+            }
+            OrderError::PrecisionExceeded { quantity, scale } => write!(
+                f,
+                "quantity {} is finer than the book's precision of 1/{}",
+                quantity, scale
+            ),
+            OrderError::ClientMetadataTooLong { len, max } => write!(
+                f,
+                "client_metadata is {} bytes, the limit is {}",
+                len, max
+            ),
+            OrderError::InvalidUserId { max } => write!(
+                f,
+                "user_id must be 1 to {} characters of letters, digits, '_', '-', '.', '@' or ':'",
+                max
+            ),
+            OrderError::InvalidProtectionPrice { price } => {
+                write!(f, "protection price {} must be a positive number", price)
+            }
+            OrderError::InvalidPegOffset { offset } => {
+                write!(f, "peg offset {} must be a finite number", offset)
+            }
+            OrderError::InvalidTakeProfit { value } => {
+                write!(f, "take-profit price or offset {} must be a positive number", value)
+            }
+            OrderError::AuctionInProgress => {
+                write!(f, "market orders are not accepted during the opening auction")
+            }
+            OrderError::TradingHalted => write!(f, "trading is halted"),
+            OrderError::CrossedQuoteSet { bid, ask } => write!(
+                f,
+                "quote set crosses itself: bid {} is at or above ask {}",
+                bid, ask
+            ),
+            OrderError::WouldLockMarket { price } => {
+                write!(f, "price {} equals the opposite best and would lock the market", price)
+            }
+            OrderError::OrderKindNotAllowed { kind, symbol } => {
+                write!(f, "rejected: {} orders are not enabled on {}", kind, symbol)
+            }
+            OrderError::MinRestingTime { min_resting_ms } => {
+                write!(f, "order must rest for {} ms before it can be repriced or enlarged", min_resting_ms)
+            }
+        }
+    }
+}
+
+impl std::error::Error for OrderError {}
+
+// A book configuration that can't work, refused when the book is built.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    // Zero, negative, not finite, or not a whole number of the finest
+    // price increment `Price` can hold.
+    InvalidTickSize { tick_size: f64, price_precision: f64 },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::InvalidTickSize { tick_size, price_precision } => write!(
+                f,
+                "tick_size {} must be a positive multiple of the price precision {}",
+                tick_size, price_precision
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
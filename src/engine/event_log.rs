@@ -0,0 +1,123 @@
+// Append-only JSONL write-ahead log of a book's `EngineEvent`s, so its state
+// can be rebuilt after a crash by replaying them. The book appends every
+// change as it makes it, once the log is attached with
+// `OrderBook::attach_event_log`. Compaction rewrites the log as a single
+// `Reset` holding the book at that moment, after which replay restores it
+// and applies only the events that follow. Each line is flushed to the OS as
+// it is written, so a crashed process loses nothing; a power cut can still
+// lose what the OS hadn't written out.
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use parking_lot::Mutex;
+
+use crate::engine::orderbook::OrderBook;
+use crate::events::EngineEvent;
+
+// Events written since the last compaction before one is due.
+pub const DEFAULT_COMPACT_EVERY: usize = 10_000;
+
+struct LogWriter {
+    file: BufWriter<File>,
+    entries_since_compaction: usize,
+}
+
+pub struct EventLog {
+    path: PathBuf,
+    compact_every: usize,
+    writer: Mutex<LogWriter>,
+}
+
+impl std::fmt::Debug for EventLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventLog")
+            .field("path", &self.path)
+            .field("compact_every", &self.compact_every)
+            .finish_non_exhaustive()
+    }
+}
+
+impl EventLog {
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self {
+            path,
+            compact_every: DEFAULT_COMPACT_EVERY,
+            writer: Mutex::new(LogWriter {
+                file: BufWriter::new(file),
+                entries_since_compaction: 0,
+            }),
+        })
+    }
+
+    pub fn with_compact_every(mut self, compact_every: usize) -> Self {
+        self.compact_every = compact_every;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn append(&self, event: &EngineEvent) -> io::Result<()> {
+        let mut writer = self.writer.lock();
+        serde_json::to_writer(&mut writer.file, event)?;
+        writer.file.write_all(b"\n")?;
+        writer.file.flush()?;
+        writer.entries_since_compaction += 1;
+        Ok(())
+    }
+
+    pub fn compaction_due(&self) -> bool {
+        self.writer.lock().entries_since_compaction >= self.compact_every
+    }
+
+    // Replaces the whole log with `reset`, which must hold the book's state
+    // after every event appended so far.
+    pub fn compact(&self, reset: &EngineEvent) -> io::Result<()> {
+        let mut writer = self.writer.lock();
+        // Written beside the log and renamed over it, so a crash mid-way
+        // leaves either the old log or the compacted one.
+        let compacted_path = self.path.with_extension("compacting");
+        {
+            let mut compacted = BufWriter::new(File::create(&compacted_path)?);
+            serde_json::to_writer(&mut compacted, reset)?;
+            compacted.write_all(b"\n")?;
+            compacted.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        }
+        fs::rename(&compacted_path, &self.path)?;
+
+        let file = OpenOptions::new().append(true).open(&self.path)?;
+        writer.file = BufWriter::new(file);
+        writer.entries_since_compaction = 0;
+        log::debug!("Compacted event log {}", self.path.display());
+        Ok(())
+    }
+
+    // Rebuilds `book` from the log at `path`, returning the number of events
+    // applied. Meant for a book at startup, before a log is attached to it.
+    // A torn last line, from a crash mid-write, is skipped.
+    pub fn replay(path: impl AsRef<Path>, book: &OrderBook) -> io::Result<usize> {
+        let mut lines = BufReader::new(File::open(path)?).lines().peekable();
+        let mut applied = 0;
+        while let Some(line) = lines.next() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let event: EngineEvent = match serde_json::from_str(&line) {
+                Ok(event) => event,
+                Err(e) if lines.peek().is_none() => {
+                    log::warn!("Ignoring unreadable last event log entry: {}", e);
+                    break;
+                }
+                Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+            };
+            book.apply_event(event)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            applied += 1;
+        }
+        Ok(applied)
+    }
+}
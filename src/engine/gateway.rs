@@ -0,0 +1,128 @@
+// Optional order-entry gateway: commands are stamped with a receive sequence
+// and executed one at a time on a dedicated engine thread in that order, so
+// the outcome no longer depends on how HTTP workers happen to interleave.
+use std::collections::HashMap;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+use parking_lot::Mutex;
+use tokio::sync::oneshot;
+
+use crate::engine::orderbook::OrderBook;
+
+type Command = Box<dyn FnOnce(&OrderBook) + Send>;
+
+struct Sequencer {
+    next_sequence: u64,
+    sender: mpsc::Sender<(u64, Command)>,
+}
+
+// One gateway per book, keyed by symbol.
+pub type OrderGateways = HashMap<String, OrderGateway>;
+
+pub struct OrderGateway {
+    // Sequence assignment and enqueueing happen under one lock, so channel
+    // order is always receive-sequence order.
+    sequencer: Mutex<Sequencer>,
+}
+
+impl OrderGateway {
+    pub fn new(orderbook: Arc<OrderBook>) -> Self {
+        let (sender, receiver) = mpsc::channel::<(u64, Command)>();
+        thread::Builder::new()
+            .name(format!("gateway-{}", orderbook.config().symbol))
+            .spawn(move || {
+                for (sequence, command) in receiver {
+                    log::trace!("Gateway executing command {}", sequence);
+                    command(&orderbook);
+                }
+            })
+            .expect("failed to spawn order gateway thread");
+
+        Self {
+            sequencer: Mutex::new(Sequencer { next_sequence: 1, sender }),
+        }
+    }
+
+    // Queues `command` and waits for the engine thread to run it. Returns the
+    // receive sequence with the result, or `None` if the engine thread has
+    // stopped. Commands run even when the caller stops waiting.
+    pub async fn submit<T, F>(&self, command: F) -> Option<(u64, T)>
+    where
+        T: Send + 'static,
+        F: FnOnce(&OrderBook) -> T + Send + 'static,
+    {
+        let (reply, response) = oneshot::channel();
+        let sequence = {
+            let mut sequencer = self.sequencer.lock();
+            let sequence = sequencer.next_sequence;
+            let command: Command = Box::new(move |book| {
+                let _ = reply.send(command(book));
+            });
+            sequencer.sender.send((sequence, command)).ok()?;
+            sequencer.next_sequence += 1;
+            sequence
+        };
+        response.await.ok().map(|result| (sequence, result))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::order::OrderSide;
+    use crate::engine::trade::Trade;
+
+    type Submission = (OrderSide, f64, f64, String);
+
+    // Price, quantity and both order ids of every trade, in print order.
+    fn prints(trades: &[Trade]) -> Vec<(f64, f64, u64, u64)> {
+        trades.iter().map(|t| (t.price, t.quantity, t.bid_order_id, t.ask_order_id)).collect()
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_submissions_match_a_replay_in_receive_order() {
+        let book = Arc::new(OrderBook::new());
+        let gateway = Arc::new(OrderGateway::new(book.clone()));
+
+        let submissions: Vec<Submission> = (0..200u64)
+            .map(|i| {
+                let side = if i % 2 == 0 { OrderSide::Bid } else { OrderSide::Ask };
+                let price = 100.0 + (i % 7) as f64 - 3.0;
+                (side, price, 1.0 + (i % 3) as f64, format!("user{}", i % 5))
+            })
+            .collect();
+        let tasks: Vec<_> = submissions
+            .iter()
+            .cloned()
+            .map(|submission| {
+                let gateway = gateway.clone();
+                tokio::spawn(async move {
+                    let (side, price, quantity, user_id) = submission.clone();
+                    let (sequence, trades) = gateway
+                        .submit(move |book| book.add_order(side, price, quantity, 1, user_id).unwrap().1)
+                        .await
+                        .unwrap();
+                    (sequence, submission, trades)
+                })
+            })
+            .collect();
+        let mut results = Vec::new();
+        for task in tasks {
+            results.push(task.await.unwrap());
+        }
+        results.sort_by_key(|(sequence, _, _)| *sequence);
+        assert_eq!(results.iter().map(|(s, _, _)| *s).collect::<Vec<_>>(), (1..=200).collect::<Vec<_>>());
+
+        let replay = OrderBook::new();
+        let mut live = Vec::new();
+        let mut replayed = Vec::new();
+        for (_, (side, price, quantity, user_id), trades) in results {
+            live.extend(prints(&trades));
+            replayed.extend(prints(&replay.add_order(side, price, quantity, 1, user_id).unwrap().1));
+        }
+        assert!(!live.is_empty());
+        assert_eq!(live, replayed);
+        assert_eq!(replay.get_market_depth(usize::MAX), book.get_market_depth(usize::MAX));
+    }
+}
@@ -0,0 +1,31 @@
+// Venue-wide emergency stop. While engaged, every book refuses new orders,
+// replacements and amends, and nothing matches; cancels still go through so
+// traders can get out. It is one flag for the whole process, so it covers
+// every symbol and venue sub-book however they were created.
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::engine::error::OrderError;
+
+static ENGAGED: AtomicBool = AtomicBool::new(false);
+
+// Returns false if it was already engaged.
+pub fn engage() -> bool {
+    !ENGAGED.swap(true, Ordering::AcqRel)
+}
+
+// Returns false if it wasn't engaged.
+pub fn release() -> bool {
+    ENGAGED.swap(false, Ordering::AcqRel)
+}
+
+pub fn is_engaged() -> bool {
+    ENGAGED.load(Ordering::Acquire)
+}
+
+pub fn check() -> Result<(), OrderError> {
+    if is_engaged() {
+        Err(OrderError::TradingHalted)
+    } else {
+        Ok(())
+    }
+}
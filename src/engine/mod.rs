@@ -1,4 +1,19 @@
+pub mod accounting;
+pub mod auction;
+pub mod candles;
+pub mod checkpoint;
+pub mod conditional;
+pub mod config;
+pub mod consolidated;
+pub mod deadman;
+pub mod error;
+pub mod event_log;
+pub mod gateway;
+pub mod killswitch;
 pub mod price;
+pub mod quantity;
 pub mod order;
 pub mod trade;
-pub mod orderbook;
\ No newline at end of file
+pub mod orderbook;
+pub mod seed;
+pub mod snapshot;
@@ -1,6 +1,24 @@
+use crate::engine::error::OrderError;
 use crate::engine::price::Price;
+use crate::engine::quantity::Qty;
 use serde::{Deserialize, Serialize};
 
+// Longest client metadata string accepted on an order, in bytes.
+pub const MAX_CLIENT_METADATA_LEN: usize = 256;
+// Longest user id accepted, in bytes.
+pub const MAX_USER_ID_LEN: usize = 64;
+
+// User ids key the per-user indexes and decide who may cancel what, so an
+// empty or odd one is refused: 1 to `MAX_USER_ID_LEN` ASCII letters, digits
+// or `_-.@:`.
+pub fn validate_user_id(user_id: &str) -> Result<(), OrderError> {
+    let allowed = |c: char| c.is_ascii_alphanumeric() || "_-.@:".contains(c);
+    if user_id.is_empty() || user_id.len() > MAX_USER_ID_LEN || !user_id.chars().all(allowed) {
+        return Err(OrderError::InvalidUserId { max: MAX_USER_ID_LEN });
+    }
+    Ok(())
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Order {
     pub id: u64,
@@ -9,6 +27,81 @@ pub struct Order {
     pub quantity: f64,
     pub timestamp: u64,
     pub user_id: String, // Added for API compatibility
+    // Opaque client tag (strategy name, internal id, ...) echoed back as-is.
+    #[serde(default)]
+    pub client_metadata: Option<String>,
+    // Venue a synthetic order was mirrored from; `None` for user orders.
+    #[serde(default)]
+    pub source: Option<String>,
+    // Good-till-date: the order is expired by the sweeper once this
+    // timestamp (ms) has passed.
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+    // Promotional order that pays no fees; only admins may set it.
+    #[serde(default)]
+    pub fee_exempt: bool,
+    #[serde(default)]
+    pub time_in_force: TimeInForce,
+    // Iceberg slice size: only this much rests in view at a time, and
+    // `quantity` is the slice currently showing.
+    #[serde(default)]
+    pub display_quantity: Option<f64>,
+    // Iceberg reserve not yet shown; never counted in depth.
+    #[serde(default)]
+    pub hidden_remaining: f64,
+    // Reprices each refreshed iceberg slice instead of requeueing it at
+    // `price`.
+    #[serde(default)]
+    pub refresh_peg: Option<RefreshPeg>,
+    // Exit placed on the other side for whatever this order fills.
+    #[serde(default)]
+    pub take_profit: Option<TakeProfit>,
+    // The order whose fill placed this one as its take-profit.
+    #[serde(default)]
+    pub parent_order_id: Option<u64>,
+}
+
+// The book price a pegged iceberg's refreshed slice is priced from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PegReference {
+    // Best price on the order's own side.
+    Primary,
+    Mid,
+    // Best price on the opposite side.
+    Market,
+}
+
+// `offset` is how far behind the reference the slice rests: below it for a
+// bid, above it for an ask. Negative offsets lean into the spread, but a
+// slice never rests where it would cross the opposite best.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RefreshPeg {
+    pub reference: PegReference,
+    #[serde(default)]
+    pub offset: f64,
+}
+
+// Where the take-profit for an order's fill rests: at a fixed `price`, or
+// `offset` beyond the average fill price, above it for a buy and below it
+// for a sell.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TakeProfit {
+    Price(f64),
+    Offset(f64),
+}
+
+impl TakeProfit {
+    // Price of the exit for an `entry_side` order that filled at
+    // `average_price`; `None` when an offset would take it to zero or below.
+    pub fn exit_price(&self, entry_side: OrderSide, average_price: f64) -> Option<f64> {
+        let price = match (self, entry_side) {
+            (TakeProfit::Price(price), _) => *price,
+            (TakeProfit::Offset(offset), OrderSide::Bid) => average_price + offset,
+            (TakeProfit::Offset(offset), OrderSide::Ask) => average_price - offset,
+        };
+        (price > 0.0).then_some(price)
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -17,15 +110,208 @@ pub enum OrderSide {
     Ask,  // Sell
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TimeInForce {
+    // Good-till-cancelled: whatever doesn't fill rests on the book.
+    #[default]
+    Gtc,
+    // Immediate-or-cancel: fill what crosses now, drop the rest.
+    Ioc,
+    // Fill-or-kill: fill the whole quantity now or do nothing.
+    Fok,
+}
+
+impl TimeInForce {
+    pub fn rests(&self) -> bool {
+        *self == TimeInForce::Gtc
+    }
+}
+
 impl Order {
     pub fn new(id: u64, side: OrderSide, price: f64, quantity: f64, timestamp: u64, user_id: String) -> Self {
         Self {
             id,
             side,
-            price: Price(price),
+            price: Price::new(price),
             quantity,
             timestamp,
             user_id,
+            client_metadata: None,
+            source: None,
+            expires_at: None,
+            fee_exempt: false,
+            time_in_force: TimeInForce::Gtc,
+            display_quantity: None,
+            hidden_remaining: 0.0,
+            refresh_peg: None,
+            take_profit: None,
+            parent_order_id: None,
+        }
+    }
+
+    pub fn with_client_metadata(mut self, client_metadata: Option<String>) -> Self {
+        self.client_metadata = client_metadata;
+        self
+    }
+
+    pub fn with_source(mut self, source: Option<String>) -> Self {
+        self.source = source;
+        self
+    }
+
+    pub fn with_expires_at(mut self, expires_at: Option<u64>) -> Self {
+        self.expires_at = expires_at;
+        self
+    }
+
+    pub fn with_fee_exempt(mut self, fee_exempt: bool) -> Self {
+        self.fee_exempt = fee_exempt;
+        self
+    }
+
+    pub fn with_time_in_force(mut self, time_in_force: TimeInForce) -> Self {
+        self.time_in_force = time_in_force;
+        self
+    }
+
+    pub fn with_display_quantity(mut self, display_quantity: Option<f64>) -> Self {
+        self.display_quantity = display_quantity;
+        self
+    }
+
+    pub fn with_refresh_peg(mut self, refresh_peg: Option<RefreshPeg>) -> Self {
+        self.refresh_peg = refresh_peg;
+        self
+    }
+
+    pub fn with_take_profit(mut self, take_profit: Option<TakeProfit>) -> Self {
+        self.take_profit = take_profit;
+        self
+    }
+
+    pub fn with_parent_order_id(mut self, parent_order_id: Option<u64>) -> Self {
+        self.parent_order_id = parent_order_id;
+        self
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    // Visible slice plus any iceberg reserve.
+    pub fn remaining_quantity(&self) -> f64 {
+        self.quantity + self.hidden_remaining
+    }
+
+    // Folds the reserve back into `quantity`, e.g. before the order matches
+    // again as a taker.
+    pub fn reveal_all(&mut self) {
+        self.quantity += self.hidden_remaining;
+        self.hidden_remaining = 0.0;
+    }
+
+    // Shows at most one slice of an iceberg about to rest and moves the rest
+    // into the reserve. Works in whole lots of `quantity_scale`.
+    pub fn split_display(&mut self, quantity_scale: u64) {
+        let Some(display) = self.display_quantity else {
+            return;
+        };
+        let display = Qty::from_f64(display, quantity_scale);
+        let total = Qty::from_f64(self.remaining_quantity(), quantity_scale);
+        let shown = display.min(total);
+        self.quantity = shown.to_f64(quantity_scale);
+        self.hidden_remaining = total.checked_sub(shown).unwrap_or(Qty::ZERO).to_f64(quantity_scale);
+    }
+
+    // Replaces an exhausted slice with the next one from the reserve. False
+    // when there is no reserve left.
+    pub fn refresh_slice(&mut self, quantity_scale: u64) -> bool {
+        if self.display_quantity.is_none() || Qty::from_f64(self.hidden_remaining, quantity_scale).is_zero() {
+            self.hidden_remaining = 0.0;
+            return false;
+        }
+        self.split_display(quantity_scale);
+        true
+    }
+}
+
+// Optional extras for a new order that most callers leave at their defaults.
+#[derive(Debug, Clone, Default)]
+pub struct OrderOptions {
+    pub client_metadata: Option<String>,
+    pub source: Option<String>,
+    pub expires_at: Option<u64>,
+    pub fee_exempt: bool,
+    pub time_in_force: TimeInForce,
+    // Makes the order an iceberg showing this much at a time.
+    pub display_quantity: Option<f64>,
+    // Reprices an iceberg's slices as they refresh.
+    pub refresh_peg: Option<RefreshPeg>,
+    // Places an exit for each fill.
+    pub take_profit: Option<TakeProfit>,
+}
+
+impl OrderOptions {
+    pub fn with_client_metadata(mut self, client_metadata: impl Into<String>) -> Self {
+        self.client_metadata = Some(client_metadata.into());
+        self
+    }
+
+    pub fn with_source(mut self, source: impl Into<String>) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+
+    pub fn with_expires_at(mut self, expires_at: u64) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+
+    pub fn with_fee_exempt(mut self, fee_exempt: bool) -> Self {
+        self.fee_exempt = fee_exempt;
+        self
+    }
+
+    pub fn with_time_in_force(mut self, time_in_force: TimeInForce) -> Self {
+        self.time_in_force = time_in_force;
+        self
+    }
+
+    pub fn with_display_quantity(mut self, display_quantity: f64) -> Self {
+        self.display_quantity = Some(display_quantity);
+        self
+    }
+
+    pub fn with_refresh_peg(mut self, refresh_peg: RefreshPeg) -> Self {
+        self.refresh_peg = Some(refresh_peg);
+        self
+    }
+
+    pub fn with_take_profit(mut self, take_profit: TakeProfit) -> Self {
+        self.take_profit = Some(take_profit);
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), OrderError> {
+        if let Some(metadata) = &self.client_metadata {
+            if metadata.len() > MAX_CLIENT_METADATA_LEN {
+                return Err(OrderError::ClientMetadataTooLong {
+                    len: metadata.len(),
+                    max: MAX_CLIENT_METADATA_LEN,
+                });
+            }
+        }
+        if let Some(peg) = self.refresh_peg {
+            if !peg.offset.is_finite() {
+                return Err(OrderError::InvalidPegOffset { offset: peg.offset });
+            }
+        }
+        if let Some(take_profit) = self.take_profit {
+            let (TakeProfit::Price(value) | TakeProfit::Offset(value)) = take_profit;
+            if !value.is_finite() || value <= 0.0 {
+                return Err(OrderError::InvalidTakeProfit { value });
+            }
         }
+        Ok(())
     }
 }
\ No newline at end of file
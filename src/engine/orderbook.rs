@@ -1,65 +1,272 @@
-use std::collections::BTreeMap;
-use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
-use std::sync::Arc;
-use crossbeam::queue::SegQueue;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use dashmap::DashMap;
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
 
-use crate::engine::order::{Order, OrderSide};
+use crate::engine::accounting::{LeaderboardMetric, UserAccount, UserAccounts};
+use crate::engine::auction::{self, AuctionInfo};
+use crate::engine::candles::{Candle, CandleInterval, CandleSeries};
+use crate::engine::conditional::{ConditionalOrder, SpreadCondition};
+use crate::engine::config::{LockedMarketPolicy, OrderBookConfig, OrderKind, SelfTradePolicy, TieBreak, TradeThroughCheck, DEFAULT_QUANTITY_SCALE};
+use crate::engine::error::{ConfigError, OrderError};
+use crate::engine::event_log::EventLog;
+use crate::engine::deadman::DeadMansSwitch;
+use crate::engine::killswitch;
+use crate::engine::order::{Order, OrderOptions, OrderSide, PegReference, RefreshPeg, TimeInForce};
 use crate::engine::price::Price;
+use crate::engine::quantity::Qty;
+use crate::engine::seed::{self, SeedError};
+use crate::engine::snapshot::{OrderBookSnapshot, RestoreError, SessionArchive};
 use crate::engine::trade::Trade;
+use crate::events::{BookCleared, BookOrder, BookOrderAction, DepthChanged, EngineEvent, EventBus, MatchEvent, OrderBookEvent, OrderEventStatus, OrderUpdate, TradeThrough};
 
 
+// A level's orders by visible size, largest first, earlier arrivals first
+// among equal sizes. Re-keyed whenever a fill or amend changes a size, so the
+// front is always the order `TieBreak::LargerSize` picks.
+#[derive(Debug, Default)]
+struct SizeIndex {
+    next_arrival: u64,
+    keys: HashMap<u64, (Reverse<Qty>, u64)>,
+    ids: BTreeMap<(Reverse<Qty>, u64), u64>,
+}
+
+impl SizeIndex {
+    fn insert(&mut self, order_id: u64, quantity: Qty) {
+        let key = (Reverse(quantity), self.next_arrival);
+        self.next_arrival += 1;
+        self.keys.insert(order_id, key);
+        self.ids.insert(key, order_id);
+    }
+
+    fn remove(&mut self, order_id: u64) {
+        if let Some(key) = self.keys.remove(&order_id) {
+            self.ids.remove(&key);
+        }
+    }
+
+    // A new size keeps the order's place among equal sizes.
+    fn resize(&mut self, order_id: u64, quantity: Qty) {
+        if let Some(key) = self.keys.get_mut(&order_id) {
+            self.ids.remove(key);
+            key.0 = Reverse(quantity);
+            self.ids.insert(*key, order_id);
+        }
+    }
+
+    fn largest(&self) -> Option<u64> {
+        self.ids.values().next().copied()
+    }
+}
+
+// Ids in arrival order. Removing an order only drops it from `orders`; its id
+// stays queued as a tombstone until it reaches the front, or until tombstones
+// outnumber live orders and the queue is compacted. Reading the front is
+// therefore amortized O(1) however deep the level is.
 #[derive(Debug)]
 pub struct OrderQueue {
     orders: DashMap<u64, Order>,
-    order_queue: SegQueue<u64>,
-    total_quantity: AtomicUsize,
+    order_queue: parking_lot::Mutex<VecDeque<u64>>,
+    // The designated maker's ids in arrival order, tombstoned the same way.
+    // Empty unless the book names a designated maker; checked ahead of the
+    // FIFO front so that priority costs no scan of the level.
+    designated_maker: Option<String>,
+    maker_queue: parking_lot::Mutex<VecDeque<u64>>,
+    // Kept as orders arrive for the book's tie-break, for the same reason:
+    // the primary venue's ids in arrival order, or every order by size.
+    tie_break: TieBreak,
+    venue_queue: parking_lot::Mutex<VecDeque<u64>>,
+    by_size: parking_lot::Mutex<SizeIndex>,
+    // Sum of the live orders' quantities, in lots.
+    total_quantity: AtomicU64,
+    // Sum of the iceberg reserves that refill at this price, in lots. Pegged
+    // icebergs refill elsewhere and aren't counted.
+    refill_reserve: AtomicU64,
+    quantity_scale: u64,
 }
 
 impl OrderQueue {
     pub fn new() -> Self {
+        Self::with_scale(DEFAULT_QUANTITY_SCALE)
+    }
+
+    pub fn with_scale(quantity_scale: u64) -> Self {
         Self {
             orders: DashMap::new(),
-            order_queue: SegQueue::new(),
-            total_quantity: AtomicUsize::new(0),
+            order_queue: parking_lot::Mutex::new(VecDeque::new()),
+            designated_maker: None,
+            maker_queue: parking_lot::Mutex::new(VecDeque::new()),
+            tie_break: TieBreak::Fifo,
+            venue_queue: parking_lot::Mutex::new(VecDeque::new()),
+            by_size: parking_lot::Mutex::new(SizeIndex::default()),
+            total_quantity: AtomicU64::new(0),
+            refill_reserve: AtomicU64::new(0),
+            quantity_scale,
+        }
+    }
+
+    // A queue that applies the book's matching priority.
+    pub fn for_config(config: &OrderBookConfig) -> Self {
+        Self {
+            designated_maker: config.designated_maker.clone(),
+            tie_break: config.tie_break.clone(),
+            ..Self::with_scale(config.quantity_scale)
+        }
+    }
+
+    fn to_qty(&self, quantity: f64) -> Qty {
+        Qty::from_f64(quantity, self.quantity_scale)
+    }
+
+    // Applies `change` to the running total in one atomic step. A change that
+    // would overflow or go below zero means the total has lost track of the
+    // orders it sums; it is refused and the total left as it was.
+    fn adjust_total(&self, change: impl Fn(Qty) -> Option<Qty>) {
+        Self::adjust(&self.total_quantity, change);
+    }
+
+    fn adjust_reserve(&self, change: impl Fn(Qty) -> Option<Qty>) {
+        Self::adjust(&self.refill_reserve, change);
+    }
+
+    fn adjust(counter: &AtomicU64, change: impl Fn(Qty) -> Option<Qty>) {
+        let result = counter.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |units| change(Qty(units)).map(Qty::units));
+        if result.is_err() {
+            log::error!("Price level total quantity out of range; update skipped");
+            debug_assert!(false, "price level total quantity out of range");
         }
     }
 
+    // The part of `hidden_remaining` that would refill at this price.
+    fn refill_qty(&self, order: &Order, hidden_remaining: f64) -> Qty {
+        if order.refresh_peg.is_some() {
+            return Qty::ZERO;
+        }
+        self.to_qty(hidden_remaining)
+    }
+
     pub fn add_order(&self, order: Order) {
-        let quantity = (order.quantity * 1_000_000.0) as usize;
+        let quantity = self.to_qty(order.quantity);
+        let reserve = self.refill_qty(&order, order.hidden_remaining);
+        if self.designated_maker.as_deref() == Some(order.user_id.as_str()) {
+            self.maker_queue.lock().push_back(order.id);
+        }
+        match &self.tie_break {
+            TieBreak::Fifo => {}
+            TieBreak::PrimaryVenue(venue) => {
+                if order.source.as_deref() == Some(venue.as_str()) {
+                    self.venue_queue.lock().push_back(order.id);
+                }
+            }
+            TieBreak::LargerSize => self.by_size.lock().insert(order.id, quantity),
+        }
         self.orders.insert(order.id, order.clone());
-        self.order_queue.push(order.id);
-        self.total_quantity.fetch_add(quantity, Ordering::Relaxed);
+        self.order_queue.lock().push_back(order.id);
+        self.adjust_total(|total| total.checked_add(quantity));
+        self.adjust_reserve(|total| total.checked_add(reserve));
     }
 
     pub fn remove_order(&self, order_id: u64) -> Option<Order> {
         if let Some((_, order)) = self.orders.remove(&order_id) {
-            let quantity = (order.quantity * 1_000_000.0) as usize;
-            self.total_quantity.fetch_sub(quantity, Ordering::Relaxed);
+            let quantity = self.to_qty(order.quantity);
+            let reserve = self.refill_qty(&order, order.hidden_remaining);
+            self.adjust_total(|total| total.checked_sub(quantity));
+            self.adjust_reserve(|total| total.checked_sub(reserve));
+            if self.tie_break == TieBreak::LargerSize {
+                self.by_size.lock().remove(order_id);
+            }
+            self.compact_if_sparse();
             Some(order)
         } else {
             None
         }
     }
 
+    // Removes the order along with its queue entry instead of leaving a
+    // tombstone, for an id that is about to be queued again (an amended
+    // order); a stale entry would otherwise hand it its old place back.
+    pub fn take_order(&self, order_id: u64) -> Option<Order> {
+        let order = self.remove_order(order_id)?;
+        self.order_queue.lock().retain(|queued| *queued != order_id);
+        self.maker_queue.lock().retain(|queued| *queued != order_id);
+        self.venue_queue.lock().retain(|queued| *queued != order_id);
+        Some(order)
+    }
+
+    // Drops tombstoned ids once they make up most of the queue, so cancels
+    // deep in a level can't grow it without bound. The maker and venue
+    // queues hold subsets of the same ids and are compacted along with it.
+    fn compact_if_sparse(&self) {
+        let mut queue = self.order_queue.lock();
+        if queue.len() > 2 * self.orders.len() + 64 {
+            queue.retain(|order_id| self.orders.contains_key(order_id));
+            self.maker_queue.lock().retain(|order_id| self.orders.contains_key(order_id));
+            self.venue_queue.lock().retain(|order_id| self.orders.contains_key(order_id));
+        }
+    }
+
+    // Pops tombstones off the front; returns the first live id.
+    fn front_id(&self, queue: &mut VecDeque<u64>) -> Option<u64> {
+        while let Some(&order_id) = queue.front() {
+            if self.orders.contains_key(&order_id) {
+                return Some(order_id);
+            }
+            queue.pop_front();
+        }
+        None
+    }
+
+    // The stored quantity is snapped to whole lots, so float noise from the
+    // caller's arithmetic never reaches the total.
     pub fn update_order(&self, order_id: u64, new_quantity: f64) -> bool {
         if let Some(mut order_ref) = self.orders.get_mut(&order_id) {
-            let old_quantity = (order_ref.quantity * 1_000_000.0) as usize;
-            let new_quantity_int = (new_quantity * 1_000_000.0) as usize;
-            
-            order_ref.quantity = new_quantity;
-            self.total_quantity.fetch_add(new_quantity_int, Ordering::Relaxed);
-            self.total_quantity.fetch_sub(old_quantity, Ordering::Relaxed);
+            let old_quantity = self.to_qty(order_ref.quantity);
+            let new_quantity = self.to_qty(new_quantity);
+
+            order_ref.quantity = new_quantity.to_f64(self.quantity_scale);
+            self.adjust_total(|total| total.checked_add(new_quantity)?.checked_sub(old_quantity));
+            if self.tie_break == TieBreak::LargerSize {
+                self.by_size.lock().resize(order_id, new_quantity);
+            }
             true
         } else {
             false
         }
     }
 
+    // Sets an iceberg's undisplayed reserve, which the total never includes.
+    pub fn update_reserve(&self, order_id: u64, hidden_remaining: f64) -> bool {
+        match self.orders.get_mut(&order_id) {
+            Some(mut order_ref) => {
+                let old_reserve = self.refill_qty(&order_ref, order_ref.hidden_remaining);
+                let new_reserve = self.refill_qty(&order_ref, hidden_remaining);
+                order_ref.hidden_remaining = hidden_remaining;
+                self.adjust_reserve(|total| total.checked_add(new_reserve)?.checked_sub(old_reserve));
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Visible quantity only: iceberg reserves are left out.
     pub fn get_total_quantity(&self) -> f64 {
-        (self.total_quantity.load(Ordering::Relaxed) as f64) / 1_000_000.0
+        self.total_qty().to_f64(self.quantity_scale)
+    }
+
+    pub fn total_qty(&self) -> Qty {
+        Qty(self.total_quantity.load(Ordering::Relaxed))
+    }
+
+    // Iceberg reserves that refill in place, on top of the visible total.
+    pub fn refill_reserve(&self) -> f64 {
+        Qty(self.refill_reserve.load(Ordering::Relaxed)).to_f64(self.quantity_scale)
     }
 
     pub fn is_empty(&self) -> bool {
@@ -70,38 +277,109 @@ impl OrderQueue {
         self.orders.len()
     }
 
+    // The order that matches next: the designated maker's oldest, if any
+    // rests here; else the one the tie-break prefers; else the FIFO front.
+    // Each is read off an ordering kept as orders arrive, never a scan.
+    // Takes one lock at a time; `compact_if_sparse` nests them.
+    //
+    // Hidden size never competes here. Within each ordering the rule is:
+    // displayed size first, in time priority, an iceberg's current slice
+    // counting as displayed from when the iceberg arrived; then hidden size,
+    // which only matches once `refresh_iceberg` has turned it into a new
+    // slice at the back, behind every order queued before the refresh.
     pub fn get_first_order(&self) -> Option<Order> {
-        let mut temp_queue = Vec::new();
-        let mut first_order = None;
-        
-        while let Some(order_id) = self.order_queue.pop() {
-            if let Some(order) = self.orders.get(&order_id) {
-                first_order = Some(order.clone());
-                temp_queue.push(order_id);
-                break;
-            }
-            temp_queue.push(order_id);
-        }
-        
-        for order_id in temp_queue {
-            self.order_queue.push(order_id);
-        }
-        
-        first_order
+        let maker_order_id = self.front_id(&mut self.maker_queue.lock());
+        let preferred_id = maker_order_id.or_else(|| match self.tie_break {
+            TieBreak::Fifo => None,
+            TieBreak::PrimaryVenue(_) => self.front_id(&mut self.venue_queue.lock()),
+            TieBreak::LargerSize => self.by_size.lock().largest(),
+        });
+        let order_id = preferred_id.or_else(|| self.front_id(&mut self.order_queue.lock()))?;
+        self.get_order(order_id)
     }
 
     pub fn remove_first_order(&self) -> Option<Order> {
-        while let Some(order_id) = self.order_queue.pop() {
-            if let Some(order) = self.remove_order(order_id) {
-                return Some(order);
-            }
-        }
-        None
+        let order_id = {
+            let mut queue = self.order_queue.lock();
+            let order_id = self.front_id(&mut queue)?;
+            queue.pop_front();
+            order_id
+        };
+        self.remove_order(order_id)
     }
 
     pub fn get_order(&self, order_id: u64) -> Option<Order> {
         self.orders.get(&order_id).map(|o| o.clone())
     }
+
+    // The order's 0-based place among the live orders in FIFO order, and how
+    // many live orders the level holds. Tombstones are skipped, not popped.
+    pub fn position(&self, order_id: u64) -> Option<(usize, usize)> {
+        if !self.orders.contains_key(&order_id) {
+            return None;
+        }
+        let queue = self.order_queue.lock();
+        let mut live = queue.iter().filter(|queued| self.orders.contains_key(queued));
+        let position = live.position(|queued| *queued == order_id)?;
+        Some((position, position + 1 + live.count()))
+    }
+
+    // Live orders in FIFO order.
+    pub fn orders_in_queue_order(&self) -> Vec<Order> {
+        self.order_queue
+            .lock()
+            .iter()
+            .filter_map(|order_id| self.orders.get(order_id).map(|order| order.clone()))
+            .collect()
+    }
+
+    pub fn remove_user_orders(&self, user_id: &str) -> Vec<Order> {
+        let order_ids: Vec<u64> = self
+            .orders
+            .iter()
+            .filter(|entry| entry.user_id == user_id)
+            .map(|entry| *entry.key())
+            .collect();
+
+        order_ids
+            .into_iter()
+            .filter_map(|order_id| self.remove_order(order_id))
+            .collect()
+    }
+
+    pub fn remove_source_orders(&self, source: &str) -> Vec<Order> {
+        let order_ids: Vec<u64> = self
+            .orders
+            .iter()
+            .filter(|entry| entry.source.as_deref() == Some(source))
+            .map(|entry| *entry.key())
+            .collect();
+
+        order_ids
+            .into_iter()
+            .filter_map(|order_id| self.remove_order(order_id))
+            .collect()
+    }
+
+    pub fn remove_expired_orders(&self, now: u64) -> Vec<Order> {
+        let order_ids: Vec<u64> = self
+            .orders
+            .iter()
+            .filter(|entry| entry.is_expired(now))
+            .map(|entry| *entry.key())
+            .collect();
+
+        order_ids
+            .into_iter()
+            .filter_map(|order_id| self.remove_order(order_id))
+            .collect()
+    }
+}
+
+impl Default for OrderQueue {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 
@@ -113,9 +391,20 @@ pub struct PriceLevel {
 
 impl PriceLevel {
     pub fn new(price: f64) -> Self {
+        Self::with_scale(price, DEFAULT_QUANTITY_SCALE)
+    }
+
+    pub fn with_scale(price: f64, quantity_scale: u64) -> Self {
+        Self {
+            price: Price::new(price),
+            orders: Arc::new(OrderQueue::with_scale(quantity_scale)),
+        }
+    }
+
+    pub fn for_config(price: f64, config: &OrderBookConfig) -> Self {
         Self {
-            price: Price(price),
-            orders: Arc::new(OrderQueue::new()),
+            price: Price::new(price),
+            orders: Arc::new(OrderQueue::for_config(config)),
         }
     }
 
@@ -150,6 +439,18 @@ impl PriceLevel {
     pub fn remove_first_order(&self) -> Option<Order> {
         self.orders.remove_first_order()
     }
+
+    pub fn remove_user_orders(&self, user_id: &str) -> Vec<Order> {
+        self.orders.remove_user_orders(user_id)
+    }
+
+    pub fn remove_expired_orders(&self, now: u64) -> Vec<Order> {
+        self.orders.remove_expired_orders(now)
+    }
+
+    pub fn remove_source_orders(&self, source: &str) -> Vec<Order> {
+        self.orders.remove_source_orders(source)
+    }
 }
 
 
@@ -164,6 +465,17 @@ pub struct OrderBookStats {
     pub spread: Option<f64>,
     pub mid_price: Option<f64>,
     pub last_match_time: Option<u64>,
+    // Time-weighted EMA of `mid_price`; `None` until the book first has
+    // both sides. It holds while a side is empty.
+    #[serde(default)]
+    pub mid_ema: Option<f64>,
+    // Notional traded over the trailing 24 hours, to the minute. Filled in
+    // by `OrderBook::get_stats`.
+    #[serde(default)]
+    pub volume_24h: f64,
+    // When `mid_ema` last took in the mid.
+    #[serde(skip)]
+    mid_ema_updated_ms: u64,
 }
 
 impl OrderBookStats {
@@ -178,9 +490,24 @@ impl OrderBookStats {
             spread: None,
             mid_price: None,
             last_match_time: None,
+            mid_ema: None,
+            volume_24h: 0.0,
+            mid_ema_updated_ms: 0,
         }
     }
 
+    // Moves `mid_ema` towards the mid that has held since its last update,
+    // by `alpha` of the gap for each second it held (compounded), so bursts
+    // of updates don't speed it up and quiet spells still pull it along.
+    pub fn advance_mid_ema(&mut self, now: u64, alpha: f64) {
+        if let (Some(ema), Some(mid)) = (self.mid_ema, self.mid_price) {
+            let elapsed_secs = now.saturating_sub(self.mid_ema_updated_ms) as f64 / 1000.0;
+            let weight = 1.0 - (1.0 - alpha).powf(elapsed_secs);
+            self.mid_ema = Some(ema + weight * (mid - ema));
+        }
+        self.mid_ema_updated_ms = now;
+    }
+
     pub fn update_market_data(&mut self, best_bid: Option<f64>, best_ask: Option<f64>) {
         self.best_bid = best_bid;
         self.best_ask = best_ask;
@@ -195,6 +522,289 @@ impl OrderBookStats {
     }
 }
 
+impl Default for OrderBookStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub type DepthSide = Vec<(f64, f64)>;
+
+// Running total of quantity from the best level outwards.
+pub fn cumulative_depth(side: &[(f64, f64)]) -> DepthSide {
+    side.iter()
+        .scan(0.0, |total, &(price, quantity)| {
+            *total += quantity;
+            Some((price, *total))
+        })
+        .collect()
+}
+
+// Levels grouped into price buckets `width` wide, at most `buckets` of them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DepthBucket {
+    // The bucket's edge nearest the other side of the book.
+    pub price: f64,
+    pub quantity: f64,
+    // Quantity-weighted average price of the levels in the bucket.
+    pub average_price: f64,
+}
+
+// Groups one side's levels, best first, into buckets `width` wide. Bids fall
+// into the bucket at or below their price and asks into the one at or above,
+// so a bucket's edge is never better than the levels in it.
+pub fn bucket_depth(side: &[(f64, f64)], order_side: OrderSide, width: f64, buckets: usize) -> Vec<DepthBucket> {
+    let mut grouped: Vec<(i64, f64, f64)> = Vec::new();
+    for &(price, quantity) in side {
+        // Nudged so a price on an edge isn't pushed a bucket out by float error.
+        let index = match order_side {
+            OrderSide::Bid => (price / width + 1e-9).floor() as i64,
+            OrderSide::Ask => (price / width - 1e-9).ceil() as i64,
+        };
+        let full = grouped.len() == buckets;
+        match grouped.last_mut() {
+            Some((last, total, notional)) if *last == index => {
+                *total += quantity;
+                *notional += price * quantity;
+            }
+            _ if full => break,
+            _ => grouped.push((index, quantity, price * quantity)),
+        }
+    }
+
+    grouped
+        .into_iter()
+        .map(|(index, quantity, notional)| DepthBucket {
+            price: Price::new(index as f64 * width).as_f64(),
+            quantity,
+            average_price: notional / quantity,
+        })
+        .collect()
+}
+
+// Least-squares slope of y on x; `None` without at least two distinct xs.
+fn regression_slope(points: &[(f64, f64)]) -> Option<f64> {
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let covariance: f64 = points.iter().map(|(x, y)| (x - mean_x) * (y - mean_y)).sum();
+    let variance: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    (variance > 0.0).then(|| covariance / variance)
+}
+
+// Bit pattern stored in the best-price caches while a side is empty.
+const NO_PRICE: u64 = u64::MAX;
+
+fn encode_price(price: Option<&Price>) -> u64 {
+    price.map_or(NO_PRICE, |p| p.as_f64().to_bits())
+}
+
+fn decode_price(bits: u64) -> Option<f64> {
+    (bits != NO_PRICE).then(|| f64::from_bits(bits))
+}
+
+#[derive(Debug, Clone)]
+pub struct CancelReplaceResult {
+    pub cancelled: Option<Order>,
+    pub order_id: Option<u64>,
+    // What of the replacement is left resting once it has matched; 0 if it
+    // wasn't placed.
+    pub remaining_quantity: f64,
+    pub trades: Vec<Trade>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MarketOrderResult {
+    pub order_id: u64,
+    pub trades: Vec<Trade>,
+    // Left unfilled and cancelled rather than rested.
+    pub cancelled_quantity: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct AmendResult {
+    pub order_id: u64,
+    // Left resting once the amendment and any fills it caused are done.
+    pub remaining_quantity: f64,
+    // Amended in place without losing its queue position.
+    pub priority_kept: bool,
+    pub trades: Vec<Trade>,
+}
+
+#[derive(Debug, Clone)]
+pub struct QuoteResult {
+    // Orders actually removed; ids that weren't the user's resting orders
+    // are skipped.
+    pub cancelled: Vec<Order>,
+    // One id per add, in the order given.
+    pub order_ids: Vec<u64>,
+    pub trades: Vec<Trade>,
+}
+
+// Result of walking the book to spend a fixed amount of quote currency.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NotionalFill {
+    pub quantity: f64,
+    pub notional: f64,
+    pub average_price: Option<f64>,
+    // False when the opposite side ran out before the target was reached.
+    pub complete: bool,
+}
+
+// What a market sweep of a fixed quantity would do to the opposite side, and
+// how much of that side would be left behind it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SweepResilience {
+    pub filled_quantity: f64,
+    pub average_price: Option<f64>,
+    // Best opposite price before the sweep, and the worst price it reached.
+    pub start_price: Option<f64>,
+    pub end_price: Option<f64>,
+    // Distance between the two, always non-negative; the percentage is of
+    // `start_price`.
+    pub price_move: f64,
+    pub price_move_pct: Option<f64>,
+    // Levels the sweep touched, including one it only partly took.
+    pub levels_swept: usize,
+    // Levels left untouched beyond the swept region, and their quantity.
+    pub levels_remaining: usize,
+    pub quantity_remaining: f64,
+    // Best opposite price once the sweep is done: the partly taken level, or
+    // the first untouched one.
+    pub resulting_best: Option<f64>,
+    // False when the opposite side ran out before `quantity` was filled.
+    pub complete: bool,
+}
+
+// Mid changes kept for TWAP; only changes are stored, so a quiet book covers
+// a long window.
+const MID_HISTORY_CAPACITY: usize = 10_000;
+const RECENT_TERMINAL_CAPACITY: usize = 10_000;
+
+const VOLUME_WINDOW_MS: u64 = 24 * 60 * 60 * 1000;
+const VOLUME_BUCKET_MS: u64 = 60 * 1000;
+
+// Traded notional per minute, oldest first, going back `VOLUME_WINDOW_MS`.
+// At most one bucket per minute, so it never holds more than a day's worth.
+#[derive(Debug, Default)]
+struct RollingVolume {
+    buckets: VecDeque<(u64, f64)>,
+}
+
+impl RollingVolume {
+    // A trade stamped earlier than the newest bucket counts towards its own
+    // minute if that bucket is still kept, otherwise towards the newest.
+    fn record(&mut self, timestamp: u64, notional: f64) {
+        let minute = timestamp - timestamp % VOLUME_BUCKET_MS;
+        if self.buckets.back().is_none_or(|&(start, _)| start < minute) {
+            self.buckets.push_back((minute, notional));
+        } else if let Some((_, volume)) = self.buckets.iter_mut().rev().find(|(start, _)| *start == minute) {
+            *volume += notional;
+        } else if let Some((_, volume)) = self.buckets.back_mut() {
+            *volume += notional;
+        }
+        self.evict(timestamp);
+    }
+
+    fn evict(&mut self, now: u64) {
+        while self.buckets.front().is_some_and(|&(start, _)| start + VOLUME_WINDOW_MS <= now) {
+            self.buckets.pop_front();
+        }
+    }
+
+    fn total(&mut self, now: u64) -> f64 {
+        self.evict(now);
+        self.buckets.iter().map(|(_, volume)| volume).sum()
+    }
+}
+
+// Owner and final status of orders that recently filled, were cancelled or
+// expired, so a late cancel can be told why it failed. Oldest evicted first.
+#[derive(Debug, Default)]
+struct TerminalOrders {
+    statuses: HashMap<u64, (String, OrderEventStatus)>,
+    order: VecDeque<u64>,
+}
+
+impl TerminalOrders {
+    fn record(&mut self, order_id: u64, user_id: &str, status: OrderEventStatus) {
+        if self.statuses.insert(order_id, (user_id.to_string(), status)).is_some() {
+            return;
+        }
+        if self.order.len() == RECENT_TERMINAL_CAPACITY {
+            if let Some(evicted) = self.order.pop_front() {
+                self.statuses.remove(&evicted);
+            }
+        }
+        self.order.push_back(order_id);
+    }
+}
+
+// An order with a take-profit attached and what it filled during the
+// current match.
+#[derive(Debug)]
+struct TakeProfitFill {
+    entry: Order,
+    quantity: f64,
+    notional: f64,
+}
+
+// Take-profits placed for recently filled orders, by the filled order's id,
+// so the response to an order can list its own. Oldest evicted first.
+#[derive(Debug, Default)]
+struct TakeProfitChildren {
+    children: HashMap<u64, Vec<u64>>,
+    order: VecDeque<u64>,
+}
+
+impl TakeProfitChildren {
+    fn record(&mut self, entry_id: u64, child_id: u64) {
+        if let Some(children) = self.children.get_mut(&entry_id) {
+            children.push(child_id);
+            return;
+        }
+        if self.order.len() == RECENT_TERMINAL_CAPACITY {
+            if let Some(evicted) = self.order.pop_front() {
+                self.children.remove(&evicted);
+            }
+        }
+        self.children.insert(entry_id, vec![child_id]);
+        self.order.push_back(entry_id);
+    }
+}
+
+// Where each user's resting orders sit, so they can be listed without
+// scanning every level. Kept in step by `publish_book_order`.
+#[derive(Debug, Default)]
+struct UserOrderIndex {
+    orders: DashMap<String, HashMap<u64, (OrderSide, Price)>>,
+}
+
+impl UserOrderIndex {
+    fn insert(&self, order: &Order) {
+        self.orders
+            .entry(order.user_id.clone())
+            .or_default()
+            .insert(order.id, (order.side, order.price));
+    }
+
+    fn remove(&self, order: &Order) {
+        if let Some(mut orders) = self.orders.get_mut(&order.user_id) {
+            orders.remove(&order.id);
+        }
+        self.orders.remove_if(&order.user_id, |_, orders| orders.is_empty());
+    }
+
+    fn get(&self, user_id: &str) -> Vec<(u64, OrderSide, Price)> {
+        self.orders.get(user_id).map_or_else(Vec::new, |orders| {
+            orders.iter().map(|(&id, &(side, price))| (id, side, price)).collect()
+        })
+    }
+
+    fn clear(&self) {
+        self.orders.clear();
+    }
+}
 
 #[derive(Debug)]
 pub struct OrderBook {
@@ -203,61 +813,784 @@ pub struct OrderBook {
     next_order_id: AtomicU64,
     stats: Arc<RwLock<OrderBookStats>>,
     matching_lock: parking_lot::Mutex<()>,
+    config: OrderBookConfig,
+    dead_man: DeadMansSwitch,
+    pending_conditionals: parking_lot::Mutex<Vec<ConditionalOrder>>,
+    trade_history: RwLock<VecDeque<Trade>>,
+    // (timestamp, mid) each time a sample found the mid changed; `None` while
+    // one side is empty. Each mid holds until the next entry.
+    mid_history: RwLock<VecDeque<(u64, Option<f64>)>>,
+    recent_terminal: parking_lot::Mutex<TerminalOrders>,
+    events: EventBus,
+    // Latest timestamp of any order placed; 0 until the first one.
+    last_updated_ms: AtomicU64,
+    // Bumped under `matching_lock` every time resting depth may have changed.
+    sequence: AtomicU64,
+    // Fills flagged by `OrderBookConfig::trade_through_check`.
+    trade_throughs: AtomicU64,
+    // Top of book mirrored out of `bids`/`asks` so hot readers (matching,
+    // depth broadcasts) don't take the BTreeMap locks. Written under
+    // `matching_lock` right after a side changes; the maps stay authoritative.
+    best_bid: AtomicU64,
+    best_ask: AtomicU64,
+    // Set while the book is collecting orders for its opening auction.
+    opening_auction: AtomicBool,
+    accounts: UserAccounts,
+    user_orders: UserOrderIndex,
+    // Latest order timestamp per `source` venue, for spotting dead feeds.
+    // A venue is dropped from here once its orders have been swept.
+    source_last_seen: DashMap<String, u64>,
+    // Trades since the last `checkpoint`; reaching
+    // `OrderBookConfig::snapshot_every_trades` wakes `checkpoint_due`.
+    trades_since_checkpoint: AtomicU64,
+    checkpoint_due: tokio::sync::Notify,
+    // Pegged iceberg slices refreshed during the current match, held back
+    // until the incoming order has rested so they are priced off the book
+    // it leaves. Only touched under `matching_lock`.
+    pegged_refreshes: parking_lot::Mutex<Vec<Order>>,
+    // Fills of orders with a take-profit attached, held until the match is
+    // recorded so the take-profits trade after it. Only touched under
+    // `matching_lock`.
+    take_profit_fills: parking_lot::Mutex<Vec<TakeProfitFill>>,
+    take_profit_children: parking_lot::Mutex<TakeProfitChildren>,
+    volume_24h: parking_lot::Mutex<RollingVolume>,
+    // One series per `CandleInterval::ALL`, in that order.
+    candles: parking_lot::Mutex<Vec<CandleSeries>>,
+    // Write-ahead log every state change is appended to, once attached.
+    // Only written under `matching_lock`.
+    event_log: OnceLock<Arc<EventLog>>,
 }
 
 impl OrderBook {
     pub fn new() -> Self {
-        Self {
+        Self::with_config(OrderBookConfig::default())
+    }
+
+    // Panics on a config `OrderBookConfig::validate` refuses; use
+    // `try_with_config` to handle that instead.
+    pub fn with_config(config: OrderBookConfig) -> Self {
+        Self::try_with_config(config).unwrap_or_else(|e| panic!("invalid order book config: {}", e))
+    }
+
+    pub fn try_with_config(config: OrderBookConfig) -> Result<Self, ConfigError> {
+        config.validate()?;
+        Ok(Self {
             bids: RwLock::new(BTreeMap::new()),
             asks: RwLock::new(BTreeMap::new()),
             next_order_id: AtomicU64::new(1),
             stats: Arc::new(RwLock::new(OrderBookStats::new())),
             matching_lock: parking_lot::Mutex::new(()),
+            dead_man: DeadMansSwitch::new(),
+            pending_conditionals: parking_lot::Mutex::new(Vec::new()),
+            trade_history: RwLock::new(VecDeque::with_capacity(config.trade_history_capacity)),
+            mid_history: RwLock::new(VecDeque::with_capacity(MID_HISTORY_CAPACITY)),
+            recent_terminal: parking_lot::Mutex::new(TerminalOrders::default()),
+            events: EventBus::new(),
+            last_updated_ms: AtomicU64::new(0),
+            sequence: AtomicU64::new(0),
+            trade_throughs: AtomicU64::new(0),
+            best_bid: AtomicU64::new(NO_PRICE),
+            best_ask: AtomicU64::new(NO_PRICE),
+            opening_auction: AtomicBool::new(config.opening_auction),
+            accounts: UserAccounts::new(),
+            user_orders: UserOrderIndex::default(),
+            source_last_seen: DashMap::new(),
+            trades_since_checkpoint: AtomicU64::new(0),
+            checkpoint_due: tokio::sync::Notify::new(),
+            pegged_refreshes: parking_lot::Mutex::new(Vec::new()),
+            take_profit_fills: parking_lot::Mutex::new(Vec::new()),
+            take_profit_children: parking_lot::Mutex::new(TakeProfitChildren::default()),
+            volume_24h: parking_lot::Mutex::new(RollingVolume::default()),
+            candles: parking_lot::Mutex::new(CandleInterval::ALL.into_iter().map(CandleSeries::new).collect()),
+            event_log: OnceLock::new(),
+            config,
+        })
+    }
+
+    pub fn config(&self) -> &OrderBookConfig {
+        &self.config
+    }
+
+    pub fn dead_man(&self) -> &DeadMansSwitch {
+        &self.dead_man
+    }
+
+    pub fn accounts(&self) -> &UserAccounts {
+        &self.accounts
+    }
+
+    pub fn last_updated_ms(&self) -> u64 {
+        self.last_updated_ms.load(Ordering::Relaxed)
+    }
+
+    pub fn sequence(&self) -> u64 {
+        self.sequence.load(Ordering::Relaxed)
+    }
+
+    pub fn trade_through_count(&self) -> u64 {
+        self.trade_throughs.load(Ordering::Relaxed)
+    }
+
+    pub fn in_opening_auction(&self) -> bool {
+        self.opening_auction.load(Ordering::Acquire)
+    }
+
+    // Caller must hold `matching_lock` and pass the side it just mutated.
+    fn store_best_bid(&self, bids: &BTreeMap<Price, PriceLevel>) {
+        self.best_bid.store(encode_price(bids.keys().next_back()), Ordering::Release);
+    }
+
+    fn store_best_ask(&self, asks: &BTreeMap<Price, PriceLevel>) {
+        self.best_ask.store(encode_price(asks.keys().next()), Ordering::Release);
+    }
+
+    // Caller must hold `matching_lock`.
+    fn bump_sequence(&self) {
+        let sequence = self.sequence.fetch_add(1, Ordering::Relaxed) + 1;
+        self.events.publish(OrderBookEvent::DepthChanged(DepthChanged { sequence }));
+    }
+
+    pub fn subscribe(&self) -> tokio::sync::broadcast::Receiver<OrderBookEvent> {
+        self.events.subscribe()
+    }
+
+    // `event` is only built when a log is attached. A failed write is
+    // logged rather than failing the operation, which has already happened.
+    fn journal(&self, event: impl FnOnce() -> EngineEvent) {
+        if let Some(log) = self.event_log.get() {
+            if let Err(e) = log.append(&event()) {
+                log::error!(" Could not append to event log {}: {}", log.path().display(), e);
+            }
         }
     }
 
- 
-    pub fn add_order(&self, side: OrderSide, price: f64, quantity: f64, timestamp: u64, user_id: String) -> (u64, Vec<Trade>) {
+    fn record_order_account(&self, user_id: &str) {
+        let account = self.accounts.record_order(user_id);
+        self.journal(|| EngineEvent::AccountUpdated { user_id: user_id.to_string(), account });
+    }
+
+    fn record_fill_account(&self, user_id: &str, side: OrderSide, price: f64, quantity: f64, fee: f64, maker: bool) {
+        let account = self.accounts.record_fill(user_id, side, price, quantity, fee, maker);
+        self.journal(|| EngineEvent::AccountUpdated { user_id: user_id.to_string(), account });
+    }
+
+    fn issue_order_id(&self) -> u64 {
         let order_id = self.next_order_id.fetch_add(1, Ordering::Relaxed);
-        let mut order = Order::new(order_id, side.clone(), price, quantity, timestamp, user_id);
+        self.journal(|| EngineEvent::OrderIdIssued { order_id });
+        order_id
+    }
+
+    fn publish_order_update(&self, order: &Order, status: OrderEventStatus, filled_quantity: f64) {
+        if matches!(status, OrderEventStatus::Filled | OrderEventStatus::Cancelled | OrderEventStatus::Expired) {
+            self.recent_terminal.lock().record(order.id, &order.user_id, status);
+        }
+        self.events.publish(OrderBookEvent::OrderUpdate(OrderUpdate {
+            user_id: order.user_id.clone(),
+            order_id: order.id,
+            status,
+            filled_quantity,
+            remaining_quantity: order.remaining_quantity(),
+            client_metadata: order.client_metadata.clone(),
+        }));
+    }
+
+    // Every resting-order change passes through here, so it also keeps
+    // `user_orders` up to date whether or not L3 events are on, and writes
+    // the change to the event log.
+    fn publish_book_order(&self, action: BookOrderAction, order: &Order) {
+        match action {
+            BookOrderAction::Add => self.user_orders.insert(order),
+            BookOrderAction::Cancel | BookOrderAction::Fill => self.user_orders.remove(order),
+            BookOrderAction::Modify => {}
+        }
+        self.journal(|| match action {
+            BookOrderAction::Add => EngineEvent::OrderPlaced { order: order.clone() },
+            BookOrderAction::Modify => EngineEvent::OrderUpdated { order: order.clone() },
+            BookOrderAction::Cancel | BookOrderAction::Fill => EngineEvent::OrderRemoved {
+                order_id: order.id,
+                side: order.side,
+                price: order.price.as_f64(),
+                action,
+            },
+        });
+        if !self.config.order_level_events {
+            return;
+        }
+        self.events.publish(OrderBookEvent::BookOrder(BookOrder {
+            action,
+            order_id: order.id,
+            side: order.side,
+            price: order.price.as_f64(),
+            quantity: match action {
+                BookOrderAction::Add | BookOrderAction::Modify => order.quantity,
+                BookOrderAction::Cancel | BookOrderAction::Fill => 0.0,
+            },
+        }));
+    }
+
+ 
+    pub fn add_order(
+        &self,
+        side: OrderSide,
+        price: f64,
+        quantity: f64,
+        timestamp: u64,
+        user_id: String,
+    ) -> Result<(u64, Vec<Trade>), OrderError> {
+        self.add_order_with_options(side, price, quantity, timestamp, user_id, OrderOptions::default())
+    }
+
+    pub fn add_order_with_options(
+        &self,
+        side: OrderSide,
+        price: f64,
+        quantity: f64,
+        timestamp: u64,
+        user_id: String,
+        options: OrderOptions,
+    ) -> Result<(u64, Vec<Trade>), OrderError> {
+        killswitch::check()?;
+        if options.source.is_none() {
+            self.config.check_order_kind(OrderKind::Limit(options.time_in_force))?;
+        }
+        let quantity = self.config.normalize_quantity(quantity)?;
+        let price = self.config.limit_price(price)?.as_f64();
+        options.validate()?;
+        let display_quantity = options.display_quantity.map(|q| self.config.normalize_quantity(q)).transpose()?;
         
+        let _lock = self.matching_lock.lock();
+        self.check_entry(side, price, options.time_in_force)?;
+        // Only an accepted order shows its venue is alive; one whose orders
+        // are all rejected still gets its stale quotes swept.
+        if let Some(source) = &options.source {
+            let mut last_seen = self.source_last_seen.entry(source.clone()).or_default();
+            *last_seen = (*last_seen).max(timestamp);
+        }
+        let order_id = self.issue_order_id();
+        let order = Order::new(order_id, side, price, quantity, timestamp, user_id)
+            .with_client_metadata(options.client_metadata)
+            .with_source(options.source)
+            .with_expires_at(options.expires_at)
+            .with_fee_exempt(options.fee_exempt)
+            .with_time_in_force(options.time_in_force)
+            .with_display_quantity(display_quantity)
+            .with_refresh_peg(options.refresh_peg)
+            .with_take_profit(options.take_profit);
         
-        let trades = self.match_order(&mut order);
+        let trades = self.place_order(order);
+        self.evaluate_conditional_orders();
         
-        if order.quantity > 0.0 {
-            match side {
-                OrderSide::Bid => {
-                    let mut bids = self.bids.write();
-                    bids.entry(Price(price))
-                        .or_insert_with(|| PriceLevel::new(price))
-                        .add_order(order);
-                }
-                OrderSide::Ask => {
-                    let mut asks = self.asks.write();
-                    asks.entry(Price(price))
-                        .or_insert_with(|| PriceLevel::new(price))
-                        .add_order(order);
-                }
+        Ok((order_id, trades))
+    }
+
+    // Sweeps the opposite side for up to `quantity` and cancels whatever is
+    // left instead of resting it. A `protection_price` stops the sweep before
+    // any level beyond it: above it for a buy, below it for a sell.
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_market_order(
+        &self,
+        side: OrderSide,
+        quantity: f64,
+        protection_price: Option<f64>,
+        timestamp: u64,
+        user_id: String,
+        options: OrderOptions,
+    ) -> Result<MarketOrderResult, OrderError> {
+        killswitch::check()?;
+        if options.source.is_none() {
+            self.config.check_order_kind(OrderKind::Market)?;
+        }
+        let quantity = self.config.normalize_quantity(quantity)?;
+        options.validate()?;
+        if let Some(price) = protection_price {
+            if !price.is_finite() || price <= 0.0 {
+                return Err(OrderError::InvalidProtectionPrice { price });
             }
         }
-
+        
+        let _lock = self.matching_lock.lock();
+        if self.in_opening_auction() {
+            return Err(OrderError::AuctionInProgress);
+        }
+        self.record_order_account(&user_id);
+        // Unprotected, the order is priced to cross every level.
+        let limit = protection_price.unwrap_or(match side {
+            OrderSide::Bid => f64::MAX,
+            OrderSide::Ask => 0.0,
+        });
+        let order_id = self.issue_order_id();
+        let mut order = Order::new(order_id, side, limit, quantity, timestamp, user_id)
+            .with_client_metadata(options.client_metadata)
+            .with_source(options.source)
+            .with_fee_exempt(options.fee_exempt)
+            .with_take_profit(options.take_profit);
+        self.last_updated_ms.fetch_max(timestamp, Ordering::Relaxed);
+        self.bump_sequence();
+        
+        let (trades, _) = self.match_order(&mut order);
+        self.rest_pegged_refreshes();
+        let filled_quantity: f64 = trades.iter().map(|t| t.quantity).sum();
+        let cancelled_quantity = order.quantity;
+        let status = if cancelled_quantity > 0.0 {
+            OrderEventStatus::Cancelled
+        } else {
+            OrderEventStatus::Filled
+        };
+        self.publish_order_update(&order, status, filled_quantity);
+        
         {
             let mut stats = self.stats.write();
             stats.total_orders_created += 1;
-            if !trades.is_empty() {
-                stats.total_orders_matched += trades.len() as u64;
-                stats.total_volume_traded += trades.iter().map(|t| t.price * t.quantity).sum::<f64>();
-                stats.last_match_time = Some(timestamp);
+            if cancelled_quantity > 0.0 {
+                stats.total_orders_cancelled += 1;
+            }
+        }
+        self.record_trades(&trades, timestamp);
+        self.place_take_profits();
+        self.evaluate_conditional_orders();
+        
+        Ok(MarketOrderResult { order_id, trades, cancelled_quantity })
+    }
+
+    // Places every order in the CSV at `path` (see `seed::parse_orders_csv`)
+    // as a plain limit order, in file order, and returns how many were placed.
+    // Nothing is placed if any row is invalid.
+    pub fn load_orders_csv(&self, path: impl AsRef<Path>) -> Result<usize, SeedError> {
+        let orders = seed::parse_orders_csv(&fs::read_to_string(path)?)?;
+        for order in &orders {
+            self.config
+                .normalize_quantity(order.quantity)
+                .and(self.config.limit_price(order.price))
+                .map_err(|e| SeedError::Row { line: order.line, reason: e.to_string() })?;
+        }
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        for order in &orders {
+            self.add_order(order.side, order.price, order.quantity, timestamp, order.user_id.clone())
+                .map_err(|e| SeedError::Row { line: order.line, reason: e.to_string() })?;
+        }
+        Ok(orders.len())
+    }
+
+    // Parks a limit order until the spread crosses `condition`'s threshold, at
+    // which point it is injected into the book like any other limit order.
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_conditional_order(
+        &self,
+        side: OrderSide,
+        price: f64,
+        quantity: f64,
+        timestamp: u64,
+        user_id: String,
+        condition: SpreadCondition,
+        options: OrderOptions,
+    ) -> Result<u64, OrderError> {
+        killswitch::check()?;
+        if options.source.is_none() {
+            self.config.check_order_kind(OrderKind::ConditionalSpread)?;
+        }
+        let quantity = self.config.normalize_quantity(quantity)?;
+        let price = self.config.limit_price(price)?.as_f64();
+        options.validate()?;
+        
+        let _lock = self.matching_lock.lock();
+        let order_id = self.issue_order_id();
+        let order = Order::new(order_id, side, price, quantity, timestamp, user_id)
+            .with_client_metadata(options.client_metadata)
+            .with_source(options.source)
+            .with_expires_at(options.expires_at)
+            .with_fee_exempt(options.fee_exempt)
+            .with_take_profit(options.take_profit);
+        
+        let conditional = ConditionalOrder { order, condition };
+        self.journal(|| EngineEvent::ConditionalPlaced { conditional: conditional.clone() });
+        self.pending_conditionals.lock().push(conditional);
+        self.evaluate_conditional_orders();
+        
+        Ok(order_id)
+    }
+
+    pub fn get_pending_conditional_orders(&self) -> Vec<ConditionalOrder> {
+        self.pending_conditionals.lock().clone()
+    }
+
+    fn evaluate_conditional_orders(&self) {
+        // Triggered orders would match; they wait until trading resumes.
+        if killswitch::is_engaged() {
+            return;
+        }
+        loop {
+            let triggered: Vec<ConditionalOrder> = {
+                let mut pending = self.pending_conditionals.lock();
+                if pending.is_empty() {
+                    return;
+                }
+                let spread = match (self.get_best_bid(), self.get_best_ask()) {
+                    (Some(bid), Some(ask)) => Some(ask - bid),
+                    _ => None,
+                };
+                let (triggered, waiting) = pending
+                    .drain(..)
+                    .partition(|c| c.condition.is_triggered(spread));
+                *pending = waiting;
+                triggered
+            };
+            
+            if triggered.is_empty() {
+                return;
+            }
+            
+            // Each injection moves the top of book, which may trip further conditions.
+            for conditional in triggered {
+                log::debug!("Conditional order {} triggered", conditional.order.id);
+                self.journal(|| EngineEvent::ConditionalRemoved { order_id: conditional.order.id });
+                self.place_order(conditional.order);
+            }
+        }
+    }
+
+    fn place_order(&self, order: Order) -> Vec<Trade> {
+        self.enter_order(order, true)
+    }
+
+    // Matches `order` and rests what is left, an iceberg showing only its
+    // first slice. `is_new` is false when an amended order re-enters, which
+    // isn't counted as another order.
+    fn enter_order(&self, mut order: Order, is_new: bool) -> Vec<Trade> {
+        order.price = self.config.round_price(order.price.as_f64());
+        // An iceberg takes liquidity with its whole quantity.
+        order.reveal_all();
+        if is_new {
+            self.record_order_account(&order.user_id);
+        }
+        let side = order.side;
+        let price = order.price.as_f64();
+        let timestamp = order.timestamp;
+        self.last_updated_ms.fetch_max(timestamp, Ordering::Relaxed);
+        self.bump_sequence();
+        
+        // During the opening auction orders only rest, crossed or not, unless
+        // `LockedMarketPolicy::Match` trades one priced at the opposite best.
+        // A FOK order that can't fill in full doesn't touch the book at all.
+        let killed = order.time_in_force == TimeInForce::Fok && self.fillable_quantity(&order) < order.quantity;
+        let halted = self.in_opening_auction()
+            && !(self.config.locked_market_policy == LockedMarketPolicy::Match && self.would_lock(side, price));
+        let (trades, self_cross) = if halted || killed {
+            (Vec::new(), false)
+        } else {
+            self.match_order(&mut order)
+        };
+        
+        let filled_quantity: f64 = trades.iter().map(|t| t.quantity).sum();
+        // IOC and FOK remainders are dropped instead of resting. So is an
+        // order self-trade prevention reduced to nothing without a fill.
+        let dropped = self_cross
+            || (order.quantity > 0.0 && !order.time_in_force.rests())
+            || (order.quantity <= 0.0 && trades.is_empty());
+        // A feed's order that would trade against the same feed's quote is
+        // dropped rather than rested, which would leave the book crossed;
+        // likewise a user's order under `SelfTradePolicy::CancelIncoming`.
+        let status = if self_cross {
+            log::debug!(
+                "Rejected order {} from {:?}: would trade with its own source or user",
+                order.id,
+                order.source
+            );
+            OrderEventStatus::Cancelled
+        } else if dropped {
+            OrderEventStatus::Cancelled
+        } else if trades.is_empty() {
+            OrderEventStatus::New
+        } else if order.quantity > 0.0 {
+            OrderEventStatus::PartiallyFilled
+        } else {
+            OrderEventStatus::Filled
+        };
+        self.publish_order_update(&order, status, filled_quantity);
+        
+        if order.quantity > 0.0 && !dropped {
+            order.split_display(self.config.quantity_scale);
+            self.publish_book_order(BookOrderAction::Add, &order);
+            match side {
+                OrderSide::Bid => {
+                    let mut bids = self.bids.write();
+                    bids.entry(Price::new(price))
+                        .or_insert_with(|| PriceLevel::for_config(price, &self.config))
+                        .add_order(order);
+                    self.store_best_bid(&bids);
+                }
+                OrderSide::Ask => {
+                    let mut asks = self.asks.write();
+                    asks.entry(Price::new(price))
+                        .or_insert_with(|| PriceLevel::for_config(price, &self.config))
+                        .add_order(order);
+                    self.store_best_ask(&asks);
+                }
+            }
+        }
+        self.rest_pegged_refreshes();
+
+        {
+            let mut stats = self.stats.write();
+            if is_new {
+                stats.total_orders_created += 1;
+            }
+            if dropped {
+                stats.total_orders_cancelled += 1;
+            }
+        }
+        self.record_trades(&trades, timestamp);
+        self.place_take_profits();
+
+        trades
+    }
+
+    // Adds `trades`, executed at `timestamp`, to the tape and the match
+    // statistics, publishes and journals them, and refreshes the derived
+    // stats.
+    fn record_trades(&self, trades: &[Trade], timestamp: u64) {
+        self.tape_trades(trades, timestamp);
+        if trades.is_empty() {
+            return;
+        }
+        for trade in trades {
+            self.events.publish(OrderBookEvent::Trade(trade.clone()));
+            self.journal(|| EngineEvent::Traded { trade: trade.clone(), executed_at: Some(timestamp) });
+        }
+
+        // Only counted here; the snapshot itself is taken by whoever is
+        // waiting in `checkpoint_due`, off the matching path.
+        if let Some(every) = self.config.snapshot_every_trades {
+            let count = trades.len() as u64;
+            if self.trades_since_checkpoint.fetch_add(count, Ordering::Relaxed) + count >= every {
+                self.checkpoint_due.notify_one();
+            }
+        }
+    }
+
+    // The tape, candles, 24h volume and stats; all a replayed trade touches.
+    fn tape_trades(&self, trades: &[Trade], timestamp: u64) {
+        if !trades.is_empty() {
+            let mut history = self.trade_history.write();
+            for trade in trades {
+                if history.len() >= self.config.trade_history_capacity {
+                    history.pop_front();
+                }
+                history.push_back(trade.clone());
+            }
+            drop(history);
+            for series in self.candles.lock().iter_mut() {
+                for trade in trades {
+                    series.record(trade, timestamp);
+                }
             }
-            self.update_stats_internal(&mut stats);
         }
 
-        (order_id, trades)
+        let mut stats = self.stats.write();
+        if !trades.is_empty() {
+            let notional: f64 = trades.iter().map(|t| t.price * t.quantity).sum();
+            self.volume_24h.lock().record(timestamp, notional);
+            stats.total_orders_matched += trades.len() as u64;
+            stats.total_volume_traded += notional;
+            stats.last_match_time = Some(timestamp);
+        }
+        self.update_stats_internal(&mut stats);
     }
 
-    fn match_order(&self, order: &mut Order) -> Vec<Trade> {
+    // Ends the opening auction. Every bid at or above the clearing price
+    // trades against every ask at or below it, all at that one price, in
+    // each side's usual priority; then continuous matching starts. A pair
+    // from the same guarded source drops the later order instead, as in
+    // continuous matching. Opening prints carry no fees. Returns the trades;
+    // empty if the book wasn't in its auction or nothing crossed.
+    pub fn open(&self, timestamp: u64) -> Vec<Trade> {
         let _lock = self.matching_lock.lock();
+        if killswitch::is_engaged() {
+            log::warn!("{} stays in its opening auction: trading is halted", self.config.symbol);
+            return Vec::new();
+        }
+        if !self.opening_auction.swap(false, Ordering::AcqRel) {
+            return Vec::new();
+        }
+
+        let (bid_depth, ask_depth) = self.get_market_depth(usize::MAX);
+        let auction = auction::clearing_price(&bid_depth, &ask_depth);
+        let price = match auction.indicative_price {
+            Some(price) => price,
+            None => {
+                log::info!("{} opened without an uncross", self.config.symbol);
+                return Vec::new();
+            }
+        };
+
+        let mut trades = Vec::new();
+        let mut dropped = 0;
+        {
+            let mut bids = self.bids.write();
+            let mut asks = self.asks.write();
+            loop {
+                let bid_price = match bids.keys().next_back() {
+                    Some(bid_price) if bid_price.as_f64() >= price => *bid_price,
+                    _ => break,
+                };
+                let ask_price = match asks.keys().next() {
+                    Some(ask_price) if ask_price.as_f64() <= price => *ask_price,
+                    _ => break,
+                };
+                let (bid_level, ask_level) = (&bids[&bid_price], &asks[&ask_price]);
+                let (bid, ask) = match (
+                    bid_level.get_first_order(),
+                    ask_level.get_first_order(),
+                ) {
+                    (Some(bid), Some(ask)) => (bid, ask),
+                    _ => break,
+                };
+
+                if self.config.prevent_same_source_match && bid.source.is_some() && bid.source == ask.source {
+                    let (level, order) = if bid.id > ask.id { (bid_level, bid) } else { (ask_level, ask) };
+                    level.remove_order(order.id);
+                    self.publish_order_update(&order, OrderEventStatus::Cancelled, 0.0);
+                    self.publish_book_order(BookOrderAction::Cancel, &order);
+                    dropped += 1;
+                } else {
+                    let quantity = bid.quantity.min(ask.quantity);
+                    trades.push(Trade::new(bid.id, ask.id, price, quantity, timestamp));
+                    // An auction print has no maker on either side.
+                    self.record_fill_account(&bid.user_id, OrderSide::Bid, price, quantity, 0.0, false);
+                    self.record_fill_account(&ask.user_id, OrderSide::Ask, price, quantity, 0.0, false);
+                    self.queue_take_profit(&bid, trades.last().unwrap());
+                    self.queue_take_profit(&ask, trades.last().unwrap());
+                    self.fill_maker(bid_level, bid, quantity);
+                    self.fill_maker(ask_level, ask, quantity);
+                }
+
+                if bids[&bid_price].is_empty() {
+                    bids.remove(&bid_price);
+                }
+                if asks[&ask_price].is_empty() {
+                    asks.remove(&ask_price);
+                }
+            }
+            self.store_best_bid(&bids);
+            self.store_best_ask(&asks);
+        }
+        self.rest_pegged_refreshes();
+
+        log::info!(
+            "{} opened at {} with {} trades ({} matched)",
+            self.config.symbol,
+            price,
+            trades.len(),
+            auction.matched_volume
+        );
+        self.bump_sequence();
+        if dropped > 0 {
+            self.stats.write().total_orders_cancelled += dropped;
+        }
+        self.record_trades(&trades, timestamp);
+        self.place_take_profits();
+        self.evaluate_conditional_orders();
+        trades
+    }
+
+    // How much of `order` would fill right now: the opposite side's quantity
+    // at or through its limit, counting the reserves of icebergs that refill
+    // at their own price. The taker's own orders are skipped under
+    // `SelfTradePolicy::CancelResting`. A level holding an order matching
+    // can't pass (same guarded source, or the taker's own under the other
+    // policies) is left out with everything beyond it.
+    fn fillable_quantity(&self, order: &Order) -> f64 {
+        let guarded_source = order.source.as_ref().filter(|_| self.config.prevent_same_source_match);
+        let self_trade_policy = self.config.self_trade_policy;
+        let level_quantity = |level: &PriceLevel| -> Option<f64> {
+            if guarded_source.is_none() && self_trade_policy.is_none() {
+                return Some(level.get_total_quantity() + level.orders.refill_reserve());
+            }
+            let mut quantity = 0.0;
+            for resting in level.orders.orders_in_queue_order() {
+                if guarded_source.is_some() && resting.source.as_ref() == guarded_source {
+                    return None;
+                }
+                match self_trade_policy {
+                    Some(SelfTradePolicy::CancelResting) if resting.user_id == order.user_id => {}
+                    Some(_) if resting.user_id == order.user_id => return None,
+                    _ if resting.refresh_peg.is_some() => quantity += resting.quantity,
+                    _ => quantity += resting.remaining_quantity(),
+                }
+            }
+            Some(quantity)
+        };
+        let limit = order.price.as_f64();
+        let mut fillable = 0.0;
+        match order.side {
+            OrderSide::Bid => {
+                for (price, level) in self.asks.read().iter() {
+                    if price.as_f64() > limit || fillable >= order.quantity {
+                        break;
+                    }
+                    match level_quantity(level) {
+                        Some(quantity) => fillable += quantity,
+                        None => break,
+                    }
+                }
+            }
+            OrderSide::Ask => {
+                for (price, level) in self.bids.read().iter().rev() {
+                    if price.as_f64() < limit || fillable >= order.quantity {
+                        break;
+                    }
+                    match level_quantity(level) {
+                        Some(quantity) => fillable += quantity,
+                        None => break,
+                    }
+                }
+            }
+        }
+        fillable
+    }
+
+    // Whether resting at `price` would leave the book locked, i.e. it is
+    // exactly the opposite best. Only possible while matching is halted.
+    fn would_lock(&self, side: OrderSide, price: f64) -> bool {
+        if !self.in_opening_auction() {
+            return false;
+        }
+        let opposite_best = match side {
+            OrderSide::Bid => self.get_best_ask(),
+            OrderSide::Ask => self.get_best_bid(),
+        };
+        opposite_best == Some(price)
+    }
+
+    // The checks a limit order entering the book must pass against its
+    // current state. Caller must hold `matching_lock`.
+    fn check_entry(&self, side: OrderSide, price: f64, time_in_force: TimeInForce) -> Result<(), OrderError> {
+        // Nothing matches during the auction, so IOC and FOK couldn't fill.
+        if !time_in_force.rests() && self.in_opening_auction() {
+            return Err(OrderError::AuctionInProgress);
+        }
+        if self.config.locked_market_policy == LockedMarketPolicy::Reject && self.would_lock(side, price) {
+            return Err(OrderError::WouldLockMarket { price });
+        }
+        Ok(())
+    }
+
+    // Returns the trades and whether matching stopped at a resting order the
+    // taker may not trade with: one from its own source (see
+    // `OrderBookConfig::prevent_same_source_match`), or its own under
+    // `SelfTradePolicy::CancelIncoming`.
+    fn match_order(&self, order: &mut Order) -> (Vec<Trade>, bool) {
         let mut trades = Vec::new();
+        let mut self_cross = false;
+        let fees = &self.config.fee_schedule;
+        let guarded_source = if self.config.prevent_same_source_match {
+            order.source.clone()
+        } else {
+            None
+        };
 
         match order.side {
             OrderSide::Bid => {
@@ -274,28 +1607,47 @@ impl OrderBook {
                     }
 
                     let mut asks = self.asks.write();
-                    if let Some(ask_level) = asks.get_mut(&Price(ask_price)) {
+                    let best_ask = asks.keys().next().map(|p| p.as_f64());
+                    if let Some(ask_level) = asks.get_mut(&Price::new(ask_price)) {
                         if let Some(ask_order) = ask_level.get_first_order() {
+                            if guarded_source.is_some() && ask_order.source == guarded_source {
+                                self_cross = true;
+                                break;
+                            }
+                            if ask_order.user_id == order.user_id && self.config.self_trade_policy.is_some() {
+                                if !self.prevent_self_trade(order, ask_order, ask_level) {
+                                    self_cross = true;
+                                    break;
+                                }
+                                if ask_level.is_empty() {
+                                    asks.remove(&Price::new(ask_price));
+                                    self.store_best_ask(&asks);
+                                }
+                                continue;
+                            }
+                            self.check_trade_through(order, ask_order.id, ask_price, best_ask);
                             let trade_quantity = order.quantity.min(ask_order.quantity);
                             
+                            let notional = ask_price * trade_quantity;
                             trades.push(Trade::new(
                                 order.id,
                                 ask_order.id,
                                 ask_price,
                                 trade_quantity,
                                 std::cmp::min(order.timestamp, ask_order.timestamp),
-                            ));
+                            ).with_executed_at(order.timestamp).with_fees(
+                                fees.maker_fee(notional, ask_order.fee_exempt),
+                                fees.taker_fee(notional, order.fee_exempt),
+                            ).with_taker(OrderSide::Bid, order.id, ask_order.id));
+                            self.record_fills(order, &ask_order, trades.last().unwrap());
 
                             order.quantity -= trade_quantity;
 
-                            if ask_order.quantity <= trade_quantity {
-                                ask_level.remove_first_order();
-                            } else {
-                                ask_level.update_order(ask_order.id, ask_order.quantity - trade_quantity);
-                            }
+                            self.fill_maker(ask_level, ask_order, trade_quantity);
 
                             if ask_level.is_empty() {
-                                asks.remove(&Price(ask_price));
+                                asks.remove(&Price::new(ask_price));
+                                self.store_best_ask(&asks);
                             }
                         } else {
                             break;
@@ -319,28 +1671,47 @@ impl OrderBook {
                     }
 
                     let mut bids = self.bids.write();
-                    if let Some(bid_level) = bids.get_mut(&Price(bid_price)) {
+                    let best_bid = bids.keys().next_back().map(|p| p.as_f64());
+                    if let Some(bid_level) = bids.get_mut(&Price::new(bid_price)) {
                         if let Some(bid_order) = bid_level.get_first_order() {
+                            if guarded_source.is_some() && bid_order.source == guarded_source {
+                                self_cross = true;
+                                break;
+                            }
+                            if bid_order.user_id == order.user_id && self.config.self_trade_policy.is_some() {
+                                if !self.prevent_self_trade(order, bid_order, bid_level) {
+                                    self_cross = true;
+                                    break;
+                                }
+                                if bid_level.is_empty() {
+                                    bids.remove(&Price::new(bid_price));
+                                    self.store_best_bid(&bids);
+                                }
+                                continue;
+                            }
+                            self.check_trade_through(order, bid_order.id, bid_price, best_bid);
                             let trade_quantity = order.quantity.min(bid_order.quantity);
                             
+                            let notional = bid_price * trade_quantity;
                             trades.push(Trade::new(
                                 bid_order.id,
                                 order.id,
                                 bid_price,
                                 trade_quantity,
                                 std::cmp::min(order.timestamp, bid_order.timestamp),
-                            ));
+                            ).with_executed_at(order.timestamp).with_fees(
+                                fees.maker_fee(notional, bid_order.fee_exempt),
+                                fees.taker_fee(notional, order.fee_exempt),
+                            ).with_taker(OrderSide::Ask, order.id, bid_order.id));
+                            self.record_fills(order, &bid_order, trades.last().unwrap());
 
                             order.quantity -= trade_quantity;
 
-                            if bid_order.quantity <= trade_quantity {
-                                bid_level.remove_first_order();
-                            } else {
-                                bid_level.update_order(bid_order.id, bid_order.quantity - trade_quantity);
-                            }
+                            self.fill_maker(bid_level, bid_order, trade_quantity);
 
                             if bid_level.is_empty() {
-                                bids.remove(&Price(bid_price));
+                                bids.remove(&Price::new(bid_price));
+                                self.store_best_bid(&bids);
                             }
                         } else {
                             break;
@@ -352,10 +1723,524 @@ impl OrderBook {
             }
         }
 
-        trades
+        (trades, self_cross)
+    }
+
+    // Takes a fill of `quantity` off a resting order's visible slice. An
+    // iceberg whose slice runs out shows its next one; anything else that
+    // runs out leaves the book.
+    fn fill_maker(&self, level: &PriceLevel, mut maker: Order, quantity: f64) {
+        maker.quantity -= quantity;
+        if maker.quantity > 0.0 {
+            level.update_order(maker.id, maker.quantity);
+            self.publish_order_update(&maker, OrderEventStatus::PartiallyFilled, quantity);
+            self.publish_book_order(BookOrderAction::Modify, &maker);
+            return;
+        }
+
+        maker.quantity = 0.0;
+        if self.refresh_iceberg(level, &mut maker) {
+            self.publish_order_update(&maker, OrderEventStatus::PartiallyFilled, quantity);
+        } else {
+            level.remove_order(maker.id);
+            self.publish_order_update(&maker, OrderEventStatus::Filled, quantity);
+            self.publish_book_order(BookOrderAction::Fill, &maker);
+        }
+    }
+
+    // Replaces an iceberg's exhausted slice with the next one from its
+    // reserve, queued at the back of `level` behind everything that arrived
+    // meanwhile: the last step of `OrderQueue::get_first_order`'s rule, so
+    // hidden size never goes ahead of displayed size at its price. Market-by-order subscribers see the old slice fill and the
+    // new one added. A pegged iceberg's slice leaves the level instead and
+    // waits in `pegged_refreshes` to be repriced. False, with the book
+    // untouched, if there is no reserve.
+    fn refresh_iceberg(&self, level: &PriceLevel, maker: &mut Order) -> bool {
+        let exhausted = maker.clone();
+        if !maker.refresh_slice(self.config.quantity_scale) {
+            return false;
+        }
+        level.orders.take_order(maker.id);
+        self.publish_book_order(BookOrderAction::Fill, &exhausted);
+        if maker.refresh_peg.is_some() {
+            self.pegged_refreshes.lock().push(maker.clone());
+            return true;
+        }
+        level.add_order(maker.clone());
+        self.publish_book_order(BookOrderAction::Add, maker);
+        true
+    }
+
+    // Rests the slices `refresh_iceberg` held back, each at its pegged price.
+    // Caller must hold `matching_lock`, with the incoming order already
+    // rested or dropped.
+    fn rest_pegged_refreshes(&self) {
+        let refreshed = std::mem::take(&mut *self.pegged_refreshes.lock());
+        for mut order in refreshed {
+            if let Some(peg) = order.refresh_peg {
+                order.price = self.pegged_price(&order, peg);
+            }
+            let price = order.price.as_f64();
+            self.publish_book_order(BookOrderAction::Add, &order);
+            let book = match order.side {
+                OrderSide::Bid => &self.bids,
+                OrderSide::Ask => &self.asks,
+            };
+            let mut book = book.write();
+            let side = order.side;
+            book.entry(Price::new(price))
+                .or_insert_with(|| PriceLevel::for_config(price, &self.config))
+                .add_order(order);
+            match side {
+                OrderSide::Bid => self.store_best_bid(&book),
+                OrderSide::Ask => self.store_best_ask(&book),
+            }
+        }
+    }
+
+    // `offset` behind the peg's reference, or the old price while the book
+    // has no reference price. Either way a slice that would cross or lock
+    // the opposite best rests one tick behind it instead, so a refresh
+    // never takes liquidity.
+    fn pegged_price(&self, order: &Order, peg: RefreshPeg) -> Price {
+        let (best_bid, best_ask) = (self.get_best_bid(), self.get_best_ask());
+        let (own_best, opposite_best) = match order.side {
+            OrderSide::Bid => (best_bid, best_ask),
+            OrderSide::Ask => (best_ask, best_bid),
+        };
+        let reference = match peg.reference {
+            PegReference::Primary => own_best,
+            PegReference::Mid => best_bid.zip(best_ask).map(|(bid, ask)| (bid + ask) / 2.0),
+            PegReference::Market => opposite_best,
+        };
+        let tick = self.config.tick_size;
+        let price = match (reference, order.side) {
+            (Some(reference), OrderSide::Bid) => self.config.round_price((reference - peg.offset).max(tick)),
+            (Some(reference), OrderSide::Ask) => self.config.round_price(reference + peg.offset),
+            (None, _) => order.price,
+        };
+
+        match (opposite_best, order.side) {
+            (Some(ask), OrderSide::Bid) if price.as_f64() >= ask => self.config.round_price((ask - tick).max(tick)),
+            (Some(bid), OrderSide::Ask) if price.as_f64() <= bid => self.config.round_price(bid + tick),
+            _ => price,
+        }
+    }
+
+    // Applies the self-trade policy to `taker` meeting its own resting
+    // `maker` in `level`. Returns false if the taker must stop matching.
+    fn prevent_self_trade(&self, taker: &mut Order, mut maker: Order, level: &PriceLevel) -> bool {
+        let quantity = match self.config.self_trade_policy {
+            Some(SelfTradePolicy::CancelResting) => maker.quantity,
+            Some(SelfTradePolicy::DecrementBoth) => {
+                let quantity = taker.quantity.min(maker.quantity);
+                taker.quantity -= quantity;
+                quantity
+            }
+            Some(SelfTradePolicy::CancelIncoming) | None => return false,
+        };
+        maker.quantity -= quantity;
+        // A decremented iceberg shows its next slice; `CancelResting` takes
+        // the whole order, reserve included.
+        if maker.quantity <= 0.0 && self.config.self_trade_policy == Some(SelfTradePolicy::DecrementBoth) {
+            maker.quantity = 0.0;
+            if self.refresh_iceberg(level, &mut maker) {
+                return true;
+            }
+        }
+        if maker.quantity <= 0.0 {
+            level.remove_order(maker.id);
+            maker.quantity = 0.0;
+            maker.hidden_remaining = 0.0;
+            self.publish_order_update(&maker, OrderEventStatus::Cancelled, 0.0);
+            self.publish_book_order(BookOrderAction::Cancel, &maker);
+            self.stats.write().total_orders_cancelled += 1;
+        } else {
+            level.update_order(maker.id, maker.quantity);
+            self.publish_book_order(BookOrderAction::Modify, &maker);
+        }
+        true
+    }
+
+    // Per-user bookkeeping for a continuous-matching fill.
+    fn record_fills(&self, taker: &Order, maker: &Order, trade: &Trade) {
+        self.record_fill_account(&taker.user_id, taker.side, trade.price, trade.quantity, trade.taker_fee, false);
+        self.record_fill_account(&maker.user_id, maker.side, trade.price, trade.quantity, trade.maker_fee, true);
+        self.queue_take_profit(taker, trade);
+        self.queue_take_profit(maker, trade);
+        if self.config.match_events {
+            self.events.publish(OrderBookEvent::Match(MatchEvent {
+                maker_user_id: maker.user_id.clone(),
+                maker_order_id: maker.id,
+                taker_user_id: taker.user_id.clone(),
+                taker_order_id: taker.id,
+                aggressor: taker.side,
+                price: trade.price,
+                quantity: trade.quantity,
+                maker_fee: trade.maker_fee,
+                taker_fee: trade.taker_fee,
+                timestamp: trade.timestamp,
+            }));
+        }
+    }
+
+    // Notes a fill of `entry` for `place_take_profits` if it has a
+    // take-profit attached. Its fills within one match add up.
+    fn queue_take_profit(&self, entry: &Order, trade: &Trade) {
+        if entry.take_profit.is_none() {
+            return;
+        }
+        let notional = trade.price * trade.quantity;
+        let mut fills = self.take_profit_fills.lock();
+        match fills.iter_mut().find(|fill| fill.entry.id == entry.id) {
+            Some(fill) => {
+                fill.quantity += trade.quantity;
+                fill.notional += notional;
+            }
+            None => fills.push(TakeProfitFill {
+                entry: entry.clone(),
+                quantity: trade.quantity,
+                notional,
+            }),
+        }
+    }
+
+    // Places a take-profit for each fill `queue_take_profit` noted: a limit
+    // order on the other side for the quantity filled, which trades like any
+    // other if it crosses. Runs once the match is recorded. Caller must hold
+    // `matching_lock`.
+    fn place_take_profits(&self) {
+        let fills = std::mem::take(&mut *self.take_profit_fills.lock());
+        for fill in fills {
+            let Some(take_profit) = fill.entry.take_profit else { continue };
+            let price = take_profit.exit_price(fill.entry.side, fill.notional / fill.quantity);
+            let (Some(price), Ok(quantity)) = (price, self.config.normalize_quantity(fill.quantity)) else {
+                log::warn!("Skipped the take-profit for order {}: no valid price or quantity", fill.entry.id);
+                continue;
+            };
+            let side = match fill.entry.side {
+                OrderSide::Bid => OrderSide::Ask,
+                OrderSide::Ask => OrderSide::Bid,
+            };
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as u64;
+            let child_id = self.issue_order_id();
+            let child = Order::new(child_id, side, price, quantity, timestamp, fill.entry.user_id.clone())
+                .with_client_metadata(fill.entry.client_metadata.clone())
+                .with_fee_exempt(fill.entry.fee_exempt)
+                .with_parent_order_id(Some(fill.entry.id));
+            log::debug!("Order {} filled {}; placing take-profit {} at {}", fill.entry.id, quantity, child_id, price);
+            self.take_profit_children.lock().record(fill.entry.id, child_id);
+            self.place_order(child);
+        }
+    }
+
+    // Ids of the take-profits placed so far for `order_id`'s fills, oldest
+    // first, if it filled recently enough to be remembered.
+    pub fn take_profit_orders(&self, order_id: u64) -> Vec<u64> {
+        self.take_profit_children.lock().children.get(&order_id).cloned().unwrap_or_default()
+    }
+
+    // `best_price` is the best opposite price read under the same book lock
+    // as the fill; filling `taker` at a worse price than that is a bug.
+    fn check_trade_through(&self, taker: &Order, maker_order_id: u64, price: f64, best_price: Option<f64>) {
+        let check = self.config.trade_through_check;
+        let best_price = match best_price {
+            Some(best_price) if check != TradeThroughCheck::Off => best_price,
+            _ => return,
+        };
+        let traded_through = match taker.side {
+            OrderSide::Bid => price > best_price,
+            OrderSide::Ask => price < best_price,
+        };
+        if !traded_through {
+            return;
+        }
+
+        self.trade_throughs.fetch_add(1, Ordering::Relaxed);
+        let message = format!(
+            "{} trade-through: order {} filled against {} at {} with {} resting",
+            self.config.symbol, taker.id, maker_order_id, price, best_price
+        );
+        if check == TradeThroughCheck::Strict {
+            if cfg!(debug_assertions) {
+                panic!("{}", message);
+            }
+            log::error!("{}", message);
+        } else {
+            log::warn!("{}", message);
+        }
+        self.events.publish(OrderBookEvent::TradeThrough(TradeThrough {
+            taker_order_id: taker.id,
+            maker_order_id,
+            price,
+            best_price,
+        }));
     }
 
     pub fn remove_order(&self, order_id: u64, user_id: &str) -> Option<Order> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        self.remove_order_at(order_id, user_id, now)
+    }
+
+    // Cancels the order as of `now`, which only matters for the
+    // `min_resting_ms` rule: a resting order placed less than that long
+    // before `now` stays on the book and `None` is returned.
+    pub fn remove_order_at(&self, order_id: u64, user_id: &str, now: u64) -> Option<Order> {
+        let _lock = self.matching_lock.lock();
+        let removed_order = self.remove_order_locked(order_id, user_id, now);
+        self.evaluate_conditional_orders();
+        removed_order
+    }
+
+    // Whether `order` has rested too briefly to be cancelled at `now`.
+    pub fn is_within_min_resting_time(&self, order: &Order, now: u64) -> bool {
+        self.config
+            .min_resting_ms
+            .is_some_and(|min_resting_ms| now.saturating_sub(order.timestamp) < min_resting_ms)
+    }
+
+    // Cancels `old_order_id` and places the replacement under one hold of the
+    // matching lock, so no other order can slip in between the two steps. If
+    // the old order is already gone the replacement is only placed when
+    // `place_if_cancel_fails` is set. The replacement keeps the cancelled
+    // order's client metadata.
+    #[allow(clippy::too_many_arguments)]
+    pub fn cancel_replace(
+        &self,
+        old_order_id: u64,
+        user_id: &str,
+        side: OrderSide,
+        price: f64,
+        quantity: f64,
+        timestamp: u64,
+        place_if_cancel_fails: bool,
+    ) -> Result<CancelReplaceResult, OrderError> {
+        // Validate the replacement up front so a bad spec never cancels the old order.
+        killswitch::check()?;
+        self.config.check_order_kind(OrderKind::Limit(TimeInForce::Gtc))?;
+        let quantity = self.config.normalize_quantity(quantity)?;
+        let price = self.config.limit_price(price)?.as_f64();
+        
+        let _lock = self.matching_lock.lock();
+        self.check_entry(side, price, TimeInForce::Gtc)?;
+        let cancelled = self.remove_order_locked(old_order_id, user_id, timestamp);
+        
+        let mut result = CancelReplaceResult { cancelled, order_id: None, remaining_quantity: 0.0, trades: Vec::new() };
+        if result.cancelled.is_some() || place_if_cancel_fails {
+            let order_id = self.issue_order_id();
+            let client_metadata = result.cancelled.as_ref().and_then(|o| o.client_metadata.clone());
+            let expires_at = result.cancelled.as_ref().and_then(|o| o.expires_at);
+            let fee_exempt = result.cancelled.as_ref().is_some_and(|o| o.fee_exempt);
+            let order = Order::new(order_id, side, price, quantity, timestamp, user_id.to_string())
+                .with_client_metadata(client_metadata)
+                .with_expires_at(expires_at)
+                .with_fee_exempt(fee_exempt);
+            result.order_id = Some(order_id);
+            result.trades = self.place_order(order);
+            result.remaining_quantity = self.resting_quantity(side, price, order_id);
+        }
+        
+        self.evaluate_conditional_orders();
+        Ok(result)
+    }
+
+    // Changes a resting order's price and/or quantity, keeping its id. A
+    // quantity decrease at the same price is applied in place and keeps the
+    // order's place in the queue; any other change takes it off the book and
+    // re-enters it at the back of its new level, matching it like a new
+    // order. `None` if `user_id` has no such order resting.
+    pub fn modify_order(
+        &self,
+        order_id: u64,
+        user_id: &str,
+        new_price: Option<f64>,
+        new_quantity: Option<f64>,
+    ) -> Result<Option<AmendResult>, OrderError> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        self.modify_order_at(order_id, user_id, new_price, new_quantity, now)
+    }
+
+    // Amends the order as of `now`, which only matters for the
+    // `min_resting_ms` rule: an order too young to cancel can still be
+    // reduced in place, but not repriced or enlarged.
+    pub fn modify_order_at(
+        &self,
+        order_id: u64,
+        user_id: &str,
+        new_price: Option<f64>,
+        new_quantity: Option<f64>,
+        now: u64,
+    ) -> Result<Option<AmendResult>, OrderError> {
+        killswitch::check()?;
+        let new_quantity = new_quantity.map(|q| self.config.normalize_quantity(q)).transpose()?;
+        let new_price = new_price.map(|p| self.config.limit_price(p)).transpose()?;
+
+        let _lock = self.matching_lock.lock();
+        let (side, price) = match self.user_orders.get(user_id).into_iter().find(|(id, _, _)| *id == order_id) {
+            Some((_, side, price)) => (side, price),
+            None => return Ok(None),
+        };
+        let book = match side {
+            OrderSide::Bid => &self.bids,
+            OrderSide::Ask => &self.asks,
+        };
+        let mut order = match book.read().get(&price).and_then(|level| level.orders.get_order(order_id)) {
+            Some(order) => order,
+            None => return Ok(None),
+        };
+        // Quantities cover an iceberg's reserve as well as its visible slice.
+        let new_price = new_price.unwrap_or(order.price);
+        let new_quantity = new_quantity.unwrap_or(order.remaining_quantity());
+
+        if new_price == order.price && new_quantity <= order.remaining_quantity() {
+            if new_quantity < order.remaining_quantity() {
+                // The reserve shrinks first; the slice only once it's gone.
+                let visible = order.quantity.min(new_quantity);
+                if let Some(level) = book.read().get(&price) {
+                    level.orders.update_reserve(order_id, new_quantity - visible);
+                    level.update_order(order_id, visible);
+                }
+                order.hidden_remaining = new_quantity - visible;
+                if visible < order.quantity {
+                    order.quantity = visible;
+                    self.publish_book_order(BookOrderAction::Modify, &order);
+                } else {
+                    // Depth is unchanged, but the log still needs the reserve.
+                    self.journal(|| EngineEvent::OrderUpdated { order: order.clone() });
+                }
+                self.bump_sequence();
+            }
+            return Ok(Some(AmendResult {
+                order_id,
+                remaining_quantity: new_quantity,
+                priority_kept: true,
+                trades: Vec::new(),
+            }));
+        }
+
+        // Re-entering cancels the order and places it again, so it must pass
+        // what both would check before it leaves the book.
+        if self.is_within_min_resting_time(&order, now) {
+            return Err(OrderError::MinRestingTime {
+                min_resting_ms: self.config.min_resting_ms.unwrap_or_default(),
+            });
+        }
+        if order.source.is_none() {
+            self.config.check_order_kind(OrderKind::Limit(order.time_in_force))?;
+        }
+        self.check_entry(side, new_price.as_f64(), order.time_in_force)?;
+
+        {
+            let mut levels = book.write();
+            if let Some(level) = levels.get(&price) {
+                level.orders.take_order(order_id);
+                if level.is_empty() {
+                    levels.remove(&price);
+                }
+            }
+            match side {
+                OrderSide::Bid => self.store_best_bid(&levels),
+                OrderSide::Ask => self.store_best_ask(&levels),
+            }
+        }
+        self.publish_book_order(BookOrderAction::Cancel, &order);
+
+        order.price = new_price;
+        order.quantity = new_quantity;
+        order.hidden_remaining = 0.0;
+        let trades = self.enter_order(order, false);
+        let remaining_quantity = self.resting_quantity(side, new_price.as_f64(), order_id);
+        self.evaluate_conditional_orders();
+        Ok(Some(AmendResult {
+            order_id,
+            remaining_quantity,
+            priority_kept: false,
+            trades,
+        }))
+    }
+
+    // Visible and hidden quantity `order_id` has resting at `price`, or 0 if
+    // it isn't there (filled, dropped, or an iceberg slice repriced by its
+    // peg).
+    fn resting_quantity(&self, side: OrderSide, price: f64, order_id: u64) -> f64 {
+        let book = match side {
+            OrderSide::Bid => self.bids.read(),
+            OrderSide::Ask => self.asks.read(),
+        };
+        book.get(&Price::new(price))
+            .and_then(|level| level.orders.get_order(order_id))
+            .map_or(0.0, |order| order.remaining_quantity())
+    }
+
+    // Replaces part or all of a maker's quotes in one step: the cancels, then
+    // the `(side, price, quantity)` adds, all under one hold of the matching
+    // lock, so no order and no depth read taken under the lock (resync, WS
+    // depth) ever sees half the set. Every add is validated first, so a bad
+    // one leaves the book untouched, as does a set whose own bid and ask
+    // cross unless `OrderBookConfig::allow_crossed_quote_sets` is on.
+    pub fn apply_quote_set(
+        &self,
+        user_id: &str,
+        cancels: Vec<u64>,
+        adds: Vec<(OrderSide, f64, f64)>,
+        timestamp: u64,
+    ) -> Result<QuoteResult, OrderError> {
+        killswitch::check()?;
+        // Quotes rest like GTC limit orders.
+        if !adds.is_empty() {
+            self.config.check_order_kind(OrderKind::Limit(TimeInForce::Gtc))?;
+        }
+        let adds = adds
+            .into_iter()
+            .map(|(side, price, quantity)| {
+                Ok((side, self.config.limit_price(price)?.as_f64(), self.config.normalize_quantity(quantity)?))
+            })
+            .collect::<Result<Vec<_>, OrderError>>()?;
+        if !self.config.allow_crossed_quote_sets {
+            let best = |wanted: OrderSide| {
+                adds.iter()
+                    .filter(move |(side, _, _)| *side == wanted)
+                    .map(|&(_, price, _)| self.config.round_price(price))
+            };
+            if let (Some(bid), Some(ask)) = (best(OrderSide::Bid).max(), best(OrderSide::Ask).min()) {
+                if bid >= ask {
+                    return Err(OrderError::CrossedQuoteSet { bid: bid.as_f64(), ask: ask.as_f64() });
+                }
+            }
+        }
+
+        let _lock = self.matching_lock.lock();
+        let mut result = QuoteResult {
+            cancelled: Vec::new(),
+            order_ids: Vec::with_capacity(adds.len()),
+            trades: Vec::new(),
+        };
+        for order_id in cancels {
+            result.cancelled.extend(self.remove_order_locked(order_id, user_id, timestamp));
+        }
+        for (side, price, quantity) in adds {
+            let order_id = self.issue_order_id();
+            let order = Order::new(order_id, side, price, quantity, timestamp, user_id.to_string());
+            result.order_ids.push(order_id);
+            result.trades.extend(self.place_order(order));
+        }
+
+        self.evaluate_conditional_orders();
+        Ok(result)
+    }
+
+    // Caller must hold `matching_lock`. Pending conditional orders aren't
+    // visible, so they are exempt from the minimum resting time.
+    fn remove_order_locked(&self, order_id: u64, user_id: &str, now: u64) -> Option<Order> {
         let mut removed_order = None;
 
         {
@@ -363,11 +2248,14 @@ impl OrderBook {
             for (price, price_level) in bids.iter_mut() {
                 if let Some(order) = price_level.orders.get_order(order_id) {
                     if order.user_id == user_id {
+                        if self.is_within_min_resting_time(&order, now) {
+                            return None;
+                        }
                         removed_order = price_level.remove_order(order_id);
                         if price_level.is_empty() {
-                            let price_to_remove = price.clone();
-                            drop(price_level);
+                            let price_to_remove = *price;
                             bids.remove(&price_to_remove);
+                            self.store_best_bid(&bids);
                         }
                         break;
                     }
@@ -380,11 +2268,14 @@ impl OrderBook {
             for (price, price_level) in asks.iter_mut() {
                 if let Some(order) = price_level.orders.get_order(order_id) {
                     if order.user_id == user_id {
+                        if self.is_within_min_resting_time(&order, now) {
+                            return None;
+                        }
                         removed_order = price_level.remove_order(order_id);
                         if price_level.is_empty() {
-                            let price_to_remove = price.clone();
-                            drop(price_level);
+                            let price_to_remove = *price;
                             asks.remove(&price_to_remove);
+                            self.store_best_ask(&asks);
                         }
                         break;
                     }
@@ -392,7 +2283,22 @@ impl OrderBook {
             }
         }
 
-        if removed_order.is_some() {
+        if let Some(order) = &removed_order {
+            self.publish_book_order(BookOrderAction::Cancel, order);
+        } else {
+            let mut pending = self.pending_conditionals.lock();
+            if let Some(index) = pending
+                .iter()
+                .position(|c| c.order.id == order_id && c.order.user_id == user_id)
+            {
+                removed_order = Some(pending.remove(index).order);
+                self.journal(|| EngineEvent::ConditionalRemoved { order_id });
+            }
+        }
+
+        if let Some(order) = &removed_order {
+            self.bump_sequence();
+            self.publish_order_update(order, OrderEventStatus::Cancelled, 0.0);
             let mut stats = self.stats.write();
             stats.total_orders_cancelled += 1;
             self.update_stats_internal(&mut stats);
@@ -401,22 +2307,455 @@ impl OrderBook {
         removed_order
     }
 
-    pub fn get_best_bid(&self) -> Option<f64> {
-        let bids = self.bids.read();
-        bids.keys().next_back().map(|p| p.as_f64())
+    // Cancels everything `user_id` has open. Orders younger than
+    // `min_resting_ms` can't be cancelled yet and stay on the book.
+    pub fn cancel_all_for_user(&self, user_id: &str) -> Vec<Order> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        self.cancel_all_for_user_at(user_id, now)
     }
 
-    pub fn get_best_ask(&self) -> Option<f64> {
-        let asks = self.asks.read();
-        asks.keys().next().map(|p| p.as_f64())
+    pub fn cancel_all_for_user_at(&self, user_id: &str, now: u64) -> Vec<Order> {
+        self.cancel_user_orders(user_id, Some(now))
     }
 
-    pub fn get_spread(&self) -> Option<f64> {
-        let stats = self.stats.read();
-        stats.spread
-    }
+    // With `min_resting_at` unset every order goes regardless of age, as the
+    // dead-man's switch needs.
+    fn cancel_user_orders(&self, user_id: &str, min_resting_at: Option<u64>) -> Vec<Order> {
+        let _lock = self.matching_lock.lock();
+        let mut cancelled = Vec::new();
 
-    pub fn get_market_depth(&self, levels: usize) -> (Vec<(f64, f64)>, Vec<(f64, f64)>) {
+        // Only the levels the user's index points at are visited, so a user
+        // with nothing resting costs one lookup however deep the book is.
+        let resting = self.user_orders.get(user_id);
+        let too_young = |order: &Order| min_resting_at.is_some_and(|now| self.is_within_min_resting_time(order, now));
+        for (side, book) in [(OrderSide::Bid, &self.bids), (OrderSide::Ask, &self.asks)] {
+            let mut book = book.write();
+            for &(order_id, _, price) in resting.iter().filter(|(_, order_side, _)| *order_side == side) {
+                let Some(level) = book.get(&price) else { continue };
+                if level.orders.get_order(order_id).is_some_and(|order| too_young(&order)) {
+                    continue;
+                }
+                if let Some(order) = level.remove_order(order_id) {
+                    cancelled.push(order);
+                }
+                if level.is_empty() {
+                    book.remove(&price);
+                }
+            }
+            match side {
+                OrderSide::Bid => self.store_best_bid(&book),
+                OrderSide::Ask => self.store_best_ask(&book),
+            }
+        }
+        for order in &cancelled {
+            self.publish_book_order(BookOrderAction::Cancel, order);
+        }
+
+        self.pending_conditionals.lock().retain(|c| {
+            if c.order.user_id == user_id {
+                self.journal(|| EngineEvent::ConditionalRemoved { order_id: c.order.id });
+                cancelled.push(c.order.clone());
+                false
+            } else {
+                true
+            }
+        });
+
+        for order in &cancelled {
+            self.publish_order_update(order, OrderEventStatus::Cancelled, 0.0);
+        }
+
+        if !cancelled.is_empty() {
+            self.bump_sequence();
+            let mut stats = self.stats.write();
+            stats.total_orders_cancelled += cancelled.len() as u64;
+            self.update_stats_internal(&mut stats);
+        }
+
+        self.evaluate_conditional_orders();
+        cancelled
+    }
+
+    // Cancels every resting order tagged with `source`, e.g. when that venue's
+    // feed has died.
+    pub fn cancel_all_for_source(&self, source: &str) -> Vec<Order> {
+        let _lock = self.matching_lock.lock();
+        let mut cancelled = Vec::new();
+
+        {
+            let mut bids = self.bids.write();
+            for level in bids.values() {
+                cancelled.extend(level.remove_source_orders(source));
+            }
+            bids.retain(|_, level| !level.is_empty());
+            self.store_best_bid(&bids);
+        }
+
+        {
+            let mut asks = self.asks.write();
+            for level in asks.values() {
+                cancelled.extend(level.remove_source_orders(source));
+            }
+            asks.retain(|_, level| !level.is_empty());
+            self.store_best_ask(&asks);
+        }
+
+        for order in &cancelled {
+            self.publish_order_update(order, OrderEventStatus::Cancelled, 0.0);
+            self.publish_book_order(BookOrderAction::Cancel, order);
+        }
+
+        if !cancelled.is_empty() {
+            self.bump_sequence();
+            let mut stats = self.stats.write();
+            stats.total_orders_cancelled += cancelled.len() as u64;
+            self.update_stats_internal(&mut stats);
+        }
+
+        self.evaluate_conditional_orders();
+        cancelled
+    }
+
+    // Makes `source`'s resting depth match a venue's book, given as
+    // `(price, quantity)` levels per side (e.g. its top-N snapshot). The venue
+    // rests one order per level, owned by `source`: it is resized in place
+    // when the level's quantity changes, cancelled once the level is no
+    // longer listed, and entered like any order (matching what it crosses)
+    // when the level is new. Levels with an unusable quantity count as absent.
+    pub fn sync_depth(&self, source: &str, bids: &[(f64, f64)], asks: &[(f64, f64)], timestamp: u64) -> Vec<Trade> {
+        if killswitch::is_engaged() {
+            return Vec::new();
+        }
+        let target = |levels: &[(f64, f64)]| -> HashMap<Price, f64> {
+            levels
+                .iter()
+                .filter(|(price, _)| price.is_finite() && *price > 0.0)
+                .filter_map(|&(price, quantity)| {
+                    Some((self.config.round_price(price), self.config.normalize_quantity(quantity).ok()?))
+                })
+                .collect()
+        };
+        let mut bid_levels = target(bids);
+        let mut ask_levels = target(asks);
+
+        {
+            let mut last_seen = self.source_last_seen.entry(source.to_string()).or_default();
+            *last_seen = (*last_seen).max(timestamp);
+        }
+
+        let _lock = self.matching_lock.lock();
+        self.last_updated_ms.fetch_max(timestamp, Ordering::Relaxed);
+        let mut cancelled = Vec::new();
+        let mut changed = false;
+        for (order_id, side, price) in self.user_orders.get(source) {
+            let (book, levels) = match side {
+                OrderSide::Bid => (&self.bids, &mut bid_levels),
+                OrderSide::Ask => (&self.asks, &mut ask_levels),
+            };
+            match levels.remove(&price) {
+                Some(quantity) => {
+                    let book = book.read();
+                    let Some(level) = book.get(&price) else { continue };
+                    let Some(mut order) = level.orders.get_order(order_id) else { continue };
+                    if order.quantity != quantity {
+                        level.update_order(order_id, quantity);
+                        order.quantity = quantity;
+                        self.publish_book_order(BookOrderAction::Modify, &order);
+                        changed = true;
+                    }
+                }
+                None => {
+                    let mut book = book.write();
+                    let Some(level) = book.get(&price) else { continue };
+                    if let Some(order) = level.remove_order(order_id) {
+                        cancelled.push(order);
+                    }
+                    if level.is_empty() {
+                        book.remove(&price);
+                    }
+                    match side {
+                        OrderSide::Bid => self.store_best_bid(&book),
+                        OrderSide::Ask => self.store_best_ask(&book),
+                    }
+                }
+            }
+        }
+
+        for order in &cancelled {
+            self.publish_order_update(order, OrderEventStatus::Cancelled, 0.0);
+            self.publish_book_order(BookOrderAction::Cancel, order);
+        }
+        if changed || !cancelled.is_empty() {
+            self.bump_sequence();
+            let mut stats = self.stats.write();
+            stats.total_orders_cancelled += cancelled.len() as u64;
+            self.update_stats_internal(&mut stats);
+        }
+
+        let mut trades = Vec::new();
+        let new_levels = bid_levels
+            .into_iter()
+            .map(|(price, quantity)| (OrderSide::Bid, price, quantity))
+            .chain(ask_levels.into_iter().map(|(price, quantity)| (OrderSide::Ask, price, quantity)));
+        for (side, price, quantity) in new_levels {
+            let order_id = self.issue_order_id();
+            let order = Order::new(order_id, side, price.as_f64(), quantity, timestamp, source.to_string())
+                .with_source(Some(source.to_string()));
+            trades.extend(self.place_order(order));
+        }
+
+        self.evaluate_conditional_orders();
+        trades
+    }
+
+    // Cancels the orders of every source venue silent for longer than
+    // `max_source_silence_ms`. The venue is then forgotten until it sends
+    // again, so each outage is swept once.
+    pub fn sweep_dead_sources(&self, now: u64) -> Vec<Order> {
+        let max_silence_ms = match self.config.max_source_silence_ms {
+            Some(max_silence_ms) => max_silence_ms,
+            None => return Vec::new(),
+        };
+
+        let dead: Vec<(String, u64)> = self
+            .source_last_seen
+            .iter()
+            .filter(|entry| now.saturating_sub(*entry.value()) > max_silence_ms)
+            .map(|entry| (entry.key().clone(), *entry.value()))
+            .collect();
+        let mut cancelled = Vec::new();
+        for (source, last_seen) in dead {
+            // Lost a race with a fresh order from the venue: it isn't dead.
+            if self.source_last_seen.remove_if(&source, |_, seen| *seen == last_seen).is_none() {
+                continue;
+            }
+            let orders = self.cancel_all_for_source(&source);
+            log::warn!(
+                "{} feed from {} silent since {}: cancelled {} orders",
+                self.config.symbol,
+                source,
+                last_seen,
+                orders.len()
+            );
+            cancelled.extend(orders);
+        }
+        cancelled
+    }
+
+    // Cancels the resting orders of every user whose dead-man's switch lapsed.
+    pub fn sweep_dead_man(&self, now: u64) -> Vec<Order> {
+        let mut cancelled = Vec::new();
+        for user_id in self.dead_man.take_expired(now) {
+            // The user has gone quiet, so even their youngest orders go.
+            let orders = self.cancel_user_orders(&user_id, None);
+            log::warn!(
+                "Dead-man's switch expired for {}: cancelled {} orders",
+                user_id,
+                orders.len()
+            );
+            cancelled.extend(orders);
+        }
+        cancelled
+    }
+
+    // Removes every resting or pending order whose `expires_at` is at or
+    // before `now`, telling each owner through an `Expired` order update.
+    pub fn sweep_expired(&self, now: u64) -> Vec<Order> {
+        let _lock = self.matching_lock.lock();
+        let mut expired = Vec::new();
+
+        {
+            let mut bids = self.bids.write();
+            for level in bids.values() {
+                expired.extend(level.remove_expired_orders(now));
+            }
+            bids.retain(|_, level| !level.is_empty());
+            self.store_best_bid(&bids);
+        }
+
+        {
+            let mut asks = self.asks.write();
+            for level in asks.values() {
+                expired.extend(level.remove_expired_orders(now));
+            }
+            asks.retain(|_, level| !level.is_empty());
+            self.store_best_ask(&asks);
+        }
+        for order in &expired {
+            self.publish_book_order(BookOrderAction::Cancel, order);
+        }
+
+        self.pending_conditionals.lock().retain(|c| {
+            if c.order.is_expired(now) {
+                self.journal(|| EngineEvent::ConditionalRemoved { order_id: c.order.id });
+                expired.push(c.order.clone());
+                false
+            } else {
+                true
+            }
+        });
+
+        if expired.is_empty() {
+            return expired;
+        }
+
+        for order in &expired {
+            log::info!(
+                "Order {} for {} expired with {} remaining",
+                order.id,
+                order.user_id,
+                order.quantity
+            );
+            self.publish_order_update(order, OrderEventStatus::Expired, 0.0);
+        }
+        self.bump_sequence();
+        {
+            let mut stats = self.stats.write();
+            stats.total_orders_cancelled += expired.len() as u64;
+            self.update_stats_internal(&mut stats);
+        }
+
+        self.evaluate_conditional_orders();
+        expired
+    }
+
+    // Owner and final status of an order that recently left the book, if it
+    // is still remembered.
+    pub fn terminal_status(&self, order_id: u64) -> Option<(String, OrderEventStatus)> {
+        self.recent_terminal.lock().statuses.get(&order_id).cloned()
+    }
+
+    // A user's resting orders in arrival order. Pending conditional orders
+    // aren't on the book and aren't included.
+    pub fn get_user_orders(&self, user_id: &str) -> Vec<Order> {
+        let mut orders: Vec<Order> = self
+            .user_orders
+            .get(user_id)
+            .into_iter()
+            .filter_map(|(order_id, side, price)| {
+                let book = match side {
+                    OrderSide::Bid => self.bids.read(),
+                    OrderSide::Ask => self.asks.read(),
+                };
+                book.get(&price).and_then(|level| level.orders.get_order(order_id))
+            })
+            .collect();
+        orders.sort_by_key(|order| order.id);
+        orders
+    }
+
+    // Looks up an open order, resting or pending, by id.
+    pub fn get_order(&self, order_id: u64) -> Option<Order> {
+        for book in [&self.bids, &self.asks] {
+            let book = book.read();
+            if let Some(order) = book.values().find_map(|level| level.orders.get_order(order_id)) {
+                return Some(order);
+            }
+        }
+
+        self.pending_conditionals
+            .lock()
+            .iter()
+            .find(|c| c.order.id == order_id)
+            .map(|c| c.order.clone())
+    }
+
+    // Where a resting order stands in its level's FIFO: its 0-based
+    // position and the number of orders resting at that price. `None` unless
+    // the order is resting. A designated maker or tie-break rule can still
+    // fill an order ahead of its FIFO turn.
+    pub fn queue_position(&self, order_id: u64) -> Option<(usize, usize)> {
+        for book in [&self.bids, &self.asks] {
+            let book = book.read();
+            if let Some(position) = book.values().find_map(|level| level.orders.position(order_id)) {
+                return Some(position);
+            }
+        }
+        None
+    }
+
+    // Empties the book if it has gone longer than `max_staleness_ms` without a
+    // new order. A book that is already empty is left alone, so the
+    // notification fires once per outage rather than on every sweep.
+    pub fn sweep_stale(&self, now: u64) -> Vec<Order> {
+        let max_staleness_ms = match self.config.max_staleness_ms {
+            Some(max_staleness_ms) => max_staleness_ms,
+            None => return Vec::new(),
+        };
+
+        let _lock = self.matching_lock.lock();
+        let last_updated_ms = self.last_updated_ms();
+        if last_updated_ms == 0 || now.saturating_sub(last_updated_ms) <= max_staleness_ms {
+            return Vec::new();
+        }
+
+        let cleared = self.cancel_all_resting_locked();
+        if cleared.is_empty() {
+            return cleared;
+        }
+
+        log::warn!(
+            "{} book stale since {}: cleared {} resting orders",
+            self.config.symbol,
+            last_updated_ms,
+            cleared.len()
+        );
+        self.events.publish(OrderBookEvent::BookCleared(BookCleared {
+            last_updated_ms,
+            cleared_orders: cleared.len(),
+        }));
+        cleared
+    }
+
+    // Cancels every resting order on both sides. Pending conditional orders
+    // are left waiting.
+    pub fn cancel_all_resting(&self) -> Vec<Order> {
+        let _lock = self.matching_lock.lock();
+        self.cancel_all_resting_locked()
+    }
+
+    fn cancel_all_resting_locked(&self) -> Vec<Order> {
+        let mut cancelled = Vec::new();
+        for book in [&self.bids, &self.asks] {
+            let mut book = book.write();
+            for level in book.values() {
+                cancelled.extend(level.orders.orders_in_queue_order());
+            }
+            book.clear();
+        }
+        self.best_bid.store(NO_PRICE, Ordering::Release);
+        self.best_ask.store(NO_PRICE, Ordering::Release);
+
+        if !cancelled.is_empty() {
+            self.bump_sequence();
+            for order in &cancelled {
+                self.publish_order_update(order, OrderEventStatus::Cancelled, 0.0);
+                self.publish_book_order(BookOrderAction::Cancel, order);
+            }
+            let mut stats = self.stats.write();
+            stats.total_orders_cancelled += cancelled.len() as u64;
+            self.update_stats_internal(&mut stats);
+        }
+        cancelled
+    }
+
+    pub fn get_best_bid(&self) -> Option<f64> {
+        decode_price(self.best_bid.load(Ordering::Acquire))
+    }
+
+    pub fn get_best_ask(&self) -> Option<f64> {
+        decode_price(self.best_ask.load(Ordering::Acquire))
+    }
+
+    pub fn get_spread(&self) -> Option<f64> {
+        let stats = self.stats.read();
+        stats.spread
+    }
+
+    pub fn get_market_depth(&self, levels: usize) -> (DepthSide, DepthSide) {
         let bids: Vec<(f64, f64)> = {
             let bids = self.bids.read();
             bids.iter()
@@ -437,21 +2776,608 @@ impl OrderBook {
         (bids, asks)
     }
 
+    // Depth together with the sequence it reflects, read under the matching
+    // lock so no update can land between the two. A client resyncing its
+    // local book applies only diffs with a later sequence on top of this.
+    pub fn depth_with_sequence(&self, levels: usize) -> (u64, DepthSide, DepthSide) {
+        let _lock = self.matching_lock.lock();
+        let (bids, asks) = self.get_market_depth(levels);
+        (self.sequence(), bids, asks)
+    }
+
+    // Slope of cumulative quantity against distance from mid over the top
+    // `levels` of each side: how much size sits behind each unit of price
+    // move, a rough proxy for price impact. Distances are measured from the
+    // best price when the other side is empty; a side with fewer than two
+    // levels has no slope.
+    pub fn depth_slope(&self, levels: usize) -> (Option<f64>, Option<f64>) {
+        let (bids, asks) = self.get_market_depth(levels);
+        let reference = match (bids.first(), asks.first()) {
+            (Some((bid, _)), Some((ask, _))) => (bid + ask) / 2.0,
+            (Some((bid, _)), None) => *bid,
+            (None, Some((ask, _))) => *ask,
+            (None, None) => return (None, None),
+        };
+
+        let slope = |side: &DepthSide| {
+            let points: Vec<(f64, f64)> = cumulative_depth(side)
+                .into_iter()
+                .map(|(price, total)| ((price - reference).abs(), total))
+                .collect();
+            regression_slope(&points)
+        };
+        (slope(&bids), slope(&asks))
+    }
+
+    // Indicative uncross of the resting book, published while an auction
+    // builds. Nothing is executed. Continuous matching never leaves the book
+    // crossed, so outside an auction this reports no match.
+    pub fn indicative_auction(&self) -> AuctionInfo {
+        let _lock = self.matching_lock.lock();
+        let (bids, asks) = self.get_market_depth(usize::MAX);
+        auction::clearing_price(&bids, &asks)
+    }
+
+    // What a taker on `side` would fill by trading `notional` worth of quote
+    // currency against the resting opposite side, best price first. The last
+    // level is taken partially; with too little liquidity the result covers
+    // whatever is resting.
+    pub fn fill_for_notional(&self, side: OrderSide, notional: f64) -> NotionalFill {
+        match side {
+            OrderSide::Bid => Self::walk_notional(self.asks.read().iter(), notional),
+            OrderSide::Ask => Self::walk_notional(self.bids.read().iter().rev(), notional),
+        }
+    }
+
+    // Simulates a taker on `side` sweeping `quantity` through the opposite
+    // side without touching the book, and reports the price move and the
+    // depth that would remain behind it.
+    pub fn sweep_resilience(&self, side: OrderSide, quantity: f64) -> SweepResilience {
+        match side {
+            OrderSide::Bid => Self::walk_sweep(self.asks.read().iter(), quantity),
+            OrderSide::Ask => Self::walk_sweep(self.bids.read().iter().rev(), quantity),
+        }
+    }
+
+    fn walk_sweep<'a>(
+        levels: impl Iterator<Item = (&'a Price, &'a PriceLevel)>,
+        quantity: f64,
+    ) -> SweepResilience {
+        let mut filled = 0.0;
+        let mut spent = 0.0;
+        let mut start_price = None;
+        let mut end_price = None;
+        let mut resulting_best = None;
+        let mut levels_swept = 0;
+        let mut levels_remaining = 0;
+        let mut quantity_remaining = 0.0;
+
+        for (price, level) in levels {
+            let price = price.as_f64();
+            let level_quantity = level.get_total_quantity();
+            start_price.get_or_insert(price);
+            let wanted = quantity - filled;
+            if wanted <= 0.0 {
+                resulting_best.get_or_insert(price);
+                levels_remaining += 1;
+                quantity_remaining += level_quantity;
+                continue;
+            }
+
+            let taken = level_quantity.min(wanted);
+            filled += taken;
+            spent += taken * price;
+            end_price = Some(price);
+            levels_swept += 1;
+            if taken < level_quantity {
+                resulting_best = Some(price);
+            }
+        }
+
+        let price_move = match (start_price, end_price) {
+            (Some(start), Some(end)) => (end - start).abs(),
+            _ => 0.0,
+        };
+        SweepResilience {
+            filled_quantity: filled,
+            average_price: (filled > 0.0).then(|| spent / filled),
+            start_price,
+            end_price,
+            price_move,
+            price_move_pct: start_price.filter(|p| *p > 0.0).map(|p| price_move / p * 100.0),
+            levels_swept,
+            levels_remaining,
+            quantity_remaining,
+            resulting_best,
+            complete: filled >= quantity,
+        }
+    }
+
+    fn walk_notional<'a>(
+        levels: impl Iterator<Item = (&'a Price, &'a PriceLevel)>,
+        notional: f64,
+    ) -> NotionalFill {
+        let mut quantity = 0.0;
+        let mut spent = 0.0;
+        let mut complete = false;
+        for (price, level) in levels {
+            let price = price.as_f64();
+            let level_notional = price * level.get_total_quantity();
+            let remaining = notional - spent;
+            if level_notional >= remaining {
+                quantity += remaining / price;
+                spent = notional;
+                complete = true;
+                break;
+            }
+            quantity += level.get_total_quantity();
+            spent += level_notional;
+        }
+
+        NotionalFill {
+            quantity,
+            notional: spent,
+            average_price: (quantity > 0.0).then(|| spent / quantity),
+            complete,
+        }
+    }
+
+    // Up to `limit` trades executed at or after `since_ms`, oldest first.
+    // Orders are stamped on arrival and matched one at a time, so the tape is
+    // in execution order and the first such trade can be binary searched.
+    pub fn trades_since(&self, since_ms: u64, limit: usize) -> Vec<Trade> {
+        let history = self.trade_history.read();
+        let start = history.partition_point(|t| t.executed_at < since_ms);
+        history.range(start..).take(limit).cloned().collect()
+    }
+
+    // The last `limit` trades on the tape, oldest first.
+    pub fn get_recent_trades(&self, limit: usize) -> Vec<Trade> {
+        let history = self.trade_history.read();
+        history.iter().skip(history.len().saturating_sub(limit)).cloned().collect()
+    }
+
+    // The top `limit` users by `metric`, PnL marked at the last print. With
+    // no prints nobody holds a position to mark.
+    pub fn leaderboard(&self, metric: LeaderboardMetric, limit: usize) -> Vec<(String, UserAccount, f64)> {
+        let mark_price = self.trade_history.read().back().map_or(0.0, |t| t.price);
+        self.accounts.leaderboard(metric, mark_price, limit)
+    }
+
+    // A user's account with its PnL marked at the last print, as on the
+    // leaderboard. `None` for a user who has never placed an order here.
+    pub fn account(&self, user_id: &str) -> Option<(UserAccount, f64)> {
+        let account = self.accounts.get(user_id)?;
+        let mark_price = self.trade_history.read().back().map_or(0.0, |t| t.price);
+        Some((account, account.net_pnl(mark_price)))
+    }
+
+    // Records the current mid for `twap`, if it changed since the last
+    // sample. Meant to be called periodically; `now` must not go backwards.
+    pub fn sample_mid(&self, now: u64) {
+        let mid = match (self.get_best_bid(), self.get_best_ask()) {
+            (Some(bid), Some(ask)) => Some((bid + ask) / 2.0),
+            _ => None,
+        };
+        let mut history = self.mid_history.write();
+        if history.back().map(|(_, last)| *last) == Some(mid) {
+            return;
+        }
+        if history.len() == MID_HISTORY_CAPACITY {
+            history.pop_front();
+        }
+        history.push_back((now, mid));
+    }
+
+    pub fn twap(&self, window: Duration) -> Option<f64> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        self.twap_at(window, now)
+    }
+
+    // Average sampled mid over `[now - window, now]`, each mid weighted by how
+    // long it held. Time with no mid (a one-sided book, or before the first
+    // sample) doesn't count. `None` if no mid held for any of the window.
+    pub fn twap_at(&self, window: Duration, now: u64) -> Option<f64> {
+        let since = now.saturating_sub(window.as_millis() as u64);
+        let history = self.mid_history.read();
+        // The last sample at or before `since` still holds at the window start.
+        let start = history.partition_point(|(t, _)| *t <= since).saturating_sub(1);
+        let mut weighted = 0.0;
+        let mut held_ms = 0u64;
+        for (i, (t, mid)) in history.iter().enumerate().skip(start) {
+            let from = (*t).max(since);
+            let until = history.get(i + 1).map_or(now, |(next, _)| (*next).min(now));
+            if let (Some(mid), true) = (mid, until > from) {
+                weighted += mid * (until - from) as f64;
+                held_ms += until - from;
+            }
+        }
+        (held_ms > 0).then(|| weighted / held_ms as f64)
+    }
+
+    pub fn realized_volatility(&self, window: Duration) -> Option<f64> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        self.realized_volatility_at(window, now)
+    }
+
+    // Population standard deviation of log returns between consecutive trades
+    // executed in `[now - window, now]`. Needs at least two trades to form a return.
+    pub fn realized_volatility_at(&self, window: Duration, now: u64) -> Option<f64> {
+        let since = now.saturating_sub(window.as_millis() as u64);
+        let prices: Vec<f64> = {
+            let history = self.trade_history.read();
+            history
+                .iter()
+                .filter(|t| t.executed_at >= since && t.executed_at <= now && t.price > 0.0)
+                .map(|t| t.price)
+                .collect()
+        };
+
+        if prices.len() < 2 {
+            return None;
+        }
+
+        let returns: Vec<f64> = prices.windows(2).map(|w| (w[1] / w[0]).ln()).collect();
+        let n = returns.len() as f64;
+        let mean = returns.iter().sum::<f64>() / n;
+        let variance = returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / n;
+        Some(variance.sqrt())
+    }
+
+    pub fn volume_profile(&self, window: Duration, buckets: usize) -> Vec<(f64, f64)> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        self.volume_profile_at(window, buckets, now)
+    }
+
+    // Volume executed in `[now - window, now]` split into `buckets` equal-width
+    // price buckets spanning the window's low to high, as (bucket midpoint,
+    // volume) pairs from low to high. If every trade printed at one price the
+    // profile is a single bucket at that price.
+    pub fn volume_profile_at(&self, window: Duration, buckets: usize, now: u64) -> Vec<(f64, f64)> {
+        let since = now.saturating_sub(window.as_millis() as u64);
+        let trades: Vec<(f64, f64)> = {
+            let history = self.trade_history.read();
+            history
+                .iter()
+                .filter(|t| t.executed_at >= since && t.executed_at <= now)
+                .map(|t| (t.price, t.quantity))
+                .collect()
+        };
+
+        if trades.is_empty() || buckets == 0 {
+            return Vec::new();
+        }
+
+        let low = trades.iter().map(|(price, _)| *price).fold(f64::INFINITY, f64::min);
+        let high = trades.iter().map(|(price, _)| *price).fold(f64::NEG_INFINITY, f64::max);
+        if high <= low {
+            return vec![(low, trades.iter().map(|(_, quantity)| quantity).sum())];
+        }
+
+        let width = (high - low) / buckets as f64;
+        let mut volumes = vec![0.0; buckets];
+        for (price, quantity) in trades {
+            // The high print lands exactly on the upper edge; keep it in the last bucket.
+            let index = (((price - low) / width) as usize).min(buckets - 1);
+            volumes[index] += quantity;
+        }
+
+        volumes
+            .into_iter()
+            .enumerate()
+            .map(|(i, volume)| (low + width * (i as f64 + 0.5), volume))
+            .collect()
+    }
+
+    pub fn snapshot(&self) -> OrderBookSnapshot {
+        let _lock = self.matching_lock.lock();
+        self.snapshot_locked()
+    }
+
+    // A snapshot that also restarts the trade count towards the next
+    // checkpoint.
+    pub fn checkpoint(&self) -> OrderBookSnapshot {
+        let _lock = self.matching_lock.lock();
+        self.trades_since_checkpoint.store(0, Ordering::Relaxed);
+        self.snapshot_locked()
+    }
+
+    // Resolves once `snapshot_every_trades` trades have printed since the
+    // last checkpoint (possibly already).
+    pub async fn checkpoint_due(&self) {
+        self.checkpoint_due.notified().await
+    }
+
+    // Snapshot, tape and stats under one hold of the matching lock, so the
+    // archive is consistent: every print on the tape is reflected in the
+    // book and stats, and nothing later is.
+    pub fn archive(&self, archived_at_ms: u64) -> SessionArchive {
+        let _lock = self.matching_lock.lock();
+        let snapshot = self.snapshot_locked();
+        SessionArchive {
+            symbol: self.config.symbol.clone(),
+            archived_at_ms,
+            config: self.config.clone(),
+            stats: snapshot.stats.clone(),
+            snapshot,
+            trades: self.trade_history.read().iter().cloned().collect(),
+        }
+    }
+
+    // Caller must hold `matching_lock`.
+    fn snapshot_locked(&self) -> OrderBookSnapshot {
+        let bids = self.bids.read();
+        let asks = self.asks.read();
+        
+        OrderBookSnapshot {
+            bids: bids.values().rev().flat_map(|level| level.orders.orders_in_queue_order()).collect(),
+            asks: asks.values().flat_map(|level| level.orders.orders_in_queue_order()).collect(),
+            next_order_id: self.next_order_id.load(Ordering::Relaxed),
+            stats: self.stats.read().clone(),
+        }
+    }
+
+    // Replaces the book's resting state with `snapshot`. The snapshot is
+    // validated first (no crossed book, duplicate ids or malformed orders), so
+    // on error the current state is left as it was. Levels may be listed in
+    // any order; within a price, orders are queued in the order given.
+    pub fn restore(&self, snapshot: OrderBookSnapshot) -> Result<(), RestoreError> {
+        snapshot.validate()?;
+        
+        let _lock = self.matching_lock.lock();
+        self.journal(|| EngineEvent::Reset {
+            snapshot: snapshot.clone(),
+            conditionals: self.pending_conditionals.lock().clone(),
+            accounts: self.accounts.snapshot(),
+        });
+        self.restore_locked(snapshot);
+        Ok(())
+    }
+
+    // Caller must hold `matching_lock` and have validated `snapshot`.
+    fn restore_locked(&self, snapshot: OrderBookSnapshot) {
+        let mut bids = self.bids.write();
+        let mut asks = self.asks.write();
+        bids.clear();
+        asks.clear();
+        self.user_orders.clear();
+        
+        let mut max_order_id = 0;
+        for (orders, book) in [(snapshot.bids, &mut *bids), (snapshot.asks, &mut *asks)] {
+            for order in orders {
+                max_order_id = max_order_id.max(order.id);
+                self.user_orders.insert(&order);
+                let price = order.price.as_f64();
+                book.entry(Price::new(price))
+                    .or_insert_with(|| PriceLevel::for_config(price, &self.config))
+                    .add_order(order);
+            }
+        }
+        
+        self.store_best_bid(&bids);
+        self.store_best_ask(&asks);
+        self.next_order_id.store(snapshot.next_order_id.max(max_order_id + 1), Ordering::Relaxed);
+        self.bump_sequence();
+        
+        let mut stats = self.stats.write();
+        *stats = snapshot.stats;
+        stats.update_market_data(
+            bids.keys().next_back().map(|p| p.as_f64()),
+            asks.keys().next().map(|p| p.as_f64()),
+        );
+    }
+
+    // Starts appending every change to `log`, which is first compacted to
+    // the book as it stands, so replaying it always starts from the right
+    // state. Fails if a log is already attached.
+    pub fn attach_event_log(&self, log: Arc<EventLog>) -> io::Result<()> {
+        let _lock = self.matching_lock.lock();
+        if self.event_log.get().is_some() {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, "an event log is already attached"));
+        }
+        log.compact(&self.reset_event_locked())?;
+        let _ = self.event_log.set(log);
+        Ok(())
+    }
+
+    // Compacts the attached log if it has grown past its `compact_every`.
+    // Returns whether it did.
+    pub fn compact_event_log(&self) -> io::Result<bool> {
+        let Some(log) = self.event_log.get().filter(|log| log.compaction_due()) else {
+            return Ok(false);
+        };
+        let _lock = self.matching_lock.lock();
+        log.compact(&self.reset_event_locked())?;
+        Ok(true)
+    }
+
+    // Caller must hold `matching_lock`.
+    fn reset_event_locked(&self) -> EngineEvent {
+        EngineEvent::Reset {
+            snapshot: self.snapshot_locked(),
+            conditionals: self.pending_conditionals.lock().clone(),
+            accounts: self.accounts.snapshot(),
+        }
+    }
+
+    // Re-applies one event from an `EventLog`. Nothing is matched: trades
+    // come from their own events, and only go to the tape and match stats,
+    // not to subscribers or the checkpoint count; accounts are set to what
+    // was logged.
+    // Order counts other than matches are as of the last `Reset`.
+    pub fn apply_event(&self, event: EngineEvent) -> Result<(), RestoreError> {
+        let _lock = self.matching_lock.lock();
+        match event {
+            EngineEvent::OrderIdIssued { order_id } => {
+                self.next_order_id.fetch_max(order_id + 1, Ordering::Relaxed);
+            }
+            EngineEvent::OrderPlaced { order } => {
+                self.next_order_id.fetch_max(order.id + 1, Ordering::Relaxed);
+                self.publish_book_order(BookOrderAction::Add, &order);
+                let price = order.price.as_f64();
+                let book = match order.side {
+                    OrderSide::Bid => &self.bids,
+                    OrderSide::Ask => &self.asks,
+                };
+                let mut book = book.write();
+                let side = order.side;
+                book.entry(Price::new(price))
+                    .or_insert_with(|| PriceLevel::for_config(price, &self.config))
+                    .add_order(order);
+                match side {
+                    OrderSide::Bid => self.store_best_bid(&book),
+                    OrderSide::Ask => self.store_best_ask(&book),
+                }
+            }
+            EngineEvent::OrderUpdated { order } => {
+                let book = match order.side {
+                    OrderSide::Bid => self.bids.read(),
+                    OrderSide::Ask => self.asks.read(),
+                };
+                let level = book.get(&order.price).filter(|level| level.orders.get_order(order.id).is_some());
+                let Some(level) = level else {
+                    return Err(RestoreError::InvalidOrder {
+                        order_id: order.id,
+                        reason: "updated while not resting".to_string(),
+                    });
+                };
+                level.update_order(order.id, order.quantity);
+                level.orders.update_reserve(order.id, order.hidden_remaining);
+            }
+            EngineEvent::OrderRemoved { order_id, side, price, action } => {
+                let book = match side {
+                    OrderSide::Bid => &self.bids,
+                    OrderSide::Ask => &self.asks,
+                };
+                let mut book = book.write();
+                let price = Price::new(price);
+                let removed = book.get(&price).and_then(|level| level.orders.take_order(order_id));
+                let Some(order) = removed else {
+                    return Err(RestoreError::InvalidOrder {
+                        order_id,
+                        reason: "removed while not resting".to_string(),
+                    });
+                };
+                if book.get(&price).is_some_and(|level| level.is_empty()) {
+                    book.remove(&price);
+                }
+                match side {
+                    OrderSide::Bid => self.store_best_bid(&book),
+                    OrderSide::Ask => self.store_best_ask(&book),
+                }
+                drop(book);
+                self.publish_book_order(action, &order);
+            }
+            EngineEvent::ConditionalPlaced { conditional } => {
+                self.next_order_id.fetch_max(conditional.order.id + 1, Ordering::Relaxed);
+                self.pending_conditionals.lock().push(conditional);
+            }
+            EngineEvent::ConditionalRemoved { order_id } => {
+                self.pending_conditionals.lock().retain(|c| c.order.id != order_id);
+            }
+            EngineEvent::Traded { mut trade, executed_at } => {
+                let timestamp = executed_at.unwrap_or(trade.timestamp);
+                // Journaled before trades carried their own execution time.
+                if trade.executed_at == 0 {
+                    trade.executed_at = timestamp;
+                }
+                self.tape_trades(&[trade], timestamp);
+            }
+            EngineEvent::AccountUpdated { user_id, account } => self.accounts.set(&user_id, account),
+            EngineEvent::Reset { snapshot, conditionals, accounts } => {
+                snapshot.validate()?;
+                self.restore_locked(snapshot);
+                *self.pending_conditionals.lock() = conditionals;
+                self.accounts.restore(accounts);
+            }
+            EngineEvent::Cleared => self.clear_locked(),
+        }
+        Ok(())
+    }
+
+    // `mid_ema` is brought up to now, so a mid that hasn't moved keeps
+    // pulling it in even when nothing else happens on the book.
     pub fn get_stats(&self) -> OrderBookStats {
-        self.stats.read().clone()
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        let mut stats = self.stats.read().clone();
+        stats.advance_mid_ema(now, self.config.mid_ema_alpha);
+        stats.volume_24h = self.get_volume_24h(now);
+        stats
+    }
+
+    // Notional traded in the 24 hours up to `now`. Volume is kept per
+    // minute, so a trade ages out within a minute of turning 24 hours old.
+    pub fn get_volume_24h(&self, now: u64) -> f64 {
+        self.volume_24h.lock().total(now)
+    }
+
+    // The last `limit` candles of `interval` up to the current one, oldest
+    // first. Intervals nothing traded in repeat the previous close with zero
+    // volume; the series starts at the oldest candle still kept.
+    pub fn get_candles(&self, interval: CandleInterval, limit: usize) -> Vec<Candle> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        self.candles
+            .lock()
+            .iter()
+            .find(|series| series.interval() == interval)
+            .map(|series| series.candles(limit, now))
+            .unwrap_or_default()
     }
 
     fn update_stats_internal(&self, stats: &mut OrderBookStats) {
         let best_bid = self.get_best_bid();
         let best_ask = self.get_best_ask();
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        // The old mid is what held until now; the new one starts here.
+        stats.advance_mid_ema(now, self.config.mid_ema_alpha);
         stats.update_market_data(best_bid, best_ask);
+        if stats.mid_ema.is_none() {
+            stats.mid_ema = stats.mid_price;
+        }
     }
 
     pub fn clear(&self) {
+        let _lock = self.matching_lock.lock();
+        self.journal(|| EngineEvent::Cleared);
+        self.clear_locked();
+    }
+
+    // Caller must hold `matching_lock`.
+    fn clear_locked(&self) {
         let mut bids = self.bids.write();
         let mut asks = self.asks.write();
         bids.clear();
         asks.clear();
+        self.store_best_bid(&bids);
+        self.store_best_ask(&asks);
+        
+        self.pending_conditionals.lock().clear();
+        *self.take_profit_children.lock() = TakeProfitChildren::default();
+        self.user_orders.clear();
+        self.trade_history.write().clear();
+        self.volume_24h.lock().buckets.clear();
+        self.candles.lock().iter_mut().for_each(CandleSeries::clear);
+        self.mid_history.write().clear();
+        self.accounts.clear();
+        self.bump_sequence();
         
         let mut stats = self.stats.write();
         *stats = OrderBookStats::new();
@@ -1,51 +1,54 @@
 use std::fmt;
-use std::cmp::Ordering;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
+// Price units per 1.0; the finest increment any book can quote in.
+pub const PRICE_SCALE: i64 = 100_000_000;
+// Tick size for books that don't set one: the finest representable.
+pub const DEFAULT_TICK_SIZE: f64 = 1.0 / PRICE_SCALE as f64;
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
-pub struct Price(pub f64);
+// A price as a whole number of 1/PRICE_SCALE units, so map keys order, hash
+// and compare exactly. Two prices that differ only by float noise are the
+// same level, and NaN can't reach a key. Serialized as a plain number.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Price(i64);
 
 impl Price {
+    // Nearest multiple of `tick_size`. Out-of-range values saturate and NaN
+    // becomes zero.
+    pub fn from_f64(value: f64, tick_size: f64) -> Self {
+        let tick_units = (tick_size * PRICE_SCALE as f64).round().max(1.0) as i64;
+        let ticks = (value * PRICE_SCALE as f64 / tick_units as f64).round() as i64;
+        Price(ticks.saturating_mul(tick_units))
+    }
+
+    // Nearest representable price, without any tick size.
+    pub fn new(value: f64) -> Self {
+        Self::from_f64(value, DEFAULT_TICK_SIZE)
+    }
+
     pub fn as_f64(&self) -> f64 {
-        self.0
+        self.0 as f64 / PRICE_SCALE as f64
     }
-}
 
-impl PartialEq for Price {
-    fn eq(&self, other: &Self) -> bool {
-        if self.0.is_nan() && other.0.is_nan() {
-            true
-        } else {
-            self.0 == other.0
-        }
+    pub fn units(&self) -> i64 {
+        self.0
     }
 }
 
-impl Eq for Price {}
-
-impl PartialOrd for Price {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        if self.0.is_nan() && other.0.is_nan() {
-            Some(Ordering::Equal)
-        } else if self.0.is_nan() {
-            Some(Ordering::Less)
-        } else if other.0.is_nan() {
-            Some(Ordering::Greater)
-        } else {
-            self.0.partial_cmp(&other.0)
-        }
+impl Serialize for Price {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(self.as_f64())
     }
 }
 
-impl Ord for Price {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other).unwrap_or(Ordering::Equal)
+impl<'de> Deserialize<'de> for Price {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        f64::deserialize(deserializer).map(Price::new)
     }
 }
 
 impl fmt::Display for Price {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:.2}", self.0)
+        write!(f, "{:.2}", self.as_f64())
     }
-}
\ No newline at end of file
+}
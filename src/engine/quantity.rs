@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+
+// A quantity as a whole number of lots, one lot being 1/scale of a unit (see
+// `OrderBookConfig::quantity_scale`). Sums and differences are exact, unlike
+// the f64 quantities used at the API boundary.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Qty(pub u64);
+
+impl Qty {
+    pub const ZERO: Qty = Qty(0);
+
+    // Nearest whole lot. Negative and NaN quantities become zero.
+    pub fn from_f64(quantity: f64, scale: u64) -> Self {
+        Qty((quantity * scale as f64).round().max(0.0) as u64)
+    }
+
+    pub fn to_f64(self, scale: u64) -> f64 {
+        self.0 as f64 / scale as f64
+    }
+
+    pub fn units(self) -> u64 {
+        self.0
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn checked_add(self, other: Qty) -> Option<Qty> {
+        self.0.checked_add(other.0).map(Qty)
+    }
+
+    pub fn checked_sub(self, other: Qty) -> Option<Qty> {
+        self.0.checked_sub(other.0).map(Qty)
+    }
+}
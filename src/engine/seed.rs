@@ -0,0 +1,88 @@
+// Seeding a book from a CSV of `side,price,quantity,user_id` rows, so a demo
+// or test scenario can be set up without writing Rust.
+use std::fmt;
+use std::io;
+
+use crate::engine::order::OrderSide;
+
+#[derive(Debug)]
+pub enum SeedError {
+    Io(io::Error),
+    // `line` is 1-based and counts the header and blank lines.
+    Row { line: usize, reason: String },
+}
+
+impl fmt::Display for SeedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SeedError::Io(e) => write!(f, "could not read seed file: {}", e),
+            SeedError::Row { line, reason } => write!(f, "line {}: {}", line, reason),
+        }
+    }
+}
+
+impl std::error::Error for SeedError {}
+
+impl From<io::Error> for SeedError {
+    fn from(e: io::Error) -> Self {
+        SeedError::Io(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeedOrder {
+    pub line: usize,
+    pub side: OrderSide,
+    pub price: f64,
+    pub quantity: f64,
+    pub user_id: String,
+}
+
+// Parses every row before anything is placed, so a bad file seeds nothing.
+// An optional header row starting with `side` is skipped, as are blank lines
+// and `#` comments. Sides are `buy`/`bid` or `sell`/`ask`, in any case.
+pub fn parse_orders_csv(text: &str) -> Result<Vec<SeedOrder>, SeedError> {
+    let mut orders = Vec::new();
+    for (i, row) in text.lines().enumerate() {
+        let line = i + 1;
+        let row = row.trim();
+        if row.is_empty() || row.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = row.split(',').map(str::trim).collect();
+        if orders.is_empty() && fields[0].eq_ignore_ascii_case("side") {
+            continue;
+        }
+        let error = |reason: String| SeedError::Row { line, reason };
+        if fields.len() != 4 {
+            return Err(error(format!("expected 4 fields (side,price,quantity,user_id), found {}", fields.len())));
+        }
+
+        let side = match fields[0].to_ascii_lowercase().as_str() {
+            "buy" | "bid" => OrderSide::Bid,
+            "sell" | "ask" => OrderSide::Ask,
+            other => return Err(error(format!("unknown side {:?}", other))),
+        };
+        let price: f64 = fields[1]
+            .parse()
+            .map_err(|_| error(format!("invalid price {:?}", fields[1])))?;
+        if !price.is_finite() || price <= 0.0 {
+            return Err(error(format!("price must be positive, got {}", price)));
+        }
+        let quantity: f64 = fields[2]
+            .parse()
+            .map_err(|_| error(format!("invalid quantity {:?}", fields[2])))?;
+        if fields[3].is_empty() {
+            return Err(error("user_id is empty".to_string()));
+        }
+
+        orders.push(SeedOrder {
+            line,
+            side,
+            price,
+            quantity,
+            user_id: fields[3].to_string(),
+        });
+    }
+    Ok(orders)
+}
@@ -0,0 +1,136 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+
+use crate::engine::config::OrderBookConfig;
+use crate::engine::order::{Order, OrderSide, MAX_CLIENT_METADATA_LEN};
+use crate::engine::orderbook::OrderBookStats;
+use crate::engine::trade::Trade;
+
+// Full resting state of a book. Orders on each side are listed level by level
+// in FIFO order, which is the order `restore` re-queues them in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderBookSnapshot {
+    pub bids: Vec<Order>,
+    pub asks: Vec<Order>,
+    pub next_order_id: u64,
+    pub stats: OrderBookStats,
+}
+
+// End-of-session capture: the resting book, the whole trade tape, the stats
+// and the config the book ran with, all taken at the same instant.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionArchive {
+    pub symbol: String,
+    pub archived_at_ms: u64,
+    pub config: OrderBookConfig,
+    pub stats: OrderBookStats,
+    pub snapshot: OrderBookSnapshot,
+    // Oldest first.
+    pub trades: Vec<Trade>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RestoreError {
+    Crossed { best_bid: f64, best_ask: f64 },
+    DuplicateOrderId(u64),
+    WrongSide { order_id: u64, expected: OrderSide },
+    InvalidOrder { order_id: u64, reason: String },
+}
+
+impl fmt::Display for RestoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RestoreError::Crossed { best_bid, best_ask } => write!(
+                f,
+                "snapshot is crossed: best bid {} >= best ask {}",
+                best_bid, best_ask
+            ),
+            RestoreError::DuplicateOrderId(order_id) => {
+                write!(f, "snapshot contains order id {} more than once", order_id)
+            }
+            RestoreError::WrongSide { order_id, expected } => write!(
+                f,
+                "order {} is listed on the {:?} side but is not a {:?} order",
+                order_id, expected, expected
+            ),
+            RestoreError::InvalidOrder { order_id, reason } => {
+                write!(f, "order {} is invalid: {}", order_id, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RestoreError {}
+
+impl OrderBookSnapshot {
+    // Written as JSON beside `path` and renamed over it, so a crash mid-way
+    // leaves the previous file intact.
+    pub fn save_to_path(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let partial_path = path.with_extension("partial");
+        {
+            let mut file = BufWriter::new(File::create(&partial_path)?);
+            serde_json::to_writer(&mut file, self)?;
+            file.write_all(b"\n")?;
+            file.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        }
+        fs::rename(&partial_path, path)
+    }
+
+    // `None` when there is no file at `path`.
+    pub fn load_from_path(path: impl AsRef<Path>) -> io::Result<Option<Self>> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        Ok(Some(serde_json::from_reader(BufReader::new(file))?))
+    }
+
+    // Checks everything `restore` relies on before any state is replaced.
+    pub fn validate(&self) -> Result<(), RestoreError> {
+        let mut seen = std::collections::HashSet::new();
+
+        for (orders, expected) in [(&self.bids, OrderSide::Bid), (&self.asks, OrderSide::Ask)] {
+            for order in orders {
+                if order.side != expected {
+                    return Err(RestoreError::WrongSide { order_id: order.id, expected });
+                }
+                if !order.price.as_f64().is_finite() || order.price.as_f64() <= 0.0 {
+                    return Err(RestoreError::InvalidOrder {
+                        order_id: order.id,
+                        reason: format!("price {} is not a positive number", order.price.as_f64()),
+                    });
+                }
+                if !order.quantity.is_finite() || order.quantity <= 0.0 {
+                    return Err(RestoreError::InvalidOrder {
+                        order_id: order.id,
+                        reason: format!("quantity {} is not a positive number", order.quantity),
+                    });
+                }
+                if order.client_metadata.as_ref().is_some_and(|m| m.len() > MAX_CLIENT_METADATA_LEN) {
+                    return Err(RestoreError::InvalidOrder {
+                        order_id: order.id,
+                        reason: "client_metadata is too long".to_string(),
+                    });
+                }
+                if !seen.insert(order.id) {
+                    return Err(RestoreError::DuplicateOrderId(order.id));
+                }
+            }
+        }
+
+        let best_bid = self.bids.iter().map(|o| o.price.as_f64()).reduce(f64::max);
+        let best_ask = self.asks.iter().map(|o| o.price.as_f64()).reduce(f64::min);
+        if let (Some(best_bid), Some(best_ask)) = (best_bid, best_ask) {
+            if best_bid >= best_ask {
+                return Err(RestoreError::Crossed { best_bid, best_ask });
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,12 +1,31 @@
 use serde::{Deserialize, Serialize};
 
+use crate::engine::order::OrderSide;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Trade {
     pub bid_order_id: u64,
     pub ask_order_id: u64,
     pub price: f64,
     pub quantity: f64,
+    // The earlier of the two orders' timestamps, usually when the maker
+    // rested; `executed_at` is when the trade itself happened.
     pub timestamp: u64,
+    #[serde(default)]
+    pub executed_at: u64,
+    #[serde(default)]
+    pub maker_fee: f64,
+    #[serde(default)]
+    pub taker_fee: f64,
+    // Side of the incoming order that took liquidity, and which order was
+    // which; all `None` for prints with no taker, such as the opening
+    // auction uncross.
+    #[serde(default, alias = "aggressor")]
+    pub taker_side: Option<OrderSide>,
+    #[serde(default)]
+    pub maker_order_id: Option<u64>,
+    #[serde(default)]
+    pub taker_order_id: Option<u64>,
 }
 
 impl Trade {
@@ -17,10 +36,120 @@ impl Trade {
             price,
             quantity,
             timestamp,
+            executed_at: timestamp,
+            maker_fee: 0.0,
+            taker_fee: 0.0,
+            taker_side: None,
+            maker_order_id: None,
+            taker_order_id: None,
         }
     }
 
+    pub fn with_executed_at(mut self, executed_at: u64) -> Self {
+        self.executed_at = executed_at;
+        self
+    }
+
+    pub fn with_fees(mut self, maker_fee: f64, taker_fee: f64) -> Self {
+        self.maker_fee = maker_fee;
+        self.taker_fee = taker_fee;
+        self
+    }
+
+    // Records the incoming order, on `taker_side`, as the taker and the
+    // resting one it hit as the maker.
+    pub fn with_taker(mut self, taker_side: OrderSide, taker_order_id: u64, maker_order_id: u64) -> Self {
+        self.taker_side = Some(taker_side);
+        self.taker_order_id = Some(taker_order_id);
+        self.maker_order_id = Some(maker_order_id);
+        self
+    }
+
     pub fn get_trade_value(&self) -> f64 {
         self.price * self.quantity
     }
-}
\ No newline at end of file
+}
+// The tape laid out column by column: entry `i` of every vector belongs to
+// the same trade. Much smaller than a list of objects for bulk export, and
+// loads straight into a data frame.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TradeColumns {
+    pub bid_order_id: Vec<u64>,
+    pub ask_order_id: Vec<u64>,
+    pub price: Vec<f64>,
+    pub quantity: Vec<f64>,
+    pub timestamp: Vec<u64>,
+    pub executed_at: Vec<u64>,
+    pub maker_fee: Vec<f64>,
+    pub taker_fee: Vec<f64>,
+    pub taker_side: Vec<Option<OrderSide>>,
+    pub maker_order_id: Vec<Option<u64>>,
+    pub taker_order_id: Vec<Option<u64>>,
+}
+
+impl TradeColumns {
+    pub fn from_trades<'a>(trades: impl IntoIterator<Item = &'a Trade>) -> Self {
+        let mut columns = Self::default();
+        for trade in trades {
+            columns.bid_order_id.push(trade.bid_order_id);
+            columns.ask_order_id.push(trade.ask_order_id);
+            columns.price.push(trade.price);
+            columns.quantity.push(trade.quantity);
+            columns.timestamp.push(trade.timestamp);
+            columns.executed_at.push(trade.executed_at);
+            columns.maker_fee.push(trade.maker_fee);
+            columns.taker_fee.push(trade.taker_fee);
+            columns.taker_side.push(trade.taker_side);
+            columns.maker_order_id.push(trade.maker_order_id);
+            columns.taker_order_id.push(trade.taker_order_id);
+        }
+        columns
+    }
+
+    pub fn len(&self) -> usize {
+        self.price.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.price.is_empty()
+    }
+
+    // Back to one `Trade` per row; `None` if the columns aren't all the
+    // same length.
+    pub fn to_trades(&self) -> Option<Vec<Trade>> {
+        let len = self.len();
+        let aligned = [
+            self.bid_order_id.len(),
+            self.ask_order_id.len(),
+            self.quantity.len(),
+            self.timestamp.len(),
+            self.executed_at.len(),
+            self.maker_fee.len(),
+            self.taker_fee.len(),
+            self.taker_side.len(),
+            self.maker_order_id.len(),
+            self.taker_order_id.len(),
+        ]
+        .iter()
+        .all(|&column| column == len);
+        if !aligned {
+            return None;
+        }
+        let trades = (0..len)
+            .map(|i| Trade {
+                bid_order_id: self.bid_order_id[i],
+                ask_order_id: self.ask_order_id[i],
+                price: self.price[i],
+                quantity: self.quantity[i],
+                timestamp: self.timestamp[i],
+                executed_at: self.executed_at[i],
+                maker_fee: self.maker_fee[i],
+                taker_fee: self.taker_fee[i],
+                taker_side: self.taker_side[i],
+                maker_order_id: self.maker_order_id[i],
+                taker_order_id: self.taker_order_id[i],
+            })
+            .collect();
+        Some(trades)
+    }
+}
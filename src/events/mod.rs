@@ -1,2 +1,180 @@
-// Event broadcasting system - Phase 2
-// This will handle WebSocket real-time updates
\ No newline at end of file
+// Event broadcasting system: the order book publishes what happened to it and
+// any number of subscribers (WebSocket sessions, ...) receive a copy. Each
+// subscriber gets its own channel from `OrderBook::subscribe`.
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::engine::accounting::UserAccount;
+use crate::engine::conditional::ConditionalOrder;
+use crate::engine::order::{Order, OrderSide};
+use crate::engine::snapshot::OrderBookSnapshot;
+use crate::engine::trade::Trade;
+
+pub const EVENT_CHANNEL_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OrderEventStatus {
+    New,
+    PartiallyFilled,
+    Filled,
+    Cancelled,
+    // Removed by the sweeper when its `expires_at` passed.
+    Expired,
+}
+
+// A change to one user's order. `filled_quantity` is the amount filled by
+// this update; `remaining_quantity` is what is still open afterwards.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderUpdate {
+    pub user_id: String,
+    pub order_id: u64,
+    pub status: OrderEventStatus,
+    pub filled_quantity: f64,
+    pub remaining_quantity: f64,
+    pub client_metadata: Option<String>,
+}
+
+// The whole book was emptied because nothing had updated it since
+// `last_updated_ms`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookCleared {
+    pub last_updated_ms: u64,
+    pub cleared_orders: usize,
+}
+
+// A fill at `price` while a better opposite price `best_price` was resting.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TradeThrough {
+    pub taker_order_id: u64,
+    pub maker_order_id: u64,
+    pub price: f64,
+    pub best_price: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BookOrderAction {
+    Add,
+    // Reduced by a partial fill.
+    Modify,
+    // Removed without trading: cancelled, expired or cleared.
+    Cancel,
+    // Removed by the fill that completed it.
+    Fill,
+}
+
+// A change to one resting order, for market-by-order consumers. Only
+// published with `OrderBookConfig::order_level_events`. `quantity` is what
+// rests after the change, zero once removed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookOrder {
+    pub action: BookOrderAction,
+    pub order_id: u64,
+    pub side: OrderSide,
+    pub price: f64,
+    pub quantity: f64,
+}
+
+// One fill with both parties identified, so each side can be told about it
+// from its own perspective. Only published with
+// `OrderBookConfig::match_events`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatchEvent {
+    pub maker_user_id: String,
+    pub maker_order_id: u64,
+    pub taker_user_id: String,
+    pub taker_order_id: u64,
+    pub aggressor: OrderSide,
+    pub price: f64,
+    pub quantity: f64,
+    pub maker_fee: f64,
+    pub taker_fee: f64,
+    pub timestamp: u64,
+}
+
+// Resting depth may have changed. `sequence` is the book's sequence after
+// the change; subscribers read whatever depth they need from the book.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct DepthChanged {
+    pub sequence: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum OrderBookEvent {
+    OrderUpdate(OrderUpdate),
+    BookCleared(BookCleared),
+    TradeThrough(TradeThrough),
+    BookOrder(BookOrder),
+    Match(MatchEvent),
+    // Every print, for the public tape; carries no user ids.
+    Trade(Trade),
+    DepthChanged(DepthChanged),
+}
+
+// One change to a book's state, as written to its `EventLog`. Applied in
+// order to the state they started from, they rebuild the same book without
+// matching anything again.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum EngineEvent {
+    // Handed out whether or not the order ever rested.
+    OrderIdIssued { order_id: u64 },
+    // Queued at the back of its level.
+    OrderPlaced { order: Order },
+    // Changed in place, keeping its queue position.
+    OrderUpdated { order: Order },
+    // Left the book: `Cancel` or `Fill`, as in `BookOrder`.
+    OrderRemoved { order_id: u64, side: OrderSide, price: f64, action: BookOrderAction },
+    ConditionalPlaced { conditional: ConditionalOrder },
+    // Triggered, cancelled or expired.
+    ConditionalRemoved { order_id: u64 },
+    // `executed_at` is when it matched, which candles and 24h volume go by;
+    // `trade.timestamp` is the older order's. Logs without it fall back to
+    // `trade.timestamp`.
+    Traded {
+        trade: Trade,
+        #[serde(default)]
+        executed_at: Option<u64>,
+    },
+    // A user's account after an order or fill was recorded against it.
+    AccountUpdated { user_id: String, account: UserAccount },
+    // The book was replaced wholesale: by `restore`, or as the first entry
+    // of a compacted log. `accounts` are every user's as of then.
+    Reset {
+        snapshot: OrderBookSnapshot,
+        conditionals: Vec<ConditionalOrder>,
+        #[serde(default)]
+        accounts: BTreeMap<String, UserAccount>,
+    },
+    Cleared,
+}
+
+#[derive(Debug)]
+pub struct EventBus {
+    sender: broadcast::Sender<OrderBookEvent>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        Self { sender }
+    }
+
+    // Never blocks: with no subscribers the event is simply dropped, and
+    // subscribers that fall behind see `RecvError::Lagged` instead of stalling
+    // the publisher.
+    pub fn publish(&self, event: OrderBookEvent) {
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<OrderBookEvent> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
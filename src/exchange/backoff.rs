@@ -0,0 +1,103 @@
+// How long an exchange feed waits before reconnecting. Each failure in a row
+// doubles the wait, up to a cap, and every wait is jittered so feeds that
+// dropped together don't all come back in the same instant. A connection
+// that stayed up long enough counts as recovered and starts the count over.
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::exchange::synthetic::Rng;
+
+pub const DEFAULT_BASE_DELAY: Duration = Duration::from_secs(1);
+pub const DEFAULT_MAX_DELAY: Duration = Duration::from_secs(60);
+// Each wait is scaled by a random factor within this fraction of 1.
+pub const DEFAULT_JITTER: f64 = 0.2;
+pub const DEFAULT_STABLE_AFTER: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+pub struct ReconnectBackoff {
+    base_delay: Duration,
+    max_delay: Duration,
+    jitter: f64,
+    stable_after: Duration,
+    // Disconnects in a row without a connection lasting `stable_after`.
+    consecutive_failures: u32,
+    rng: Rng,
+}
+
+impl ReconnectBackoff {
+    pub fn new() -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos() as u64;
+        Self {
+            base_delay: DEFAULT_BASE_DELAY,
+            max_delay: DEFAULT_MAX_DELAY,
+            jitter: DEFAULT_JITTER,
+            stable_after: DEFAULT_STABLE_AFTER,
+            consecutive_failures: 0,
+            rng: Rng::new(seed),
+        }
+    }
+
+    pub fn consecutive_failures(&self) -> u32 {
+        self.consecutive_failures
+    }
+
+    // The wait after `failures` earlier disconnects in a row, before jitter:
+    // the base delay doubled each time, never above the cap.
+    pub fn delay_for(&self, failures: u32) -> Duration {
+        let factor = 2u32.saturating_pow(failures);
+        self.base_delay.saturating_mul(factor).min(self.max_delay)
+    }
+
+    // Records a disconnect from a connection that was up for `uptime` (zero
+    // if it never connected) and returns how long to wait before the next
+    // attempt.
+    pub fn next_delay(&mut self, uptime: Duration) -> Duration {
+        if uptime >= self.stable_after {
+            self.consecutive_failures = 0;
+        }
+        let delay = self.delay_for(self.consecutive_failures);
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        let scale = 1.0 + self.jitter * (2.0 * self.rng.next_f64() - 1.0);
+        delay.mul_f64(scale)
+    }
+}
+
+impl Default for ReconnectBackoff {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn waits_double_up_to_the_cap() {
+        let backoff = ReconnectBackoff::new();
+        let delays: Vec<u64> = (0..8).map(|failures| backoff.delay_for(failures).as_secs()).collect();
+        assert_eq!(delays, vec![1, 2, 4, 8, 16, 32, 60, 60]);
+        assert_eq!(backoff.delay_for(u32::MAX), DEFAULT_MAX_DELAY);
+    }
+
+    #[test]
+    fn jitter_stays_in_bounds_and_a_stable_connection_starts_over() {
+        let mut backoff = ReconnectBackoff::new();
+        for failures in 0..10 {
+            let expected = backoff.delay_for(failures);
+            let delay = backoff.next_delay(Duration::ZERO);
+            assert!(delay >= expected.mul_f64(1.0 - DEFAULT_JITTER) && delay <= expected.mul_f64(1.0 + DEFAULT_JITTER));
+        }
+        assert_eq!(backoff.consecutive_failures(), 10);
+
+        // A connection that dropped early keeps counting up.
+        backoff.next_delay(DEFAULT_STABLE_AFTER - Duration::from_secs(1));
+        assert_eq!(backoff.consecutive_failures(), 11);
+
+        let delay = backoff.next_delay(DEFAULT_STABLE_AFTER);
+        assert_eq!(backoff.consecutive_failures(), 1);
+        assert!(delay <= DEFAULT_BASE_DELAY.mul_f64(1.0 + DEFAULT_JITTER));
+    }
+}
@@ -1,26 +1,96 @@
-use futures_util::{SinkExt, StreamExt};
+use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
-use tokio_tungstenite::{connect_async, tungstenite::Message};
+use std::collections::BTreeMap;
 use std::sync::Arc;
-use url::Url;
 
 use crate::engine::orderbook::OrderBook;
-use crate::engine::order::OrderSide;
+use crate::engine::price::Price;
+use crate::exchange::feed::{DepthSnapshot, ExchangeFeed, ExchangeWebSocket, FeedMessage};
+use crate::exchange::supervisor::FeedSupervisor;
 
+// Levels per side mirrored into the book.
+const DEPTH_LEVELS: usize = 20;
+// Levels per side fetched by a REST resync; diffs keep them current.
+const SNAPSHOT_LEVELS: usize = 1000;
+
+// REST depth snapshot, as `[price, quantity]` strings, best first.
 #[derive(Debug, Deserialize, Serialize)]
-struct BinanceTrade {
-    #[serde(rename = "e")]
-    event_type: String,
-    #[serde(rename = "E")]
-    event_time: u64,
-    #[serde(rename = "s")]
-    symbol: String,
-    #[serde(rename = "p")]
-    price: String,
-    #[serde(rename = "q")]
-    quantity: String,
-    #[serde(rename = "m")]
-    is_buyer_maker: bool,
+struct BinanceDepth {
+    #[serde(rename = "lastUpdateId")]
+    last_update_id: u64,
+    bids: Vec<[String; 2]>,
+    asks: Vec<[String; 2]>,
+}
+
+// One `@depth@100ms` diff: every level that changed between update ids `U`
+// and `u`, with a zero quantity for a level that emptied.
+#[derive(Debug, Deserialize, Serialize)]
+struct BinanceDepthUpdate {
+    #[serde(rename = "U")]
+    first_update_id: u64,
+    #[serde(rename = "u")]
+    final_update_id: u64,
+    #[serde(rename = "b")]
+    bids: Vec<[String; 2]>,
+    #[serde(rename = "a")]
+    asks: Vec<[String; 2]>,
+}
+
+// A level that doesn't parse is left out rather than failing the whole
+// message.
+fn parse_levels(levels: &[[String; 2]]) -> impl Iterator<Item = (f64, f64)> + '_ {
+    levels
+        .iter()
+        .filter_map(|[price, quantity]| Some((price.parse().ok()?, quantity.parse().ok()?)))
+}
+
+// Binance's full book rebuilt from a REST snapshot plus the diffs after it.
+#[derive(Debug)]
+struct LocalDepth {
+    bids: BTreeMap<Price, f64>,
+    asks: BTreeMap<Price, f64>,
+    // `u` of the last diff applied, or the snapshot's `lastUpdateId`.
+    last_update_id: u64,
+    needs_resync: bool,
+    // Diffs skipped by the gap that triggered the pending resync; `None`
+    // before the first snapshot.
+    missed_updates: Option<u64>,
+    // Built from the stream alone, with no snapshot: the next diff is taken
+    // whatever update id it starts at.
+    awaiting_first_diff: bool,
+}
+
+impl Default for LocalDepth {
+    fn default() -> Self {
+        Self {
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+            last_update_id: 0,
+            needs_resync: true,
+            missed_updates: None,
+            awaiting_first_diff: false,
+        }
+    }
+}
+
+impl LocalDepth {
+    fn apply(side: &mut BTreeMap<Price, f64>, levels: &[[String; 2]]) {
+        for (price, quantity) in parse_levels(levels) {
+            if quantity > 0.0 {
+                side.insert(Price::new(price), quantity);
+            } else {
+                side.remove(&Price::new(price));
+            }
+        }
+    }
+
+    fn top(&self) -> DepthSnapshot {
+        let level = |(price, quantity): (&Price, &f64)| (price.as_f64(), *quantity);
+        DepthSnapshot {
+            bids: self.bids.iter().rev().take(DEPTH_LEVELS).map(level).collect(),
+            asks: self.asks.iter().take(DEPTH_LEVELS).map(level).collect(),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -31,6 +101,14 @@ pub enum Coin {
 }
 
 impl Coin {
+    pub fn ticker(&self) -> &str {
+        match self {
+            Coin::BTC => "BTC",
+            Coin::ETH => "ETH",
+            Coin::SOL => "SOL",
+        }
+    }
+
     pub fn symbol(&self) -> &str {
         match self {
             Coin::BTC => "btcusdt",
@@ -39,6 +117,15 @@ impl Coin {
         }
     }
 
+    // Kraken still calls Bitcoin XBT.
+    pub fn kraken_ticker(&self) -> &str {
+        match self {
+            Coin::BTC => "XBT",
+            Coin::ETH => "ETH",
+            Coin::SOL => "SOL",
+        }
+    }
+
     pub fn display_name(&self) -> &str {
         match self {
             Coin::BTC => "Bitcoin",
@@ -48,136 +135,138 @@ impl Coin {
     }
 }
 
-pub struct BinanceWebSocket {
-    orderbook: Arc<OrderBook>,
-    coin: Coin,
-}
-
-impl BinanceWebSocket {
-    pub fn new(orderbook: Arc<OrderBook>, coin: Coin) -> Self {
-        Self { orderbook, coin }
-    }
-
-    
-    pub async fn connect(&self) -> Result<(), String> {
-        let symbol = self.coin.symbol();
-        let url = format!("wss://stream.binance.com:9443/ws/{}@trade", symbol);
-        
-        log::info!("🌐 Connecting to Binance WebSocket: {}", url);
-        
-        let url = Url::parse(&url).map_err(|e| e.to_string())?;
-        let (ws_stream, _) = connect_async(url).await.map_err(|e| e.to_string())?;
-        
-        log::info!("✅ Connected to Binance for {}", self.coin.display_name());
-        
-        let (mut _write, mut read) = ws_stream.split();
-        
-        while let Some(message) = read.next().await {
-            match message {
-                Ok(Message::Text(text)) => {
-                    if let Ok(trade) = serde_json::from_str::<BinanceTrade>(&text) {
-                        self.process_trade(trade).await;
-                    }
-                }
-                Ok(Message::Close(_)) => {
-                    log::warn!(" Binance WebSocket closed");
-                    break;
-                }
-                Err(e) => {
-                    log::error!("WebSocket error: {}", e);
-                    break;
-                }
-                _ => {}
-            }
+// Quote currency of a market. A feed without one trades each venue's usual
+// pair: USDT on Binance and Bybit, USD on Coinbase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quote {
+    USD,
+    USDT,
+    USDC,
+    EUR,
+}
+
+impl Quote {
+    pub fn code(&self) -> &str {
+        match self {
+            Quote::USD => "USD",
+            Quote::USDT => "USDT",
+            Quote::USDC => "USDC",
+            Quote::EUR => "EUR",
         }
-        
-        Ok(())
     }
 
-    async fn process_trade(&self, trade: BinanceTrade) {
-        let price: f64 = match trade.price.parse() {
-            Ok(p) => p,
-            Err(_) => return,
-        };
-        
-        let quantity: f64 = match trade.quantity.parse() {
-            Ok(q) => q,
-            Err(_) => return,
-        };
-        
-        
-        
-        let side = if trade.is_buyer_maker {
-            OrderSide::Ask 
-        } else {
-            OrderSide::Bid 
-        };
-        
-        
-        self.add_market_depth(price, quantity, side);
-        
-        log::debug!(
-            "📊 {} Trade: {} @ ${:.2} ({})",
-            self.coin.display_name(),
-            quantity,
-            price,
-            if trade.is_buyer_maker { "SELL" } else { "BUY" }
-        );
+    pub fn parse(code: &str) -> Option<Self> {
+        match code.trim().to_ascii_uppercase().as_str() {
+            "USD" => Some(Quote::USD),
+            "USDT" => Some(Quote::USDT),
+            "USDC" => Some(Quote::USDC),
+            "EUR" => Some(Quote::EUR),
+            _ => None,
+        }
     }
+}
 
-   
-    fn add_market_depth(&self, current_price: f64, quantity: f64, _side: OrderSide) {
-        let timestamp = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as u64;
-        
-        
-        for i in 1..=5 {
-            let bid_price = current_price - (i as f64 * 0.5);
-            let bid_qty = quantity * (1.0 + (i as f64 * 0.1));
-            
-            self.orderbook.add_order(
-                OrderSide::Bid,
-                bid_price,
-                bid_qty,
-                timestamp,
-                format!("binance_bid_{}", i),
-            );
+// Mirrors Binance's real top 20 levels. The book is kept from the diff
+// stream on top of a REST snapshot; each diff must pick up at the update id
+// the last one ended on, and a gap means diffs were lost, so the book is
+// rebuilt from a fresh snapshot instead of drifting.
+#[derive(Debug, Default)]
+pub struct Binance {
+    depth: Mutex<LocalDepth>,
+}
+
+pub type BinanceWebSocket = ExchangeWebSocket<Binance>;
+
+fn pair(coin: &Coin, quote: Option<Quote>) -> String {
+    match quote {
+        Some(quote) => format!("{}{}", coin.ticker(), quote.code()),
+        None => coin.symbol().to_ascii_uppercase(),
+    }
+}
+
+impl ExchangeFeed for Binance {
+    const NAME: &'static str = "Binance";
+
+    fn url(&self, coin: &Coin, quote: Option<Quote>) -> String {
+        format!("wss://stream.binance.com:9443/ws/{}@depth@100ms", pair(coin, quote).to_ascii_lowercase())
+    }
+
+    fn parse_message(&self, text: &str) -> Option<FeedMessage> {
+        let update: BinanceDepthUpdate = serde_json::from_str(text).ok()?;
+        let mut depth = self.depth.lock();
+        if depth.needs_resync || update.final_update_id <= depth.last_update_id {
+            return None;
         }
-        
-        
-        for i in 1..=5 {
-            let ask_price = current_price + (i as f64 * 0.5);
-            let ask_qty = quantity * (1.0 + (i as f64 * 0.1));
-            
-            self.orderbook.add_order(
-                OrderSide::Ask,
-                ask_price,
-                ask_qty,
-                timestamp,
-                format!("binance_ask_{}", i),
-            );
+
+        let expected = depth.last_update_id + 1;
+        if update.first_update_id > expected && !depth.awaiting_first_diff {
+            let missed = update.first_update_id - expected;
+            log::warn!(" Binance depth gap: expected update {}, got {} ({} missed)", expected, update.first_update_id, missed);
+            depth.needs_resync = true;
+            depth.missed_updates = Some(missed);
+            return None;
         }
+
+        LocalDepth::apply(&mut depth.bids, &update.bids);
+        LocalDepth::apply(&mut depth.asks, &update.asks);
+        depth.last_update_id = update.final_update_id;
+        depth.awaiting_first_diff = false;
+        Some(FeedMessage::Depth(depth.top()))
     }
 
-    
-    pub fn start(orderbook: Arc<OrderBook>, coin: Coin) {
-        tokio::spawn(async move {
-            let ws = BinanceWebSocket::new(orderbook, coin);
-            
-            loop {
-                if let Err(e) = ws.connect().await {
-                    log::error!("Binance connection error: {}", e);
-                    log::info!("🔄 Reconnecting in 5 seconds...");
-                    tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-                }
-            }
-        });
+    // Binance has no USD pairs.
+    fn lists_quote(quote: Quote) -> bool {
+        matches!(quote, Quote::USDT | Quote::USDC | Quote::EUR)
+    }
+
+    fn snapshot_url(&self, coin: &Coin, quote: Option<Quote>) -> Option<String> {
+        Some(format!(
+            "https://api.binance.com/api/v3/depth?symbol={}&limit={}",
+            pair(coin, quote),
+            SNAPSHOT_LEVELS
+        ))
     }
-}
 
+    fn needs_resync(&self) -> bool {
+        self.depth.lock().needs_resync
+    }
+
+    fn apply_snapshot(&self, snapshot: &str) -> Option<FeedMessage> {
+        let snapshot: BinanceDepth = serde_json::from_str(snapshot).ok()?;
+        let mut depth = self.depth.lock();
+        match depth.missed_updates.take() {
+            Some(missed) => log::info!(
+                "🔄 Resynced Binance depth at update {} after {} missed updates",
+                snapshot.last_update_id,
+                missed
+            ),
+            None => log::info!("📡 Binance depth snapshot at update {}", snapshot.last_update_id),
+        }
+
+        depth.bids.clear();
+        depth.asks.clear();
+        LocalDepth::apply(&mut depth.bids, &snapshot.bids);
+        LocalDepth::apply(&mut depth.asks, &snapshot.asks);
+        depth.last_update_id = snapshot.last_update_id;
+        depth.needs_resync = false;
+        depth.awaiting_first_diff = false;
+        Some(FeedMessage::Depth(depth.top()))
+    }
 
+    // Levels only appear once a diff touches them, so the mirrored book is
+    // thin until the stream has covered it; a later gap tries a snapshot
+    // again.
+    fn build_from_stream(&self) {
+        let mut depth = self.depth.lock();
+        depth.bids.clear();
+        depth.asks.clear();
+        depth.needs_resync = false;
+        depth.missed_updates = None;
+        depth.awaiting_first_diff = true;
+    }
+}
+
+#[derive(Default)]
 pub struct MultiCoinBinance {
     orderbooks: Vec<(Coin, Arc<OrderBook>)>,
 }
@@ -194,10 +283,62 @@ impl MultiCoinBinance {
     }
 
    
-    pub fn start_all(&self) {
+    pub fn start_all(&self, supervisor: &FeedSupervisor) {
         for (coin, orderbook) in &self.orderbooks {
             log::info!("Starting {} feed", coin.display_name());
-            BinanceWebSocket::start(orderbook.clone(), coin.clone());
+            supervisor.add(BinanceWebSocket::new(orderbook.clone(), coin.clone()));
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diff(first: u64, last: u64, bid: &str) -> String {
+        format!(r#"{{"U":{},"u":{},"b":[["{}","1.0"]],"a":[]}}"#, first, last, bid)
+    }
+
+    #[test]
+    fn a_gap_in_update_ids_waits_on_a_snapshot() {
+        let venue = Binance::default();
+        assert!(venue.needs_resync());
+        assert!(venue.parse_message(&diff(5, 6, "100")).is_none());
+
+        venue.apply_snapshot(r#"{"lastUpdateId":10,"bids":[["99","2.0"]],"asks":[["101","2.0"]]}"#).unwrap();
+        assert!(!venue.needs_resync());
+        assert!(venue.parse_message(&diff(8, 10, "98")).is_none());
+        let Some(FeedMessage::Depth(depth)) = venue.parse_message(&diff(9, 12, "100")) else {
+            panic!("a diff straddling the snapshot is applied");
+        };
+        assert_eq!(depth.bids, vec![(100.0, 1.0), (99.0, 2.0)]);
+
+        assert!(venue.parse_message(&diff(20, 21, "100.5")).is_none());
+        assert!(venue.needs_resync());
+        assert!(venue.parse_message(&diff(22, 23, "100.5")).is_none());
+    }
+
+    #[test]
+    fn a_gap_waits_on_a_snapshot_unless_built_from_the_stream() {
+        let venue = Binance::default();
+        assert!(venue.needs_resync());
+        assert!(venue.parse_message(&diff(5, 6, "100")).is_none());
+
+        venue.apply_snapshot(r#"{"lastUpdateId":10,"bids":[["99","2.0"]],"asks":[["101","2.0"]]}"#).unwrap();
+        assert!(venue.parse_message(&diff(11, 12, "100")).is_some());
+        assert!(venue.parse_message(&diff(20, 21, "100.5")).is_none());
+        assert!(venue.needs_resync());
+
+        venue.build_from_stream();
+        assert!(!venue.needs_resync());
+        let Some(FeedMessage::Depth(depth)) = venue.parse_message(&diff(40, 41, "98")) else {
+            panic!("the first diff after the fallback is applied");
+        };
+        assert_eq!(depth.bids, vec![(98.0, 1.0)]);
+        assert!(depth.asks.is_empty());
+
+        assert!(venue.parse_message(&diff(42, 42, "97")).is_some());
+        assert!(venue.parse_message(&diff(50, 51, "96")).is_none());
+        assert!(venue.needs_resync());
+    }
+}
@@ -0,0 +1,57 @@
+use std::collections::HashMap;
+use parking_lot::RwLock;
+
+// Shared anchor for the synthetic depth of one coin: every venue reports its
+// last trade price and the book is built around the median of those, so the
+// three feeds produce one coherent book instead of three overlapping ones.
+#[derive(Debug, Default)]
+pub struct ConsensusMid {
+    last_prices: RwLock<HashMap<String, f64>>,
+}
+
+impl ConsensusMid {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Records `venue`'s latest price and returns the updated consensus.
+    pub fn update(&self, venue: &str, price: f64) -> f64 {
+        let mut last_prices = self.last_prices.write();
+        last_prices.insert(venue.to_string(), price);
+        median(last_prices.values().copied().collect()).unwrap_or(price)
+    }
+
+    pub fn consensus(&self) -> Option<f64> {
+        median(self.last_prices.read().values().copied().collect())
+    }
+}
+
+pub fn median(mut values: Vec<f64>) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(|a, b| a.total_cmp(b));
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        Some((values[mid - 1] + values[mid]) / 2.0)
+    } else {
+        Some(values[mid])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn consensus_is_the_median_of_three_venues() {
+        let mid = ConsensusMid::new();
+        mid.update("binance", 100.0);
+        mid.update("coinbase", 130.0);
+        assert_eq!(mid.update("bybit", 90.0), 100.0);
+        assert_eq!(mid.consensus(), Some(100.0));
+
+        // A venue's new price replaces its old one rather than adding a fourth.
+        assert_eq!(mid.update("bybit", 120.0), 120.0);
+    }
+}
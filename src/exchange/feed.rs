@@ -0,0 +1,630 @@
+// What the exchange clients have in common: connect, subscribe and read
+// messages into the book until the connection drops; `FeedSupervisor`
+// reconnects them. A venue only says where to connect and how to read its
+// messages (`ExchangeFeed`); `ExchangeWebSocket` does the rest.
+use futures_util::{SinkExt, StreamExt};
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use url::Url;
+
+use crate::engine::orderbook::OrderBook;
+use crate::engine::order::{OrderOptions, OrderSide};
+use crate::exchange::binance::{Coin, Quote};
+use crate::exchange::consensus::ConsensusMid;
+use crate::exchange::price_filter::PriceGapFilter;
+use crate::exchange::supervisor::{FeedHealth, FeedRun, SupervisedFeed};
+use crate::exchange::throttle::{FeedThrottle, DEFAULT_MAX_OPS_PER_SEC};
+
+// How long the read loop waits before flushing a held-back throttled update.
+const THROTTLE_FLUSH_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+
+// REST snapshot tries per resync before depth is built from the stream
+// alone, and the wait after the first failed one (doubling each time).
+pub const DEFAULT_RESYNC_ATTEMPTS: u32 = 3;
+pub const DEFAULT_RESYNC_BASE_DELAY: Duration = Duration::from_millis(500);
+
+pub type SnapshotFetch<'a> = Pin<Box<dyn Future<Output = Result<String, String>> + Send + 'a>>;
+
+// Fetches the body of a venue's REST depth snapshot.
+pub trait SnapshotFetcher: Send + Sync + 'static {
+    fn fetch<'a>(&'a self, url: &'a str) -> SnapshotFetch<'a>;
+}
+
+#[derive(Debug, Default)]
+pub struct HttpSnapshotFetcher;
+
+impl SnapshotFetcher for HttpSnapshotFetcher {
+    fn fetch<'a>(&'a self, url: &'a str) -> SnapshotFetch<'a> {
+        Box::pin(async move {
+            reqwest::get(url)
+                .await
+                .and_then(|response| response.error_for_status())
+                .map_err(|e| e.to_string())?
+                .text()
+                .await
+                .map_err(|e| e.to_string())
+        })
+    }
+}
+
+// One print from a venue, in engine terms.
+#[derive(Debug, Clone, Copy)]
+pub struct NormalizedTrade {
+    pub price: f64,
+    pub quantity: f64,
+    pub side: OrderSide,
+}
+
+// The venue's real top of book as `(price, quantity)` levels, best first,
+// replacing whatever it mirrored before.
+#[derive(Debug, Clone)]
+pub struct DepthSnapshot {
+    pub bids: Vec<(f64, f64)>,
+    pub asks: Vec<(f64, f64)>,
+}
+
+#[derive(Debug, Clone)]
+pub enum FeedMessage {
+    // Prints to build synthetic depth around (one message may carry several).
+    Trades(Vec<NormalizedTrade>),
+    Depth(DepthSnapshot),
+}
+
+// Synthetic depth a trade-driven venue lays around its last price: `levels`
+// per side, `spacing` apart, the i-th sized `quantity * (base + i * step)`.
+#[derive(Debug, Clone, Copy)]
+pub struct DepthLadder {
+    pub levels: usize,
+    pub spacing: f64,
+    pub base: f64,
+    pub step: f64,
+}
+
+pub trait ExchangeFeed: Default + Send + Sync + 'static {
+    // Shown in logs; lowercased, it is the `source` tag on the venue's orders
+    // and its key in the consensus mid.
+    const NAME: &'static str;
+
+    fn url(&self, coin: &Coin, quote: Option<Quote>) -> String;
+
+    // Sent right after connecting, for venues that subscribe in-band.
+    fn subscribe_message(&self, _coin: &Coin, _quote: Option<Quote>) -> Option<String> {
+        None
+    }
+
+    // `None` for anything that isn't market data (acks, pings, ...).
+    fn parse_message(&self, text: &str) -> Option<FeedMessage>;
+
+    // How to build depth from `FeedMessage::Trades`; `None` for venues that
+    // only send depth.
+    fn ladder(&self) -> Option<DepthLadder> {
+        None
+    }
+
+    fn lists_quote(quote: Quote) -> bool;
+
+    // REST depth snapshot to build from, for venues that stream depth diffs
+    // and so need a starting point.
+    fn snapshot_url(&self, _coin: &Coin, _quote: Option<Quote>) -> Option<String> {
+        None
+    }
+
+    // Set when the diffs can no longer be applied (a sequence gap, or no
+    // snapshot yet); the client then fetches `snapshot_url`.
+    fn needs_resync(&self) -> bool {
+        false
+    }
+
+    // Rebuilds from a `snapshot_url` response; `None` if it doesn't parse.
+    fn apply_snapshot(&self, _snapshot: &str) -> Option<FeedMessage> {
+        None
+    }
+
+    // Gives up on the snapshot after every try failed: builds depth from the
+    // diffs alone, starting with the next one, and clears `needs_resync`.
+    // A venue that leaves it set has its connection dropped instead.
+    fn build_from_stream(&self) {}
+}
+
+// An update that made it past the price filter, as held by the throttle.
+#[derive(Debug, Clone)]
+enum FeedUpdate {
+    Ladder { anchor: f64, quantity: f64 },
+    Depth(DepthSnapshot),
+}
+
+pub struct ExchangeWebSocket<V: ExchangeFeed> {
+    venue: V,
+    orderbook: Arc<OrderBook>,
+    coin: Coin,
+    quote: Option<Quote>,
+    consensus: Option<Arc<ConsensusMid>>,
+    price_filter: Option<PriceGapFilter>,
+    throttle: FeedThrottle<FeedUpdate>,
+    fetcher: Arc<dyn SnapshotFetcher>,
+    resync_attempts: u32,
+    resync_base_delay: Duration,
+}
+
+impl<V: ExchangeFeed> ExchangeWebSocket<V> {
+    pub fn new(orderbook: Arc<OrderBook>, coin: Coin) -> Self {
+        Self {
+            venue: V::default(),
+            orderbook,
+            coin,
+            quote: None,
+            consensus: None,
+            price_filter: None,
+            throttle: FeedThrottle::new(V::NAME, DEFAULT_MAX_OPS_PER_SEC),
+            fetcher: Arc::new(HttpSnapshotFetcher),
+            resync_attempts: DEFAULT_RESYNC_ATTEMPTS,
+            resync_base_delay: DEFAULT_RESYNC_BASE_DELAY,
+        }
+    }
+
+    pub fn lists_quote(quote: Quote) -> bool {
+        V::lists_quote(quote)
+    }
+
+    // Trade the coin's pair in `quote`, which the venue must list.
+    pub fn with_quote(mut self, quote: Quote) -> Self {
+        self.quote = Some(quote);
+        self
+    }
+
+    pub fn with_max_ops_per_sec(mut self, max_ops_per_sec: usize) -> Self {
+        self.throttle = FeedThrottle::new(V::NAME, max_ops_per_sec);
+        self
+    }
+
+    // Report this venue's price to the cross-venue consensus mid. Synthetic
+    // depth is anchored on the consensus instead of the venue's own price;
+    // mirrored real depth stays where the venue has it.
+    pub fn with_consensus(mut self, consensus: Arc<ConsensusMid>) -> Self {
+        self.consensus = Some(consensus);
+        self
+    }
+
+    // Ignore prices (trades, or a snapshot's mid) more than `max_gap_pct`
+    // percent from the last accepted one.
+    pub fn with_max_price_gap_pct(mut self, max_gap_pct: f64) -> Self {
+        self.price_filter = Some(PriceGapFilter::new(V::NAME, max_gap_pct));
+        self
+    }
+
+    pub fn with_snapshot_fetcher(mut self, fetcher: impl SnapshotFetcher) -> Self {
+        self.fetcher = Arc::new(fetcher);
+        self
+    }
+
+    // Try a resync's snapshot `attempts` times (at least once), waiting
+    // `base_delay` after the first failure and twice as long after each
+    // one since.
+    pub fn with_resync_retries(mut self, attempts: u32, base_delay: Duration) -> Self {
+        self.resync_attempts = attempts.max(1);
+        self.resync_base_delay = base_delay;
+        self
+    }
+
+    fn source(&self) -> String {
+        V::NAME.to_ascii_lowercase()
+    }
+
+    pub async fn connect(&self, health: &FeedHealth) -> Result<(), String> {
+        let url = self.venue.url(&self.coin, self.quote);
+
+        log::info!("🌐 Connecting to {} WebSocket: {}", V::NAME, url);
+
+        let url = Url::parse(&url).map_err(|e| e.to_string())?;
+        let (ws_stream, _) = connect_async(url).await.map_err(|e| e.to_string())?;
+
+        log::info!("✅ Connected to {} for {}", V::NAME, self.coin.display_name());
+        health.connected();
+
+        let (mut write, mut read) = ws_stream.split();
+
+        if let Some(subscribe_msg) = self.venue.subscribe_message(&self.coin, self.quote) {
+            write.send(Message::Text(subscribe_msg)).await.map_err(|e| e.to_string())?;
+            log::info!("📡 Subscribed to {} {} feed", V::NAME, self.coin.display_name());
+        }
+
+        loop {
+            // Messages queue on the socket meanwhile; the venue drops those
+            // the snapshot already covers.
+            if self.venue.needs_resync() {
+                self.resync().await?;
+            }
+
+            let message = match tokio::time::timeout(THROTTLE_FLUSH_INTERVAL, read.next()).await {
+                Ok(Some(message)) => message,
+                Ok(None) => break,
+                Err(_) => {
+                    if let Some(update) = self.throttle.take_due(now_ms()) {
+                        self.apply(update);
+                    }
+                    continue;
+                }
+            };
+
+            match message {
+                Ok(Message::Text(text)) => self.process_text(&text),
+                Ok(Message::Close(_)) => {
+                    log::warn!("{} WebSocket closed", V::NAME);
+                    break;
+                }
+                Err(e) => {
+                    log::error!(" {} WebSocket error: {}", V::NAME, e);
+                    break;
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    // Rebuilds the venue's depth from its REST snapshot, retrying with a
+    // doubling wait. Once every try has failed the venue builds from the
+    // stream alone; an error, which drops the connection, is left for
+    // venues that can't.
+    async fn resync(&self) -> Result<(), String> {
+        let Some(url) = self.venue.snapshot_url(&self.coin, self.quote) else {
+            return Ok(());
+        };
+        for attempt in 1..=self.resync_attempts {
+            let applied = match self.fetcher.fetch(&url).await {
+                Ok(body) => self.venue.apply_snapshot(&body).ok_or_else(|| "unreadable snapshot".to_string()),
+                Err(e) => Err(e),
+            };
+            match applied {
+                Ok(FeedMessage::Depth(snapshot)) => {
+                    self.process_depth(snapshot);
+                    return Ok(());
+                }
+                Ok(FeedMessage::Trades(_)) => return Ok(()),
+                Err(e) if attempt < self.resync_attempts => {
+                    let delay = self.resync_base_delay.saturating_mul(2u32.saturating_pow(attempt - 1));
+                    log::warn!(
+                        " {} depth snapshot failed (attempt {}/{}): {}; retrying in {:.1}s",
+                        V::NAME,
+                        attempt,
+                        self.resync_attempts,
+                        e,
+                        delay.as_secs_f64()
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                Err(e) => log::error!(
+                    " {} depth snapshot failed (attempt {}/{}): {}",
+                    V::NAME,
+                    attempt,
+                    self.resync_attempts,
+                    e
+                ),
+            }
+        }
+
+        log::warn!(" Building {} {} depth from the stream alone", V::NAME, self.coin.display_name());
+        self.venue.build_from_stream();
+        if self.venue.needs_resync() {
+            return Err(format!("no {} depth snapshot after {} attempts", V::NAME, self.resync_attempts));
+        }
+        Ok(())
+    }
+
+    fn process_text(&self, text: &str) {
+        match self.venue.parse_message(text) {
+            Some(FeedMessage::Trades(trades)) => {
+                for trade in trades {
+                    self.process_trade(trade);
+                }
+            }
+            Some(FeedMessage::Depth(snapshot)) => self.process_depth(snapshot),
+            None => {}
+        }
+    }
+
+    fn process_trade(&self, trade: NormalizedTrade) {
+        let Some(ladder) = self.venue.ladder() else {
+            return;
+        };
+        if let Some(filter) = &self.price_filter {
+            if !filter.accept(trade.price) {
+                return;
+            }
+        }
+
+        let anchor = match &self.consensus {
+            Some(consensus) => consensus.update(&self.source(), trade.price),
+            None => trade.price,
+        };
+
+        let update = FeedUpdate::Ladder { anchor, quantity: trade.quantity };
+        let ops = 2 * ladder.levels;
+        if let Some(update) = self.throttle.submit(update, ops, now_ms()) {
+            self.apply(update);
+        }
+
+        log::debug!(
+            "📊 [{}] {} Trade: {:.4} @ ${:.2} ({:?})",
+            V::NAME,
+            self.coin.display_name(),
+            trade.quantity,
+            trade.price,
+            trade.side
+        );
+    }
+
+    fn process_depth(&self, snapshot: DepthSnapshot) {
+        let (Some(&(best_bid, _)), Some(&(best_ask, _))) = (snapshot.bids.first(), snapshot.asks.first()) else {
+            return;
+        };
+        let mid = (best_bid + best_ask) / 2.0;
+
+        if let Some(filter) = &self.price_filter {
+            if !filter.accept(mid) {
+                return;
+            }
+        }
+
+        if let Some(consensus) = &self.consensus {
+            consensus.update(&self.source(), mid);
+        }
+
+        log::debug!(
+            "📊 [{}] {} depth: {} bids, {} asks, mid ${:.2}",
+            V::NAME,
+            self.coin.display_name(),
+            snapshot.bids.len(),
+            snapshot.asks.len(),
+            mid
+        );
+
+        // Each level is at most one engine operation.
+        let ops = snapshot.bids.len() + snapshot.asks.len();
+        if let Some(update) = self.throttle.submit(FeedUpdate::Depth(snapshot), ops, now_ms()) {
+            self.apply(update);
+        }
+    }
+
+    fn apply(&self, update: FeedUpdate) {
+        match update {
+            FeedUpdate::Ladder { anchor, quantity } => self.add_market_depth(anchor, quantity),
+            FeedUpdate::Depth(snapshot) => {
+                self.orderbook.sync_depth(&self.source(), &snapshot.bids, &snapshot.asks, now_ms());
+            }
+        }
+    }
+
+    fn add_market_depth(&self, current_price: f64, quantity: f64) {
+        let Some(ladder) = self.venue.ladder() else {
+            return;
+        };
+        let source = self.source();
+        let timestamp = now_ms();
+
+        for side in [OrderSide::Bid, OrderSide::Ask] {
+            for i in 1..=ladder.levels {
+                let offset = i as f64 * ladder.spacing;
+                let price = match side {
+                    OrderSide::Bid => current_price - offset,
+                    OrderSide::Ask => current_price + offset,
+                };
+                let side_name = if side == OrderSide::Bid { "bid" } else { "ask" };
+
+                let _ = self.orderbook.add_order_with_options(
+                    side,
+                    price,
+                    quantity * (ladder.base + i as f64 * ladder.step),
+                    timestamp,
+                    format!("{}_{}_{}", source, side_name, i),
+                    OrderOptions::default().with_source(source.clone()),
+                );
+            }
+        }
+    }
+}
+
+impl<V: ExchangeFeed> SupervisedFeed for ExchangeWebSocket<V> {
+    // `Binance BTC-USDT`, or `Binance BTC` in the venue's usual quote.
+    fn name(&self) -> String {
+        match self.quote {
+            Some(quote) => format!("{} {}-{}", V::NAME, self.coin.ticker(), quote.code()),
+            None => format!("{} {}", V::NAME, self.coin.ticker()),
+        }
+    }
+
+    fn run<'a>(&'a self, health: &'a FeedHealth) -> FeedRun<'a> {
+        Box::pin(self.connect(health))
+    }
+}
+
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    // Reads `trade <price> <quantity>` and `depth <bid> <ask>` lines.
+    #[derive(Default)]
+    struct MockVenue;
+
+    impl ExchangeFeed for MockVenue {
+        const NAME: &'static str = "Mock";
+
+        fn url(&self, _coin: &Coin, _quote: Option<Quote>) -> String {
+            "wss://mock.invalid/ws".to_string()
+        }
+
+        fn parse_message(&self, text: &str) -> Option<FeedMessage> {
+            let fields: Vec<&str> = text.split_whitespace().collect();
+            let number = |i: usize| fields.get(i)?.parse::<f64>().ok();
+            match *fields.first()? {
+                "trade" => Some(FeedMessage::Trades(vec![NormalizedTrade {
+                    price: number(1)?,
+                    quantity: number(2)?,
+                    side: OrderSide::Bid,
+                }])),
+                "depth" => Some(FeedMessage::Depth(DepthSnapshot {
+                    bids: vec![(number(1)?, 1.0)],
+                    asks: vec![(number(2)?, 1.0)],
+                })),
+                _ => None,
+            }
+        }
+
+        fn ladder(&self) -> Option<DepthLadder> {
+            Some(DepthLadder { levels: 2, spacing: 1.0, base: 1.0, step: 0.0 })
+        }
+
+        fn lists_quote(_quote: Quote) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn a_venue_only_parses_and_the_client_fills_the_book() {
+        let book = Arc::new(OrderBook::new());
+        let feed = ExchangeWebSocket::<MockVenue>::new(book.clone(), Coin::BTC);
+        assert_eq!(feed.name(), "Mock BTC");
+
+        feed.process_text("ack");
+        assert_eq!(book.get_best_bid(), None);
+
+        feed.process_text("trade 100 0.5");
+        assert_eq!(book.get_market_depth(5), (vec![(99.0, 0.5), (98.0, 0.5)], vec![(101.0, 0.5), (102.0, 0.5)]));
+        assert!(book.get_user_orders("mock_bid_1").iter().all(|order| order.source.as_deref() == Some("mock")));
+
+        let book = Arc::new(OrderBook::new());
+        let feed = ExchangeWebSocket::<MockVenue>::new(book.clone(), Coin::BTC).with_quote(Quote::EUR);
+        assert_eq!(feed.name(), "Mock BTC-EUR");
+        feed.process_text("depth 99.5 100.5");
+        assert_eq!(book.get_market_depth(5), (vec![(99.5, 1.0)], vec![(100.5, 1.0)]));
+    }
+
+    // A venue whose depth waits on a snapshot until it's applied or given up on.
+    #[derive(Default)]
+    struct GappedVenue {
+        needs_resync: AtomicBool,
+        from_stream: AtomicBool,
+        no_fallback: AtomicBool,
+    }
+
+    impl ExchangeFeed for GappedVenue {
+        const NAME: &'static str = "Gapped";
+
+        fn url(&self, _coin: &Coin, _quote: Option<Quote>) -> String {
+            "wss://gapped.invalid/ws".to_string()
+        }
+
+        fn parse_message(&self, _text: &str) -> Option<FeedMessage> {
+            None
+        }
+
+        fn lists_quote(_quote: Quote) -> bool {
+            true
+        }
+
+        fn snapshot_url(&self, _coin: &Coin, _quote: Option<Quote>) -> Option<String> {
+            Some("https://gapped.invalid/depth".to_string())
+        }
+
+        fn needs_resync(&self) -> bool {
+            self.needs_resync.load(Ordering::SeqCst)
+        }
+
+        fn apply_snapshot(&self, snapshot: &str) -> Option<FeedMessage> {
+            let (bid, ask) = snapshot.split_once(' ')?;
+            self.needs_resync.store(false, Ordering::SeqCst);
+            Some(FeedMessage::Depth(DepthSnapshot {
+                bids: vec![(bid.parse().ok()?, 1.0)],
+                asks: vec![(ask.parse().ok()?, 1.0)],
+            }))
+        }
+
+        fn build_from_stream(&self) {
+            if self.no_fallback.load(Ordering::SeqCst) {
+                return;
+            }
+            self.from_stream.store(true, Ordering::SeqCst);
+            self.needs_resync.store(false, Ordering::SeqCst);
+        }
+    }
+
+    // Hands out the queued responses in order, then fails.
+    #[derive(Default)]
+    struct MockFetcher {
+        responses: parking_lot::Mutex<std::collections::VecDeque<Result<String, String>>>,
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl SnapshotFetcher for MockFetcher {
+        fn fetch<'a>(&'a self, _url: &'a str) -> SnapshotFetch<'a> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let response = self.responses.lock().pop_front().unwrap_or_else(|| Err("unreachable".to_string()));
+            Box::pin(async move { response })
+        }
+    }
+
+    fn gapped_feed(responses: Vec<Result<String, String>>) -> (ExchangeWebSocket<GappedVenue>, Arc<OrderBook>, Arc<AtomicUsize>) {
+        let book = Arc::new(OrderBook::new());
+        let calls = Arc::new(AtomicUsize::new(0));
+        let fetcher = MockFetcher { responses: parking_lot::Mutex::new(responses.into()), calls: calls.clone() };
+        let feed = ExchangeWebSocket::<GappedVenue>::new(book.clone(), Coin::BTC)
+            .with_snapshot_fetcher(fetcher)
+            .with_resync_retries(3, Duration::from_millis(1));
+        feed.venue.needs_resync.store(true, Ordering::SeqCst);
+        (feed, book, calls)
+    }
+
+    #[tokio::test]
+    async fn a_resync_rebuilds_the_book_from_the_fetched_snapshot() {
+        let (feed, book, calls) = gapped_feed(vec![Ok("99 101".to_string())]);
+
+        assert_eq!(feed.resync().await, Ok(()));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(!feed.venue.needs_resync());
+        assert_eq!(book.get_market_depth(5), (vec![(99.0, 1.0)], vec![(101.0, 1.0)]));
+    }
+
+    #[tokio::test]
+    async fn a_resync_retries_failed_snapshots() {
+        let (feed, book, calls) =
+            gapped_feed(vec![Err("503".to_string()), Ok("garbage".to_string()), Ok("99 101".to_string())]);
+
+        assert_eq!(feed.resync().await, Ok(()));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert!(!feed.venue.from_stream.load(Ordering::SeqCst));
+        assert_eq!(book.get_market_depth(5), (vec![(99.0, 1.0)], vec![(101.0, 1.0)]));
+    }
+
+    #[tokio::test]
+    async fn a_resync_falls_back_to_the_stream_once_every_try_fails() {
+        let (feed, book, calls) = gapped_feed(Vec::new());
+
+        assert_eq!(feed.resync().await, Ok(()));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert!(feed.venue.from_stream.load(Ordering::SeqCst));
+        assert!(!feed.venue.needs_resync());
+        assert_eq!(book.get_best_bid(), None);
+    }
+
+    #[tokio::test]
+    async fn a_venue_without_a_fallback_drops_the_connection() {
+        let (feed, _book, calls) = gapped_feed(Vec::new());
+        feed.venue.no_fallback.store(true, Ordering::SeqCst);
+
+        assert!(feed.resync().await.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert!(feed.venue.needs_resync());
+    }
+}
@@ -0,0 +1,97 @@
+use serde_json::{json, Value};
+
+use crate::engine::order::OrderSide;
+use crate::exchange::binance::{Coin, Quote};
+use crate::exchange::feed::{DepthLadder, ExchangeFeed, ExchangeWebSocket, FeedMessage, NormalizedTrade};
+
+// Builds depth around the prints on Kraken's `trade` channel.
+#[derive(Debug, Default)]
+pub struct Kraken;
+
+pub type KrakenWebSocket = ExchangeWebSocket<Kraken>;
+
+fn pair(coin: &Coin, quote: Option<Quote>) -> String {
+    let quote = quote.unwrap_or(Quote::USD);
+    format!("{}/{}", coin.kraken_ticker(), quote.code())
+}
+
+impl ExchangeFeed for Kraken {
+    const NAME: &'static str = "Kraken";
+
+    fn url(&self, _coin: &Coin, _quote: Option<Quote>) -> String {
+        "wss://ws.kraken.com".to_string()
+    }
+
+    fn subscribe_message(&self, coin: &Coin, quote: Option<Quote>) -> Option<String> {
+        let subscribe_msg = json!({
+            "event": "subscribe",
+            "pair": [pair(coin, quote)],
+            "subscription": {"name": "trade"}
+        });
+        Some(subscribe_msg.to_string())
+    }
+
+    // Trades arrive array-framed as `[channel_id, [[price, volume, time,
+    // side, order_type, misc], ...], "trade", pair]`, with `side` `b` or `s`.
+    // Events (heartbeats, subscription acks) are objects and are skipped, as
+    // are prints that don't parse.
+    fn parse_message(&self, text: &str) -> Option<FeedMessage> {
+        let frame: Vec<Value> = serde_json::from_str(text).ok()?;
+        if frame.get(2).and_then(Value::as_str) != Some("trade") {
+            return None;
+        }
+        let trades = frame
+            .get(1)?
+            .as_array()?
+            .iter()
+            .filter_map(|trade| {
+                let side = match trade.get(3)?.as_str()? {
+                    "b" => OrderSide::Bid,
+                    "s" => OrderSide::Ask,
+                    _ => return None,
+                };
+                Some(NormalizedTrade {
+                    price: trade.get(0)?.as_str()?.parse().ok()?,
+                    quantity: trade.get(1)?.as_str()?.parse().ok()?,
+                    side,
+                })
+            })
+            .collect();
+        Some(FeedMessage::Trades(trades))
+    }
+
+    fn ladder(&self) -> Option<DepthLadder> {
+        Some(DepthLadder { levels: 3, spacing: 0.7, base: 0.85, step: 0.1 })
+    }
+
+    fn lists_quote(quote: Quote) -> bool {
+        matches!(quote, Quote::USD | Quote::USDT | Quote::USDC | Quote::EUR)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_array_framed_trades_and_skips_events() {
+        let frame = r#"[337,[["43210.5","0.25","1700000000.1","b","m",""],["43209.0","1.5","1700000000.2","s","l",""],["bad","1","0","b","m",""]],"trade","XBT/USD"]"#;
+        let Some(FeedMessage::Trades(trades)) = Kraken.parse_message(frame) else {
+            panic!("not read as trades");
+        };
+        let read: Vec<(f64, f64, OrderSide)> = trades.iter().map(|t| (t.price, t.quantity, t.side)).collect();
+        assert_eq!(read, vec![(43210.5, 0.25, OrderSide::Bid), (43209.0, 1.5, OrderSide::Ask)]);
+
+        assert!(Kraken.parse_message(r#"{"event":"heartbeat"}"#).is_none());
+        assert!(Kraken.parse_message(r#"[42,{"a":[]},"book-10","XBT/USD"]"#).is_none());
+    }
+
+    #[test]
+    fn subscribes_to_the_kraken_pair() {
+        let message: Value = serde_json::from_str(&Kraken.subscribe_message(&Coin::BTC, None).unwrap()).unwrap();
+        assert_eq!(message["pair"], json!(["XBT/USD"]));
+        assert_eq!(message["subscription"]["name"], "trade");
+        let message: Value = serde_json::from_str(&Kraken.subscribe_message(&Coin::SOL, Some(Quote::EUR)).unwrap()).unwrap();
+        assert_eq!(message["pair"], json!(["SOL/EUR"]));
+    }
+}
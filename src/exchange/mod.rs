@@ -1,8 +1,23 @@
 
+pub mod backoff;
 pub mod binance;
 pub mod coinbase;
 pub mod bybit;
+pub mod consensus;
+pub mod feed;
+pub mod kraken;
+pub mod price_filter;
+pub mod supervisor;
+pub mod synthetic;
+pub mod throttle;
 
-pub use binance::{BinanceWebSocket, Coin, MultiCoinBinance};
-pub use coinbase::CoinbaseWebSocket;
-pub use bybit::BybitWebSocket;
\ No newline at end of file
+pub use binance::{Binance, BinanceWebSocket, Coin, MultiCoinBinance, Quote};
+pub use coinbase::{Coinbase, CoinbaseWebSocket};
+pub use bybit::{Bybit, BybitWebSocket};
+pub use consensus::ConsensusMid;
+pub use feed::{ExchangeFeed, ExchangeWebSocket};
+pub use kraken::{Kraken, KrakenWebSocket};
+pub use price_filter::PriceGapFilter;
+pub use supervisor::FeedSupervisor;
+pub use synthetic::SyntheticFeed;
+pub use throttle::FeedThrottle;
\ No newline at end of file
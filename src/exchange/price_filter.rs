@@ -0,0 +1,92 @@
+use parking_lot::Mutex;
+
+// Ticks in a row that may be rejected before the filter concludes the market
+// really moved and re-anchors on the new price.
+const MAX_CONSECUTIVE_REJECTS: usize = 5;
+
+#[derive(Debug, Default)]
+struct FilterState {
+    last_price: Option<f64>,
+    consecutive_rejects: usize,
+}
+
+// Drops trade prints that jump more than `max_gap_pct` percent away from the
+// feed's last accepted price, so one bad tick can't drag the synthetic depth
+// with it. A sustained move is let through after a few rejected ticks.
+#[derive(Debug)]
+pub struct PriceGapFilter {
+    name: String,
+    max_gap_pct: f64,
+    state: Mutex<FilterState>,
+}
+
+impl PriceGapFilter {
+    pub fn new(name: impl Into<String>, max_gap_pct: f64) -> Self {
+        Self {
+            name: name.into(),
+            max_gap_pct,
+            state: Mutex::new(FilterState::default()),
+        }
+    }
+
+    pub fn max_gap_pct(&self) -> f64 {
+        self.max_gap_pct
+    }
+
+    // Whether `price` is plausible given the last accepted price. The first
+    // price is always accepted.
+    pub fn accept(&self, price: f64) -> bool {
+        let mut state = self.state.lock();
+        let last_price = match state.last_price {
+            Some(last_price) => last_price,
+            None => {
+                state.last_price = Some(price);
+                return true;
+            }
+        };
+
+        let gap_pct = (price - last_price).abs() / last_price * 100.0;
+        if gap_pct <= self.max_gap_pct || state.consecutive_rejects >= MAX_CONSECUTIVE_REJECTS {
+            state.last_price = Some(price);
+            state.consecutive_rejects = 0;
+            return true;
+        }
+
+        state.consecutive_rejects += 1;
+        log::warn!(
+            "🚫 {} rejected tick @ {:.2}: {:.1}% from last price {:.2}",
+            self.name,
+            price,
+            gap_pct,
+            last_price
+        );
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outlier_tick_is_filtered_while_normal_ticks_pass() {
+        let filter = PriceGapFilter::new("test", 10.0);
+        assert!(filter.accept(100.0));
+        assert!(filter.accept(105.0));
+        assert!(!filter.accept(150.0));
+        // The outlier didn't move the anchor.
+        assert!(filter.accept(96.0));
+        assert!(!filter.accept(50.0));
+    }
+
+    #[test]
+    fn sustained_move_is_accepted_after_repeated_rejects() {
+        let filter = PriceGapFilter::new("test", 10.0);
+        assert!(filter.accept(100.0));
+        for _ in 0..MAX_CONSECUTIVE_REJECTS {
+            assert!(!filter.accept(200.0));
+        }
+        assert!(filter.accept(200.0));
+        assert!(filter.accept(205.0));
+    }
+}
@@ -0,0 +1,302 @@
+// Owns every exchange feed task. Each feed reconnects with its own backoff,
+// but connection attempts across all feeds share a few slots, so when a
+// network blip drops every feed at once they come back a few at a time
+// instead of hitting the venues together. Each feed's state is kept here for
+// `/health`.
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::exchange::backoff::ReconnectBackoff;
+
+pub const DEFAULT_MAX_CONCURRENT_CONNECTS: usize = 2;
+// An attempt still not connected after this long is abandoned, so a hung
+// handshake can't keep its connect slot.
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+
+pub type FeedRun<'a> = Pin<Box<dyn Future<Output = Result<(), String>> + Send + 'a>>;
+
+// A feed the supervisor can start and restart. `run` connects and reads
+// until the connection ends, calling `health.connected()` once it is up.
+pub trait SupervisedFeed: Send + Sync + 'static {
+    fn name(&self) -> String;
+
+    fn run<'a>(&'a self, health: &'a FeedHealth) -> FeedRun<'a>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedState {
+    // Queued for a connect slot.
+    Waiting,
+    Connecting,
+    Connected,
+    // Disconnected; retries at `retry_at_ms`.
+    Backoff,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeedStatus {
+    pub name: String,
+    pub state: FeedState,
+    // Times the feed has been restarted after its connection ended.
+    pub restarts: u64,
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+    pub connected_since_ms: Option<u64>,
+    pub retry_at_ms: Option<u64>,
+}
+
+pub struct FeedHealth {
+    status: Mutex<FeedStatus>,
+    // The connect slot, held from the start of an attempt until it connects
+    // or fails.
+    slot: Mutex<Option<OwnedSemaphorePermit>>,
+}
+
+impl FeedHealth {
+    fn new(name: String) -> Self {
+        Self {
+            status: Mutex::new(FeedStatus {
+                name,
+                state: FeedState::Waiting,
+                restarts: 0,
+                consecutive_failures: 0,
+                last_error: None,
+                connected_since_ms: None,
+                retry_at_ms: None,
+            }),
+            slot: Mutex::new(None),
+        }
+    }
+
+    // Frees the connect slot for the next feed waiting.
+    pub fn connected(&self) {
+        self.slot.lock().take();
+        let mut status = self.status.lock();
+        status.state = FeedState::Connected;
+        status.connected_since_ms = Some(now_ms());
+    }
+
+    pub fn status(&self) -> FeedStatus {
+        self.status.lock().clone()
+    }
+
+    fn set_state(&self, state: FeedState) {
+        self.status.lock().state = state;
+    }
+
+    fn attempting(&self, slot: OwnedSemaphorePermit) {
+        *self.slot.lock() = Some(slot);
+        let mut status = self.status.lock();
+        status.state = FeedState::Connecting;
+        status.retry_at_ms = None;
+    }
+
+    fn ended(&self, error: Option<String>, backoff: &ReconnectBackoff, retry_at_ms: u64) {
+        self.slot.lock().take();
+        let mut status = self.status.lock();
+        status.state = FeedState::Backoff;
+        status.restarts += 1;
+        status.consecutive_failures = backoff.consecutive_failures();
+        if error.is_some() {
+            status.last_error = error;
+        }
+        status.connected_since_ms = None;
+        status.retry_at_ms = Some(retry_at_ms);
+    }
+}
+
+pub struct FeedSupervisor {
+    connect_slots: Arc<Semaphore>,
+    connect_timeout: Duration,
+    feeds: RwLock<Vec<Arc<FeedHealth>>>,
+}
+
+impl FeedSupervisor {
+    pub fn new() -> Self {
+        Self::with_max_concurrent_connects(DEFAULT_MAX_CONCURRENT_CONNECTS)
+    }
+
+    // At most `max_concurrent_connects` feeds (at least one) connecting at
+    // any moment.
+    pub fn with_max_concurrent_connects(max_concurrent_connects: usize) -> Self {
+        Self {
+            connect_slots: Arc::new(Semaphore::new(max_concurrent_connects.max(1))),
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            feeds: RwLock::new(Vec::new()),
+        }
+    }
+
+    pub fn with_connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = connect_timeout;
+        self
+    }
+
+    // Starts `feed` and keeps restarting it whenever its connection ends.
+    // Must be called from within a Tokio runtime.
+    pub fn add<F: SupervisedFeed>(&self, feed: F) {
+        let name = feed.name();
+        let health = Arc::new(FeedHealth::new(name.clone()));
+        self.feeds.write().push(health.clone());
+        let slots = self.connect_slots.clone();
+        let connect_timeout = self.connect_timeout;
+
+        tokio::spawn(async move {
+            let mut backoff = ReconnectBackoff::new();
+            loop {
+                health.set_state(FeedState::Waiting);
+                let Ok(slot) = slots.clone().acquire_owned().await else {
+                    return;
+                };
+                health.attempting(slot);
+
+                let started_at = Instant::now();
+                let error = run_with_connect_timeout(&feed, &health, connect_timeout).await.err();
+                if let Some(e) = &error {
+                    log::error!(" {} connection error: {}", name, e);
+                }
+                let delay = backoff.next_delay(started_at.elapsed());
+                health.ended(error, &backoff, now_ms() + delay.as_millis() as u64);
+                log::info!(
+                    "🔄 Reconnecting to {} in {:.1}s (attempt {})...",
+                    name,
+                    delay.as_secs_f64(),
+                    backoff.consecutive_failures()
+                );
+                tokio::time::sleep(delay).await;
+            }
+        });
+    }
+
+    pub fn statuses(&self) -> Vec<FeedStatus> {
+        self.feeds.read().iter().map(|health| health.status()).collect()
+    }
+}
+
+impl Default for FeedSupervisor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Runs `feed` until its connection ends, or fails it once `connect_timeout`
+// passes without it calling `health.connected()`. Dropping the run cancels
+// the attempt, and `ended` frees its slot.
+async fn run_with_connect_timeout<F: SupervisedFeed>(feed: &F, health: &FeedHealth, connect_timeout: Duration) -> Result<(), String> {
+    let run = feed.run(health);
+    tokio::pin!(run);
+    let deadline = tokio::time::sleep(connect_timeout);
+    tokio::pin!(deadline);
+    let mut deadline_passed = false;
+    loop {
+        tokio::select! {
+            result = &mut run => return result,
+            _ = &mut deadline, if !deadline_passed => {
+                deadline_passed = true;
+                if health.status().state == FeedState::Connecting {
+                    return Err(format!("not connected after {:?}", connect_timeout));
+                }
+            }
+        }
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    // Connects unless `hangs`, then stays up for `up_for` (forever if
+    // `None`) before dropping.
+    struct MockFeed {
+        name: &'static str,
+        hangs: bool,
+        up_for: Option<Duration>,
+        runs: Arc<AtomicU32>,
+    }
+
+    impl MockFeed {
+        fn new(name: &'static str, up_for: Option<Duration>) -> Self {
+            Self { name, hangs: false, up_for, runs: Arc::new(AtomicU32::new(0)) }
+        }
+    }
+
+    impl SupervisedFeed for MockFeed {
+        fn name(&self) -> String {
+            self.name.to_string()
+        }
+
+        fn run<'a>(&'a self, health: &'a FeedHealth) -> FeedRun<'a> {
+            Box::pin(async move {
+                self.runs.fetch_add(1, Ordering::SeqCst);
+                if self.hangs {
+                    std::future::pending::<()>().await;
+                }
+                health.connected();
+                match self.up_for {
+                    Some(up_for) => tokio::time::sleep(up_for).await,
+                    None => std::future::pending().await,
+                }
+                Err("connection reset".to_string())
+            })
+        }
+    }
+
+    async fn wait_for(supervisor: &FeedSupervisor, until: impl Fn(&[FeedStatus]) -> bool) -> Vec<FeedStatus> {
+        let waited = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let statuses = supervisor.statuses();
+                if until(&statuses) {
+                    return statuses;
+                }
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        });
+        waited.await.expect("feed never reached the expected state")
+    }
+
+    #[tokio::test]
+    async fn a_dropped_feed_is_restarted_and_its_state_reported() {
+        let supervisor = FeedSupervisor::new();
+        let feed = MockFeed::new("Mock BTC", Some(Duration::from_millis(50)));
+        let runs = feed.runs.clone();
+        supervisor.add(feed);
+
+        let statuses = wait_for(&supervisor, |s| s[0].state == FeedState::Connected).await;
+        assert_eq!((statuses[0].name.as_str(), statuses[0].restarts), ("Mock BTC", 0));
+        assert!(statuses[0].connected_since_ms.is_some());
+
+        let statuses = wait_for(&supervisor, |s| s[0].state == FeedState::Backoff).await;
+        assert_eq!(statuses[0].restarts, 1);
+        assert_eq!(statuses[0].last_error.as_deref(), Some("connection reset"));
+        assert!(statuses[0].retry_at_ms.is_some() && statuses[0].connected_since_ms.is_none());
+
+        wait_for(&supervisor, |s| s[0].state == FeedState::Connected).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn a_hung_connect_gives_up_its_slot() {
+        let supervisor = FeedSupervisor::with_max_concurrent_connects(1).with_connect_timeout(Duration::from_millis(50));
+        supervisor.add(MockFeed { hangs: true, ..MockFeed::new("Hung", None) });
+        wait_for(&supervisor, |s| s[0].state == FeedState::Connecting).await;
+        supervisor.add(MockFeed::new("Healthy", None));
+
+        let statuses = wait_for(&supervisor, |s| s[1].state == FeedState::Connected).await;
+        assert_eq!(statuses[0].state, FeedState::Backoff);
+        assert_eq!(statuses[0].last_error.as_deref(), Some("not connected after 50ms"));
+    }
+}
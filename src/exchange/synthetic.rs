@@ -0,0 +1,191 @@
+// Offline stand-in for the exchange feeds: a seeded random walk per symbol
+// that keeps two-sided depth around the walk and now and then crosses the
+// spread so the book also prints trades. Same seed and timestamps, same book.
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::engine::orderbook::OrderBook;
+use crate::engine::order::{OrderOptions, OrderSide};
+use crate::engine::trade::Trade;
+use crate::exchange::binance::Coin;
+
+pub const DEFAULT_VOLATILITY: f64 = 0.0005;
+pub const DEFAULT_UPDATE_INTERVAL: Duration = Duration::from_millis(250);
+
+const SOURCE: &str = "synthetic";
+const MAKER_USER: &str = "synthetic_mm";
+const TAKER_USER: &str = "synthetic_taker";
+const DEPTH_LEVELS: usize = 5;
+// Distance between depth levels, as a fraction of the price.
+const LEVEL_SPACING: f64 = 0.0001;
+// Chance per step of an order crossing the spread.
+const CROSS_PROBABILITY: f64 = 0.3;
+
+// splitmix64: tiny, seedable and good enough for demo data.
+#[derive(Debug, Clone)]
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    pub(crate) fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in [0, 1).
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    // Standard normal via Box-Muller.
+    fn next_gaussian(&mut self) -> f64 {
+        let u1 = 1.0 - self.next_f64();
+        let u2 = self.next_f64();
+        (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+    }
+}
+
+fn round_to(value: f64, decimals: i32) -> f64 {
+    let factor = 10f64.powi(decimals);
+    (value * factor).round() / factor
+}
+
+pub struct SyntheticFeed {
+    orderbook: Arc<OrderBook>,
+    coin: Coin,
+    rng: Rng,
+    price: f64,
+    // Standard deviation of the log return per update.
+    volatility: f64,
+    update_interval: Duration,
+}
+
+impl SyntheticFeed {
+    pub fn new(orderbook: Arc<OrderBook>, coin: Coin, seed: u64) -> Self {
+        let price = match coin {
+            Coin::BTC => 43_000.0,
+            Coin::ETH => 2_300.0,
+            Coin::SOL => 100.0,
+        };
+        Self {
+            orderbook,
+            coin,
+            rng: Rng::new(seed),
+            price,
+            volatility: DEFAULT_VOLATILITY,
+            update_interval: DEFAULT_UPDATE_INTERVAL,
+        }
+    }
+
+    pub fn with_volatility(mut self, volatility: f64) -> Self {
+        self.volatility = volatility;
+        self
+    }
+
+    pub fn with_update_interval(mut self, update_interval: Duration) -> Self {
+        self.update_interval = update_interval;
+        self
+    }
+
+    pub fn price(&self) -> f64 {
+        self.price
+    }
+
+    // Moves the walk one step, requotes the depth around it and maybe sends a
+    // crossing order. Returns the trades that order produced.
+    pub fn step(&mut self, timestamp: u64) -> Vec<Trade> {
+        self.price = round_to(self.price * (self.volatility * self.rng.next_gaussian()).exp(), 2);
+
+        self.orderbook.cancel_all_for_source(SOURCE);
+        let spacing = (self.price * LEVEL_SPACING).max(0.01);
+        for i in 1..=DEPTH_LEVELS {
+            let offset = spacing * i as f64;
+            for (side, price) in [(OrderSide::Bid, self.price - offset), (OrderSide::Ask, self.price + offset)] {
+                let quantity = round_to(0.1 + self.rng.next_f64() * 2.0, 4);
+                let _ = self.orderbook.add_order_with_options(
+                    side,
+                    round_to(price, 2),
+                    quantity,
+                    timestamp,
+                    MAKER_USER.to_string(),
+                    OrderOptions::default().with_source(SOURCE),
+                );
+            }
+        }
+
+        if self.rng.next_f64() >= CROSS_PROBABILITY {
+            return Vec::new();
+        }
+        // Sized within the touch so the taker fills completely and never rests.
+        let (side, touch) = if self.rng.next_f64() < 0.5 {
+            (OrderSide::Bid, self.orderbook.get_best_ask())
+        } else {
+            (OrderSide::Ask, self.orderbook.get_best_bid())
+        };
+        let touch = match touch {
+            Some(touch) => touch,
+            None => return Vec::new(),
+        };
+        let quantity = round_to(0.05 + self.rng.next_f64() * 0.05, 4);
+        self.orderbook
+            .add_order(side, touch, quantity, timestamp, TAKER_USER.to_string())
+            .map(|(_, trades)| trades)
+            .unwrap_or_default()
+    }
+
+    pub fn spawn(mut self) {
+        tokio::spawn(async move {
+            log::info!(
+                "🎲 Synthetic {} feed from ${:.2} (volatility {})",
+                self.coin.display_name(),
+                self.price,
+                self.volatility
+            );
+            let mut interval = tokio::time::interval(self.update_interval);
+            loop {
+                interval.tick().await;
+                let timestamp = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap()
+                    .as_millis() as u64;
+                self.step(timestamp);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Levels = Vec<(f64, f64)>;
+
+    // Prices and sizes of every trade over `steps` steps, plus the book left.
+    fn run(seed: u64, steps: u64) -> (Levels, (Levels, Levels)) {
+        let book = Arc::new(OrderBook::new());
+        let mut feed = SyntheticFeed::new(book.clone(), Coin::BTC, seed);
+        let trades = (0..steps)
+            .flat_map(|step| feed.step(1_000 + step * 250))
+            .map(|trade| (trade.price, trade.quantity))
+            .collect();
+        (trades, book.get_market_depth(DEPTH_LEVELS))
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_trades_and_two_sided_book() {
+        let (trades, (bids, asks)) = run(42, 100);
+        assert!(!trades.is_empty());
+        assert_eq!(bids.len(), DEPTH_LEVELS);
+        assert_eq!(asks.len(), DEPTH_LEVELS);
+        assert!(bids[0].0 < asks[0].0);
+
+        assert_eq!(run(42, 100), (trades.clone(), (bids, asks)));
+        assert_ne!(run(7, 100).0, trades);
+    }
+}
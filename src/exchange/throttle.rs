@@ -0,0 +1,114 @@
+use parking_lot::Mutex;
+
+pub const DEFAULT_MAX_OPS_PER_SEC: usize = 2_000;
+
+const WINDOW_MS: u64 = 1_000;
+
+#[derive(Debug)]
+struct ThrottleState<T> {
+    window_start: u64,
+    used: usize,
+    received: u64,
+    dropped: u64,
+    pending: Option<(T, usize)>,
+}
+
+// Caps how many engine operations a feed may issue per second. Updates over
+// budget are sampled away, except that the newest one is held back and applied
+// as soon as budget frees up, so the book always ends up at the latest tick.
+#[derive(Debug)]
+pub struct FeedThrottle<T> {
+    name: String,
+    max_ops_per_sec: usize,
+    state: Mutex<ThrottleState<T>>,
+}
+
+impl<T> FeedThrottle<T> {
+    pub fn new(name: impl Into<String>, max_ops_per_sec: usize) -> Self {
+        Self {
+            name: name.into(),
+            max_ops_per_sec,
+            state: Mutex::new(ThrottleState {
+                window_start: 0,
+                used: 0,
+                received: 0,
+                dropped: 0,
+                pending: None,
+            }),
+        }
+    }
+
+    pub fn max_ops_per_sec(&self) -> usize {
+        self.max_ops_per_sec
+    }
+
+    // Returns `item` if its `ops` fit in the current window, otherwise keeps it
+    // as the pending update (superseding any older pending one).
+    pub fn submit(&self, item: T, ops: usize, now: u64) -> Option<T> {
+        let mut state = self.state.lock();
+        self.roll_window(&mut state, now);
+        state.received += 1;
+
+        if state.pending.is_some() {
+            state.dropped += 1;
+        }
+
+        if state.used + ops <= self.max_ops_per_sec {
+            state.used += ops;
+            state.pending = None;
+            Some(item)
+        } else {
+            state.pending = Some((item, ops));
+            None
+        }
+    }
+
+    // Hands back the held-back update once a new window has room for it.
+    pub fn take_due(&self, now: u64) -> Option<T> {
+        let mut state = self.state.lock();
+        self.roll_window(&mut state, now);
+
+        let ops = state.pending.as_ref()?.1;
+        if state.used + ops > self.max_ops_per_sec {
+            return None;
+        }
+        state.used += ops;
+        state.pending.take().map(|(item, _)| item)
+    }
+
+    fn roll_window(&self, state: &mut ThrottleState<T>, now: u64) {
+        if now.saturating_sub(state.window_start) < WINDOW_MS {
+            return;
+        }
+        if state.dropped > 0 {
+            log::warn!(
+                "⏱️ {} throttled: dropped {} of {} updates ({:.1}%)",
+                self.name,
+                state.dropped,
+                state.received,
+                state.dropped as f64 * 100.0 / state.received as f64
+            );
+        }
+        state.window_start = now;
+        state.used = 0;
+        state.received = 0;
+        state.dropped = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flood_is_capped_and_the_newest_update_is_applied_later() {
+        let throttle = FeedThrottle::new("test", 10);
+        let passed: Vec<u64> = (0..100).filter_map(|i| throttle.submit(i, 1, 1_000 + i)).collect();
+        assert_eq!(passed, (0..10).collect::<Vec<_>>());
+
+        // Still the same window: no room yet.
+        assert_eq!(throttle.take_due(1_500), None);
+        assert_eq!(throttle.take_due(2_000), Some(99));
+        assert_eq!(throttle.take_due(2_001), None);
+    }
+}
@@ -6,6 +6,8 @@ pub mod exchange;
 
 
 pub use engine::{
+    config::{OrderBookConfig, PrecisionPolicy},
+    error::OrderError,
     order::{Order, OrderSide},
     orderbook::{OrderBook, OrderBookStats},
     price::Price,
@@ -1,10 +1,103 @@
-use std::sync::Arc;
 use std::collections::HashMap;
+use std::sync::Arc;
 use actix_web::{web::{self, Data}, App, HttpServer};
 use actix_cors::Cors;
+use order_book_hybrid::engine::checkpoint::Checkpointer;
+use order_book_hybrid::engine::config::{FeeSchedule, LockedMarketPolicy, OrderBookConfig, OrderKind, SelfTradePolicy, TieBreak, TradeThroughCheck};
+use order_book_hybrid::engine::consolidated::ConsolidatedBook;
+use order_book_hybrid::engine::event_log::{EventLog, DEFAULT_COMPACT_EVERY};
+use order_book_hybrid::engine::gateway::{OrderGateway, OrderGateways};
 use order_book_hybrid::engine::orderbook::OrderBook;
 use order_book_hybrid::api::{routes, websocket};
-use order_book_hybrid::exchange::{BinanceWebSocket, CoinbaseWebSocket, BybitWebSocket, Coin};
+use order_book_hybrid::api::auth::ApiKeys;
+use order_book_hybrid::api::books::BookRegistry;
+use order_book_hybrid::api::connections::ConnectionRegistry;
+use order_book_hybrid::exchange::{Binance, Bybit, Coin, Coinbase, ConsensusMid, ExchangeFeed, ExchangeWebSocket, Kraken, Quote};
+use order_book_hybrid::exchange::supervisor::{FeedSupervisor, DEFAULT_MAX_CONCURRENT_CONNECTS};
+use order_book_hybrid::exchange::synthetic::{self, SyntheticFeed};
+use order_book_hybrid::exchange::throttle::DEFAULT_MAX_OPS_PER_SEC;
+
+// The book a venue's feed writes to: the venue's own sub-book when venue
+// books are enabled, the symbol's shared book otherwise.
+fn feed_book(orderbook: &Arc<OrderBook>, venues: Option<&ConsolidatedBook>, venue: &str) -> Arc<OrderBook> {
+    match venues {
+        Some(venues) => venues.venue(venue),
+        None => orderbook.clone(),
+    }
+}
+
+// One book the server runs: a coin, optionally in a fixed quote currency.
+struct Market {
+    coin: Coin,
+    quote: Option<Quote>,
+    book: Arc<OrderBook>,
+    venues: Option<Arc<ConsolidatedBook>>,
+}
+
+// `BTC-USDT` for a fixed quote, plain `BTC` for a book fed in each venue's
+// usual quote.
+fn market_symbol(coin: &Coin, quote: Option<Quote>) -> String {
+    match quote {
+        Some(quote) => format!("{}-{}", coin.ticker(), quote.code()),
+        None => coin.ticker().to_string(),
+    }
+}
+
+// ORDER_TYPES_<SYMBOL>=limit,ioc,fok,market,conditional limits the order
+// types users may place on that book (`-` in the symbol becomes `_`, e.g.
+// ORDER_TYPES_SOL_USDT). Unset, every type is allowed.
+fn allowed_order_kinds(symbol: &str) -> Option<Vec<OrderKind>> {
+    let value = std::env::var(format!("ORDER_TYPES_{}", symbol.replace('-', "_"))).ok()?;
+    let mut kinds = Vec::new();
+    for name in value.split(',').filter(|n| !n.trim().is_empty()) {
+        match OrderKind::parse(name) {
+            Some(kind) if !kinds.contains(&kind) => kinds.push(kind),
+            Some(_) => {}
+            None => log::warn!(" Ignoring unknown order type {} for {}", name.trim(), symbol),
+        }
+    }
+    Some(kinds)
+}
+
+// With a fixed quote, only venues that list the coin in it are connected.
+fn start_feeds(
+    supervisor: &FeedSupervisor,
+    market: &Market,
+    consensus_mid: bool,
+    max_ops_per_sec: usize,
+    max_price_gap_pct: Option<f64>,
+) {
+    let consensus = consensus_mid.then(|| Arc::new(ConsensusMid::new()));
+    start_feed::<Binance>(supervisor, market, consensus.clone(), max_ops_per_sec, max_price_gap_pct);
+    start_feed::<Coinbase>(supervisor, market, consensus.clone(), max_ops_per_sec, max_price_gap_pct);
+    start_feed::<Bybit>(supervisor, market, consensus.clone(), max_ops_per_sec, max_price_gap_pct);
+    start_feed::<Kraken>(supervisor, market, consensus, max_ops_per_sec, max_price_gap_pct);
+}
+
+fn start_feed<V: ExchangeFeed>(
+    supervisor: &FeedSupervisor,
+    market: &Market,
+    consensus: Option<Arc<ConsensusMid>>,
+    max_ops_per_sec: usize,
+    max_price_gap_pct: Option<f64>,
+) {
+    if market.quote.is_some_and(|quote| !V::lists_quote(quote)) {
+        return;
+    }
+    let orderbook = feed_book(&market.book, market.venues.as_deref(), &V::NAME.to_ascii_lowercase());
+    let mut feed = ExchangeWebSocket::<V>::new(orderbook, market.coin.clone())
+        .with_max_ops_per_sec(max_ops_per_sec);
+    if let Some(quote) = market.quote {
+        feed = feed.with_quote(quote);
+    }
+    if let Some(consensus) = consensus {
+        feed = feed.with_consensus(consensus);
+    }
+    if let Some(max_gap_pct) = max_price_gap_pct {
+        feed = feed.with_max_price_gap_pct(max_gap_pct);
+    }
+    supervisor.add(feed);
+}
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
@@ -16,15 +109,211 @@ async fn main() -> std::io::Result<()> {
     log::info!("═══════════════════════════════════════");
     
    
-    let btc_orderbook = Arc::new(OrderBook::new());
-    let sol_orderbook = Arc::new(OrderBook::new());
-    let eth_orderbook = Arc::new(OrderBook::new());
+    // TIE_BREAK=primary:<venue> or TIE_BREAK=size makes same-price matching
+    // between venues deterministic; the default is plain FIFO.
+    let tie_break = match std::env::var("TIE_BREAK").ok().as_deref() {
+        Some("size") => TieBreak::LargerSize,
+        Some(value) => match value.strip_prefix("primary:") {
+            Some(venue) => TieBreak::PrimaryVenue(venue.to_string()),
+            None => TieBreak::Fifo,
+        },
+        None => TieBreak::Fifo,
+    };
+    let mut base_config = OrderBookConfig::default()
+        .with_tie_break(tie_break)
+        .with_prevent_same_source_match(true);
+    // BOOK_MAX_STALENESS_MS clears a book once its feeds have gone quiet this long.
+    if let Some(max_staleness_ms) = std::env::var("BOOK_MAX_STALENESS_MS").ok().and_then(|v| v.parse().ok()) {
+        base_config = base_config.with_max_staleness_ms(max_staleness_ms);
+    }
+    // FEED_MAX_SILENCE_MS cancels a venue's synthetic orders once its feed
+    // has sent nothing for this long, leaving the live venues' depth.
+    if let Some(max_silence_ms) = std::env::var("FEED_MAX_SILENCE_MS").ok().and_then(|v| v.parse().ok()) {
+        base_config = base_config.with_max_source_silence_ms(max_silence_ms);
+    }
+    // TRADE_THROUGH_CHECK=report logs fills worse than the best opposite
+    // price; =strict additionally treats them as a bug in debug builds.
+    let trade_through_check = match std::env::var("TRADE_THROUGH_CHECK").ok().as_deref() {
+        Some("report") => TradeThroughCheck::Report,
+        Some("strict") => TradeThroughCheck::Strict,
+        _ => TradeThroughCheck::Off,
+    };
+    base_config = base_config.with_trade_through_check(trade_through_check);
+    // MAKER_FEE_RATE / TAKER_FEE_RATE are fractions of notional (0.001 = 10 bps).
+    let fee_rate = |name: &str| std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(0.0);
+    base_config = base_config.with_fee_schedule(FeeSchedule::new(fee_rate("MAKER_FEE_RATE"), fee_rate("TAKER_FEE_RATE")));
+    // L3_EVENTS=1 publishes every resting-order change for the WebSocket `l3` channel.
+    let l3_events = std::env::var("L3_EVENTS").is_ok_and(|v| v == "1" || v.eq_ignore_ascii_case("true"));
+    base_config = base_config.with_order_level_events(l3_events);
+    // MATCH_EVENTS=1 tells both maker and taker about each fill on their `orders` channel.
+    let match_events = std::env::var("MATCH_EVENTS").is_ok_and(|v| v == "1" || v.eq_ignore_ascii_case("true"));
+    base_config = base_config.with_match_events(match_events);
+    // OPENING_AUCTION_MS collects orders without matching for this long after
+    // startup, then uncrosses each book at a single opening price.
+    let opening_auction_ms: Option<u64> = std::env::var("OPENING_AUCTION_MS").ok().and_then(|v| v.parse().ok());
+    base_config = base_config.with_opening_auction(opening_auction_ms.is_some());
+    // MIN_RESTING_MS refuses user cancels of orders younger than this.
+    if let Some(min_resting_ms) = std::env::var("MIN_RESTING_MS").ok().and_then(|v| v.parse().ok()) {
+        base_config = base_config.with_min_resting_ms(min_resting_ms);
+    }
+    // SELF_TRADE_POLICY=cancel_resting|cancel_incoming|decrement_both stops a
+    // user's orders from trading with each other.
+    let self_trade_policy = match std::env::var("SELF_TRADE_POLICY").ok().as_deref() {
+        Some("cancel_resting") => Some(SelfTradePolicy::CancelResting),
+        Some("cancel_incoming") => Some(SelfTradePolicy::CancelIncoming),
+        Some("decrement_both") => Some(SelfTradePolicy::DecrementBoth),
+        _ => None,
+    };
+    if let Some(policy) = self_trade_policy {
+        base_config = base_config.with_self_trade_policy(policy);
+    }
+    // TICK_SIZE rounds limit prices to a grid, e.g. 0.01. It must be a whole
+    // multiple of 1e-8, or startup fails.
+    if let Some(tick_size) = std::env::var("TICK_SIZE").ok().and_then(|v| v.parse::<f64>().ok()) {
+        base_config = base_config.with_tick_size(tick_size);
+    }
+    // TRADE_HISTORY_CAPACITY sets how many prints /trades can look back over.
+    if let Some(capacity) = std::env::var("TRADE_HISTORY_CAPACITY").ok().and_then(|v| v.parse().ok()) {
+        base_config = base_config.with_trade_history_capacity(capacity);
+    }
+    // MID_EMA_ALPHA sets how much of the gap to the mid /stats' mid_ema
+    // closes per second, in (0, 1].
+    if let Some(alpha) = std::env::var("MID_EMA_ALPHA").ok().and_then(|v| v.parse::<f64>().ok()) {
+        if alpha > 0.0 && alpha <= 1.0 {
+            base_config = base_config.with_mid_ema_alpha(alpha);
+        } else {
+            log::warn!("Ignoring MID_EMA_ALPHA={}: must be in (0, 1]", alpha);
+        }
+    }
+    // SNAPSHOT_DIR=snapshots restores each book from its last snapshot there
+    // and keeps writing new ones every SNAPSHOT_EVERY_TRADES trades or
+    // SNAPSHOT_EVERY_MS, whichever comes first, and once more on shutdown.
+    let snapshot_dir = std::env::var("SNAPSHOT_DIR").ok();
+    if let Some(trades) = std::env::var("SNAPSHOT_EVERY_TRADES").ok().and_then(|v| v.parse().ok()).filter(|&n: &u64| n > 0) {
+        base_config = base_config.with_snapshot_every_trades(trades);
+    }
+    if let Some(every_ms) = std::env::var("SNAPSHOT_EVERY_MS").ok().and_then(|v| v.parse().ok()).filter(|&ms: &u64| ms > 0) {
+        base_config = base_config.with_snapshot_every_ms(every_ms);
+    }
+    // LOCKED_MARKET_POLICY=match|reject|allow decides what happens during the
+    // opening auction to an order priced at the opposite best.
+    let locked_market_policy = match std::env::var("LOCKED_MARKET_POLICY").ok().as_deref() {
+        Some("reject") => LockedMarketPolicy::Reject,
+        Some("allow") => LockedMarketPolicy::Allow,
+        _ => LockedMarketPolicy::Match,
+    };
+    base_config = base_config.with_locked_market_policy(locked_market_policy);
+    // VENUE_BOOKS=1 keeps a sub-book per venue for each symbol instead of
+    // feeding all venues into the shared book; /depth/consolidated sums them.
+    let venue_books = std::env::var("VENUE_BOOKS").is_ok_and(|v| v == "1" || v.eq_ignore_ascii_case("true"));
+    let venues_for = |symbol: &str| {
+        venue_books.then(|| Arc::new(ConsolidatedBook::new(base_config.clone().with_symbol(symbol))))
+    };
+    // QUOTE_CURRENCIES=USD,USDT,EUR keeps a separate book per quote for each
+    // coin (BTC-USD, BTC-USDT, ...), each fed only by venues listing that
+    // pair. Without it each coin has one book fed in every venue's own quote.
+    let mut quotes: Vec<Quote> = Vec::new();
+    for code in std::env::var("QUOTE_CURRENCIES").unwrap_or_default().split(',').filter(|c| !c.trim().is_empty()) {
+        match Quote::parse(code) {
+            Some(quote) if !quotes.contains(&quote) => quotes.push(quote),
+            Some(_) => {}
+            None => log::warn!(" Ignoring unknown quote currency {}", code.trim()),
+        }
+    }
+    let quotes: Vec<Option<Quote>> = if quotes.is_empty() {
+        vec![None]
+    } else {
+        quotes.into_iter().map(Some).collect()
+    };
+    
+    let mut markets = Vec::new();
+    for coin in [Coin::BTC, Coin::SOL, Coin::ETH] {
+        for &quote in &quotes {
+            let symbol = market_symbol(&coin, quote);
+            let mut config = base_config.clone().with_symbol(&symbol);
+            if let Some(kinds) = allowed_order_kinds(&symbol) {
+                config = config.with_allowed_order_kinds(kinds);
+            }
+            markets.push(Market {
+                book: Arc::new(
+                    OrderBook::try_with_config(config)
+                        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?,
+                ),
+                venues: venues_for(&symbol),
+                coin: coin.clone(),
+                quote,
+            });
+        }
+    }
+    let default_book = markets[0].book.clone();
 
     log::info!("✅ Multi-coin OrderBooks initialized:");
-    log::info!("   • Bitcoin (BTC)");
-    log::info!("   • Solana (SOL)");
-    log::info!("   • Ethereum (ETH)");
+    for market in &markets {
+        log::info!("   • {} ({})", market.coin.display_name(), market.book.config().symbol);
+    }
     log::info!("");
+
+    let mut shutdown_checkpoints = Vec::new();
+    if let Some(dir) = &snapshot_dir {
+        if base_config.snapshot_every_trades.is_none() && base_config.snapshot_every_ms.is_none() {
+            log::warn!(" SNAPSHOT_DIR is set without SNAPSHOT_EVERY_TRADES or SNAPSHOT_EVERY_MS; snapshots will only be written on shutdown");
+        }
+        if let Err(e) = std::fs::create_dir_all(dir) {
+            log::error!(" Could not create snapshot directory {}: {}", dir, e);
+        }
+        for market in &markets {
+            let checkpointer = Checkpointer::new(market.book.clone(), dir);
+            match checkpointer.restore() {
+                Ok(true) => log::info!(" Restored {} from {}", market.book.config().symbol, checkpointer.path().display()),
+                Ok(false) => {}
+                Err(e) => log::error!(" Could not restore from {}: {}", checkpointer.path().display(), e),
+            }
+            checkpointer.spawn();
+            shutdown_checkpoints.push(Checkpointer::new(market.book.clone(), dir));
+        }
+    }
+
+    // EVENT_LOG_DIR=events journals every change to each book to
+    // <symbol>.events.jsonl there and replays it on startup, on top of any
+    // snapshot. The log is compacted every EVENT_LOG_COMPACT_EVERY events.
+    if let Ok(dir) = std::env::var("EVENT_LOG_DIR") {
+        let compact_every = std::env::var("EVENT_LOG_COMPACT_EVERY")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|&n: &usize| n > 0)
+            .unwrap_or(DEFAULT_COMPACT_EVERY);
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            log::error!(" Could not create event log directory {}: {}", dir, e);
+        }
+        for market in &markets {
+            let path = std::path::Path::new(&dir).join(format!("{}.events.jsonl", market.book.config().symbol));
+            if path.exists() {
+                match EventLog::replay(&path, &market.book) {
+                    Ok(count) => log::info!(" Replayed {} events into {} from {}", count, market.book.config().symbol, path.display()),
+                    Err(e) => {
+                        // Attaching would compact the log over what couldn't
+                        // be replayed, so leave it for inspection.
+                        log::error!(" Could not replay {}, not journaling {}: {}", path.display(), market.book.config().symbol, e);
+                        continue;
+                    }
+                }
+            }
+            let attached = EventLog::open(&path)
+                .and_then(|log| market.book.attach_event_log(Arc::new(log.with_compact_every(compact_every))));
+            if let Err(e) = attached {
+                log::error!(" Could not open event log {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    // SEED_ORDERS_CSV=orders.csv places side,price,quantity,user_id rows on
+    // the first BTC book (the API's default symbol) before the feeds start.
+    if let Ok(path) = std::env::var("SEED_ORDERS_CSV") {
+        match default_book.load_orders_csv(&path) {
+            Ok(count) => log::info!(" Seeded {} orders from {}", count, path),
+            Err(e) => log::error!(" Could not seed orders from {}: {}", path, e),
+        }
+    }
     
     log::info!("═══════════════════════════════");
     log::info!("Lock-free OrderBook initialized");
@@ -32,7 +321,7 @@ async fn main() -> std::io::Result<()> {
     log::info!(" Using:");
     log::info!("   - parking_lot::RwLock for BTreeMap");
     log::info!("   - DashMap for order storage");
-    log::info!("   - SegQueue for FIFO ordering");
+    log::info!("   - VecDeque FIFO with lazy removal");
     log::info!("   - AtomicU64 for counters");
 
     log::info!("═══════════════════════════════");
@@ -40,50 +329,154 @@ async fn main() -> std::io::Result<()> {
     log::info!("");
     log::info!("═══════════════════════════════");
 
-    log::info!(" Starting Bitcoin (BTC) Feeds...");
-    BinanceWebSocket::start(btc_orderbook.clone(), Coin::BTC);
-    tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
-    
-    CoinbaseWebSocket::start(btc_orderbook.clone(), Coin::BTC);
-    tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
-    
-    BybitWebSocket::start(btc_orderbook.clone(), Coin::BTC);
-    tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
-    
-    log::info!(" BTC feeds: Binance + Coinbase + Bybit");
-    log::info!("");
+    // FEED_MAX_CONCURRENT_CONNECTS caps how many exchange feeds may be
+    // connecting at once, so a reconnect storm is spread out.
+    let max_concurrent_connects = std::env::var("FEED_MAX_CONCURRENT_CONNECTS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_MAX_CONCURRENT_CONNECTS);
+    let feed_supervisor = Data::new(FeedSupervisor::with_max_concurrent_connects(max_concurrent_connects));
 
-    log::info!(" Starting Solana (SOL) Feeds...");
-    BinanceWebSocket::start(sol_orderbook.clone(), Coin::SOL);
-    tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
+    // FEED_MODE=synthetic swaps the exchange connections for a seeded random
+    // walk per book, so the server runs offline and in CI.
+    let synthetic_feeds = std::env::var("FEED_MODE").is_ok_and(|v| v.eq_ignore_ascii_case("synthetic"));
+    if synthetic_feeds {
+        let seed: u64 = std::env::var("SYNTHETIC_SEED").ok().and_then(|v| v.parse().ok()).unwrap_or(42);
+        let volatility = std::env::var("SYNTHETIC_VOLATILITY")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(synthetic::DEFAULT_VOLATILITY);
+        let update_interval = std::env::var("SYNTHETIC_INTERVAL_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .map(std::time::Duration::from_millis)
+            .unwrap_or(synthetic::DEFAULT_UPDATE_INTERVAL);
+        for (i, market) in markets.iter().enumerate() {
+            let book = feed_book(&market.book, market.venues.as_deref(), "synthetic");
+            SyntheticFeed::new(book, market.coin.clone(), seed.wrapping_add(i as u64))
+                .with_volatility(volatility)
+                .with_update_interval(update_interval)
+                .spawn();
+        }
+        log::info!(" Synthetic feeds running (seed {})", seed);
+    } else {
+        // CONSENSUS_MID=1 anchors every venue's synthetic depth on the median of
+        // the venues' last prices instead of each venue's own trade price.
+        let consensus_mid = std::env::var("CONSENSUS_MID")
+            .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+        if consensus_mid {
+            log::info!(" Anchoring synthetic depth on cross-venue consensus mid");
+        }
     
-    CoinbaseWebSocket::start(sol_orderbook.clone(), Coin::SOL);
-    tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
-    
-    BybitWebSocket::start(sol_orderbook.clone(), Coin::SOL);
-    tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
-    
-    log::info!(" SOL feeds: Binance + Coinbase + Bybit");
-    log::info!("");
+        // Caps engine operations per feed so a volume spike can't saturate matching.
+        let max_ops_per_sec = std::env::var("FEED_MAX_OPS_PER_SEC")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MAX_OPS_PER_SEC);
 
-    log::info!(" Starting Ethereum (ETH) Feeds...");
-    BinanceWebSocket::start(eth_orderbook.clone(), Coin::ETH);
-    tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
+        // FEED_MAX_PRICE_GAP_PCT=10 ignores trades more than 10% away from the
+        // venue's last accepted price, so a bad tick can't move the book.
+        let max_price_gap_pct: Option<f64> = std::env::var("FEED_MAX_PRICE_GAP_PCT")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|pct: &f64| *pct > 0.0);
+
+        for market in &markets {
+            let symbol = &market.book.config().symbol;
+            log::info!(" Starting {} ({}) Feeds...", market.coin.display_name(), symbol);
+            start_feeds(&feed_supervisor, market, consensus_mid, max_ops_per_sec, max_price_gap_pct);
+            log::info!("");
+        }
+
+          log::info!("═══════════════════════════════════════");
+        log::info!(" All exchanges streaming live data!");
+        log::info!("═══════════════════════════════════════");
+    }
     
-    CoinbaseWebSocket::start(eth_orderbook.clone(), Coin::ETH);
-    tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
+    let books = Data::new(
+        markets[1..]
+            .iter()
+            .fold(BookRegistry::new(default_book.clone()), |books, market| books.with_book(market.book.clone())),
+    );
+    let venue_registry: HashMap<String, Arc<ConsolidatedBook>> = markets
+        .iter()
+        .filter_map(|market| Some((market.book.config().symbol.clone(), market.venues.clone()?)))
+        .collect();
+    let venue_registry = (!venue_registry.is_empty()).then(|| Data::new(venue_registry));
     
-    BybitWebSocket::start(eth_orderbook.clone(), Coin::ETH);
-    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+    let api_keys = Data::new(ApiKeys::from_env());
+    log::info!(" Loaded {} API keys", api_keys.len());
+    // WS_MAX_CONNECTIONS refuses WebSocket upgrades with 503 beyond this many sessions.
+    let mut connections = ConnectionRegistry::new();
+    if let Some(max_connections) = std::env::var("WS_MAX_CONNECTIONS").ok().and_then(|v| v.parse().ok()) {
+        connections = connections.with_max_connections(max_connections);
+    }
+    // WS_KEEPALIVE_MS sends every session a Heartbeat frame this often, so
+    // clients can tell a dead connection from a quiet market.
+    if let Some(keepalive_ms) = std::env::var("WS_KEEPALIVE_MS").ok().and_then(|v| v.parse().ok()).filter(|&ms: &u64| ms > 0) {
+        connections = connections.with_keepalive_interval(std::time::Duration::from_millis(keepalive_ms));
+    }
+    let connections = Data::new(connections);
+    // ORDER_GATEWAY=1 sequences each book's order entry through its own
+    // engine thread, so concurrent requests are applied in the order they
+    // were received.
+    let gateway = std::env::var("ORDER_GATEWAY")
+        .is_ok_and(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+        .then(|| {
+            let gateways: OrderGateways = books
+                .symbols()
+                .into_iter()
+                .filter_map(|symbol| {
+                    let book = books.get(Some(&symbol))?.clone();
+                    Some((symbol, OrderGateway::new(book)))
+                })
+                .collect();
+            Data::new(gateways)
+        });
+    if gateway.is_some() {
+        log::info!(" Order-entry gateway enabled");
+    }
     
-    log::info!(" ETH feeds: Binance + Coinbase + Bybit");
-    log::info!("");
-
-      log::info!("═══════════════════════════════════════");
-    log::info!(" All exchanges streaming live data!");
-    log::info!("═══════════════════════════════════════");
+    let mut swept_books: Vec<Arc<OrderBook>> = markets.iter().map(|market| market.book.clone()).collect();
+    for venues in markets.iter().filter_map(|market| market.venues.as_ref()) {
+        swept_books.extend(venues.books());
+    }
+    if let Some(opening_auction_ms) = opening_auction_ms {
+        let books = swept_books.clone();
+        log::info!(" Opening auction: continuous matching starts in {} ms", opening_auction_ms);
+        tokio::spawn(async move {
+            tokio::time::sleep(tokio::time::Duration::from_millis(opening_auction_ms)).await;
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as u64;
+            for book in &books {
+                book.open(now);
+            }
+        });
+    }
     
-    let orderbook = btc_orderbook.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_millis(250));
+        loop {
+            interval.tick().await;
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as u64;
+            for book in &swept_books {
+                book.sweep_dead_man(now);
+                book.sweep_expired(now);
+                book.sweep_stale(now);
+                book.sweep_dead_sources(now);
+                book.sample_mid(now);
+                if let Err(e) = book.compact_event_log() {
+                    log::error!(" Could not compact event log for {}: {}", book.config().symbol, e);
+                }
+            }
+        }
+    });
     
     log::info!("═══════════════════════════════════════");
     log::info!("  HTTP server on http://127.0.0.1:8080");
@@ -97,16 +490,47 @@ async fn main() -> std::io::Result<()> {
     log::info!("");
      log::info!(" Available endpoints:");
     log::info!("   GET  /health           - Health check");
-    log::info!("   GET  /depth            - Order book depth");
+    log::info!("   GET  /config           - Active matching configuration");
+    log::info!("   GET  /depth/{{symbol}}   - Order book depth (first book when omitted)");
+    log::info!("   GET  /depth/resync     - Full depth with sequence");
+    log::info!("   GET  /depth/consolidated - Depth summed across venue books");
+    log::info!("   GET  /nbbo/{{symbol}}    - Best bid/ask across venue books, by venue");
     log::info!("   GET  /stats            - Statistics");
+    log::info!("   GET  /stats/volatility - Realized volatility");
+    log::info!("   GET  /stats/twap       - Time-weighted average mid");
+    log::info!("   GET  /stats/depth-slope - Depth vs distance from mid");
+    log::info!("   GET  /volume-profile   - Traded volume by price");
+    log::info!("   GET  /candles/{{symbol}} - OHLCV candles (?interval=1m|5m|1h&limit=)");
+    log::info!("   GET  /trades           - Trades since a timestamp");
+    log::info!("   GET  /trades/export    - Whole tape as JSONL or columnar arrays");
+    log::info!("   GET  /leaderboard      - Top users by volume, PnL or orders");
+    log::info!("   GET  /account          - A user's totals and maker spread capture");
+    log::info!("   GET  /quote            - Fill for a quote notional");
+    log::info!("   GET  /resilience       - Simulated sweep and depth left behind it");
+    log::info!("   GET  /auction/indicative - Indicative auction uncross");
     log::info!("   POST /order            - Create order");
+    log::info!("   GET  /order/{{id}}       - Query an open order");
+    log::info!("   GET  /order/{{id}}/position - Place in the order's price level queue");
+    log::info!("   GET  /orders?user_id=  - List a user's resting orders");
     log::info!("   DELETE /order          - Cancel order");
-    log::info!("   GET  /ws               - WebSocket stream");
+    log::info!("   DELETE /orders         - Cancel all of a user's orders");
+    log::info!("   DELETE /orders/all     - Cancel a user's orders on every symbol");
+    log::info!("   PATCH /order           - Amend price or quantity");
+    log::info!("   POST /order/cancel-replace - Atomically cancel and replace");
+    log::info!("   POST /deadman          - Arm/disarm dead-man's switch");
+    log::info!("   POST /deadman/heartbeat - Keep dead-man's switch alive");
+    log::info!("   GET  /admin/connections - List WebSocket connections");
+    log::info!("   DELETE /admin/connections/{{id}} - Force-disconnect one");
+    log::info!("   GET  /admin/archive - Download book, trades, stats and config");
+    log::info!("   POST /admin/kill?cancel= - Halt trading on every symbol");
+    log::info!("   POST /admin/revive     - Resume trading after a kill");
+    log::info!("   GET  /ws?symbol=       - WebSocket stream");
+    log::info!(" Other routes take ?symbol= (order entry: a `symbol` field); default {}", default_book.config().symbol);
     log::info!("═══════════════════════════════════════");
     log::info!(" Server ready! Accepting connections...");
     log::info!("");
     
-    HttpServer::new(move || {
+    let server = HttpServer::new(move || {
 
         let cors = Cors::default()
             .allow_any_origin()
@@ -114,17 +538,67 @@ async fn main() -> std::io::Result<()> {
             .allow_any_header()
             .max_age(3600);
 
-        App::new()
+        let mut app = App::new()
             .wrap(cors)
-            .app_data(Data::new(orderbook.clone()))
+            .app_data(books.clone())
+            .app_data(api_keys.clone())
+            .app_data(connections.clone())
+            .app_data(feed_supervisor.clone());
+        if let Some(gateway) = &gateway {
+            app = app.app_data(gateway.clone());
+        }
+        if let Some(venues) = &venue_registry {
+            app = app.app_data(venues.clone());
+        }
+        app
             .service(routes::health_check)
+            .service(routes::get_config)
+            .service(routes::resync_depth)
+            .service(routes::get_consolidated_depth)
+            .service(routes::get_nbbo)
+            // After the fixed /depth/* paths, which /depth/{symbol} would shadow.
             .service(routes::get_depth)
             .service(routes::create_order)
+            .service(routes::get_order)
+            .service(routes::get_queue_position)
+            .service(routes::get_user_orders)
             .service(routes::delete_order)
+            .service(routes::cancel_all_orders)
+            .service(routes::cancel_orders_everywhere)
+            .service(routes::amend_order)
+            .service(routes::cancel_replace_order)
             .service(routes::get_stats)
+            .service(routes::get_volatility)
+            .service(routes::get_twap)
+            .service(routes::get_depth_slope)
+            .service(routes::get_volume_profile)
+            .service(routes::get_candles)
+            .service(routes::get_trades)
+            .service(routes::export_trades)
+            .service(routes::get_leaderboard)
+            .service(routes::get_account)
+            .service(routes::get_quote)
+            .service(routes::get_resilience)
+            .service(routes::get_indicative_auction)
+            .service(routes::arm_dead_man)
+            .service(routes::dead_man_heartbeat)
+            .service(routes::list_connections)
+            .service(routes::disconnect_connection)
+            .service(routes::get_archive)
+            .service(routes::kill_trading)
+            .service(routes::revive_trading)
             .route("/ws", web::get().to(websocket::ws_index))
     })
     .bind("127.0.0.1:8080")?
     .run()
-    .await
+    .await;
+    
+    // The API has stopped taking orders; keep each book as it stands.
+    for checkpointer in &shutdown_checkpoints {
+        match checkpointer.write() {
+            Ok(()) => log::info!(" Saved {}", checkpointer.path().display()),
+            Err(e) => log::error!(" Could not write {}: {}", checkpointer.path().display(), e),
+        }
+    }
+    server
 }
\ No newline at end of file
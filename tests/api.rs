@@ -0,0 +1,699 @@
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use actix_web::web::Data;
+use actix_web::{test, App};
+use serde_json::json;
+
+use order_book_hybrid::api::auth::ApiKeys;
+use order_book_hybrid::api::books::BookRegistry;
+use order_book_hybrid::api::routes;
+use order_book_hybrid::api::types::{AccountResponse, CancelEverywhereResponse, CancelRejectReason, ConfigResponse, CreateOrderResponse, DeleteOrderResponse, DepthLevel, DepthResponse, DepthResyncResponse, NormalizedDepthLevel, NormalizedDepthResponse, OrderResponse, QueuePositionResponse, ResilienceResponse, ResponseEnvelope, RestReason, Side, TradesResponse};
+use order_book_hybrid::engine::config::{FeeSchedule, OrderBookConfig, OrderKind, PrecisionPolicy};
+use order_book_hybrid::engine::order::{OrderSide, TimeInForce};
+use order_book_hybrid::engine::orderbook::OrderBook;
+use order_book_hybrid::engine::snapshot::SessionArchive;
+use order_book_hybrid::engine::trade::{Trade, TradeColumns};
+
+// The routes as `main` registers them, over `books` and `api_keys`.
+macro_rules! init_app {
+    ($books:expr) => {
+        init_app!($books, ApiKeys::new())
+    };
+    ($books:expr, $api_keys:expr) => {
+        test::init_service(
+            App::new()
+                .app_data(Data::new($books))
+                .app_data(Data::new($api_keys))
+                .service(routes::get_config)
+                .service(routes::resync_depth)
+                .service(routes::get_depth)
+                .service(routes::get_trades)
+                .service(routes::export_trades)
+                .service(routes::create_order)
+                .service(routes::get_order)
+                .service(routes::get_queue_position)
+                .service(routes::get_user_orders)
+                .service(routes::delete_order)
+                .service(routes::cancel_all_orders)
+                .service(routes::cancel_orders_everywhere)
+                .service(routes::amend_order)
+                .service(routes::cancel_replace_order)
+                .service(routes::get_account)
+                .service(routes::get_resilience)
+                .service(routes::arm_dead_man)
+                .service(routes::dead_man_heartbeat)
+                .service(routes::get_archive),
+        )
+        .await
+    };
+}
+
+fn single_book() -> (Arc<OrderBook>, BookRegistry) {
+    let book = Arc::new(OrderBook::new());
+    (book.clone(), BookRegistry::new(book))
+}
+
+#[actix_web::test]
+async fn market_sweep_reports_best_worst_and_levels_touched() {
+    let (book, books) = single_book();
+    for (i, price) in [100.0, 101.0, 102.0].into_iter().enumerate() {
+        book.add_order(OrderSide::Ask, price, 1.0, i as u64, "maker".to_string()).unwrap();
+    }
+    let app = init_app!(books);
+
+    let request = test::TestRequest::post()
+        .uri("/order")
+        .set_json(json!({ "price": 0.0, "quantity": 3.0, "user_id": "taker", "side": "Buy", "order_type": "Market" }))
+        .to_request();
+    let response: CreateOrderResponse = test::call_and_read_body_json(&app, request).await;
+
+    assert_eq!(response.filled_quantity, 3.0);
+    assert_eq!(response.best_price, Some(100.0));
+    assert_eq!(response.worst_price, Some(102.0));
+    assert_eq!(response.levels_touched, 3);
+}
+
+#[actix_web::test]
+async fn dead_man_routes_need_the_users_own_key() {
+    let (book, books) = single_book();
+    let mut api_keys = ApiKeys::new();
+    api_keys.insert("alice-key", "alice");
+    api_keys.insert("bob-key", "bob");
+    let app = init_app!(books, api_keys);
+
+    let arm = |key: Option<&str>, user_id: &str| {
+        let mut request = test::TestRequest::post()
+            .uri("/deadman")
+            .set_json(json!({ "user_id": user_id, "timeout_ms": 5000 }));
+        if let Some(key) = key {
+            request = request.insert_header(("X-API-Key", key));
+        }
+        request.to_request()
+    };
+
+    assert_eq!(test::call_service(&app, arm(None, "alice")).await.status(), 401);
+    assert_eq!(test::call_service(&app, arm(Some("bob-key"), "alice")).await.status(), 403);
+    assert_eq!(test::call_service(&app, arm(Some("alice-key"), "")).await.status(), 400);
+    assert!(book.dead_man().expires_at("alice").is_none());
+
+    assert_eq!(test::call_service(&app, arm(Some("alice-key"), "alice")).await.status(), 200);
+    assert!(book.dead_man().expires_at("alice").is_some());
+
+    let heartbeat = test::TestRequest::post()
+        .uri("/deadman/heartbeat")
+        .insert_header(("X-API-Key", "bob-key"))
+        .set_json(json!({ "user_id": "alice" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, heartbeat).await.status(), 403);
+}
+
+#[actix_web::test]
+async fn depth_rounds_quantities_while_matching_uses_exact_ones() {
+    let book = Arc::new(OrderBook::with_config(OrderBookConfig::default().with_display_quantity_decimals(2)));
+    book.add_order(OrderSide::Ask, 100.0, 1.234567, 1, "maker".to_string()).unwrap();
+    let app = init_app!(BookRegistry::new(book.clone()));
+
+    let depth: DepthResponse = test::call_and_read_body_json(&app, test::TestRequest::get().uri("/depth").to_request()).await;
+    assert_eq!(depth.asks[0].quantity, 1.23);
+    assert_eq!(book.get_market_depth(1).1, vec![(100.0, 1.234567)]);
+
+    let (_, trades) = book.add_order(OrderSide::Bid, 100.0, 1.234567, 2, "taker".to_string()).unwrap();
+    assert_eq!(trades[0].quantity, 1.234567);
+    assert!(book.get_best_ask().is_none());
+}
+
+#[actix_web::test]
+async fn prefixed_order_id_round_trips_through_create_and_delete() {
+    let book = Arc::new(OrderBook::with_config(OrderBookConfig::default().with_symbol("ETH")));
+    let app = init_app!(BookRegistry::new(book.clone()));
+
+    let create = test::TestRequest::post()
+        .uri("/order")
+        .set_json(json!({ "price": 100.0, "quantity": 2.0, "user_id": "alice", "side": "Buy" }))
+        .to_request();
+    let created: CreateOrderResponse = test::call_and_read_body_json(&app, create).await;
+    assert!(created.order_id.starts_with("ETH-"), "{}", created.order_id);
+
+    let delete = test::TestRequest::delete()
+        .uri("/order")
+        .set_json(json!({ "order_id": created.order_id, "user_id": "alice" }))
+        .to_request();
+    let deleted: DeleteOrderResponse = test::call_and_read_body_json(&app, delete).await;
+    assert!(deleted.success);
+    assert_eq!(deleted.remaining_quantity, 2.0);
+    assert!(book.get_best_bid().is_none());
+}
+
+#[actix_web::test]
+async fn enveloped_response_wraps_payload_with_server_time() {
+    let (book, books) = single_book();
+    book.add_order(OrderSide::Ask, 101.0, 2.0, 1, "maker".to_string()).unwrap();
+    let app = init_app!(books);
+
+    let request = test::TestRequest::get()
+        .uri("/depth")
+        .insert_header(("Accept", routes::ENVELOPE_MEDIA_TYPE))
+        .to_request();
+    let before = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+    let envelope: ResponseEnvelope<DepthResponse> = test::call_and_read_body_json(&app, request).await;
+
+    assert!(envelope.server_time_ms >= before);
+    assert_eq!(envelope.symbol, "BTC");
+    assert_eq!(envelope.data.asks[0].price, 101.0);
+    assert_eq!(envelope.data.asks[0].quantity, 2.0);
+
+    // Without the media type the payload comes bare.
+    let bare: DepthResponse = test::call_and_read_body_json(&app, test::TestRequest::get().uri("/depth").to_request()).await;
+    assert_eq!(bare.asks.len(), 1);
+}
+
+#[actix_web::test]
+async fn client_metadata_round_trips_through_create_query_and_cancel() {
+    let (_, books) = single_book();
+    let app = init_app!(books);
+    let metadata = "strategy=mm;leg=2";
+
+    let create = test::TestRequest::post()
+        .uri("/order")
+        .set_json(json!({ "price": 99.0, "quantity": 1.0, "user_id": "alice", "side": "Buy", "client_metadata": metadata }))
+        .to_request();
+    let created: CreateOrderResponse = test::call_and_read_body_json(&app, create).await;
+    assert_eq!(created.client_metadata.as_deref(), Some(metadata));
+
+    let query = test::TestRequest::get().uri(&format!("/order/{}", created.order_id)).to_request();
+    let queried: OrderResponse = test::call_and_read_body_json(&app, query).await;
+    assert_eq!(queried.client_metadata.as_deref(), Some(metadata));
+
+    let delete = test::TestRequest::delete()
+        .uri("/order")
+        .set_json(json!({ "order_id": created.order_id, "user_id": "alice" }))
+        .to_request();
+    let deleted: DeleteOrderResponse = test::call_and_read_body_json(&app, delete).await;
+    assert_eq!(deleted.client_metadata.as_deref(), Some(metadata));
+}
+
+#[actix_web::test]
+async fn only_admins_may_place_fee_exempt_orders() {
+    let (book, books) = single_book();
+    let mut api_keys = ApiKeys::new();
+    api_keys.insert("alice-key", "alice");
+    api_keys.insert("ops-key", "ops");
+    api_keys.add_admin("ops");
+    let app = init_app!(books, api_keys);
+
+    let create = |key: &str| {
+        test::TestRequest::post()
+            .uri("/order")
+            .insert_header(("X-API-Key", key))
+            .set_json(json!({ "price": 99.0, "quantity": 1.0, "user_id": "alice", "side": "Buy", "fee_exempt": true }))
+            .to_request()
+    };
+
+    assert_eq!(test::call_service(&app, create("alice-key")).await.status(), 403);
+    assert!(book.get_best_bid().is_none());
+    assert_eq!(test::call_service(&app, create("ops-key")).await.status(), 200);
+    assert_eq!(book.get_best_bid(), Some(99.0));
+}
+
+#[actix_web::test]
+async fn deleting_a_filled_market_order_reports_it_as_terminal() {
+    let (book, books) = single_book();
+    book.add_order(OrderSide::Ask, 100.0, 1.0, 1, "maker".to_string()).unwrap();
+    let app = init_app!(books);
+
+    let create = test::TestRequest::post()
+        .uri("/order")
+        .set_json(json!({ "price": 0.0, "quantity": 1.0, "user_id": "taker", "side": "Buy", "order_type": "Market" }))
+        .to_request();
+    let created: CreateOrderResponse = test::call_and_read_body_json(&app, create).await;
+    assert_eq!(created.filled_quantity, 1.0);
+
+    let delete = |order_id: &str| {
+        test::TestRequest::delete()
+            .uri("/order")
+            .set_json(json!({ "order_id": order_id, "user_id": "taker" }))
+            .to_request()
+    };
+    let deleted: DeleteOrderResponse = test::call_and_read_body_json(&app, delete(&created.order_id)).await;
+    assert!(!deleted.success);
+    assert_eq!(deleted.reason, Some(CancelRejectReason::AlreadyFilled));
+
+    let unknown: DeleteOrderResponse = test::call_and_read_body_json(&app, delete("BTC-999999")).await;
+    assert_eq!(unknown.reason, Some(CancelRejectReason::NotFound));
+}
+
+#[actix_web::test]
+async fn resting_limit_orders_say_why_they_rested() {
+    let (book, books) = single_book();
+    book.add_order(OrderSide::Ask, 101.0, 1.0, 1, "maker".to_string()).unwrap();
+    let app = init_app!(books);
+
+    let create = |price: f64, quantity: f64| {
+        test::TestRequest::post()
+            .uri("/order")
+            .set_json(json!({ "price": price, "quantity": quantity, "user_id": "alice", "side": "Buy" }))
+            .to_request()
+    };
+
+    let resting: CreateOrderResponse = test::call_and_read_body_json(&app, create(100.0, 1.0)).await;
+    assert_eq!(resting.rest_reason, Some(RestReason::NoMarketableLiquidity));
+
+    let filled: CreateOrderResponse = test::call_and_read_body_json(&app, create(101.0, 0.5)).await;
+    assert_eq!(filled.rest_reason, None);
+
+    let swept: CreateOrderResponse = test::call_and_read_body_json(&app, create(101.0, 2.0)).await;
+    assert_eq!(swept.filled_quantity, 0.5);
+    assert_eq!(swept.rest_reason, Some(RestReason::LimitPriceReached));
+}
+
+#[actix_web::test]
+async fn trades_since_excludes_older_prints() {
+    let (book, books) = single_book();
+    for timestamp in [1_000, 2_000, 3_000] {
+        book.add_order(OrderSide::Ask, 100.0, 1.0, timestamp, "maker".to_string()).unwrap();
+        book.add_order(OrderSide::Bid, 100.0, 1.0, timestamp, "taker".to_string()).unwrap();
+    }
+    let app = init_app!(books);
+    let trades = |query: &str| test::TestRequest::get().uri(&format!("/trades?{}", query)).to_request();
+
+    let since: TradesResponse = test::call_and_read_body_json(&app, trades("since_ms=2000")).await;
+    assert_eq!(since.trades.iter().map(|t| t.timestamp).collect::<Vec<_>>(), vec![2_000, 3_000]);
+    assert!(!since.has_more);
+
+    let limited: TradesResponse = test::call_and_read_body_json(&app, trades("since_ms=1000&limit=2")).await;
+    assert_eq!(limited.trades.iter().map(|t| t.timestamp).collect::<Vec<_>>(), vec![1_000, 2_000]);
+    assert!(limited.has_more);
+
+    let none: TradesResponse = test::call_and_read_body_json(&app, trades("since_ms=3001")).await;
+    assert!(none.trades.is_empty());
+}
+
+#[actix_web::test]
+async fn config_reflects_a_customised_book() {
+    let config = OrderBookConfig::default()
+        .with_symbol("ETH")
+        .with_tick_size(0.05)
+        .with_quantity_scale(1_000, PrecisionPolicy::Reject)
+        .with_fee_schedule(FeeSchedule::new(0.0001, 0.0005))
+        .with_allowed_order_kinds([OrderKind::Market])
+        .with_min_resting_ms(250);
+    let app = init_app!(BookRegistry::new(Arc::new(OrderBook::with_config(config))));
+
+    let response: ConfigResponse = test::call_and_read_body_json(&app, test::TestRequest::get().uri("/config").to_request()).await;
+
+    assert_eq!(response.symbol, "ETH");
+    assert_eq!(response.tick_size, 0.05);
+    assert_eq!(response.lot_size, 0.001);
+    assert_eq!(response.precision_policy, PrecisionPolicy::Reject);
+    assert_eq!((response.fees.maker_rate, response.fees.taker_rate), (0.0001, 0.0005));
+    assert_eq!(response.order_types, vec!["Market"]);
+    assert_eq!(response.min_resting_ms, Some(250));
+}
+
+#[actix_web::test]
+async fn archive_holds_all_four_sections_and_parses_back() {
+    let (book, books) = single_book();
+    // More prints than fit in one streamed chunk.
+    for i in 0..1_500u64 {
+        book.add_order(OrderSide::Ask, 100.0, 1.0, i, "maker".to_string()).unwrap();
+        book.add_order(OrderSide::Bid, 100.0, 1.0, i, "taker".to_string()).unwrap();
+    }
+    book.add_order(OrderSide::Bid, 99.0, 2.0, 2_000, "alice".to_string()).unwrap();
+    let mut api_keys = ApiKeys::new();
+    api_keys.insert("ops-key", "ops");
+    api_keys.add_admin("ops");
+    let app = init_app!(books, api_keys);
+
+    let request = test::TestRequest::get().uri("/admin/archive").insert_header(("X-API-Key", "ops-key")).to_request();
+    let body = test::call_and_read_body(&app, request).await;
+    let archive: SessionArchive = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(archive.symbol, "BTC");
+    assert_eq!(archive.config.symbol, "BTC");
+    assert_eq!(archive.stats.total_volume_traded, 150_000.0);
+    assert_eq!(archive.snapshot.bids.len(), 1);
+    assert_eq!(archive.snapshot.bids[0].price.as_f64(), 99.0);
+    assert_eq!(archive.trades.len(), 1_500);
+    assert_eq!(archive.trades.last().map(|t| t.timestamp), Some(1_499));
+}
+
+#[actix_web::test]
+async fn amend_refuses_a_malformed_user_id() {
+    let (book, books) = single_book();
+    let (order_id, _) = book.add_order(OrderSide::Bid, 99.0, 1.0, 1, "alice".to_string()).unwrap();
+    let app = init_app!(books);
+
+    for user_id in ["", "not a valid id!"] {
+        let request = test::TestRequest::patch()
+            .uri("/order")
+            .set_json(json!({ "order_id": format!("BTC-{:06}", order_id), "user_id": user_id, "price": 98.0 }))
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), 400);
+    }
+    assert_eq!(book.get_best_bid(), Some(99.0));
+}
+
+#[actix_web::test]
+async fn pct_depth_gives_each_level_as_an_offset_from_mid() {
+    let (book, books) = single_book();
+    book.add_order(OrderSide::Bid, 99.0, 1.0, 1, "maker".to_string()).unwrap();
+    book.add_order(OrderSide::Bid, 95.0, 2.0, 2, "maker".to_string()).unwrap();
+    book.add_order(OrderSide::Ask, 101.0, 1.0, 3, "maker".to_string()).unwrap();
+    book.add_order(OrderSide::Ask, 110.0, 3.0, 4, "maker".to_string()).unwrap();
+    let app = init_app!(books);
+
+    let request = test::TestRequest::get().uri("/depth?normalize=pct").to_request();
+    let depth: NormalizedDepthResponse = test::call_and_read_body_json(&app, request).await;
+
+    assert_eq!(depth.mid, Some(100.0));
+    let offsets = |levels: &[NormalizedDepthLevel]| levels.iter().map(|l| (l.price, l.pct_from_mid.unwrap())).collect::<Vec<_>>();
+    assert_eq!(offsets(&depth.bids), vec![(99.0, -1.0), (95.0, -5.0)]);
+    assert_eq!(offsets(&depth.asks), vec![(101.0, 1.0), (110.0, 10.0)]);
+    assert_eq!(depth.asks[1].quantity, 3.0);
+
+    // One-sided, there's no mid to measure from.
+    let (book, books) = single_book();
+    book.add_order(OrderSide::Bid, 99.0, 1.0, 1, "maker".to_string()).unwrap();
+    let app = init_app!(books);
+    let request = test::TestRequest::get().uri("/depth?normalize=pct").to_request();
+    let depth: NormalizedDepthResponse = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(depth.mid, None);
+    assert_eq!(depth.bids[0].pct_from_mid, None);
+}
+
+#[actix_web::test]
+async fn resilience_reports_the_move_and_the_depth_left_beyond_it() {
+    let (book, books) = single_book();
+    for (i, (price, quantity)) in [(100.0, 1.0), (101.0, 2.0), (102.0, 3.0), (105.0, 4.0)].into_iter().enumerate() {
+        book.add_order(OrderSide::Ask, price, quantity, i as u64, "maker".to_string()).unwrap();
+    }
+    let app = init_app!(books);
+
+    let request = test::TestRequest::get().uri("/resilience?side=buy&quantity=2.5").to_request();
+    let sweep: ResilienceResponse = test::call_and_read_body_json(&app, request).await;
+
+    assert_eq!(sweep.filled_quantity, 2.5);
+    assert!((sweep.average_price.unwrap() - 100.6).abs() < 1e-9);
+    assert_eq!((sweep.start_price, sweep.end_price), (Some(100.0), Some(101.0)));
+    assert_eq!(sweep.price_move, 1.0);
+    assert_eq!(sweep.price_move_pct, Some(1.0));
+    assert_eq!(sweep.levels_swept, 2);
+    assert_eq!(sweep.levels_remaining, 2);
+    assert_eq!(sweep.quantity_remaining, 7.0);
+    assert_eq!(sweep.resulting_best, Some(101.0));
+    assert!(sweep.complete);
+    // Only simulated: the book is as it was.
+    assert_eq!(book.get_market_depth(1).1, vec![(100.0, 1.0)]);
+}
+
+#[actix_web::test]
+async fn requests_reach_the_book_their_symbol_names() {
+    let btc = Arc::new(OrderBook::new());
+    let sol = Arc::new(OrderBook::with_config(OrderBookConfig::default().with_symbol("SOL")));
+    let app = init_app!(BookRegistry::new(btc.clone()).with_book(sol.clone()));
+
+    let request = test::TestRequest::post()
+        .uri("/order")
+        .set_json(json!({ "price": 150.0, "quantity": 2.0, "user_id": "alice", "side": "Sell", "symbol": "sol" }))
+        .to_request();
+    let created: CreateOrderResponse = test::call_and_read_body_json(&app, request).await;
+    assert!(created.order_id.starts_with("SOL-"));
+    assert_eq!(sol.get_best_ask(), Some(150.0));
+    assert_eq!(btc.get_best_ask(), None);
+
+    for uri in ["/depth/SOL", "/depth?symbol=sol"] {
+        let depth: DepthResponse = test::call_and_read_body_json(&app, test::TestRequest::get().uri(uri).to_request()).await;
+        assert_eq!(depth.asks[0].price, 150.0);
+    }
+    let depth: DepthResponse = test::call_and_read_body_json(&app, test::TestRequest::get().uri("/depth").to_request()).await;
+    assert!(depth.asks.is_empty());
+
+    let request = test::TestRequest::get().uri(&format!("/order/{}", created.order_id)).to_request();
+    let order: OrderResponse = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(order.price, 150.0);
+
+    let request = test::TestRequest::get().uri("/depth/XRP").to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), 404);
+}
+
+#[actix_web::test]
+async fn cancel_everywhere_clears_the_user_on_every_symbol() {
+    let btc = Arc::new(OrderBook::new());
+    let sol = Arc::new(OrderBook::with_config(OrderBookConfig::default().with_symbol("SOL")));
+    btc.add_order(OrderSide::Bid, 100.0, 1.0, 1, "alice".to_string()).unwrap();
+    btc.add_order(OrderSide::Ask, 105.0, 1.0, 2, "bob".to_string()).unwrap();
+    sol.add_order(OrderSide::Bid, 20.0, 3.0, 3, "alice".to_string()).unwrap();
+    sol.add_order(OrderSide::Ask, 25.0, 3.0, 4, "alice".to_string()).unwrap();
+    let mut api_keys = ApiKeys::new();
+    api_keys.insert("alice-key", "alice");
+    api_keys.insert("bob-key", "bob");
+    let app = init_app!(BookRegistry::new(btc.clone()).with_book(sol.clone()), api_keys);
+
+    let cancel = |key: &str| {
+        test::TestRequest::delete()
+            .uri("/orders/all?user_id=alice")
+            .insert_header(("X-API-Key", key))
+            .to_request()
+    };
+    assert_eq!(test::call_service(&app, cancel("bob-key")).await.status(), 403);
+    assert_eq!(sol.get_user_orders("alice").len(), 2);
+
+    let request = cancel("alice-key");
+    let response: CancelEverywhereResponse = test::call_and_read_body_json(&app, request).await;
+
+    assert_eq!(response.cancelled_count, 3);
+    assert_eq!(response.symbols["SOL"].cancelled_count, 2);
+    assert!(response.symbols["SOL"].order_ids.iter().all(|id| id.starts_with("SOL-")));
+    assert_eq!(response.symbols.values().map(|summary| summary.cancelled_count).collect::<Vec<_>>(), vec![1, 2]);
+    assert!(btc.get_user_orders("alice").is_empty());
+    assert!(sol.get_user_orders("alice").is_empty());
+    assert_eq!(btc.get_best_ask(), Some(105.0));
+}
+
+#[actix_web::test]
+async fn each_quote_market_is_its_own_book() {
+    let usd = Arc::new(OrderBook::with_config(OrderBookConfig::default().with_symbol("BTC-USD")));
+    let usdt = Arc::new(OrderBook::with_config(OrderBookConfig::default().with_symbol("BTC-USDT")));
+    let app = init_app!(BookRegistry::new(usd.clone()).with_book(usdt.clone()));
+
+    let mut order_ids = Vec::new();
+    for (symbol, price) in [("BTC-USD", 43_000.0), ("BTC-USDT", 43_050.0)] {
+        let request = test::TestRequest::post()
+            .uri("/order")
+            .set_json(json!({ "price": price, "quantity": 1.0, "user_id": "alice", "side": "Buy", "symbol": symbol }))
+            .to_request();
+        let created: CreateOrderResponse = test::call_and_read_body_json(&app, request).await;
+        order_ids.push(created.order_id);
+    }
+    assert_eq!(order_ids, vec!["BTC-USD-000001", "BTC-USDT-000001"]);
+    assert_eq!(usd.get_best_bid(), Some(43_000.0));
+    assert_eq!(usdt.get_best_bid(), Some(43_050.0));
+
+    // The same engine id on each book, told apart by the symbol in the id.
+    for (order_id, price) in order_ids.iter().zip([43_000.0, 43_050.0]) {
+        let request = test::TestRequest::get().uri(&format!("/order/{}", order_id)).to_request();
+        let order: OrderResponse = test::call_and_read_body_json(&app, request).await;
+        assert_eq!(order.price, price);
+    }
+    let depth: DepthResponse = test::call_and_read_body_json(&app, test::TestRequest::get().uri("/depth/btc-usdt").to_request()).await;
+    assert_eq!(depth.bids[0].price, 43_050.0);
+}
+
+#[actix_web::test]
+async fn resync_stops_each_side_once_it_covers_max_notional() {
+    let (book, books) = single_book();
+    for (side, price, quantity) in [
+        (OrderSide::Bid, 100.0, 1.0),
+        (OrderSide::Bid, 99.0, 1.0),
+        (OrderSide::Bid, 98.0, 5.0),
+        (OrderSide::Ask, 101.0, 1.0),
+        (OrderSide::Ask, 102.0, 1.0),
+        (OrderSide::Ask, 103.0, 1.0),
+    ] {
+        book.add_order(side, price, quantity, 1, "maker".to_string()).unwrap();
+    }
+    let app = init_app!(books);
+    let prices = |levels: &[DepthLevel]| levels.iter().map(|level| level.price).collect::<Vec<_>>();
+
+    // 100 + 99 and 101 + 102 are the first to reach 150 on each side.
+    let request = test::TestRequest::get().uri("/depth/resync?max_notional=150").to_request();
+    let resync: DepthResyncResponse = test::call_and_read_body_json(&app, request).await;
+    assert!(resync.truncated);
+    assert_eq!(prices(&resync.bids), vec![100.0, 99.0]);
+    assert_eq!(prices(&resync.asks), vec![101.0, 102.0]);
+
+    let request = test::TestRequest::get().uri("/depth/resync?max_notional=10000").to_request();
+    let resync: DepthResyncResponse = test::call_and_read_body_json(&app, request).await;
+    assert!(!resync.truncated);
+    assert_eq!((resync.bids.len(), resync.asks.len()), (3, 3));
+
+    let request = test::TestRequest::get().uri("/depth/resync?max_notional=0").to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), 400);
+}
+
+#[actix_web::test]
+async fn a_market_order_is_refused_on_a_limit_only_book() {
+    let config = OrderBookConfig::default()
+        .with_symbol("SOL")
+        .with_allowed_order_kinds([OrderKind::Limit(TimeInForce::Gtc)]);
+    let book = Arc::new(OrderBook::with_config(config));
+    book.add_order(OrderSide::Ask, 150.0, 1.0, 1, "maker".to_string()).unwrap();
+    let app = init_app!(BookRegistry::new(book.clone()));
+
+    let request = test::TestRequest::post()
+        .uri("/order")
+        .set_json(json!({ "price": 0.0, "quantity": 1.0, "user_id": "taker", "side": "Buy", "order_type": "Market" }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), 400);
+    let body = String::from_utf8(test::read_body(response).await.to_vec()).unwrap();
+    assert!(body.contains("rejected: market orders are not enabled on SOL"), "{}", body);
+    assert_eq!(book.get_market_depth(1).1, vec![(150.0, 1.0)]);
+
+    // Limit orders still go through.
+    let request = test::TestRequest::post()
+        .uri("/order")
+        .set_json(json!({ "price": 150.0, "quantity": 1.0, "user_id": "taker", "side": "Buy" }))
+        .to_request();
+    let created: CreateOrderResponse = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(created.filled_quantity, 1.0);
+}
+
+#[actix_web::test]
+async fn malformed_user_ids_are_refused_on_entry_cancel_and_lookup() {
+    let (book, books) = single_book();
+    let (order_id, _) = book.add_order(OrderSide::Bid, 99.0, 1.0, 1, "alice".to_string()).unwrap();
+    let app = init_app!(books);
+
+    for user_id in [String::new(), "a".repeat(65), "bob smith".to_string()] {
+        let create = test::TestRequest::post()
+            .uri("/order")
+            .set_json(json!({ "price": 99.0, "quantity": 1.0, "user_id": user_id, "side": "Buy" }))
+            .to_request();
+        assert_eq!(test::call_service(&app, create).await.status(), 400, "create as {:?}", user_id);
+
+        let delete = test::TestRequest::delete()
+            .uri("/order")
+            .set_json(json!({ "order_id": format!("BTC-{:06}", order_id), "user_id": user_id }))
+            .to_request();
+        assert_eq!(test::call_service(&app, delete).await.status(), 400, "delete as {:?}", user_id);
+
+        let lookup = test::TestRequest::get().uri(&format!("/orders?user_id={}", user_id.replace(' ', "%20"))).to_request();
+        assert_eq!(test::call_service(&app, lookup).await.status(), 400, "lookup as {:?}", user_id);
+    }
+    // 64 characters is still fine.
+    let create = test::TestRequest::post()
+        .uri("/order")
+        .set_json(json!({ "price": 98.0, "quantity": 1.0, "user_id": "a".repeat(64), "side": "Buy" }))
+        .to_request();
+    assert!(test::call_service(&app, create).await.status().is_success());
+    assert_eq!(book.get_market_depth(5).0, vec![(99.0, 1.0), (98.0, 1.0)]);
+}
+
+#[actix_web::test]
+async fn the_second_of_three_orders_is_one_back_in_its_queue() {
+    let (book, books) = single_book();
+    let ids: Vec<u64> = (0..3)
+        .map(|i| book.add_order(OrderSide::Bid, 99.0, 1.0, i, format!("maker{}", i)).unwrap().0)
+        .collect();
+    book.add_order(OrderSide::Bid, 98.0, 1.0, 3, "maker".to_string()).unwrap();
+    assert_eq!(book.queue_position(ids[1]), Some((1, 3)));
+    let app = init_app!(books);
+
+    let position = |id: u64| test::TestRequest::get().uri(&format!("/order/BTC-{:06}/position", id)).to_request();
+    let response: QueuePositionResponse = test::call_and_read_body_json(&app, position(ids[1])).await;
+    assert_eq!(response.order_id, format!("BTC-{:06}", ids[1]));
+    assert_eq!((response.position, response.orders_at_level), (1, 3));
+
+    // Moves up as the order ahead of it fills.
+    book.add_order(OrderSide::Ask, 99.0, 1.0, 4, "taker".to_string()).unwrap();
+    let response: QueuePositionResponse = test::call_and_read_body_json(&app, position(ids[1])).await;
+    assert_eq!((response.position, response.orders_at_level), (0, 2));
+    assert_eq!(test::call_service(&app, position(ids[0])).await.status(), 404);
+}
+
+#[actix_web::test]
+async fn columnar_export_lines_up_with_the_row_export() {
+    let (book, books) = single_book();
+    for (i, (price, quantity)) in [(100.0, 1.0), (101.5, 0.25), (99.0, 3.0)].into_iter().enumerate() {
+        let timestamp = 1_000 + i as u64;
+        book.add_order(OrderSide::Ask, price, quantity, timestamp, "maker".to_string()).unwrap();
+        book.add_order(OrderSide::Bid, price, quantity, timestamp, "taker".to_string()).unwrap();
+    }
+    let app = init_app!(books);
+
+    let body = test::call_and_read_body(&app, test::TestRequest::get().uri("/trades/export").to_request()).await;
+    let rows: Vec<Trade> = std::str::from_utf8(&body)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(rows.len(), 3);
+
+    let request = test::TestRequest::get().uri("/trades/export?format=columnar").to_request();
+    let columns: TradeColumns = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(columns.price, vec![100.0, 101.5, 99.0]);
+    for len in [columns.bid_order_id.len(), columns.ask_order_id.len(), columns.quantity.len(), columns.timestamp.len(), columns.maker_fee.len(), columns.taker_fee.len(), columns.taker_side.len()] {
+        assert_eq!(len, columns.len());
+    }
+    let rebuilt = columns.to_trades().unwrap();
+    assert_eq!(serde_json::to_value(&rebuilt).unwrap(), serde_json::to_value(&rows).unwrap());
+
+    let request = test::TestRequest::get().uri("/trades/export?format=csv").to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), 400);
+}
+
+#[actix_web::test]
+async fn an_entry_with_a_take_profit_places_a_child_for_each_fill() {
+    let (book, books) = single_book();
+    book.add_order(OrderSide::Ask, 100.0, 1.0, 1, "maker".to_string()).unwrap();
+    let app = init_app!(books);
+
+    let request = test::TestRequest::post()
+        .uri("/order")
+        .set_json(json!({
+            "price": 100.0, "quantity": 3.0, "user_id": "alice", "side": "Buy",
+            "take_profit": { "offset": 5.0 }
+        }))
+        .to_request();
+    let created: CreateOrderResponse = test::call_and_read_body_json(&app, request).await;
+    assert_eq!((created.filled_quantity, created.remaining_quantity), (1.0, 2.0));
+    assert_eq!(created.take_profit_order_ids.len(), 1);
+
+    // Sized to the partial fill, on the other side, `offset` above it.
+    let request = test::TestRequest::get().uri(&format!("/order/{}", created.take_profit_order_ids[0])).to_request();
+    let child: OrderResponse = test::call_and_read_body_json(&app, request).await;
+    assert_eq!((child.user_id.as_str(), child.side, child.price, child.remaining_quantity), ("alice", Side::Sell, 105.0, 1.0));
+
+    // The resting remainder filling later places another.
+    book.add_order(OrderSide::Ask, 100.0, 1.5, 2, "maker".to_string()).unwrap();
+    let entry_id = created.order_id.rsplit('-').next().unwrap().parse().unwrap();
+    assert_eq!(book.take_profit_orders(entry_id).len(), 2);
+    assert_eq!(book.get_market_depth(5).1, vec![(105.0, 2.5)]);
+}
+
+#[actix_web::test]
+async fn a_maker_buying_the_bid_and_selling_the_ask_captures_the_spread() {
+    let (book, books) = single_book();
+    book.add_order(OrderSide::Bid, 99.0, 2.0, 1, "mm".to_string()).unwrap();
+    book.add_order(OrderSide::Ask, 101.0, 2.0, 2, "mm".to_string()).unwrap();
+    book.add_order(OrderSide::Ask, 99.0, 2.0, 3, "seller".to_string()).unwrap();
+    let app = init_app!(books);
+
+    let account = |user_id: &str| test::TestRequest::get().uri(&format!("/account?user_id={}", user_id)).to_request();
+    let half: AccountResponse = test::call_and_read_body_json(&app, account("mm")).await;
+    assert_eq!((half.maker.bought_quantity, half.maker.average_buy_price), (2.0, Some(99.0)));
+    assert_eq!((half.maker.captured_spread, half.maker.net_edge), (None, None));
+
+    book.add_order(OrderSide::Bid, 101.0, 2.0, 4, "buyer".to_string()).unwrap();
+    let mm: AccountResponse = test::call_and_read_body_json(&app, account("mm")).await;
+    assert_eq!((mm.maker.sold_quantity, mm.maker.average_sell_price), (2.0, Some(101.0)));
+    assert_eq!(mm.maker.captured_spread, Some(2.0));
+    assert_eq!(mm.maker.net_edge, Some(4.0 - mm.maker.fees));
+    assert!(mm.maker.net_edge.unwrap() > 0.0);
+
+    // The takers on the other side of those fills made nothing.
+    let seller: AccountResponse = test::call_and_read_body_json(&app, account("seller")).await;
+    assert_eq!((seller.maker.sold_quantity, seller.maker.captured_spread), (0.0, None));
+}
@@ -0,0 +1,69 @@
+// The kill switch is one flag for the whole process, so it gets a test
+// binary of its own rather than halting the books of tests running
+// alongside it.
+use std::sync::Arc;
+
+use actix_web::web::Data;
+use actix_web::{test, App};
+use serde_json::json;
+
+use order_book_hybrid::api::auth::ApiKeys;
+use order_book_hybrid::api::books::BookRegistry;
+use order_book_hybrid::api::routes;
+use order_book_hybrid::api::types::KillSwitchResponse;
+use order_book_hybrid::engine::config::OrderBookConfig;
+use order_book_hybrid::engine::killswitch;
+use order_book_hybrid::engine::order::OrderSide;
+use order_book_hybrid::engine::orderbook::OrderBook;
+
+#[actix_web::test]
+async fn a_killed_venue_refuses_orders_on_every_symbol_until_revived() {
+    let btc = Arc::new(OrderBook::new());
+    let sol = Arc::new(OrderBook::with_config(OrderBookConfig::default().with_symbol("SOL")));
+    btc.add_order(OrderSide::Bid, 100.0, 1.0, 1, "alice".to_string()).unwrap();
+    sol.add_order(OrderSide::Ask, 25.0, 2.0, 2, "alice".to_string()).unwrap();
+    sol.add_order(OrderSide::Ask, 26.0, 2.0, 3, "bob".to_string()).unwrap();
+
+    let mut api_keys = ApiKeys::new();
+    api_keys.insert("admin-key", "admin");
+    api_keys.add_admin("admin");
+    api_keys.insert("alice-key", "alice");
+    let app = test::init_service(
+        App::new()
+            .app_data(Data::new(BookRegistry::new(btc.clone()).with_book(sol.clone())))
+            .app_data(Data::new(api_keys))
+            .service(routes::create_order)
+            .service(routes::kill_trading)
+            .service(routes::revive_trading),
+    )
+    .await;
+
+    let admin = |uri: &str, key: &str| test::TestRequest::post().uri(uri).insert_header(("X-API-Key", key)).to_request();
+    let order = |symbol: &str, price: f64| {
+        test::TestRequest::post()
+            .uri("/order")
+            .set_json(json!({ "price": price, "quantity": 1.0, "user_id": "carol", "side": "Buy", "symbol": symbol }))
+            .to_request()
+    };
+
+    assert_eq!(test::call_service(&app, admin("/admin/kill", "alice-key")).await.status(), 403);
+    assert!(!killswitch::is_engaged());
+
+    let killed: KillSwitchResponse = test::call_and_read_body_json(&app, admin("/admin/kill?cancel=true", "admin-key")).await;
+    assert!(killed.killed);
+    assert_eq!(killed.cancelled_orders.values().collect::<Vec<_>>(), vec![&1, &2]);
+    assert!(killswitch::is_engaged());
+
+    for (symbol, price) in [("BTC", 99.0), ("SOL", 30.0)] {
+        assert_eq!(test::call_service(&app, order(symbol, price)).await.status(), 400);
+    }
+    assert!(btc.add_order(OrderSide::Bid, 99.0, 1.0, 4, "carol".to_string()).is_err());
+    assert_eq!((btc.get_best_bid(), sol.get_best_ask()), (None, None));
+
+    let revived: KillSwitchResponse = test::call_and_read_body_json(&app, admin("/admin/revive", "admin-key")).await;
+    assert!(!revived.killed);
+    for (symbol, price) in [("BTC", 99.0), ("SOL", 30.0)] {
+        assert!(test::call_service(&app, order(symbol, price)).await.status().is_success());
+    }
+    assert_eq!((btc.get_best_bid(), sol.get_best_bid()), (Some(99.0), Some(30.0)));
+}
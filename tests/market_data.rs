@@ -0,0 +1,297 @@
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use order_book_hybrid::engine::candles::CandleInterval;
+use order_book_hybrid::engine::config::OrderBookConfig;
+use order_book_hybrid::engine::order::OrderSide;
+use order_book_hybrid::engine::orderbook::{bucket_depth, OrderBook, OrderBookStats};
+use order_book_hybrid::engine::trade::Trade;
+use order_book_hybrid::events::OrderBookEvent;
+
+// Prints one lot at `price`, with maker and taker both stamped `timestamp`.
+fn print_trade(book: &OrderBook, price: f64, quantity: f64, timestamp: u64) {
+    book.add_order(OrderSide::Ask, price, quantity, timestamp, "maker".to_string()).unwrap();
+    let (_, trades) = book.add_order(OrderSide::Bid, price, quantity, timestamp, "taker".to_string()).unwrap();
+    assert_eq!(trades.len(), 1);
+}
+
+#[test]
+fn realized_volatility_of_hand_built_series() {
+    let book = OrderBook::new();
+    print_trade(&book, 100.0, 1.0, 1_000);
+    print_trade(&book, 110.0, 1.0, 2_000);
+    print_trade(&book, 99.0, 1.0, 3_000);
+
+    // Two returns, ln(1.1) and ln(0.9); each sits half their gap from the mean.
+    let expected = ((1.1f64).ln() - (0.9f64).ln()) / 2.0;
+    let volatility = book.realized_volatility_at(Duration::from_secs(10), 3_000).unwrap();
+    assert!((volatility - expected).abs() < 1e-12);
+
+    // Only the last two prints fall in a 1.5s window: one return, no spread.
+    assert_eq!(book.realized_volatility_at(Duration::from_millis(1_500), 3_000), Some(0.0));
+    assert_eq!(book.realized_volatility_at(Duration::from_millis(500), 3_000), None);
+}
+
+#[test]
+fn realized_volatility_counts_trades_against_long_resting_orders() {
+    let book = OrderBook::new();
+    book.add_order(OrderSide::Ask, 100.0, 1.0, 1_000, "maker".to_string()).unwrap();
+    book.add_order(OrderSide::Ask, 110.0, 1.0, 1_000, "maker".to_string()).unwrap();
+    book.add_order(OrderSide::Bid, 100.0, 1.0, 400_000, "taker".to_string()).unwrap();
+    book.add_order(OrderSide::Bid, 110.0, 1.0, 401_000, "taker".to_string()).unwrap();
+
+    // Both makers rested well over five minutes before they traded.
+    let volatility = book.realized_volatility_at(Duration::from_secs(300), 401_000);
+    assert_eq!(volatility, Some(0.0));
+}
+
+#[test]
+fn volume_profile_buckets_trades_by_price() {
+    let book = OrderBook::new();
+    print_trade(&book, 100.0, 1.0, 1_000);
+    print_trade(&book, 104.0, 2.0, 2_000);
+    print_trade(&book, 106.0, 3.0, 3_000);
+    print_trade(&book, 110.0, 4.0, 4_000);
+
+    let profile = book.volume_profile_at(Duration::from_secs(60), 2, 5_000);
+    assert_eq!(profile, vec![(102.5, 3.0), (107.5, 7.0)]);
+
+    // The window leaves out the first two prints.
+    let profile = book.volume_profile_at(Duration::from_millis(2_500), 4, 5_000);
+    assert_eq!(profile, vec![(106.5, 3.0), (107.5, 0.0), (108.5, 0.0), (109.5, 4.0)]);
+}
+
+#[test]
+fn volume_profile_keeps_volume_traded_against_old_resting_orders() {
+    let book = OrderBook::new();
+    book.add_order(OrderSide::Ask, 100.0, 5.0, 1_000, "maker".to_string()).unwrap();
+    book.add_order(OrderSide::Bid, 100.0, 2.0, 3_600_000, "taker".to_string()).unwrap();
+
+    // The maker rested long before the window; the trade is inside it.
+    let profile = book.volume_profile_at(Duration::from_secs(300), 10, 3_600_000);
+    assert_eq!(profile, vec![(100.0, 2.0)]);
+}
+
+#[test]
+fn resync_pairs_depth_with_its_exact_sequence() {
+    let book = Arc::new(OrderBook::new());
+    let writer = {
+        let book = book.clone();
+        std::thread::spawn(move || {
+            // Each non-crossing order adds one level and one sequence step.
+            for i in 0..2_000 {
+                book.add_order(OrderSide::Bid, 1.0 + i as f64, 1.0, i, "mm".to_string()).unwrap();
+            }
+        })
+    };
+    while !writer.is_finished() {
+        let (sequence, bids, _) = book.depth_with_sequence(usize::MAX);
+        assert_eq!(bids.len() as u64, sequence);
+    }
+    writer.join().unwrap();
+
+    // Changes after a resync carry the very next sequence numbers.
+    let mut events = book.subscribe();
+    let (sequence, _, _) = book.depth_with_sequence(usize::MAX);
+    book.add_order(OrderSide::Ask, 5_000.0, 1.0, 0, "mm".to_string()).unwrap();
+    book.remove_order_at(1, "mm", 0).unwrap();
+    let sequences: Vec<u64> = std::iter::from_fn(|| events.try_recv().ok())
+        .filter_map(|event| match event {
+            OrderBookEvent::DepthChanged(changed) => Some(changed.sequence),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(sequences, vec![sequence + 1, sequence + 2]);
+}
+
+#[test]
+fn fill_for_notional_walks_a_staircase() {
+    let book = OrderBook::new();
+    for (price, quantity) in [(100.0, 1.0), (101.0, 2.0), (102.0, 3.0)] {
+        book.add_order(OrderSide::Ask, price, quantity, 1, "maker".to_string()).unwrap();
+    }
+
+    // 100 + 202 from the first two steps, the last 48 from the third.
+    let fill = book.fill_for_notional(OrderSide::Bid, 350.0);
+    let quantity = 3.0 + 48.0 / 102.0;
+    assert!(fill.complete);
+    assert!((fill.notional - 350.0).abs() < 1e-9);
+    assert!((fill.quantity - quantity).abs() < 1e-9);
+    assert!((fill.average_price.unwrap() - 350.0 / quantity).abs() < 1e-9);
+
+    let short = book.fill_for_notional(OrderSide::Bid, 1_000.0);
+    assert!(!short.complete);
+    assert!((short.notional - 608.0).abs() < 1e-9);
+    assert_eq!(short.quantity, 6.0);
+}
+
+#[test]
+fn indicative_auction_tracks_a_building_book_without_trading() {
+    let book = OrderBook::with_config(OrderBookConfig::default().with_opening_auction(true));
+    book.add_order(OrderSide::Bid, 101.0, 2.0, 1, "buyer".to_string()).unwrap();
+    book.add_order(OrderSide::Bid, 100.0, 1.0, 2, "buyer".to_string()).unwrap();
+    book.add_order(OrderSide::Ask, 99.0, 1.0, 3, "seller".to_string()).unwrap();
+    let (_, trades) = book.add_order(OrderSide::Ask, 100.0, 2.0, 4, "seller".to_string()).unwrap();
+    assert!(trades.is_empty());
+
+    let auction = book.indicative_auction();
+    assert_eq!(auction.indicative_price, Some(100.0));
+    assert_eq!(auction.matched_volume, 3.0);
+    assert_eq!(auction.imbalance, 0.0);
+
+    // Another bid at the clearing price adds to the imbalance, not the match.
+    book.add_order(OrderSide::Bid, 100.0, 1.0, 5, "buyer".to_string()).unwrap();
+    let auction = book.indicative_auction();
+    assert_eq!(auction.indicative_price, Some(100.0));
+    assert_eq!(auction.matched_volume, 3.0);
+    assert_eq!(auction.imbalance, 1.0);
+    assert!(book.get_recent_trades(10).is_empty());
+}
+
+#[test]
+fn depth_slope_of_a_linear_ladder() {
+    let book = OrderBook::new();
+    assert_eq!(book.depth_slope(10), (None, None));
+    for i in 1..=4u64 {
+        book.add_order(OrderSide::Bid, 100.0 - i as f64, 2.0, i, "mm".to_string()).unwrap();
+        book.add_order(OrderSide::Ask, 100.0 + i as f64, 1.0, i, "mm".to_string()).unwrap();
+    }
+
+    // Every unit away from the mid of 100 adds 2 lots of bids and 1 of asks.
+    let (bid_slope, ask_slope) = book.depth_slope(10);
+    assert!((bid_slope.unwrap() - 2.0).abs() < 1e-9);
+    assert!((ask_slope.unwrap() - 1.0).abs() < 1e-9);
+}
+
+#[test]
+fn twap_weights_each_mid_by_how_long_it_held() {
+    let book = OrderBook::new();
+    assert_eq!(book.twap_at(Duration::from_secs(5), 4_000), None);
+
+    book.add_order(OrderSide::Bid, 99.0, 1.0, 0, "mm".to_string()).unwrap();
+    book.add_order(OrderSide::Ask, 101.0, 1.0, 0, "mm".to_string()).unwrap();
+    book.sample_mid(0);
+    book.add_order(OrderSide::Bid, 100.0, 1.0, 1_000, "mm".to_string()).unwrap();
+    book.sample_mid(1_000);
+
+    // 100 for one second, then 100.5 for three.
+    let twap = book.twap_at(Duration::from_secs(4), 4_000).unwrap();
+    assert!((twap - 100.375).abs() < 1e-9, "{}", twap);
+    assert_eq!(book.twap_at(Duration::from_secs(2), 4_000), Some(100.5));
+}
+
+#[test]
+fn trade_tape_keeps_only_its_capacity_of_latest_prints() {
+    let book = OrderBook::with_config(OrderBookConfig::default().with_trade_history_capacity(3));
+    for (i, price) in [100.0, 101.0, 102.0, 103.0, 104.0].into_iter().enumerate() {
+        print_trade(&book, price, 1.0, 1_000 * i as u64);
+    }
+
+    let prices = |trades: Vec<Trade>| trades.into_iter().map(|t| t.price).collect::<Vec<_>>();
+    assert_eq!(prices(book.get_recent_trades(10)), vec![102.0, 103.0, 104.0]);
+    assert_eq!(prices(book.get_recent_trades(2)), vec![103.0, 104.0]);
+    assert_eq!(prices(book.trades_since(3_000, 10)), vec![103.0, 104.0]);
+}
+
+#[test]
+fn trades_since_goes_by_when_a_trade_executed_not_when_its_maker_rested() {
+    let book = OrderBook::new();
+    book.add_order(OrderSide::Ask, 100.0, 1.0, 1_000, "maker".to_string()).unwrap();
+    print_trade(&book, 99.0, 1.0, 4_000);
+    let (_, trades) = book.add_order(OrderSide::Bid, 100.0, 1.0, 6_000, "taker".to_string()).unwrap();
+    assert_eq!((trades[0].timestamp, trades[0].executed_at), (1_000, 6_000));
+
+    let since = book.trades_since(5_000, 10);
+    assert_eq!(since.iter().map(|t| t.price).collect::<Vec<_>>(), vec![100.0]);
+    assert_eq!(book.trades_since(4_000, 10).len(), 2);
+}
+
+#[test]
+fn mid_ema_lags_a_step_and_converges_on_a_steady_mid() {
+    let alpha = 0.1;
+    let mut stats = OrderBookStats::new();
+    stats.update_market_data(Some(99.0), Some(101.0));
+    stats.mid_ema = stats.mid_price;
+    stats.advance_mid_ema(0, alpha);
+
+    // A steady mid leaves it where it is.
+    stats.advance_mid_ema(5_000, alpha);
+    assert_eq!(stats.mid_ema, Some(100.0));
+
+    // One second after the mid steps to 110 it has moved alpha of the way.
+    stats.update_market_data(Some(109.0), Some(111.0));
+    stats.advance_mid_ema(6_000, alpha);
+    let after_one_second = stats.mid_ema.unwrap();
+    assert!((after_one_second - 101.0).abs() < 1e-9);
+
+    // Ten updates a tenth of a second apart move it as far as one a second later.
+    let mut bursty = stats.clone();
+    for i in 1..=10 {
+        bursty.advance_mid_ema(6_000 + 100 * i, alpha);
+    }
+    stats.advance_mid_ema(7_000, alpha);
+    assert!((bursty.mid_ema.unwrap() - stats.mid_ema.unwrap()).abs() < 1e-9);
+
+    // Still short of 110 after a few seconds, all but there after two minutes.
+    stats.advance_mid_ema(10_000, alpha);
+    assert!(stats.mid_ema.unwrap() < 106.0);
+    stats.advance_mid_ema(120_000, alpha);
+    assert!((stats.mid_ema.unwrap() - 110.0).abs() < 0.01);
+}
+
+#[test]
+fn mid_ema_is_none_until_the_book_has_both_sides() {
+    let book = OrderBook::new();
+    book.add_order(OrderSide::Bid, 99.0, 1.0, 1, "maker".to_string()).unwrap();
+    assert_eq!(book.get_stats().mid_ema, None);
+    book.add_order(OrderSide::Ask, 101.0, 1.0, 2, "maker".to_string()).unwrap();
+    assert!((book.get_stats().mid_ema.unwrap() - 100.0).abs() < 1e-9);
+}
+
+#[test]
+fn each_bucket_reports_the_quantity_weighted_price_of_its_levels() {
+    let asks = [(100.2, 1.0), (100.8, 3.0), (101.0, 2.0), (101.5, 2.0)];
+    let buckets = bucket_depth(&asks, OrderSide::Ask, 1.0, 20);
+    let read: Vec<(f64, f64)> = buckets.iter().map(|bucket| (bucket.price, bucket.quantity)).collect();
+    // 101.0 sits on an edge and stays in the bucket it closes.
+    assert_eq!(read, vec![(101.0, 6.0), (102.0, 2.0)]);
+    assert!((buckets[0].average_price - (100.2 + 3.0 * 100.8 + 2.0 * 101.0) / 6.0).abs() < 1e-9);
+    assert_eq!(buckets[1].average_price, 101.5);
+
+    let bids = [(99.9, 2.0), (99.1, 2.0), (98.0, 1.0), (97.5, 1.0)];
+    let buckets = bucket_depth(&bids, OrderSide::Bid, 1.0, 2);
+    let read: Vec<(f64, f64, f64)> = buckets.iter().map(|b| (b.price, b.quantity, b.average_price)).collect();
+    assert_eq!(read.len(), 2);
+    assert_eq!((read[0].0, read[0].1), (99.0, 4.0));
+    assert!((read[0].2 - 99.5).abs() < 1e-9);
+    assert_eq!((read[1].0, read[1].1, read[1].2), (98.0, 1.0, 98.0));
+}
+
+#[test]
+fn volume_24h_ages_out_a_day_after_it_traded() {
+    const HOUR: u64 = 3_600_000;
+    let book = OrderBook::new();
+    print_trade(&book, 100.0, 2.0, HOUR);
+    print_trade(&book, 50.0, 1.0, 12 * HOUR);
+
+    assert_eq!(book.get_volume_24h(12 * HOUR), 250.0);
+    assert_eq!(book.get_volume_24h(24 * HOUR), 250.0);
+    assert_eq!(book.get_volume_24h(25 * HOUR), 50.0);
+    assert_eq!(book.get_volume_24h(36 * HOUR), 0.0);
+    assert_eq!(book.get_stats().total_volume_traded, 250.0);
+}
+
+#[test]
+fn a_candle_is_bucketed_by_when_its_trade_executed() {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+    let book = OrderBook::new();
+    book.add_order(OrderSide::Ask, 100.0, 2.0, now - 600_000, "maker".to_string()).unwrap();
+    let (_, trades) = book.add_order(OrderSide::Bid, 100.0, 2.0, now, "taker".to_string()).unwrap();
+    assert_eq!(trades[0].timestamp, now - 600_000);
+
+    // Not ten one-minute candles from when the maker rested.
+    let candles = book.get_candles(CandleInterval::OneMinute, 20);
+    assert_eq!(candles[0].start_ms, now - now % 60_000);
+    assert_eq!((candles[0].open, candles[0].volume), (100.0, 2.0));
+    assert!(candles.len() <= 2);
+}
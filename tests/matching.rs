@@ -0,0 +1,732 @@
+use std::sync::Arc;
+use std::thread;
+
+use order_book_hybrid::api::types::{format_order_id, Fill};
+use order_book_hybrid::engine::conditional::{SpreadCondition, SpreadDirection};
+use order_book_hybrid::engine::config::{FeeSchedule, LockedMarketPolicy, OrderBookConfig, OrderKind, SelfTradePolicy, TieBreak, TradeThroughCheck};
+use order_book_hybrid::engine::error::{ConfigError, OrderError};
+use order_book_hybrid::engine::order::{Order, OrderOptions, OrderSide, PegReference, RefreshPeg, TimeInForce};
+use order_book_hybrid::engine::orderbook::{OrderBook, OrderQueue};
+use order_book_hybrid::events::{OrderBookEvent, OrderEventStatus};
+
+#[test]
+fn designated_maker_matches_ahead_of_earlier_order_at_same_price() {
+    let book = OrderBook::with_config(OrderBookConfig::default().with_designated_maker("mm"));
+    let (early_id, _) = book.add_order(OrderSide::Ask, 100.0, 1.0, 1, "early".to_string()).unwrap();
+    let (maker_id, _) = book.add_order(OrderSide::Ask, 100.0, 1.0, 2, "mm".to_string()).unwrap();
+
+    let (_, trades) = book.add_order(OrderSide::Bid, 100.0, 1.0, 3, "taker".to_string()).unwrap();
+
+    assert_eq!(trades.len(), 1);
+    assert_eq!(trades[0].ask_order_id, maker_id);
+    assert!(book.get_order(early_id).is_some());
+}
+
+#[test]
+fn a_sweep_takes_the_designated_maker_first_then_the_rest_in_time_order() {
+    let book = OrderBook::with_config(OrderBookConfig::default().with_designated_maker("mm"));
+    let ask = |user: &str, timestamp| book.add_order(OrderSide::Ask, 100.0, 1.0, timestamp, user.to_string()).unwrap().0;
+    let (a, m1, b, m2, c) = (ask("early", 1), ask("mm", 2), ask("early", 3), ask("mm", 4), ask("early", 5));
+    // A size increase sends the maker's first order behind its second.
+    book.modify_order(m1, "mm", None, Some(2.0)).unwrap();
+
+    let (_, trades) = book.add_order(OrderSide::Bid, 100.0, 6.0, 6, "taker".to_string()).unwrap();
+    assert_eq!(trades.iter().map(|t| t.ask_order_id).collect::<Vec<_>>(), vec![m2, m1, a, b, c]);
+}
+
+#[test]
+fn dead_man_cancels_orders_once_heartbeats_lapse() {
+    let book = OrderBook::new();
+    let (order_id, _) = book.add_order(OrderSide::Bid, 99.0, 1.0, 0, "mm".to_string()).unwrap();
+    book.dead_man().arm("mm", 1_000, 0);
+
+    assert!(book.dead_man().heartbeat("mm", 900));
+    assert!(book.sweep_dead_man(1_500).is_empty());
+    assert!(book.get_order(order_id).is_some());
+
+    let cancelled = book.sweep_dead_man(1_900);
+    assert_eq!(cancelled.iter().map(|o| o.id).collect::<Vec<_>>(), vec![order_id]);
+    assert!(book.get_order(order_id).is_none());
+    assert!(!book.dead_man().heartbeat("mm", 2_000));
+}
+
+#[test]
+fn conditional_order_fires_when_spread_crosses_threshold() {
+    let book = OrderBook::new();
+    book.add_order(OrderSide::Bid, 99.0, 1.0, 1, "mm".to_string()).unwrap();
+    book.add_order(OrderSide::Ask, 105.0, 1.0, 2, "mm".to_string()).unwrap();
+    let condition = SpreadCondition::new(2.0, SpreadDirection::Narrows);
+    let order_id = book
+        .add_conditional_order(OrderSide::Bid, 101.0, 1.0, 3, "trader".to_string(), condition, OrderOptions::default())
+        .unwrap();
+
+    assert_eq!(book.get_pending_conditional_orders().len(), 1);
+    assert!(book.queue_position(order_id).is_none());
+
+    book.add_order(OrderSide::Bid, 103.0, 1.0, 4, "mm".to_string()).unwrap();
+
+    assert!(book.get_pending_conditional_orders().is_empty());
+    assert!(book.queue_position(order_id).is_some());
+    assert_eq!(book.get_order(order_id).map(|o| o.price.as_f64()), Some(101.0));
+}
+
+#[test]
+fn cancel_replace_swaps_orders_in_one_call() {
+    let book = OrderBook::new();
+    let (old_id, _) = book.add_order(OrderSide::Bid, 99.0, 1.0, 1, "alice".to_string()).unwrap();
+    book.add_order(OrderSide::Ask, 101.0, 0.5, 2, "bob".to_string()).unwrap();
+
+    let result = book.cancel_replace(old_id, "alice", OrderSide::Bid, 101.0, 2.0, 3, false).unwrap();
+
+    assert_eq!(result.cancelled.map(|o| o.id), Some(old_id));
+    let new_id = result.order_id.unwrap();
+    assert!(book.get_order(old_id).is_none());
+    assert_eq!(result.trades.len(), 1);
+    assert_eq!(result.remaining_quantity, 1.5);
+    assert_eq!(book.get_order(new_id).map(|o| o.remaining_quantity()), Some(1.5));
+}
+
+#[test]
+fn cancel_replace_refuses_a_bad_replacement_without_cancelling() {
+    let market_only = OrderBook::with_config(OrderBookConfig::default().with_allowed_order_kinds([OrderKind::Market]));
+    let (old_id, _) = market_only
+        .add_order_with_options(OrderSide::Bid, 99.0, 1.0, 1, "feed".to_string(), OrderOptions::default().with_source("venue"))
+        .unwrap();
+    let err = market_only.cancel_replace(old_id, "feed", OrderSide::Bid, 98.0, 1.0, 2, false).unwrap_err();
+    assert!(matches!(err, OrderError::OrderKindNotAllowed { .. }));
+    assert!(market_only.get_order(old_id).is_some());
+
+    let book = OrderBook::with_config(OrderBookConfig::default().with_tick_size(0.5));
+    let (old_id, _) = book.add_order(OrderSide::Bid, 99.0, 1.0, 1, "alice".to_string()).unwrap();
+    let err = book.cancel_replace(old_id, "alice", OrderSide::Bid, 0.2, 1.0, 2, true).unwrap_err();
+    assert_eq!(err, OrderError::InvalidPrice { price: 0.2 });
+    assert!(book.get_order(old_id).is_some());
+
+    let auction = OrderBook::with_config(
+        OrderBookConfig::default()
+            .with_opening_auction(true)
+            .with_locked_market_policy(LockedMarketPolicy::Reject),
+    );
+    auction.add_order(OrderSide::Ask, 100.0, 1.0, 1, "bob".to_string()).unwrap();
+    let (old_id, _) = auction.add_order(OrderSide::Bid, 99.0, 1.0, 2, "alice".to_string()).unwrap();
+    let err = auction.cancel_replace(old_id, "alice", OrderSide::Bid, 100.0, 1.0, 3, false).unwrap_err();
+    assert_eq!(err, OrderError::WouldLockMarket { price: 100.0 });
+    assert!(auction.get_order(old_id).is_some());
+}
+
+#[test]
+fn primary_venue_matches_first_at_equal_price() {
+    let book = OrderBook::with_config(OrderBookConfig::default().with_tie_break(TieBreak::PrimaryVenue("binance".to_string())));
+    let venue = |source: &str| OrderOptions::default().with_source(source);
+    book.add_order_with_options(OrderSide::Ask, 100.0, 1.0, 1, "coinbase".to_string(), venue("coinbase")).unwrap();
+    let (primary_id, _) = book
+        .add_order_with_options(OrderSide::Ask, 100.0, 1.0, 2, "binance".to_string(), venue("binance"))
+        .unwrap();
+
+    let (_, trades) = book.add_order(OrderSide::Bid, 100.0, 1.0, 3, "taker".to_string()).unwrap();
+
+    assert_eq!(trades.len(), 1);
+    assert_eq!(trades[0].ask_order_id, primary_id);
+}
+
+#[test]
+fn a_sweep_takes_the_primary_venue_in_time_order_before_the_rest() {
+    let book = OrderBook::with_config(OrderBookConfig::default().with_tie_break(TieBreak::PrimaryVenue("binance".to_string())));
+    let ask = |venue: &str, timestamp| {
+        let options = OrderOptions::default().with_source(venue);
+        book.add_order_with_options(OrderSide::Ask, 100.0, 1.0, timestamp, venue.to_string(), options).unwrap().0
+    };
+    let (c1, b1, c2, b2) = (ask("coinbase", 1), ask("binance", 2), ask("coinbase", 3), ask("binance", 4));
+
+    // A partial fill leaves the venue's first order at the front.
+    let (_, trades) = book.add_order(OrderSide::Bid, 100.0, 0.5, 5, "taker".to_string()).unwrap();
+    assert_eq!(trades[0].ask_order_id, b1);
+    let (_, trades) = book.add_order(OrderSide::Bid, 100.0, 3.5, 6, "taker".to_string()).unwrap();
+    assert_eq!(trades.iter().map(|t| t.ask_order_id).collect::<Vec<_>>(), vec![b1, b2, c1, c2]);
+}
+
+#[test]
+fn a_sweep_under_larger_size_follows_sizes_as_they_shrink() {
+    let book = OrderBook::with_config(OrderBookConfig::default().with_tie_break(TieBreak::LargerSize));
+    let ask = |quantity, timestamp| book.add_order(OrderSide::Ask, 100.0, quantity, timestamp, "maker".to_string()).unwrap().0;
+    let (a, b, c, d) = (ask(1.0, 1), ask(3.0, 2), ask(3.0, 3), ask(2.0, 4));
+
+    // The earlier of the two largest goes first, and shrinks to 1.5.
+    let (_, trades) = book.add_order(OrderSide::Bid, 100.0, 1.5, 5, "taker".to_string()).unwrap();
+    assert_eq!(trades[0].ask_order_id, b);
+    let (_, trades) = book.add_order(OrderSide::Bid, 100.0, 7.5, 6, "taker".to_string()).unwrap();
+    assert_eq!(trades.iter().map(|t| t.ask_order_id).collect::<Vec<_>>(), vec![c, d, b, a]);
+}
+
+#[test]
+fn same_venue_orders_never_trade_with_each_other() {
+    let book = OrderBook::with_config(OrderBookConfig::default().with_prevent_same_source_match(true));
+    let venue = || OrderOptions::default().with_source("binance");
+    let (ask_id, _) = book.add_order_with_options(OrderSide::Ask, 100.0, 1.0, 1, "binance".to_string(), venue()).unwrap();
+
+    let (bid_id, trades) = book.add_order_with_options(OrderSide::Bid, 101.0, 1.0, 2, "binance".to_string(), venue()).unwrap();
+
+    assert!(trades.is_empty());
+    assert!(book.get_order(ask_id).is_some());
+    assert!(book.get_order(bid_id).is_none());
+    assert_eq!(book.get_best_bid(), None);
+}
+
+#[test]
+fn stale_book_is_cleared_and_flagged() {
+    let book = OrderBook::with_config(OrderBookConfig::default().with_max_staleness_ms(1_000));
+    let mut events = book.subscribe();
+    book.add_order(OrderSide::Bid, 99.0, 1.0, 10_000, "mm".to_string()).unwrap();
+    book.add_order(OrderSide::Ask, 101.0, 1.0, 10_000, "mm".to_string()).unwrap();
+
+    assert!(book.sweep_stale(11_000).is_empty());
+    assert_eq!(book.sweep_stale(11_001).len(), 2);
+    assert_eq!(book.get_market_depth(10), (vec![], vec![]));
+
+    let cleared = std::iter::from_fn(|| events.try_recv().ok())
+        .find_map(|event| match event {
+            OrderBookEvent::BookCleared(cleared) => Some(cleared),
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(cleared.last_updated_ms, 10_000);
+    assert_eq!(cleared.cleared_orders, 2);
+}
+
+#[test]
+fn normal_matching_reports_no_trade_throughs() {
+    let book = OrderBook::with_config(OrderBookConfig::default().with_trade_through_check(TradeThroughCheck::Strict));
+    for i in 0..20u64 {
+        book.add_order(OrderSide::Ask, 100.0 + i as f64, 1.0, i, "asks".to_string()).unwrap();
+        book.add_order(OrderSide::Bid, 99.0 - i as f64, 1.0, i, "bids".to_string()).unwrap();
+    }
+
+    let (_, bought) = book.add_order(OrderSide::Bid, 110.0, 15.0, 100, "buyer".to_string()).unwrap();
+    let (_, sold) = book.add_order(OrderSide::Ask, 90.0, 15.0, 101, "seller".to_string()).unwrap();
+
+    assert_eq!(bought.len(), 11);
+    // The buyer's 4-lot remainder rests at 110 and trades first.
+    assert_eq!(sold.len(), 11);
+    assert_eq!(book.trade_through_count(), 0);
+}
+
+#[test]
+fn fee_exempt_order_pays_no_fees_at_the_same_rate() {
+    let book = OrderBook::with_config(OrderBookConfig::default().with_fee_schedule(FeeSchedule::new(0.001, 0.002)));
+    book.add_order_with_options(OrderSide::Ask, 100.0, 1.0, 1, "promo".to_string(), OrderOptions::default().with_fee_exempt(true))
+        .unwrap();
+    book.add_order(OrderSide::Ask, 100.0, 1.0, 2, "maker".to_string()).unwrap();
+
+    let (_, exempt) = book
+        .add_order_with_options(OrderSide::Bid, 100.0, 1.0, 3, "promo".to_string(), OrderOptions::default().with_fee_exempt(true))
+        .unwrap();
+    let (_, normal) = book.add_order(OrderSide::Bid, 100.0, 1.0, 4, "taker".to_string()).unwrap();
+
+    assert_eq!((exempt[0].maker_fee, exempt[0].taker_fee), (0.0, 0.0));
+    assert_eq!((normal[0].maker_fee, normal[0].taker_fee), (0.1, 0.2));
+}
+
+#[test]
+fn iceberg_refresh_queues_behind_visible_orders_at_its_price() {
+    let book = OrderBook::new();
+    let iceberg = OrderOptions::default().with_display_quantity(1.0);
+    let (iceberg_id, _) = book.add_order_with_options(OrderSide::Ask, 100.0, 3.0, 1, "iceberg".to_string(), iceberg).unwrap();
+    let (visible_id, _) = book.add_order(OrderSide::Ask, 100.0, 1.0, 2, "visible".to_string()).unwrap();
+
+    let (_, trades) = book.add_order(OrderSide::Bid, 100.0, 3.0, 3, "taker".to_string()).unwrap();
+
+    // The iceberg's first slice had time priority; its refreshed slice queued
+    // behind the visible order.
+    let makers: Vec<u64> = trades.iter().map(|t| t.ask_order_id).collect();
+    assert_eq!(makers, vec![iceberg_id, visible_id, iceberg_id]);
+    assert_eq!(book.get_order(iceberg_id).map(|o| o.remaining_quantity()), Some(1.0));
+}
+
+#[test]
+fn a_mixed_level_matches_visible_then_hidden_size() {
+    let book = OrderBook::new();
+    let ask = |quantity, timestamp, options| {
+        book.add_order_with_options(OrderSide::Ask, 100.0, quantity, timestamp, "maker".to_string(), options).unwrap().0
+    };
+    let first = ask(1.0, 1, OrderOptions::default());
+    let iceberg = ask(3.0, 2, OrderOptions::default().with_display_quantity(1.0));
+    let (second, third) = (ask(1.0, 3, OrderOptions::default()), ask(1.0, 4, OrderOptions::default()));
+    // Only the iceberg's slice shows; its reserve of 2 is hidden.
+    assert_eq!(book.get_market_depth(1).1, vec![(100.0, 4.0)]);
+
+    let (_, trades) = book.add_order(OrderSide::Bid, 100.0, 6.0, 5, "taker".to_string()).unwrap();
+
+    // Visible orders and the displayed slice in time order, then the
+    // reserve, one refreshed slice at a time.
+    let makers: Vec<u64> = trades.iter().map(|t| t.ask_order_id).collect();
+    assert_eq!(makers, vec![first, iceberg, second, third, iceberg, iceberg]);
+}
+
+#[test]
+fn pegged_iceberg_refresh_reprices_its_slice() {
+    let book = OrderBook::with_config(OrderBookConfig::default().with_tick_size(0.5));
+    book.add_order(OrderSide::Bid, 95.0, 5.0, 1, "bidder".to_string()).unwrap();
+    let pegged = |offset| {
+        OrderOptions::default()
+            .with_display_quantity(1.0)
+            .with_refresh_peg(RefreshPeg { reference: PegReference::Market, offset })
+    };
+    let (iceberg_id, _) = book.add_order_with_options(OrderSide::Ask, 100.0, 3.0, 2, "iceberg".to_string(), pegged(2.0)).unwrap();
+
+    book.add_order(OrderSide::Bid, 100.0, 1.0, 3, "taker".to_string()).unwrap();
+
+    // The new slice rests `offset` above the best bid, not at 100.
+    let iceberg = book.get_order(iceberg_id).unwrap();
+    assert_eq!((iceberg.price.as_f64(), iceberg.quantity, iceberg.hidden_remaining), (97.0, 1.0, 1.0));
+    assert_eq!(book.get_market_depth(5).1, vec![(97.0, 1.0)]);
+
+    // An offset into the spread would cross the bid; the slice stops a tick
+    // above it instead.
+    let book = OrderBook::with_config(OrderBookConfig::default().with_tick_size(0.5));
+    book.add_order(OrderSide::Bid, 95.0, 5.0, 1, "bidder".to_string()).unwrap();
+    let (crossing_id, _) = book.add_order_with_options(OrderSide::Ask, 99.0, 2.0, 2, "crossing".to_string(), pegged(-10.0)).unwrap();
+    book.add_order(OrderSide::Bid, 99.0, 1.0, 3, "taker".to_string()).unwrap();
+
+    let crossing = book.get_order(crossing_id).unwrap();
+    assert_eq!((crossing.price.as_f64(), crossing.quantity), (95.5, 1.0));
+    assert_eq!(book.get_best_bid(), Some(95.0));
+}
+
+#[test]
+fn cancel_inside_minimum_resting_time_is_refused() {
+    let book = OrderBook::with_config(OrderBookConfig::default().with_min_resting_ms(500));
+    let (order_id, _) = book.add_order(OrderSide::Bid, 99.0, 1.0, 10_000, "alice".to_string()).unwrap();
+
+    assert!(book.remove_order_at(order_id, "alice", 10_100).is_none());
+    assert_eq!(book.get_best_bid(), Some(99.0));
+
+    assert_eq!(book.remove_order_at(order_id, "alice", 10_500).map(|o| o.id), Some(order_id));
+    assert!(book.get_best_bid().is_none());
+}
+
+#[test]
+fn reducing_an_iceberg_shrinks_its_reserve_before_its_slice() {
+    let book = OrderBook::new();
+    let iceberg = OrderOptions::default().with_display_quantity(2.0);
+    let (order_id, _) = book.add_order_with_options(OrderSide::Ask, 100.0, 5.0, 1, "alice".to_string(), iceberg).unwrap();
+
+    let amended = book.modify_order(order_id, "alice", None, Some(4.0)).unwrap().unwrap();
+    assert!(amended.priority_kept);
+    let order = book.get_order(order_id).unwrap();
+    assert_eq!((order.quantity, order.hidden_remaining), (2.0, 2.0));
+    assert_eq!(book.get_market_depth(1).1, vec![(100.0, 2.0)]);
+
+    book.modify_order(order_id, "alice", None, Some(1.0)).unwrap().unwrap();
+    let order = book.get_order(order_id).unwrap();
+    assert_eq!((order.quantity, order.hidden_remaining), (1.0, 0.0));
+    assert_eq!(book.get_market_depth(1).1, vec![(100.0, 1.0)]);
+}
+
+#[test]
+fn opening_auction_clears_preloaded_crossing_orders_at_one_price() {
+    let book = OrderBook::with_config(OrderBookConfig::default().with_opening_auction(true));
+    book.add_order(OrderSide::Bid, 102.0, 1.0, 1, "b1".to_string()).unwrap();
+    book.add_order(OrderSide::Bid, 101.0, 2.0, 2, "b2".to_string()).unwrap();
+    book.add_order(OrderSide::Ask, 99.0, 1.0, 3, "a1".to_string()).unwrap();
+    book.add_order(OrderSide::Ask, 100.0, 1.0, 4, "a2".to_string()).unwrap();
+    book.add_order(OrderSide::Ask, 101.0, 2.0, 5, "a3".to_string()).unwrap();
+    assert!(book.in_opening_auction());
+
+    let trades = book.open(10);
+
+    assert!(!book.in_opening_auction());
+    assert!(trades.len() > 1);
+    assert!(trades.iter().all(|t| t.price == 101.0), "{:?}", trades);
+    assert_eq!(trades.iter().map(|t| t.quantity).sum::<f64>(), 3.0);
+    assert_eq!(book.get_best_ask(), Some(101.0));
+    assert_eq!(book.get_best_bid(), None);
+    assert!(book.open(20).is_empty());
+}
+
+#[test]
+fn book_refuses_a_tick_finer_than_its_price_precision() {
+    let err = OrderBook::try_with_config(OrderBookConfig::default().with_tick_size(0.5e-8)).err().unwrap();
+    assert_eq!(err, ConfigError::InvalidTickSize { tick_size: 0.5e-8, price_precision: 1e-8 });
+    assert!(err.to_string().contains("tick_size"), "{}", err);
+    assert!(OrderBook::try_with_config(OrderBookConfig::default().with_tick_size(0.0)).is_err());
+    assert!(OrderBook::try_with_config(OrderBookConfig::default().with_tick_size(0.01)).is_ok());
+}
+
+#[test]
+fn quote_set_refresh_is_never_seen_half_applied() {
+    let book = Arc::new(OrderBook::new());
+    let quotes = [
+        vec![(OrderSide::Bid, 99.0, 1.0), (OrderSide::Ask, 101.0, 1.0)],
+        vec![(OrderSide::Bid, 98.0, 2.0), (OrderSide::Ask, 102.0, 2.0)],
+    ];
+    let mut live = book.apply_quote_set("mm", Vec::new(), quotes[0].clone(), 0).unwrap().order_ids;
+
+    let reader = {
+        let book = book.clone();
+        thread::spawn(move || {
+            let whole_sets = [
+                (vec![(99.0, 1.0)], vec![(101.0, 1.0)]),
+                (vec![(98.0, 2.0)], vec![(102.0, 2.0)]),
+            ];
+            for _ in 0..20_000 {
+                let (_, bids, asks) = book.depth_with_sequence(10);
+                assert!(whole_sets.contains(&(bids.clone(), asks.clone())), "saw {:?} / {:?}", bids, asks);
+            }
+        })
+    };
+    for i in 1..2_000u64 {
+        let result = book.apply_quote_set("mm", live, quotes[(i % 2) as usize].clone(), i).unwrap();
+        assert_eq!(result.cancelled.len(), 2);
+        live = result.order_ids;
+    }
+    reader.join().unwrap();
+}
+
+#[test]
+fn protected_market_buy_stops_at_its_ceiling_and_cancels_the_rest() {
+    let book = OrderBook::new();
+    for (i, price) in [100.0, 101.0, 102.0, 103.0].into_iter().enumerate() {
+        book.add_order(OrderSide::Ask, price, 1.0, i as u64, "maker".to_string()).unwrap();
+    }
+
+    let result = book
+        .add_market_order(OrderSide::Bid, 3.5, Some(101.5), 10, "taker".to_string(), OrderOptions::default())
+        .unwrap();
+
+    assert_eq!(result.trades.iter().map(|t| t.price).collect::<Vec<_>>(), vec![100.0, 101.0]);
+    assert_eq!(result.cancelled_quantity, 1.5);
+    assert!(book.queue_position(result.order_id).is_none());
+    assert_eq!(book.get_best_ask(), Some(102.0));
+    assert_eq!(book.get_best_bid(), None);
+}
+
+#[test]
+fn time_in_force_decides_what_happens_to_the_unfilled_rest() {
+    let book = OrderBook::new();
+    book.add_order(OrderSide::Ask, 100.0, 1.0, 1, "maker".to_string()).unwrap();
+    book.add_order(OrderSide::Ask, 101.0, 1.0, 2, "maker".to_string()).unwrap();
+    let tif = |time_in_force| OrderOptions::default().with_time_in_force(time_in_force);
+
+    // FOK for more than the whole side executes nothing.
+    let (fok_id, trades) = book.add_order_with_options(OrderSide::Bid, 101.0, 3.0, 3, "taker".to_string(), tif(TimeInForce::Fok)).unwrap();
+    assert!(trades.is_empty());
+    assert_eq!(book.get_market_depth(10).1, vec![(100.0, 1.0), (101.0, 1.0)]);
+    assert_eq!(book.terminal_status(fok_id).map(|(_, status)| status), Some(OrderEventStatus::Cancelled));
+
+    // IOC takes what crosses its limit and cancels the rest.
+    let (ioc_id, trades) = book.add_order_with_options(OrderSide::Bid, 100.0, 3.0, 4, "taker".to_string(), tif(TimeInForce::Ioc)).unwrap();
+    assert_eq!(trades.iter().map(|t| (t.price, t.quantity)).collect::<Vec<_>>(), vec![(100.0, 1.0)]);
+    assert!(book.queue_position(ioc_id).is_none());
+    assert_eq!(book.get_best_bid(), None);
+    assert_eq!(book.terminal_status(ioc_id).map(|(_, status)| status), Some(OrderEventStatus::Cancelled));
+
+    // GTC rests whatever it can't fill.
+    let (gtc_id, trades) = book.add_order_with_options(OrderSide::Bid, 101.0, 3.0, 5, "taker".to_string(), tif(TimeInForce::Gtc)).unwrap();
+    assert_eq!(trades.len(), 1);
+    assert_eq!(book.get_order(gtc_id).map(|o| o.remaining_quantity()), Some(2.0));
+}
+
+#[test]
+fn crossing_your_own_resting_order_trades_nothing_under_cancel_resting() {
+    let book = OrderBook::with_config(OrderBookConfig::default().with_self_trade_policy(SelfTradePolicy::CancelResting));
+    let (own_ask, _) = book.add_order(OrderSide::Ask, 100.0, 1.0, 1, "alice".to_string()).unwrap();
+    let (other_ask, _) = book.add_order(OrderSide::Ask, 101.0, 1.0, 2, "bob".to_string()).unwrap();
+
+    let (_, trades) = book.add_order(OrderSide::Bid, 100.0, 1.0, 3, "alice".to_string()).unwrap();
+
+    assert!(trades.is_empty());
+    assert!(book.get_order(own_ask).is_none());
+    assert!(book.get_order(other_ask).is_some());
+    assert_eq!(book.get_best_bid(), Some(100.0));
+}
+
+#[test]
+fn queue_total_stays_exact_over_many_random_partial_updates() {
+    const SCALE: u64 = 1_000_000;
+    let queue = OrderQueue::with_scale(SCALE);
+    // Live order ids with their reference quantity in lots.
+    let mut reference: Vec<(u64, u64)> = Vec::new();
+    let mut next_id = 0;
+    // splitmix64, so the sequence is the same every run.
+    let mut state = 0x5EED_u64;
+    let mut next = move |bound: u64| {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        (z ^ (z >> 31)) % bound
+    };
+    let lots_to_f64 = |lots: u64| lots as f64 / SCALE as f64;
+
+    for _ in 0..100_000 {
+        let roll = next(100);
+        if reference.is_empty() || roll == 0 {
+            let lots = 1 + next(10 * SCALE);
+            queue.add_order(Order::new(next_id, OrderSide::Bid, 100.0, lots_to_f64(lots), 0, "mm".to_string()));
+            reference.push((next_id, lots));
+            next_id += 1;
+            continue;
+        }
+        let index = next(reference.len() as u64) as usize;
+        let (id, lots) = reference[index];
+        // Partial fills mostly, with the odd full fill or cancel.
+        let filled = if roll == 1 { lots } else { 1 + next(lots) };
+        if filled == lots {
+            assert!(queue.remove_order(id).is_some());
+            reference.swap_remove(index);
+        } else {
+            assert!(queue.update_order(id, lots_to_f64(lots - filled)));
+            reference[index].1 = lots - filled;
+        }
+    }
+
+    assert_eq!(queue.total_qty().units(), reference.iter().map(|(_, lots)| lots).sum::<u64>());
+    assert_eq!(queue.len(), reference.len());
+}
+
+#[test]
+fn locked_market_policies_during_the_auction() {
+    let auction_book = |policy| {
+        let book = OrderBook::with_config(OrderBookConfig::default().with_opening_auction(true).with_locked_market_policy(policy));
+        book.add_order(OrderSide::Ask, 100.0, 1.0, 1, "seller".to_string()).unwrap();
+        book
+    };
+
+    let matching = auction_book(LockedMarketPolicy::Match);
+    let (_, trades) = matching.add_order(OrderSide::Bid, 100.0, 1.0, 2, "buyer".to_string()).unwrap();
+    assert_eq!(trades.iter().map(|t| (t.price, t.quantity)).collect::<Vec<_>>(), vec![(100.0, 1.0)]);
+    assert_eq!((matching.get_best_bid(), matching.get_best_ask()), (None, None));
+
+    let rejecting = auction_book(LockedMarketPolicy::Reject);
+    let err = rejecting.add_order(OrderSide::Bid, 100.0, 1.0, 2, "buyer".to_string()).unwrap_err();
+    assert_eq!(err, OrderError::WouldLockMarket { price: 100.0 });
+    assert_eq!((rejecting.get_best_bid(), rejecting.get_best_ask()), (None, Some(100.0)));
+
+    let allowing = auction_book(LockedMarketPolicy::Allow);
+    let (_, trades) = allowing.add_order(OrderSide::Bid, 100.0, 1.0, 2, "buyer".to_string()).unwrap();
+    assert!(trades.is_empty());
+    assert_eq!((allowing.get_best_bid(), allowing.get_best_ask()), (Some(100.0), Some(100.0)));
+}
+
+#[test]
+fn limit_prices_that_round_to_nothing_are_refused_everywhere() {
+    let book = OrderBook::with_config(OrderBookConfig::default().with_tick_size(0.5));
+    let user = || "alice".to_string();
+    for price in [0.2, 0.0, -1.0, f64::NAN] {
+        let err = book.add_order(OrderSide::Bid, price, 1.0, 1, user()).unwrap_err();
+        assert!(matches!(err, OrderError::InvalidPrice { .. }), "{} gave {:?}", price, err);
+    }
+    let condition = SpreadCondition::new(2.0, SpreadDirection::Narrows);
+    let err = book.add_conditional_order(OrderSide::Bid, 0.2, 1.0, 1, user(), condition, OrderOptions::default()).unwrap_err();
+    assert_eq!(err, OrderError::InvalidPrice { price: 0.2 });
+    let err = book.apply_quote_set("alice", Vec::new(), vec![(OrderSide::Bid, 0.2, 1.0)], 1).unwrap_err();
+    assert_eq!(err, OrderError::InvalidPrice { price: 0.2 });
+    assert!(book.get_pending_conditional_orders().is_empty());
+    assert_eq!(book.get_best_bid(), None);
+
+    let (order_id, _) = book.add_order(OrderSide::Bid, 99.0, 1.0, 1, user()).unwrap();
+    let err = book.modify_order(order_id, "alice", Some(0.2), None).unwrap_err();
+    assert_eq!(err, OrderError::InvalidPrice { price: 0.2 });
+    assert_eq!(book.get_order(order_id).map(|o| o.price.as_f64()), Some(99.0));
+}
+
+#[test]
+fn crossing_quote_pair_is_rejected_without_touching_the_book() {
+    let book = OrderBook::new();
+    let old = book.apply_quote_set("mm", Vec::new(), vec![(OrderSide::Bid, 98.0, 1.0), (OrderSide::Ask, 102.0, 1.0)], 1).unwrap();
+
+    let crossing = vec![(OrderSide::Bid, 101.0, 1.0), (OrderSide::Ask, 100.0, 1.0)];
+    let err = book.apply_quote_set("mm", old.order_ids.clone(), crossing, 2).unwrap_err();
+    assert_eq!(err, OrderError::CrossedQuoteSet { bid: 101.0, ask: 100.0 });
+    // The old quotes weren't cancelled either.
+    assert_eq!(book.get_market_depth(10), (vec![(98.0, 1.0)], vec![(102.0, 1.0)]));
+
+    let valid = vec![(OrderSide::Bid, 99.0, 1.0), (OrderSide::Ask, 100.0, 1.0)];
+    let result = book.apply_quote_set("mm", old.order_ids, valid, 3).unwrap();
+    assert_eq!(result.cancelled.len(), 2);
+    assert!(result.trades.is_empty());
+    assert_eq!(book.get_market_depth(10), (vec![(99.0, 1.0)], vec![(100.0, 1.0)]));
+
+    let allowing = OrderBook::with_config(OrderBookConfig::default().with_allow_crossed_quote_sets(true));
+    let result = allowing
+        .apply_quote_set("mm", Vec::new(), vec![(OrderSide::Ask, 100.0, 1.0), (OrderSide::Bid, 101.0, 1.0)], 1)
+        .unwrap();
+    assert_eq!(result.trades.len(), 1);
+}
+
+#[test]
+fn amending_off_the_book_is_checked_before_the_order_leaves_it() {
+    let young = OrderBook::with_config(OrderBookConfig::default().with_min_resting_ms(500));
+    let (order_id, _) = young.add_order(OrderSide::Bid, 99.0, 2.0, 10_000, "alice".to_string()).unwrap();
+    let err = young.modify_order_at(order_id, "alice", Some(98.0), None, 10_100).unwrap_err();
+    assert_eq!(err, OrderError::MinRestingTime { min_resting_ms: 500 });
+    assert_eq!(young.queue_position(order_id).map(|(position, _)| position), Some(0));
+    // Shrinking in place cancels nothing, so it is allowed straight away.
+    assert!(young.modify_order_at(order_id, "alice", None, Some(1.0), 10_100).unwrap().unwrap().priority_kept);
+    assert!(young.modify_order_at(order_id, "alice", Some(98.0), None, 10_500).unwrap().is_some());
+    assert_eq!(young.get_best_bid(), Some(98.0));
+
+    let auction = OrderBook::with_config(
+        OrderBookConfig::default()
+            .with_opening_auction(true)
+            .with_locked_market_policy(LockedMarketPolicy::Reject),
+    );
+    auction.add_order(OrderSide::Ask, 100.0, 1.0, 1, "bob".to_string()).unwrap();
+    let (order_id, _) = auction.add_order(OrderSide::Bid, 99.0, 1.0, 2, "alice".to_string()).unwrap();
+    let err = auction.modify_order(order_id, "alice", Some(100.0), None).unwrap_err();
+    assert_eq!(err, OrderError::WouldLockMarket { price: 100.0 });
+    assert_eq!(auction.get_order(order_id).map(|o| o.price.as_f64()), Some(99.0));
+
+    // Limit orders aren't open to users here, so a triggered conditional
+    // order can't be re-entered as one.
+    let conditional_only = OrderBook::with_config(OrderBookConfig::default().with_allowed_order_kinds([OrderKind::ConditionalSpread]));
+    let venue = || OrderOptions::default().with_source("venue");
+    conditional_only.add_order_with_options(OrderSide::Bid, 99.0, 1.0, 1, "feed".to_string(), venue()).unwrap();
+    conditional_only.add_order_with_options(OrderSide::Ask, 105.0, 1.0, 1, "feed".to_string(), venue()).unwrap();
+    let condition = SpreadCondition::new(2.0, SpreadDirection::Narrows);
+    let order_id = conditional_only
+        .add_conditional_order(OrderSide::Bid, 101.0, 2.0, 2, "alice".to_string(), condition, OrderOptions::default())
+        .unwrap();
+    conditional_only.add_order_with_options(OrderSide::Bid, 103.0, 1.0, 3, "feed".to_string(), venue()).unwrap();
+    assert!(conditional_only.queue_position(order_id).is_some());
+    let err = conditional_only.modify_order(order_id, "alice", None, Some(3.0)).unwrap_err();
+    assert!(matches!(err, OrderError::OrderKindNotAllowed { .. }));
+    assert_eq!(conditional_only.get_order(order_id).map(|o| o.remaining_quantity()), Some(2.0));
+}
+
+#[test]
+fn market_orders_are_the_taker_on_either_side() {
+    let book = OrderBook::new();
+    let (resting_ask, _) = book.add_order(OrderSide::Ask, 101.0, 1.0, 1, "maker".to_string()).unwrap();
+    let (resting_bid, _) = book.add_order(OrderSide::Bid, 99.0, 1.0, 2, "maker".to_string()).unwrap();
+
+    let buy = book.add_market_order(OrderSide::Bid, 1.0, None, 3, "buyer".to_string(), OrderOptions::default()).unwrap();
+    let trade = &buy.trades[0];
+    assert_eq!((trade.bid_order_id, trade.ask_order_id), (buy.order_id, resting_ask));
+    assert_eq!(trade.taker_side, Some(OrderSide::Bid));
+    assert_eq!(trade.maker_order_id, Some(resting_ask));
+    assert_eq!(trade.taker_order_id, Some(buy.order_id));
+    let fill = Fill::new(trade, "BTC");
+    assert_eq!(fill.maker_order_id, Some(format_order_id("BTC", resting_ask)));
+    assert_eq!(fill.taker_order_id, Some(format_order_id("BTC", buy.order_id)));
+
+    let sell = book.add_market_order(OrderSide::Ask, 1.0, None, 4, "seller".to_string(), OrderOptions::default()).unwrap();
+    let trade = &sell.trades[0];
+    assert_eq!((trade.bid_order_id, trade.ask_order_id), (resting_bid, sell.order_id));
+    assert_eq!(trade.taker_side, Some(OrderSide::Ask));
+    assert_eq!(trade.maker_order_id, Some(resting_bid));
+    assert_eq!(trade.taker_order_id, Some(sell.order_id));
+    let fill = Fill::new(trade, "BTC");
+    assert_eq!(fill.maker_order_id, Some(format_order_id("BTC", resting_bid)));
+    assert_eq!(fill.taker_order_id, Some(format_order_id("BTC", sell.order_id)));
+}
+
+#[test]
+fn a_silent_venue_loses_its_orders_while_live_ones_keep_theirs() {
+    let book = OrderBook::with_config(OrderBookConfig::default().with_max_source_silence_ms(1_000));
+    let venue = |name: &str| OrderOptions::default().with_source(name);
+    let (dead_bid, _) = book.add_order_with_options(OrderSide::Bid, 99.0, 1.0, 1_000, "feed".to_string(), venue("binance")).unwrap();
+    let (dead_ask, _) = book.add_order_with_options(OrderSide::Ask, 101.0, 1.0, 1_000, "feed".to_string(), venue("binance")).unwrap();
+    let (live_bid, _) = book.add_order_with_options(OrderSide::Bid, 99.0, 2.0, 1_500, "feed".to_string(), venue("kraken")).unwrap();
+    let (own_ask, _) = book.add_order(OrderSide::Ask, 102.0, 1.0, 1_000, "alice".to_string()).unwrap();
+
+    assert!(book.sweep_dead_sources(1_900).is_empty());
+
+    let mut swept: Vec<u64> = book.sweep_dead_sources(2_100).iter().map(|order| order.id).collect();
+    swept.sort_unstable();
+    assert_eq!(swept, vec![dead_bid, dead_ask]);
+    assert!(book.get_order(live_bid).is_some());
+    assert!(book.get_order(own_ask).is_some());
+    assert_eq!(book.get_market_depth(5), (vec![(99.0, 2.0)], vec![(102.0, 1.0)]));
+    assert_eq!(book.get_stats().total_orders_cancelled, 2);
+
+    // Each outage is swept once.
+    assert!(book.sweep_dead_sources(2_400).is_empty());
+}
+
+#[test]
+fn a_venue_whose_orders_are_all_rejected_still_counts_as_silent() {
+    let book = OrderBook::with_config(
+        OrderBookConfig::default()
+            .with_max_source_silence_ms(1_000)
+            .with_opening_auction(true)
+            .with_locked_market_policy(LockedMarketPolicy::Reject),
+    );
+    let venue = || OrderOptions::default().with_source("binance");
+    let (stale_bid, _) = book.add_order_with_options(OrderSide::Bid, 99.0, 1.0, 1_000, "feed".to_string(), venue()).unwrap();
+    let err = book.add_order_with_options(OrderSide::Ask, 99.0, 1.0, 1_800, "feed".to_string(), venue()).unwrap_err();
+    assert_eq!(err, OrderError::WouldLockMarket { price: 99.0 });
+
+    let swept: Vec<u64> = book.sweep_dead_sources(2_100).iter().map(|order| order.id).collect();
+    assert_eq!(swept, vec![stale_bid]);
+}
+
+#[test]
+fn cancel_all_leaves_young_orders_but_the_dead_man_takes_everything() {
+    let book = OrderBook::with_config(OrderBookConfig::default().with_min_resting_ms(500));
+    let (old_bid, _) = book.add_order(OrderSide::Bid, 99.0, 1.0, 1_000, "alice".to_string()).unwrap();
+    let (young_ask, _) = book.add_order(OrderSide::Ask, 101.0, 1.0, 1_400, "alice".to_string()).unwrap();
+
+    let cancelled: Vec<u64> = book.cancel_all_for_user_at("alice", 1_600).iter().map(|order| order.id).collect();
+    assert_eq!(cancelled, vec![old_bid]);
+    assert_eq!(book.get_best_bid(), None);
+    assert_eq!(book.get_best_ask(), Some(101.0));
+    assert_eq!(book.get_stats().total_orders_cancelled, 1);
+
+    book.dead_man().arm("alice", 100, 1_400);
+    let swept: Vec<u64> = book.sweep_dead_man(1_600).iter().map(|order| order.id).collect();
+    assert_eq!(swept, vec![young_ask]);
+    assert_eq!(book.get_best_ask(), None);
+}
+
+#[test]
+fn a_hundred_lot_iceberg_showing_ten_refills_nine_times() {
+    let book = OrderBook::new();
+    let iceberg = || OrderOptions::default().with_display_quantity(10.0);
+    let (iceberg_id, _) = book.add_order_with_options(OrderSide::Ask, 100.0, 100.0, 1, "maker".to_string(), iceberg()).unwrap();
+
+    let mut refills = 0;
+    for i in 0..10 {
+        assert_eq!(book.get_market_depth(1).1, vec![(100.0, 10.0)]);
+        let (_, trades) = book.add_order(OrderSide::Bid, 100.0, 10.0, 2 + i, "taker".to_string()).unwrap();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].quantity, 10.0);
+        if book.get_order(iceberg_id).is_some() {
+            refills += 1;
+        }
+    }
+    assert_eq!(refills, 9);
+    assert_eq!(book.get_best_ask(), None);
+
+    // The reserve refills in place, so a fill-or-kill can count on all of it.
+    let (iceberg_id, _) = book.add_order_with_options(OrderSide::Ask, 100.0, 100.0, 20, "maker".to_string(), iceberg()).unwrap();
+    let fok = OrderOptions::default().with_time_in_force(TimeInForce::Fok);
+    let (_, trades) = book.add_order_with_options(OrderSide::Bid, 100.0, 100.0, 21, "taker".to_string(), fok.clone()).unwrap();
+    assert_eq!(trades.len(), 10);
+    assert!(trades.iter().all(|trade| trade.ask_order_id == iceberg_id && trade.quantity == 10.0));
+    book.add_order_with_options(OrderSide::Ask, 100.0, 100.0, 22, "maker".to_string(), iceberg()).unwrap();
+    let (_, trades) = book.add_order_with_options(OrderSide::Bid, 100.0, 100.5, 23, "taker".to_string(), fok).unwrap();
+    assert!(trades.is_empty());
+    assert_eq!(book.get_market_depth(1).1, vec![(100.0, 10.0)]);
+}
+
+#[test]
+fn sync_depth_mirrors_a_venue_one_order_per_level() {
+    let book = OrderBook::new();
+    let (own_bid, _) = book.add_order(OrderSide::Bid, 99.0, 0.5, 1, "alice".to_string()).unwrap();
+
+    book.sync_depth("binance", &[(99.0, 1.0), (98.0, 2.0)], &[(101.0, 1.5)], 2);
+    assert_eq!(book.get_market_depth(5), (vec![(99.0, 1.5), (98.0, 2.0)], vec![(101.0, 1.5)]));
+    assert_eq!(book.get_user_orders("binance").len(), 3);
+
+    // Resized, added and removed in place rather than piled on.
+    book.sync_depth("binance", &[(99.0, 3.0), (97.0, 1.0)], &[], 3);
+    assert_eq!(book.get_market_depth(5), (vec![(99.0, 3.5), (97.0, 1.0)], vec![]));
+    assert_eq!(book.get_user_orders("binance").len(), 2);
+    assert_eq!(book.get_order(own_bid).map(|order| order.quantity), Some(0.5));
+
+    // A new level that crosses trades like any order.
+    let trades = book.sync_depth("binance", &[(99.0, 3.0), (97.0, 1.0)], &[(99.0, 1.0)], 4);
+    assert_eq!(trades.iter().map(|trade| trade.quantity).sum::<f64>(), 1.0);
+    assert!(book.get_best_ask().is_none());
+}
@@ -0,0 +1,211 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use order_book_hybrid::engine::candles::CandleInterval;
+use order_book_hybrid::engine::checkpoint::Checkpointer;
+use order_book_hybrid::engine::config::OrderBookConfig;
+use order_book_hybrid::engine::event_log::EventLog;
+use order_book_hybrid::engine::order::{OrderOptions, OrderSide};
+use order_book_hybrid::engine::orderbook::OrderBook;
+use order_book_hybrid::engine::price::Price;
+use order_book_hybrid::engine::snapshot::{OrderBookSnapshot, RestoreError};
+use order_book_hybrid::events::OrderBookEvent;
+
+fn two_sided_book() -> OrderBook {
+    let book = OrderBook::new();
+    book.add_order(OrderSide::Bid, 100.0, 1.0, 1, "alice".to_string()).unwrap();
+    book.add_order(OrderSide::Ask, 101.0, 2.0, 2, "bob".to_string()).unwrap();
+    book
+}
+
+// A fresh path under the system temp dir, unique to this test process.
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("order_book_{}_{}", std::process::id(), name))
+}
+
+// The parts of a book a replay must reproduce exactly.
+fn resting_state(book: &OrderBook) -> serde_json::Value {
+    let snapshot = book.snapshot();
+    serde_json::json!({ "bids": snapshot.bids, "asks": snapshot.asks, "next_order_id": snapshot.next_order_id })
+}
+
+#[test]
+fn restoring_a_crossed_snapshot_fails_clearly() {
+    let mut snapshot = two_sided_book().snapshot();
+    snapshot.asks[0].price = Price::new(99.0);
+
+    let book = OrderBook::new();
+    book.add_order(OrderSide::Bid, 50.0, 1.0, 1, "carol".to_string()).unwrap();
+    let err = book.restore(snapshot).unwrap_err();
+
+    assert_eq!(err, RestoreError::Crossed { best_bid: 100.0, best_ask: 99.0 });
+    assert_eq!(err.to_string(), "snapshot is crossed: best bid 100 >= best ask 99");
+    assert_eq!(book.get_best_bid(), Some(50.0));
+}
+
+#[test]
+fn a_snapshot_saved_to_disk_restores_the_same_depth_and_stats() {
+    let book = two_sided_book();
+    book.add_order(OrderSide::Bid, 99.5, 3.0, 3, "carol".to_string()).unwrap();
+    book.add_order(OrderSide::Bid, 101.0, 0.5, 4, "dave".to_string()).unwrap();
+    let path = temp_path("round_trip.snapshot.json");
+    book.snapshot().save_to_path(&path).unwrap();
+
+    let loaded = OrderBookSnapshot::load_from_path(&path).unwrap().unwrap();
+    fs::remove_file(&path).unwrap();
+    assert!(OrderBookSnapshot::load_from_path(&path).unwrap().is_none());
+    let restored = OrderBook::new();
+    restored.restore(loaded).unwrap();
+
+    assert_eq!(restored.get_market_depth(10), book.get_market_depth(10));
+    assert_eq!(resting_state(&restored), resting_state(&book));
+    assert_eq!(serde_json::to_value(restored.snapshot().stats).unwrap(), serde_json::to_value(book.snapshot().stats).unwrap());
+    let (next_id, _) = restored.add_order(OrderSide::Bid, 90.0, 1.0, 5, "erin".to_string()).unwrap();
+    assert_eq!(next_id, book.snapshot().next_order_id);
+}
+
+#[test]
+fn compacted_log_replays_to_the_same_book_as_the_full_log() {
+    let log_path = temp_path("compaction.jsonl");
+    let log = Arc::new(EventLog::open(&log_path).unwrap().with_compact_every(4));
+    let book = OrderBook::new();
+    book.attach_event_log(log).unwrap();
+
+    let (cancelled_id, _) = book.add_order(OrderSide::Bid, 99.0, 1.0, 1, "alice".to_string()).unwrap();
+    book.add_order(OrderSide::Bid, 98.0, 2.0, 2, "alice".to_string()).unwrap();
+    book.add_order(OrderSide::Ask, 101.0, 3.0, 3, "bob".to_string()).unwrap();
+    book.add_order(OrderSide::Bid, 101.0, 1.0, 4, "carol".to_string()).unwrap();
+    book.remove_order(cancelled_id, "alice").unwrap();
+    let before_compaction = fs::read_to_string(&log_path).unwrap();
+
+    assert!(book.compact_event_log().unwrap());
+    book.add_order(OrderSide::Ask, 102.0, 1.0, 5, "bob".to_string()).unwrap();
+    book.add_order(OrderSide::Ask, 98.0, 1.5, 6, "dave".to_string()).unwrap();
+
+    // The full log is everything written before compaction followed by the
+    // compacted log's tail after its `Reset`.
+    let compacted = fs::read_to_string(&log_path).unwrap();
+    let tail: String = compacted.lines().skip(1).map(|line| format!("{}\n", line)).collect();
+    let full_path = temp_path("compaction_full.jsonl");
+    let full = before_compaction + &tail;
+    assert!(compacted.lines().count() < full.lines().count());
+    fs::write(&full_path, full).unwrap();
+
+    let from_compacted = OrderBook::new();
+    EventLog::replay(&log_path, &from_compacted).unwrap();
+    let from_full = OrderBook::new();
+    EventLog::replay(&full_path, &from_full).unwrap();
+    fs::remove_file(&log_path).unwrap();
+    fs::remove_file(&full_path).unwrap();
+
+    assert_eq!(resting_state(&from_compacted), resting_state(&book));
+    assert_eq!(resting_state(&from_full), resting_state(&book));
+    assert_eq!(from_full.get_market_depth(10), book.get_market_depth(10));
+}
+
+// Everything a replay must reproduce, beyond the resting orders: the tape,
+// candles, 24h volume, match stats and every user's account.
+fn replayed_state(book: &OrderBook, now: u64) -> serde_json::Value {
+    let stats = book.snapshot().stats;
+    serde_json::json!({
+        "resting": resting_state(book),
+        "tape": book.trades_since(0, usize::MAX),
+        "candles": book.get_candles(CandleInterval::OneMinute, 10),
+        "volume_24h": book.get_volume_24h(now),
+        "matched": [stats.total_orders_matched as f64, stats.total_volume_traded, stats.last_match_time.unwrap_or(0) as f64],
+        "accounts": book.accounts().snapshot(),
+    })
+}
+
+#[test]
+fn a_recorded_session_replays_to_an_identical_book() {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+    let log_path = temp_path("record_replay.jsonl");
+    let log = Arc::new(EventLog::open(&log_path).unwrap().with_compact_every(3));
+    let book = OrderBook::with_config(OrderBookConfig::default().with_snapshot_every_trades(2));
+    book.attach_event_log(log).unwrap();
+
+    let start = now - 300_000;
+    book.add_order(OrderSide::Ask, 101.0, 2.0, start, "maker".to_string()).unwrap();
+    let iceberg = OrderOptions::default().with_display_quantity(1.0);
+    book.add_order_with_options(OrderSide::Ask, 102.0, 3.0, start, "iceberg".to_string(), iceberg).unwrap();
+    let (bid_id, _) = book.add_order(OrderSide::Bid, 99.0, 4.0, start + 1, "alice".to_string()).unwrap();
+    book.add_order(OrderSide::Bid, 101.0, 1.5, now - 120_000, "taker".to_string()).unwrap();
+
+    book.add_market_order(OrderSide::Bid, 2.0, None, now - 60_000, "taker".to_string(), OrderOptions::default()).unwrap();
+    book.modify_order(bid_id, "alice", None, Some(2.5)).unwrap();
+    book.add_order(OrderSide::Ask, 99.0, 1.0, now, "maker".to_string()).unwrap();
+    book.add_order(OrderSide::Bid, 98.0, 1.0, now, "bob".to_string()).unwrap();
+    assert_eq!(book.get_recent_trades(10).len(), 5);
+
+    let replayed = OrderBook::with_config(OrderBookConfig::default().with_snapshot_every_trades(2));
+    let mut events = replayed.subscribe();
+    EventLog::replay(&log_path, &replayed).unwrap();
+
+    assert_eq!(replayed_state(&replayed, now), replayed_state(&book, now));
+    assert_eq!(replayed.accounts().get("taker").unwrap().position, 3.5);
+    // Replayed prints reach the tape only, not subscribers.
+    while let Ok(event) = events.try_recv() {
+        assert!(!matches!(event, OrderBookEvent::Trade(_)));
+    }
+
+    // A compacted log carries the accounts in its `Reset`.
+    assert!(book.compact_event_log().unwrap());
+    let from_compacted = OrderBook::new();
+    EventLog::replay(&log_path, &from_compacted).unwrap();
+    fs::remove_file(&log_path).unwrap();
+    assert_eq!(resting_state(&from_compacted), resting_state(&book));
+    assert_eq!(serde_json::to_value(from_compacted.accounts().snapshot()).unwrap(), serde_json::to_value(book.accounts().snapshot()).unwrap());
+}
+
+#[test]
+fn loading_a_csv_reproduces_its_depth() {
+    let path = temp_path("seed.csv");
+    fs::write(&path, "side,price,quantity,user_id\nbuy,99.5,1.0,alice\nbid,99.5,0.5,bob\n\n# asks\nsell,101,2,carol\n").unwrap();
+    let book = OrderBook::new();
+    assert_eq!(book.load_orders_csv(&path).unwrap(), 3);
+    assert_eq!(book.get_market_depth(10), (vec![(99.5, 1.5)], vec![(101.0, 2.0)]));
+
+    fs::write(&path, "buy,99,1,alice\nsell,abc,1,bob\n").unwrap();
+    let empty = OrderBook::new();
+    let err = empty.load_orders_csv(&path).unwrap_err();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(err.to_string(), "line 2: invalid price \"abc\"");
+    assert!(empty.get_best_bid().is_none());
+}
+
+#[tokio::test]
+async fn a_checkpoint_is_written_once_n_trades_have_printed() {
+    let dir = temp_path("checkpoints");
+    fs::create_dir_all(&dir).unwrap();
+    let book = Arc::new(OrderBook::with_config(OrderBookConfig::default().with_snapshot_every_trades(3)));
+    let checkpointer = Checkpointer::new(book.clone(), &dir);
+    let path = checkpointer.path().to_path_buf();
+    let _ = fs::remove_file(&path);
+    checkpointer.spawn();
+
+    let print = |i: u64| {
+        book.add_order(OrderSide::Ask, 100.0, 1.0, i, "maker".to_string()).unwrap();
+        book.add_order(OrderSide::Bid, 100.0, 1.0, i, "taker".to_string()).unwrap();
+    };
+    print(1);
+    print(2);
+    book.add_order(OrderSide::Bid, 99.0, 1.0, 3, "alice".to_string()).unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!path.exists());
+
+    print(4);
+    let written = async {
+        while !path.exists() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(5), written).await.expect("no checkpoint after three trades");
+
+    let restored = Arc::new(OrderBook::new());
+    assert!(Checkpointer::new(restored.clone(), &dir).restore().unwrap());
+    assert_eq!(resting_state(&restored), resting_state(&book));
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,360 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_web::web::{self, Data};
+use actix_web::{App, HttpServer};
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+use order_book_hybrid::api::auth::ApiKeys;
+use order_book_hybrid::api::books::BookRegistry;
+use order_book_hybrid::api::connections::ConnectionRegistry;
+use order_book_hybrid::api::websocket;
+use order_book_hybrid::engine::config::OrderBookConfig;
+use order_book_hybrid::engine::order::{OrderOptions, OrderSide};
+use order_book_hybrid::engine::orderbook::OrderBook;
+use order_book_hybrid::events::EVENT_CHANNEL_CAPACITY;
+
+type Client = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+// Serves `/ws` for `book` on a free local port.
+fn start_server(book: Arc<OrderBook>, api_keys: ApiKeys, connections: Data<ConnectionRegistry>) -> SocketAddr {
+    start_server_for(BookRegistry::new(book), api_keys, connections)
+}
+
+fn start_server_for(books: BookRegistry, api_keys: ApiKeys, connections: Data<ConnectionRegistry>) -> SocketAddr {
+    let books = Data::new(books);
+    let api_keys = Data::new(api_keys);
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(books.clone())
+            .app_data(api_keys.clone())
+            .app_data(connections.clone())
+            .route("/ws", web::get().to(websocket::ws_index))
+    })
+    .workers(1)
+    .disable_signals()
+    .bind(("127.0.0.1", 0))
+    .unwrap();
+    let addr = server.addrs()[0];
+    actix_rt::spawn(server.run());
+    addr
+}
+
+fn api_keys() -> ApiKeys {
+    let mut api_keys = ApiKeys::new();
+    api_keys.insert("alice-key", "alice");
+    api_keys.insert("bob-key", "bob");
+    api_keys
+}
+
+async fn connect(addr: SocketAddr, api_key: &str) -> Client {
+    let (client, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws?api_key={}", addr, api_key))
+        .await
+        .unwrap();
+    client
+}
+
+async fn send(client: &mut Client, command: Value) {
+    client.send(Message::Text(command.to_string())).await.unwrap();
+}
+
+// The next frame of `frame_type`, skipping any others.
+async fn next_frame(client: &mut Client, frame_type: &str) -> Value {
+    let read = async {
+        loop {
+            let message = client.next().await.expect("connection closed").expect("read failed");
+            if let Message::Text(text) = message {
+                let frame: Value = serde_json::from_str(&text).unwrap();
+                if frame["type"] == frame_type {
+                    return frame;
+                }
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(5), read).await.expect("no frame in time")
+}
+
+async fn subscribe_orders(client: &mut Client) {
+    send(client, json!({ "action": "subscribe", "channel": "orders" })).await;
+    assert_eq!(next_frame(client, "Subscribed").await["channel"], "orders");
+}
+
+#[actix_web::test]
+async fn order_updates_only_reach_their_owner() {
+    let book = Arc::new(OrderBook::new());
+    let addr = start_server(book.clone(), api_keys(), Data::new(ConnectionRegistry::new()));
+    let mut alice = connect(addr, "alice-key").await;
+    let mut bob = connect(addr, "bob-key").await;
+    subscribe_orders(&mut alice).await;
+    subscribe_orders(&mut bob).await;
+
+    let (alice_order, _) = book.add_order(OrderSide::Bid, 99.0, 1.0, 1, "alice".to_string()).unwrap();
+    assert_eq!(next_frame(&mut alice, "OrderUpdate").await["order_id"], format!("BTC-{:06}", alice_order));
+
+    // Bob's first update is for his own order, so Alice's never reached him.
+    let (bob_order, _) = book.add_order(OrderSide::Bid, 98.0, 1.0, 2, "bob".to_string()).unwrap();
+    assert_eq!(next_frame(&mut bob, "OrderUpdate").await["order_id"], format!("BTC-{:06}", bob_order));
+
+    // Nor may Bob subscribe to Alice's orders.
+    send(&mut bob, json!({ "action": "subscribe", "channel": "orders", "user_id": "alice" })).await;
+    assert_eq!(next_frame(&mut bob, "Error").await["message"], "not authorized for this user's orders");
+}
+
+// Waits for `connections` to list `expected` sessions; they register and
+// unregister from their own actor, a moment after the socket opens or closes.
+async fn wait_for_connections(connections: &ConnectionRegistry, expected: usize) {
+    let wait = async {
+        while connections.len() != expected {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(5), wait).await.expect("connection count never settled");
+}
+
+#[actix_web::test]
+async fn sessions_register_on_connect_and_unregister_on_close() {
+    let connections = Data::new(ConnectionRegistry::new());
+    let addr = start_server(Arc::new(OrderBook::new()), api_keys(), connections.clone());
+
+    let mut alice = connect(addr, "alice-key").await;
+    wait_for_connections(&connections, 1).await;
+    let listed = connections.list();
+    assert_eq!(listed[0].user_id.as_deref(), Some("alice"));
+    assert_eq!(listed[0].symbol, "BTC");
+    assert_eq!(listed[0].channels, vec!["depth", "stats"]);
+
+    alice.close(None).await.unwrap();
+    wait_for_connections(&connections, 0).await;
+}
+
+#[actix_web::test]
+async fn expiring_order_notifies_its_owner() {
+    let book = Arc::new(OrderBook::new());
+    let addr = start_server(book.clone(), api_keys(), Data::new(ConnectionRegistry::new()));
+    let mut alice = connect(addr, "alice-key").await;
+    subscribe_orders(&mut alice).await;
+
+    let options = OrderOptions::default().with_expires_at(5_000);
+    let (order_id, _) = book.add_order_with_options(OrderSide::Bid, 99.0, 1.0, 1_000, "alice".to_string(), options).unwrap();
+    assert_eq!(next_frame(&mut alice, "OrderUpdate").await["status"], "New");
+
+    assert_eq!(book.sweep_expired(5_000).len(), 1);
+    let expired = next_frame(&mut alice, "OrderUpdate").await;
+    assert_eq!(expired["order_id"], format!("BTC-{:06}", order_id));
+    assert_eq!(expired["status"], "Expired");
+}
+
+#[actix_web::test]
+async fn l3_channel_streams_add_then_cancel_without_the_owner() {
+    let book = Arc::new(OrderBook::with_config(OrderBookConfig::default().with_order_level_events(true)));
+    let addr = start_server(book.clone(), api_keys(), Data::new(ConnectionRegistry::new()));
+    let mut bob = connect(addr, "bob-key").await;
+    send(&mut bob, json!({ "action": "subscribe", "channel": "l3" })).await;
+    assert_eq!(next_frame(&mut bob, "Subscribed").await["channel"], "l3");
+
+    let (order_id, _) = book.add_order(OrderSide::Bid, 99.0, 1.5, 1, "alice".to_string()).unwrap();
+    book.remove_order(order_id, "alice").unwrap();
+
+    let order_id = format!("BTC-{:06}", order_id);
+    let added = next_frame(&mut bob, "BookOrder").await;
+    assert_eq!((added["action"].as_str(), added["order_id"].as_str()), (Some("Add"), Some(order_id.as_str())));
+    assert_eq!((added["side"].as_str(), added["price"].as_f64(), added["quantity"].as_f64()), (Some("Bid"), Some(99.0), Some(1.5)));
+    assert!(added.get("user_id").is_none());
+    let cancelled = next_frame(&mut bob, "BookOrder").await;
+    assert_eq!((cancelled["action"].as_str(), cancelled["order_id"].as_str()), (Some("Cancel"), Some(order_id.as_str())));
+}
+
+#[actix_web::test]
+async fn connections_past_the_cap_are_refused_until_a_slot_frees() {
+    let connections = Data::new(ConnectionRegistry::new().with_max_connections(2));
+    let addr = start_server(Arc::new(OrderBook::new()), api_keys(), connections.clone());
+    let url = format!("ws://{}/ws?api_key=alice-key", addr);
+
+    let mut first = connect(addr, "alice-key").await;
+    let _second = connect(addr, "bob-key").await;
+    match tokio_tungstenite::connect_async(&url).await {
+        Err(tokio_tungstenite::tungstenite::Error::Http(response)) => assert_eq!(response.status(), 503),
+        other => panic!("third connection wasn't refused: {:?}", other.map(|_| ())),
+    }
+
+    first.close(None).await.unwrap();
+    wait_for_connections(&connections, 1).await;
+    // The slot is released just after the session unregisters.
+    let reconnect = async {
+        loop {
+            if let Ok((client, _)) = tokio_tungstenite::connect_async(&url).await {
+                return client;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(5), reconnect).await.expect("slot never freed");
+}
+
+#[actix_web::test]
+async fn a_cross_tells_the_maker_and_the_taker_their_own_side() {
+    let book = Arc::new(OrderBook::with_config(OrderBookConfig::default().with_match_events(true)));
+    let addr = start_server(book.clone(), api_keys(), Data::new(ConnectionRegistry::new()));
+    let mut alice = connect(addr, "alice-key").await;
+    let mut bob = connect(addr, "bob-key").await;
+    subscribe_orders(&mut alice).await;
+    subscribe_orders(&mut bob).await;
+
+    let (maker_id, _) = book.add_order(OrderSide::Ask, 100.0, 2.0, 1, "alice".to_string()).unwrap();
+    let (taker_id, _) = book.add_order(OrderSide::Bid, 100.0, 1.5, 2, "bob".to_string()).unwrap();
+    let (maker_id, taker_id) = (format!("BTC-{:06}", maker_id), format!("BTC-{:06}", taker_id));
+
+    let maker = next_frame(&mut alice, "Match").await;
+    assert_eq!(maker["role"], "Maker");
+    assert_eq!(maker["order_id"], maker_id.as_str());
+    let taker = next_frame(&mut bob, "Match").await;
+    assert_eq!(taker["role"], "Taker");
+    assert_eq!(taker["order_id"], taker_id.as_str());
+
+    for frame in [&maker, &taker] {
+        assert_eq!(frame["maker_order_id"], maker_id.as_str());
+        assert_eq!(frame["taker_order_id"], taker_id.as_str());
+        assert_eq!(frame["aggressor_side"], "Bid");
+        assert_eq!((frame["price"].as_f64(), frame["quantity"].as_f64()), (Some(100.0), Some(1.5)));
+    }
+}
+
+#[actix_web::test]
+async fn a_session_streams_the_book_its_symbol_names() {
+    let btc = Arc::new(OrderBook::new());
+    let sol = Arc::new(OrderBook::with_config(OrderBookConfig::default().with_symbol("SOL")));
+    let books = BookRegistry::new(btc.clone()).with_book(sol.clone());
+    let addr = start_server_for(books, api_keys(), Data::new(ConnectionRegistry::new()));
+
+    let (mut alice, _) = tokio_tungstenite::connect_async(format!("ws://{}/ws?api_key=alice-key&symbol=sol", addr))
+        .await
+        .unwrap();
+    subscribe_orders(&mut alice).await;
+    btc.add_order(OrderSide::Bid, 99.0, 1.0, 1, "alice".to_string()).unwrap();
+    let (sol_order, _) = sol.add_order(OrderSide::Bid, 150.0, 1.0, 2, "alice".to_string()).unwrap();
+    // The BTC order came first, so had it been streamed it would be read first.
+    assert_eq!(next_frame(&mut alice, "OrderUpdate").await["order_id"], format!("SOL-{:06}", sol_order));
+
+    let refused = tokio_tungstenite::connect_async(format!("ws://{}/ws?symbol=XRP", addr)).await;
+    assert!(refused.is_err());
+}
+
+#[actix_web::test]
+async fn heartbeats_keep_coming_on_an_idle_book() {
+    let book = Arc::new(OrderBook::new());
+    let connections = Data::new(ConnectionRegistry::new().with_keepalive_interval(Duration::from_millis(100)));
+    let addr = start_server(book, api_keys(), connections);
+    let mut client = connect(addr, "alice-key").await;
+
+    let started = std::time::Instant::now();
+    let mut times = Vec::new();
+    for _ in 0..4 {
+        let heartbeat = next_frame(&mut client, "Heartbeat").await;
+        assert_eq!(heartbeat["sequence"], 0);
+        times.push(heartbeat["server_time_ms"].as_u64().unwrap());
+    }
+    // Four beats 100 ms apart, well inside the 1 s stats timer.
+    assert!(started.elapsed() < Duration::from_millis(900));
+    for pair in times.windows(2) {
+        assert!(pair[1] - pair[0] >= 50, "heartbeats at {:?}", times);
+    }
+}
+
+#[actix_web::test]
+async fn trades_are_streamed_with_their_taker_side() {
+    let book = Arc::new(OrderBook::new());
+    let addr = start_server(book.clone(), api_keys(), Data::new(ConnectionRegistry::new()));
+    let mut client = connect(addr, "alice-key").await;
+    // Sent once the session is running, so it is already listening for trades.
+    next_frame(&mut client, "StatsUpdate").await;
+
+    book.add_order(OrderSide::Ask, 100.0, 1.0, 1, "maker".to_string()).unwrap();
+    let (_, trades) = book.add_order(OrderSide::Bid, 100.0, 0.4, 2, "taker".to_string()).unwrap();
+    let frame = next_frame(&mut client, "TradeExecuted").await;
+    assert_eq!((frame["price"].as_f64(), frame["quantity"].as_f64()), (Some(100.0), Some(0.4)));
+    assert_eq!(frame["side"], "Bid");
+    assert_eq!(frame["timestamp"], trades[0].timestamp);
+}
+
+#[actix_web::test]
+async fn a_client_that_stops_reading_never_holds_up_matching() {
+    let book = Arc::new(OrderBook::new());
+    let addr = start_server(book.clone(), api_keys(), Data::new(ConnectionRegistry::new()));
+    let mut client = connect(addr, "alice-key").await;
+    next_frame(&mut client, "StatsUpdate").await;
+
+    // Several times what the event channel holds, with nobody reading.
+    let started = std::time::Instant::now();
+    let trade_count = 3 * EVENT_CHANNEL_CAPACITY as u64;
+    for i in 0..trade_count {
+        book.add_order(OrderSide::Ask, 100.0, 1.0, i, "maker".to_string()).unwrap();
+        book.add_order(OrderSide::Bid, 100.0, 1.0, i, "taker".to_string()).unwrap();
+    }
+    assert!(started.elapsed() < Duration::from_secs(5));
+
+    // The session either kept up and sent every trade, or fell behind and
+    // was told to reconnect; it never sends a partial stream.
+    let mut streamed = 0;
+    let read = async {
+        while let Some(Ok(message)) = client.next().await {
+            match message {
+                Message::Text(text) if text.contains("\"TradeExecuted\"") => {
+                    streamed += 1;
+                    if streamed == trade_count {
+                        return;
+                    }
+                }
+                Message::Close(Some(frame)) => {
+                    assert_eq!(u16::from(frame.code), 1013);
+                    return;
+                }
+                _ => {}
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(10), read).await.expect("neither every trade nor a close arrived");
+}
+
+#[actix_web::test]
+async fn a_client_keeps_its_book_from_the_snapshot_and_deltas() {
+    let book = Arc::new(OrderBook::new());
+    book.add_order(OrderSide::Bid, 99.0, 1.0, 1, "alice".to_string()).unwrap();
+    book.add_order(OrderSide::Ask, 101.0, 1.0, 2, "bob".to_string()).unwrap();
+    let addr = start_server(book.clone(), api_keys(), Data::new(ConnectionRegistry::new()));
+    let mut client = connect(addr, "alice-key").await;
+
+    let snapshot = next_frame(&mut client, "DepthUpdate").await;
+    let levels = |side: &Value| -> Vec<(f64, f64)> { serde_json::from_value(side.clone()).unwrap() };
+    let (mut bids, mut asks) = (levels(&snapshot["bids"]), levels(&snapshot["asks"]));
+    assert_eq!((bids.clone(), asks.clone()), (vec![(99.0, 1.0)], vec![(101.0, 1.0)]));
+    let snapshot_sequence = snapshot["sequence"].as_u64().unwrap();
+    let mut sequence = snapshot_sequence;
+
+    book.add_order(OrderSide::Bid, 99.5, 2.0, 3, "alice".to_string()).unwrap();
+    book.add_order(OrderSide::Bid, 101.0, 1.0, 4, "carol".to_string()).unwrap();
+    let expected = book.get_market_depth(20);
+    assert_eq!(expected, (vec![(99.5, 2.0), (99.0, 1.0)], vec![]));
+
+    while (bids.clone(), asks.clone()) != expected {
+        // Deltas sent together share the sequence they were read at.
+        let delta = next_frame(&mut client, "DepthDelta").await;
+        assert!(delta["sequence"].as_u64().unwrap() >= sequence);
+        sequence = delta["sequence"].as_u64().unwrap();
+        assert!(sequence > snapshot_sequence);
+        let (price, quantity) = (delta["price"].as_f64().unwrap(), delta["new_quantity"].as_f64().unwrap());
+        let (side, descending) = match delta["side"].as_str().unwrap() {
+            "Bid" => (&mut bids, true),
+            _ => (&mut asks, false),
+        };
+        side.retain(|&(p, _)| p != price);
+        if quantity > 0.0 {
+            side.push((price, quantity));
+        }
+        side.sort_by(|a, b| if descending { b.0.total_cmp(&a.0) } else { a.0.total_cmp(&b.0) });
+    }
+}